@@ -9,11 +9,14 @@ pub mod disk_space;
 pub mod docker;
 pub mod focused_window;
 pub mod github;
+pub mod group;
+pub mod http;
 pub mod hueshift;
 pub mod ibus;
 pub mod kdeconnect;
 pub mod keyboard_layout;
 pub mod load;
+pub mod lock_keys;
 pub mod maildir;
 pub mod memory;
 pub mod mpd;
@@ -26,8 +29,12 @@ pub mod notmuch;
 pub mod nvidia_gpu;
 pub mod pacman;
 pub mod pomodoro;
+pub mod scratchpad;
 pub mod sound;
 pub mod speedtest;
+pub mod static_text;
+pub mod systemd;
+pub mod tail;
 pub mod taskwarrior;
 pub mod temperature;
 pub mod template;
@@ -49,11 +56,14 @@ use self::disk_space::*;
 use self::docker::*;
 use self::focused_window::*;
 use self::github::*;
+use self::group::*;
+use self::http::*;
 use self::hueshift::*;
 use self::ibus::*;
 use self::kdeconnect::*;
 use self::keyboard_layout::*;
 use self::load::*;
+use self::lock_keys::*;
 use self::maildir::*;
 use self::memory::*;
 use self::mpd::*;
@@ -66,8 +76,12 @@ use self::notmuch::*;
 use self::nvidia_gpu::*;
 use self::pacman::*;
 use self::pomodoro::*;
+use self::scratchpad::*;
 use self::sound::*;
 use self::speedtest::*;
+use self::static_text::*;
+use self::systemd::*;
+use self::tail::*;
 use self::taskwarrior::*;
 use self::temperature::*;
 use self::template::*;
@@ -78,17 +92,27 @@ use self::watson::*;
 use self::weather::*;
 use self::xrandr::*;
 
-use std::time::Duration;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use serde::de::Deserialize;
-use toml::value::Value;
+use toml::value::{Table, Value};
 
-use crate::config::Config;
+use crate::config::{closest_match, unknown_field, Config};
+use crate::de::deserialize_duration;
 use crate::errors::*;
-use crate::input::I3BarEvent;
+use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::widget::I3BarWidget;
+use crate::signals::convert_to_valid_signal;
+use crate::subprocess::spawn_child_async;
+use crate::util::{interpolate_command, pseudo_uuid, record_rendered_values};
+use crate::widget::{I3BarWidget, State, WidgetOptions};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Update {
@@ -131,6 +155,16 @@ pub trait Block {
     fn click(&mut self, _event: &I3BarEvent) -> Result<()> {
         Ok(())
     }
+
+    /// The properties of the widgets configured with the shared options, like `block_id`.
+    fn widget_options(&self) -> Option<&WidgetOptions> {
+        None
+    }
+
+    /// Selects the format that is shown of a block configured with a list of formats, see
+    /// `util::Formats`. Blocks that accept a list as `format` render the format at `index`
+    /// from their next update on.
+    fn set_format_index(&mut self, _index: usize) {}
 }
 
 pub trait ConfigBlock: Block {
@@ -147,10 +181,10 @@ pub trait ConfigBlock: Block {
 }
 
 macro_rules! block {
-    ($block_type:ident, $block_config:expr, $config:expr, $update_request:expr) => {{
+    ($block_type:ident, $name:expr, $block_config:expr, $config:expr, $update_request:expr) => {{
         let block_config: <$block_type as ConfigBlock>::Config =
             <$block_type as ConfigBlock>::Config::deserialize($block_config)
-                .configuration_error("Failed to deserialize block config.")?;
+                .map_err(|error| block_config_error($name, error))?;
         let mut main_config = $config;
         if let Some(ref overrides) = block_config.color_overrides {
             for entry in overrides {
@@ -182,53 +216,962 @@ macro_rules! block {
     }};
 }
 
+/// Runs `$action!` with the type of the block with the given name and the remaining arguments.
+macro_rules! with_block_type {
+    ($action:ident, $name:expr, $($arg:expr),*) => {
+        match $name {
+            // Please keep these in alphabetical order.
+            "apt" => $action!(Apt, $($arg),*),
+            "backlight" => $action!(Backlight, $($arg),*),
+            "battery" => $action!(Battery, $($arg),*),
+            "bluetooth" => $action!(Bluetooth, $($arg),*),
+            "cpu" => $action!(Cpu, $($arg),*),
+            "custom" => $action!(Custom, $($arg),*),
+            "custom_dbus" => $action!(CustomDBus, $($arg),*),
+            "disk_space" => $action!(DiskSpace, $($arg),*),
+            "docker" => $action!(Docker, $($arg),*),
+            "focused_window" => $action!(FocusedWindow, $($arg),*),
+            "github" => $action!(Github, $($arg),*),
+            "group" => $action!(Group, $($arg),*),
+            "http" => $action!(Http, $($arg),*),
+            "hueshift" => $action!(Hueshift, $($arg),*),
+            "ibus" => $action!(IBus, $($arg),*),
+            "kdeconnect" => $action!(KDEConnect, $($arg),*),
+            "keyboard_layout" => $action!(KeyboardLayout, $($arg),*),
+            "load" => $action!(Load, $($arg),*),
+            "lock_keys" => $action!(LockKeys, $($arg),*),
+            "maildir" => $action!(Maildir, $($arg),*),
+            "memory" => $action!(Memory, $($arg),*),
+            "mpd" => $action!(Mpd, $($arg),*),
+            "music" => $action!(Music, $($arg),*),
+            "net" => $action!(Net, $($arg),*),
+            "networkmanager" => $action!(NetworkManager, $($arg),*),
+            "notify" => $action!(Notify, $($arg),*),
+            #[cfg(feature = "notmuch")]
+            "notmuch" => $action!(Notmuch, $($arg),*),
+            "nvidia_gpu" => $action!(NvidiaGpu, $($arg),*),
+            "pacman" => $action!(Pacman, $($arg),*),
+            "pomodoro" => $action!(Pomodoro, $($arg),*),
+            "scratchpad" => $action!(Scratchpad, $($arg),*),
+            "sound" => $action!(Sound, $($arg),*),
+            "speedtest" => $action!(SpeedTest, $($arg),*),
+            "static_text" => $action!(StaticText, $($arg),*),
+            "systemd" => $action!(Systemd, $($arg),*),
+            "tail" => $action!(Tail, $($arg),*),
+            "taskwarrior" => $action!(Taskwarrior, $($arg),*),
+            "temperature" => $action!(Temperature, $($arg),*),
+            "template" => $action!(Template, $($arg),*),
+            "time" => $action!(Time, $($arg),*),
+            "toggle" => $action!(Toggle, $($arg),*),
+            "uptime" => $action!(Uptime, $($arg),*),
+            "watson" => $action!(Watson, $($arg),*),
+            "weather" => $action!(Weather, $($arg),*),
+            "xrandr" => $action!(Xrandr, $($arg),*),
+            other => Err(BlockError(other.to_string(), "Unknown block!".to_string())),
+        }
+    };
+}
+
+/// Deserializes the configuration of a block, like `block!` does before creating the block.
+macro_rules! check_block_config {
+    ($block_type:ident, $name:expr, $block_config:expr) => {
+        <$block_type as ConfigBlock>::Config::deserialize($block_config)
+            .map(|_| ())
+            .map_err(|error| block_config_error($name, error))
+    };
+}
+
+/// How often a hidden block whose first update failed is updated, and its `if_command` run.
+const HIDDEN_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long an `if_command` may run before it's killed and the block is hidden.
+const IF_COMMAND_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How often an `if_command` is run.
+const IF_COMMAND_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Runs an `if_command`, without access to the bar's input and output, and tells whether it
+/// succeeded in time.
+fn check_if_command(if_command: &str) -> bool {
+    let mut child = match Command::new("sh")
+        .args(&["-c", if_command])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+    let deadline = Instant::now() + IF_COMMAND_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return status.success(),
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(5)),
+            _ => {
+                child.kill().ok();
+                child.wait().ok();
+                return false;
+            }
+        }
+    }
+}
+
+/// Wraps a block that is only shown while its `if_command` exits successfully.
+struct ConditionalBlock {
+    inner: Box<dyn Block>,
+    /// Set by the thread running the `if_command`
+    condition: Arc<AtomicBool>,
+    visible: bool,
+    last_update: Option<Update>,
+}
+
+impl ConditionalBlock {
+    /// Runs the `if_command` on a thread of its own, so that it neither holds up the bar nor
+    /// depends on when the block is updated, and requests an update whenever its result changes.
+    fn new(
+        inner: Box<dyn Block>,
+        if_command: String,
+        update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let condition = Arc::new(AtomicBool::new(false));
+        let thread_condition = condition.clone();
+        let id = inner.id().to_string();
+        thread::Builder::new()
+            .name("if_command".into())
+            .spawn(move || loop {
+                let holds = check_if_command(&if_command);
+                if thread_condition.swap(holds, Ordering::SeqCst) != holds
+                    && update_request
+                        .send(Task {
+                            id: id.clone(),
+                            update_time: Instant::now(),
+                        })
+                        .is_err()
+                {
+                    break;
+                }
+                thread::sleep(IF_COMMAND_INTERVAL);
+            })
+            .block_error("if_command", "failed to start if_command thread")?;
+
+        Ok(ConditionalBlock {
+            inner,
+            condition,
+            visible: false,
+            last_update: None,
+        })
+    }
+}
+
+impl Block for ConditionalBlock {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.visible {
+            self.inner.view()
+        } else {
+            vec![]
+        }
+    }
+
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.visible = self.condition.load(Ordering::SeqCst);
+
+        // Keep updating the hidden block on its schedule so it shows up again as soon as the
+        // condition holds, but don't fail because of a block that isn't shown anyway.
+        match self.inner.update() {
+            Ok(update) => {
+                self.last_update = update.clone();
+                Ok(update)
+            }
+            // Without a schedule of its own yet, the block would never be updated again
+            Err(_) if !self.visible => Ok(Some(
+                self.last_update
+                    .clone()
+                    .unwrap_or(Update::Every(HIDDEN_RETRY_INTERVAL)),
+            )),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn signal(&mut self, signal: i32) -> Result<()> {
+        self.inner.signal(signal)
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if self.visible {
+            self.inner.click(event)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Wraps a block that is updated right away when the process receives its real-time signal.
+struct SignalBlock {
+    inner: Box<dyn Block>,
+    signal: i32,
+    update_request: Sender<Task>,
+}
+
+impl Block for SignalBlock {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.inner.view()
+    }
+
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.inner.update()
+    }
+
+    fn signal(&mut self, signal: i32) -> Result<()> {
+        // The update goes through the scheduler like any other requested update
+        if signal == self.signal {
+            self.update_request.send(Task {
+                id: self.id().to_string(),
+                update_time: Instant::now(),
+            })?;
+        }
+        self.inner.signal(signal)
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        self.inner.click(event)
+    }
+}
+
+/// When this environment variable is set, the duration of every update of every block is
+/// printed to stderr.
+const TIMING_VAR: &str = "I3STATUS_RS_TIMING";
+
+/// Wraps a block to measure how long its updates take.
+struct TimedBlock {
+    inner: Box<dyn Block>,
+    name: String,
+    updates: u64,
+}
+
+impl Block for TimedBlock {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.inner.view()
+    }
+
+    fn update(&mut self) -> Result<Option<Update>> {
+        let start = Instant::now();
+        let update = self.inner.update();
+        self.updates += 1;
+        eprintln!(
+            "{} ({}): update {} took {:.3} ms",
+            self.name,
+            self.inner.id(),
+            self.updates,
+            start.elapsed().as_secs_f64() * 1000.0
+        );
+        update
+    }
+
+    fn signal(&mut self, signal: i32) -> Result<()> {
+        self.inner.signal(signal)
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        self.inner.click(event)
+    }
+}
+
+/// Wraps a block to run a command whenever the state of its widgets changes.
+struct StateChangeBlock {
+    inner: Box<dyn Block>,
+    command: String,
+    /// The state after the last update, `None` before the block was shown
+    state: Option<State>,
+}
+
+impl StateChangeBlock {
+    /// The state picked by the thresholds, or else the most urgent state of the widgets.
+    fn current_state(&self) -> Option<State> {
+        let widgets = self.inner.view();
+        if widgets.is_empty() {
+            return None;
+        }
+        if let Some(state) = self
+            .inner
+            .widget_options()
+            .and_then(|options| options.state)
+        {
+            return Some(state);
+        }
+        State::BY_URGENCY
+            .iter()
+            .find(|state| widgets.iter().any(|widget| widget.state() == **state))
+            .cloned()
+    }
+
+    /// Runs the command with the new state as argument if the state changed. A hidden block
+    /// keeps its last state.
+    fn check_state(&mut self) {
+        let state = match self.current_state() {
+            Some(state) => state,
+            None => return,
+        };
+        if let Some(previous) = self.state.replace(state) {
+            if previous != state {
+                let command = format!("{} {}", self.command, state.name());
+                spawn_child_async("sh", &["-c", &command]).ok();
+            }
+        }
+    }
+}
+
+impl Block for StateChangeBlock {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.inner.view()
+    }
+
+    fn update(&mut self) -> Result<Option<Update>> {
+        let update = self.inner.update();
+        self.check_state();
+        update
+    }
+
+    fn signal(&mut self, signal: i32) -> Result<()> {
+        let result = self.inner.signal(signal);
+        self.check_state();
+        result
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        let result = self.inner.click(event);
+        self.check_state();
+        result
+    }
+
+    fn widget_options(&self) -> Option<&WidgetOptions> {
+        self.inner.widget_options()
+    }
+}
+
+/// Wraps a block to run a command on left clicks instead of the block's own action, with the
+/// placeholders of the block's format in the command replaced by their values.
+struct ClickCommandBlock {
+    inner: Box<dyn Block>,
+    command: String,
+    /// The name of the block's widgets in click events
+    block_id: String,
+    /// The values rendered by the last update that rendered any
+    values: HashMap<String, String>,
+}
+
+impl ClickCommandBlock {
+    fn record_values<T>(&mut self, f: impl FnOnce(&mut Box<dyn Block>) -> T) -> T {
+        let inner = &mut self.inner;
+        let (result, values) = record_rendered_values(|| f(inner));
+        if !values.is_empty() {
+            self.values = values;
+        }
+        result
+    }
+}
+
+impl Block for ClickCommandBlock {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.inner.view()
+    }
+
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.record_values(|inner| inner.update())
+    }
+
+    fn signal(&mut self, signal: i32) -> Result<()> {
+        self.record_values(|inner| inner.signal(signal))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button == MouseButton::Left && event.matches_name(&self.block_id) {
+            let command = interpolate_command(&self.command, &self.values);
+            spawn_child_async("sh", &["-c", &command])
+                .block_error("click_command", "could not spawn child")
+        } else {
+            self.record_values(|inner| inner.click(event))
+        }
+    }
+
+    fn widget_options(&self) -> Option<&WidgetOptions> {
+        self.inner.widget_options()
+    }
+}
+
+/// Wraps a block that was configured with a list of formats. The block is told which format to
+/// show, which clicks with the cycle button and, if configured, a timer advance.
+struct FormatCycle {
+    inner: Box<dyn Block>,
+    index: usize,
+    formats: usize,
+    button: MouseButton,
+    rotation_interval: Option<Duration>,
+    last_rotation: Instant,
+}
+
+impl FormatCycle {
+    fn new(
+        name: &str,
+        block_config: Value,
+        formats: usize,
+        button: MouseButton,
+        rotation_interval: Option<Duration>,
+        config: Config,
+        update_request: Sender<Task>,
+    ) -> Result<Self> {
+        // Blocks that support a list of formats take it as their `format` option
+        let inner =
+            create_block_without_shared_options(name, block_config, config, update_request)?;
+
+        Ok(FormatCycle {
+            inner,
+            index: 0,
+            formats,
+            button,
+            rotation_interval,
+            last_rotation: Instant::now(),
+        })
+    }
+
+    fn next_format(&mut self) {
+        self.index = (self.index + 1) % self.formats;
+        self.inner.set_format_index(self.index);
+        self.last_rotation = Instant::now();
+    }
+}
+
+impl Block for FormatCycle {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.inner.view()
+    }
+
+    fn update(&mut self) -> Result<Option<Update>> {
+        let rotation_interval = match self.rotation_interval {
+            Some(rotation_interval) => rotation_interval,
+            None => return self.inner.update(),
+        };
+
+        // The scheduler may wake us up slightly before the rotation is due
+        if self.last_rotation.elapsed() + Duration::from_millis(10) >= rotation_interval {
+            self.next_format();
+        }
+        let update = self.inner.update()?;
+
+        // Wake up in time for the next rotation
+        let remaining = rotation_interval
+            .checked_sub(self.last_rotation.elapsed())
+            .unwrap_or_default();
+        Ok(Some(match update {
+            Some(Update::Every(interval)) => Update::Every(interval.min(remaining)),
+            _ => Update::Every(remaining),
+        }))
+    }
+
+    fn signal(&mut self, signal: i32) -> Result<()> {
+        self.inner.signal(signal)
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button == self.button && event.matches_name(self.inner.id()) {
+            // Render the block again with the next format
+            self.next_format();
+            self.inner.update()?;
+            Ok(())
+        } else {
+            self.inner.click(event)
+        }
+    }
+}
+
+/// A block with shared widget options. With a `block_id`, its widgets are named by the
+/// `block_id` and carry their own names as instance, which is how clicks are routed back to them.
+struct WidgetOptionsBlock {
+    inner: Box<dyn Block>,
+    options: WidgetOptions,
+}
+
+impl Block for WidgetOptionsBlock {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.inner.view()
+    }
+
+    fn update(&mut self) -> Result<Option<Update>> {
+        let inner = &mut self.inner;
+        self.options.record_state(|| inner.update())
+    }
+
+    fn signal(&mut self, signal: i32) -> Result<()> {
+        let inner = &mut self.inner;
+        self.options.record_state(|| inner.signal(signal))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        let inner = &mut self.inner;
+        let block_id = self.options.block_id.clone();
+        self.options
+            .record_state(|| WidgetOptionsBlock::route_click(inner, block_id.as_ref(), event))
+    }
+
+    fn widget_options(&self) -> Option<&WidgetOptions> {
+        Some(&self.options)
+    }
+}
+
+impl WidgetOptionsBlock {
+    fn route_click(
+        inner: &mut Box<dyn Block>,
+        block_id: Option<&String>,
+        event: &I3BarEvent,
+    ) -> Result<()> {
+        let for_block_id = match block_id {
+            Some(block_id) => event.matches_name(block_id),
+            None => false,
+        };
+        if for_block_id {
+            // The block_id is passed on as the instance, so that blocks know the name the
+            // click was meant for
+            let event = I3BarEvent {
+                name: event.instance.clone(),
+                instance: block_id.cloned(),
+                ..event.clone()
+            };
+            inner.click(&event)
+        } else {
+            inner.click(event)
+        }
+    }
+}
+
+/// Shared options that apply to all widgets of a block.
+pub const WIDGET_OPTIONS: &[&str] = &[
+    "block_id",
+    "min_width",
+    "align",
+    "separator",
+    "separator_block_width",
+    "thresholds",
+    "markup",
+    "background",
+    "border",
+    "border_top",
+    "border_right",
+    "border_bottom",
+    "border_left",
+];
+
+/// Shared options that apply to the block itself.
+const BLOCK_OPTIONS: &[&str] = &[
+    "click_command",
+    "cycle_button",
+    "if_command",
+    "on_state_change",
+    "rotation_interval",
+    "signal",
+];
+
+/// Describes why the configuration of a block is invalid, and for an unknown field suggests the
+/// field that was most likely meant.
+fn block_config_error(name: &str, error: toml::de::Error) -> Error {
+    let cause = error.to_string();
+    let message = match unknown_field(&cause) {
+        Some((field, expected)) => {
+            let candidates: Vec<&str> = expected
+                .iter()
+                .map(String::as_str)
+                .chain(WIDGET_OPTIONS.iter().cloned())
+                .chain(BLOCK_OPTIONS.iter().cloned())
+                .collect();
+            match closest_match(&field, &candidates) {
+                Some(candidate) => format!(
+                    "unknown field `{}` in block '{}', did you mean `{}`?",
+                    field, name, candidate
+                ),
+                None => format!("unknown field `{}` in block '{}'", field, name),
+            }
+        }
+        None => format!("invalid configuration of block '{}'", name),
+    };
+    ConfigurationError(message, (cause, format!("{:?}", error)))
+}
+
+/// The options every block accepts next to its own.
+struct SharedOptions {
+    if_command: Option<String>,
+    on_state_change: Option<String>,
+    click_command: Option<String>,
+    widget_options: Option<WidgetOptions>,
+    signal: Option<i32>,
+    rotation_interval: Option<Duration>,
+    cycle_button: Option<MouseButton>,
+    /// The number of formats, when the block was configured with a list of them
+    formats: Option<usize>,
+}
+
+/// Removes the options shared by all blocks from the configuration of a block and checks them.
+fn shared_options(name: &str, block_config: &mut Value) -> Result<SharedOptions> {
+    // Options shared by all blocks
+    let if_command = match block_config
+        .as_table_mut()
+        .and_then(|t| t.remove("if_command"))
+    {
+        Some(Value::String(command)) => Some(command),
+        Some(_) => {
+            return Err(ConfigurationError(
+                name.to_string(),
+                (
+                    "if_command must be a string".to_string(),
+                    "invalid if_command".to_string(),
+                ),
+            ))
+        }
+        None => None,
+    };
+
+    let on_state_change = match block_config
+        .as_table_mut()
+        .and_then(|t| t.remove("on_state_change"))
+    {
+        Some(Value::String(command)) => Some(command),
+        Some(_) => {
+            return Err(ConfigurationError(
+                name.to_string(),
+                (
+                    "on_state_change must be a string".to_string(),
+                    "invalid on_state_change".to_string(),
+                ),
+            ))
+        }
+        None => None,
+    };
+
+    let click_command = match block_config
+        .as_table_mut()
+        .and_then(|t| t.remove("click_command"))
+    {
+        Some(Value::String(command)) => Some(command),
+        Some(_) => {
+            return Err(ConfigurationError(
+                name.to_string(),
+                (
+                    "click_command must be a string".to_string(),
+                    "invalid click_command".to_string(),
+                ),
+            ))
+        }
+        None => None,
+    };
+
+    let mut widget_options = Table::new();
+    if let Some(table) = block_config.as_table_mut() {
+        for key in WIDGET_OPTIONS {
+            if let Some(value) = table.remove(*key) {
+                widget_options.insert(key.to_string(), value);
+            }
+        }
+    }
+    let widget_options = if widget_options.is_empty() {
+        // The click command needs a name to recognize the clicks on the block
+        click_command.as_ref().map(|_| WidgetOptions::default())
+    } else {
+        match Value::Table(widget_options.clone()).try_into::<WidgetOptions>() {
+            Ok(options) => Some(options),
+            Err(error) => {
+                // Tell which of the options is invalid on its own
+                let key = widget_options.iter().find_map(|(key, value)| {
+                    let mut option = Table::new();
+                    option.insert(key.clone(), value.clone());
+                    Value::Table(option)
+                        .try_into::<WidgetOptions>()
+                        .err()
+                        .map(|_| key.clone())
+                });
+                let message = match key {
+                    Some(key) => format!("invalid {}", key),
+                    None => "invalid widget options".to_string(),
+                };
+                return Err(ConfigurationError(
+                    name.to_string(),
+                    (format!("{}: {}", message, error), message),
+                ));
+            }
+        }
+    };
+    if let Some(color) = widget_options
+        .as_ref()
+        .and_then(WidgetOptions::invalid_color)
+    {
+        return Err(BlockError(
+            name.to_string(),
+            format!("invalid color '{}', expected e.g. #ff0000", color),
+        ));
+    }
+
+    let signal = match block_config.as_table_mut().and_then(|t| t.remove("signal")) {
+        Some(Value::Integer(signal)) => {
+            // A cast would wrap large numbers around to valid signals
+            let signal = i32::try_from(signal).map_err(|_| {
+                ConfigurationError(
+                    name.to_string(),
+                    (
+                        format!("signal {} is out of range", signal),
+                        "invalid signal".to_string(),
+                    ),
+                )
+            })?;
+            Some(convert_to_valid_signal(signal)?)
+        }
+        Some(_) => {
+            return Err(ConfigurationError(
+                name.to_string(),
+                (
+                    "signal must be a number".to_string(),
+                    "invalid signal".to_string(),
+                ),
+            ))
+        }
+        None => None,
+    };
+
+    let rotation_interval = match block_config
+        .as_table_mut()
+        .and_then(|t| t.remove("rotation_interval"))
+    {
+        Some(value) => Some(
+            deserialize_duration(value)
+                .configuration_error("rotation_interval must be a number of seconds")?,
+        ),
+        None => None,
+    };
+
+    let cycle_button = match block_config
+        .as_table_mut()
+        .and_then(|t| t.remove("cycle_button"))
+    {
+        Some(value) => Some(
+            value
+                .try_into::<MouseButton>()
+                .configuration_error("cycle_button must be the name of a mouse button")?,
+        ),
+        None => None,
+    };
+
+    // A list of formats is cycled through, the block renders one of them at a time
+    let formats = match block_config.get("format") {
+        Some(Value::Array(formats))
+            if formats.is_empty() || formats.iter().any(|format| !format.is_str()) =>
+        {
+            return Err(ConfigurationError(
+                name.to_string(),
+                (
+                    "format must be a string or a non-empty list of strings".to_string(),
+                    "invalid format".to_string(),
+                ),
+            ))
+        }
+        Some(Value::Array(formats)) => Some(formats.len()),
+        _ => None,
+    };
+    if formats.is_none() && rotation_interval.is_some() {
+        return Err(ConfigurationError(
+            name.to_string(),
+            (
+                "rotation_interval requires a list of formats".to_string(),
+                "invalid rotation_interval".to_string(),
+            ),
+        ));
+    }
+    if formats.is_none() && cycle_button.is_some() {
+        return Err(ConfigurationError(
+            name.to_string(),
+            (
+                "cycle_button requires a list of formats".to_string(),
+                "invalid cycle_button".to_string(),
+            ),
+        ));
+    }
+
+    Ok(SharedOptions {
+        if_command,
+        on_state_change,
+        click_command,
+        widget_options,
+        signal,
+        rotation_interval,
+        cycle_button,
+        formats,
+    })
+}
+
+/// Checks the configuration of a block without creating it, so nothing is started.
+pub fn validate_block(name: &str, mut block_config: Value) -> Result<()> {
+    shared_options(name, &mut block_config)?;
+    if name == "group" {
+        let group = GroupConfig::deserialize(block_config)
+            .map_err(|error| block_config_error(name, error))?;
+        for (name, block_config) in group_blocks(group.blocks)? {
+            validate_block(&name, block_config)?;
+        }
+        return Ok(());
+    }
+    with_block_type!(check_block_config, name, name, block_config)
+}
+
 pub fn create_block(
+    name: &str,
+    mut block_config: Value,
+    config: Config,
+    update_request: Sender<Task>,
+) -> Result<Box<dyn Block>> {
+    let SharedOptions {
+        if_command,
+        on_state_change,
+        click_command,
+        widget_options,
+        signal,
+        rotation_interval,
+        cycle_button,
+        formats,
+    } = shared_options(name, &mut block_config)?;
+
+    // The formats of the block escape the values of their placeholders for pango markup
+    let mut config = config;
+    config.markup = widget_options.as_ref().and_then(|options| options.markup);
+
+    let block = match formats {
+        Some(formats) => Box::new(FormatCycle::new(
+            name,
+            block_config,
+            formats,
+            cycle_button.unwrap_or(MouseButton::Left),
+            rotation_interval,
+            config,
+            update_request.clone(),
+        )?),
+        None => {
+            create_block_without_shared_options(name, block_config, config, update_request.clone())?
+        }
+    };
+
+    let block: Box<dyn Block> = match if_command {
+        Some(if_command) => Box::new(ConditionalBlock::new(
+            block,
+            if_command,
+            update_request.clone(),
+        )?),
+        None => block,
+    };
+
+    let block: Box<dyn Block> = match signal {
+        Some(signal) => Box::new(SignalBlock {
+            inner: block,
+            signal,
+            update_request,
+        }),
+        None => block,
+    };
+
+    let block: Box<dyn Block> = if std::env::var_os(TIMING_VAR).is_some() {
+        Box::new(TimedBlock {
+            inner: block,
+            name: name.to_string(),
+            updates: 0,
+        })
+    } else {
+        block
+    };
+
+    let block: Box<dyn Block> = match widget_options {
+        Some(mut options) => match click_command {
+            Some(command) => {
+                let block_id = options.block_id.get_or_insert_with(pseudo_uuid).clone();
+                Box::new(ClickCommandBlock {
+                    inner: Box::new(WidgetOptionsBlock {
+                        inner: block,
+                        options,
+                    }),
+                    command,
+                    block_id,
+                    values: HashMap::new(),
+                })
+            }
+            None => Box::new(WidgetOptionsBlock {
+                inner: block,
+                options,
+            }),
+        },
+        None => block,
+    };
+
+    // Outside of the widget options to see the state picked by the thresholds
+    Ok(match on_state_change {
+        Some(command) => Box::new(StateChangeBlock {
+            inner: block,
+            command,
+            state: None,
+        }),
+        None => block,
+    })
+}
+
+fn create_block_without_shared_options(
     name: &str,
     block_config: Value,
     config: Config,
     update_request: Sender<Task>,
 ) -> Result<Box<dyn Block>> {
-    match name {
-        // Please keep these in alphabetical order.
-        "apt" => block!(Apt, block_config, config, update_request),
-        "backlight" => block!(Backlight, block_config, config, update_request),
-        "battery" => block!(Battery, block_config, config, update_request),
-        "bluetooth" => block!(Bluetooth, block_config, config, update_request),
-        "cpu" => block!(Cpu, block_config, config, update_request),
-        "custom" => block!(Custom, block_config, config, update_request),
-        "custom_dbus" => block!(CustomDBus, block_config, config, update_request),
-        "disk_space" => block!(DiskSpace, block_config, config, update_request),
-        "docker" => block!(Docker, block_config, config, update_request),
-        "focused_window" => block!(FocusedWindow, block_config, config, update_request),
-        "github" => block!(Github, block_config, config, update_request),
-        "hueshift" => block!(Hueshift, block_config, config, update_request),
-        "ibus" => block!(IBus, block_config, config, update_request),
-        "kdeconnect" => block!(KDEConnect, block_config, config, update_request),
-        "keyboard_layout" => block!(KeyboardLayout, block_config, config, update_request),
-        "load" => block!(Load, block_config, config, update_request),
-        "maildir" => block!(Maildir, block_config, config, update_request),
-        "memory" => block!(Memory, block_config, config, update_request),
-        "mpd" => block!(Mpd, block_config, config, update_request),
-        "music" => block!(Music, block_config, config, update_request),
-        "net" => block!(Net, block_config, config, update_request),
-        "networkmanager" => block!(NetworkManager, block_config, config, update_request),
-        "notify" => block!(Notify, block_config, config, update_request),
-        #[cfg(feature = "notmuch")]
-        "notmuch" => block!(Notmuch, block_config, config, update_request),
-        "nvidia_gpu" => block!(NvidiaGpu, block_config, config, update_request),
-        "pacman" => block!(Pacman, block_config, config, update_request),
-        "pomodoro" => block!(Pomodoro, block_config, config, update_request),
-        "sound" => block!(Sound, block_config, config, update_request),
-        "speedtest" => block!(SpeedTest, block_config, config, update_request),
-        "taskwarrior" => block!(Taskwarrior, block_config, config, update_request),
-        "temperature" => block!(Temperature, block_config, config, update_request),
-        "template" => block!(Template, block_config, config, update_request),
-        "time" => block!(Time, block_config, config, update_request),
-        "toggle" => block!(Toggle, block_config, config, update_request),
-        "uptime" => block!(Uptime, block_config, config, update_request),
-        "watson" => block!(Watson, block_config, config, update_request),
-        "weather" => block!(Weather, block_config, config, update_request),
-        "xrandr" => block!(Xrandr, block_config, config, update_request),
-        other => Err(BlockError(other.to_string(), "Unknown block!".to_string())),
+    with_block_type!(block, name, name, block_config, config, update_request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(toml: &str) -> Value {
+        toml.parse::<Value>().unwrap()
+    }
+
+    #[test]
+    fn test_validate_block() {
+        assert!(validate_block("time", table("format = \"%H:%M\"\ninterval = 5")).is_ok());
+        assert!(validate_block("time", table("interva = 5")).is_err());
+        assert!(validate_block("nope", table("")).is_err());
+        assert!(validate_block("time", table("rotation_interval = 5")).is_err());
+        assert!(validate_block("load", table("format = [\"{1m}\", \"{5m}\"]")).is_ok());
+
+        // The blocks of a group are checked as well
+        let group = "[[blocks]]\nblock = \"time\"\n";
+        assert!(validate_block("group", table(group)).is_ok());
+        assert!(validate_block("group", table(&format!("{}interva = 5", group))).is_err());
+        assert!(validate_block("group", table(&format!("{}block_id = \"a\"", group))).is_err());
+        assert!(validate_block("group", table("blocks = []")).is_err());
     }
 }
@@ -3,32 +3,49 @@ use std::env;
 use std::fs;
 use std::io::Write;
 use std::process::Command;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crossbeam_channel::Sender;
+use crossbeam_channel::{unbounded, RecvTimeoutError, Sender};
 use regex::Regex;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
+use crate::cache::{Cache, Cached};
 use crate::config::Config;
 use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::subprocess::spawn_child_async;
+use crate::util::{format_stale, pseudo_uuid, FormatTemplate, Formats};
 use crate::widget::{I3BarWidget, State};
 use crate::widgets::button::ButtonWidget;
 
+/// The output of `apt list --upgradable`, or why it couldn't be run.
+type AptUpdates = std::result::Result<String, String>;
+
 pub struct Apt {
     output: ButtonWidget,
     id: String,
-    update_interval: Duration,
     format: FormatTemplate,
     format_singular: FormatTemplate,
     format_up_to_date: FormatTemplate,
     warning_updates_regex: Option<Regex>,
     critical_updates_regex: Option<Regex>,
-    config_path: String,
+    warning_count: Option<usize>,
+    critical_count: Option<usize>,
+    hide_when_uptodate: bool,
+    on_click: Option<String>,
+    /// The last result of the checking thread, `None` until the first check is done
+    updates: Arc<Mutex<Option<AptUpdates>>>,
+    /// The updates of the last successful check, shown as stale while checks fail
+    last_updates: Option<String>,
+    /// Template for the text while the data is outdated
+    stale_format: String,
+    empty: bool,
+    tx_refresh: Sender<()>,
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -42,8 +59,8 @@ pub struct AptConfig {
     pub interval: Duration,
 
     /// Format override
-    #[serde(default = "AptConfig::default_format")]
-    pub format: String,
+    #[serde(default = "AptConfig::default_formats")]
+    pub format: Formats,
 
     /// Alternative format override for when exactly 1 update is available
     #[serde(default = "AptConfig::default_format")]
@@ -64,6 +81,29 @@ pub struct AptConfig {
     #[serde(default = "AptConfig::default_critical_updates_regex")]
     pub critical_updates_regex: Option<String>,
 
+    /// Indicate a `warning` state for the block if at least this many updates are available
+    #[serde(
+        default = "AptConfig::default_warning_count",
+        alias = "warning_updates"
+    )]
+    pub warning_count: Option<usize>,
+
+    /// Indicate a `critical` state for the block if at least this many updates are available
+    #[serde(
+        default = "AptConfig::default_critical_count",
+        alias = "critical_updates"
+    )]
+    pub critical_count: Option<usize>,
+
+    /// Hide the block while no updates are available
+    #[serde(default = "AptConfig::default_hide_when_uptodate")]
+    pub hide_when_uptodate: bool,
+
+    /// Shell command run on a left click instead of checking for updates again,
+    /// e.g. to upgrade in a terminal
+    #[serde(default = "AptConfig::default_on_click")]
+    pub on_click: Option<String>,
+
     #[serde(default = "AptConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
@@ -77,6 +117,10 @@ impl AptConfig {
         "{count}".to_owned()
     }
 
+    fn default_formats() -> Formats {
+        Self::default_format().into()
+    }
+
     fn default_warning_updates_regex() -> Option<String> {
         None
     }
@@ -85,6 +129,22 @@ impl AptConfig {
         None
     }
 
+    fn default_warning_count() -> Option<usize> {
+        None
+    }
+
+    fn default_critical_count() -> Option<usize> {
+        None
+    }
+
+    fn default_hide_when_uptodate() -> bool {
+        false
+    }
+
+    fn default_on_click() -> Option<String> {
+        None
+    }
+
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
         None
     }
@@ -96,7 +156,7 @@ impl ConfigBlock for Apt {
     fn new(
         block_config: Self::Config,
         config: Config,
-        _tx_update_request: Sender<Task>,
+        tx_update_request: Sender<Task>,
     ) -> Result<Self> {
         let mut cache_dir = env::temp_dir();
         cache_dir.push("i3rs-apt");
@@ -117,16 +177,72 @@ impl ConfigBlock for Apt {
         let mut config_file = fs::File::create(cache_dir.clone())
             .block_error("apt", "Failed to create config file")?;
         write!(config_file, "{}", apt_conf).block_error("apt", "Failed to write to config file")?;
+        let config_path = cache_dir.into_os_string().into_string().unwrap();
+
+        // Updating the package database takes a while, so it's done on a thread which
+        // requests an update of the block whenever it's done
+        let id = pseudo_uuid();
+        let updates = Arc::new(Mutex::new(None));
+        let (tx_refresh, rx_refresh) = unbounded::<()>();
+        let thread_updates = updates.clone();
+        let thread_id = id.clone();
+        let interval = block_config.interval;
+
+        // After a restart, the cached updates are shown until they're due to be checked again.
+        // The updates only depend on the apt configuration.
+        let cache = Cache::new("apt", &config_path, config.cache_ttl);
+        let cached: Option<Cached<String>> = cache.load();
+        let first_check_in = cached
+            .as_ref()
+            .and_then(|cached| cached.remaining(interval));
+
+        thread::Builder::new()
+            .name("apt".into())
+            .spawn(move || {
+                if let Some(wait) = first_check_in {
+                    if let Err(RecvTimeoutError::Disconnected) = rx_refresh.recv_timeout(wait) {
+                        return;
+                    }
+                }
+                loop {
+                    let result = get_updates_list(&config_path);
+                    if let Ok(ref updates_list) = result {
+                        cache.store(updates_list);
+                    }
+                    *thread_updates
+                        .lock()
+                        .expect("main thread paniced while holding apt mutex") = Some(result);
+                    tx_update_request
+                        .send(Task {
+                            id: thread_id.clone(),
+                            update_time: Instant::now(),
+                        })
+                        .expect("unable to send task from apt thread");
+
+                    match rx_refresh.recv_timeout(interval) {
+                        Ok(()) => {
+                            // Several clicks during a check only need one more check
+                            while rx_refresh.try_recv().is_ok() {}
+                        }
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+            })
+            .block_error("apt", "failed to start apt thread")?;
 
         Ok(Apt {
-            id: pseudo_uuid(),
-            update_interval: block_config.interval,
-            format: FormatTemplate::from_string(&block_config.format)
-                .block_error("apt", "Invalid format specified for apt::format")?,
+            id,
+            format: FormatTemplate::from_formats(&block_config.format)
+                .block_error("apt", "Invalid format specified for apt::format")?
+                .with_markup(config.markup),
             format_singular: FormatTemplate::from_string(&block_config.format_singular)
-                .block_error("apt", "Invalid format specified for apt::format_singular")?,
+                .block_error("apt", "Invalid format specified for apt::format_singular")?
+                .with_markup(config.markup),
             format_up_to_date: FormatTemplate::from_string(&block_config.format_up_to_date)
-                .block_error("apt", "Invalid format specified for apt::format_up_to_date")?,
+                .block_error("apt", "Invalid format specified for apt::format_up_to_date")?
+                .with_markup(config.markup),
+            stale_format: config.stale_format.clone(),
             output: ButtonWidget::new(config, "apt").with_icon("update"),
             warning_updates_regex: match block_config.warning_updates_regex {
                 None => None, // no regex configured
@@ -158,7 +274,14 @@ impl ConfigBlock for Apt {
                     Some(regex)
                 }
             },
-            config_path: cache_dir.into_os_string().into_string().unwrap(),
+            warning_count: block_config.warning_count,
+            critical_count: block_config.critical_count,
+            hide_when_uptodate: block_config.hide_when_uptodate,
+            on_click: block_config.on_click,
+            updates,
+            last_updates: cached.map(|cached| cached.value),
+            empty: false,
+            tx_refresh,
         })
     }
 }
@@ -171,23 +294,27 @@ fn has_critical_update(updates: &str, regex: &Regex) -> bool {
     updates.lines().filter(|line| regex.is_match(line)).count() > 0
 }
 
-fn get_updates_list(config_path: &str) -> Result<String> {
-    // Update database
-    Command::new("sh")
+fn get_updates_list(config_path: &str) -> AptUpdates {
+    // Update database, which fails e.g. without network
+    let status = Command::new("sh")
         .env("APT_CONFIG", config_path)
         .args(&["-c", "apt update"])
         .output()
-        .block_error("apt", "Failed to run `apt update` command")?;
+        .map_err(|_| "Failed to run `apt update` command".to_string())?
+        .status;
+    if !status.success() {
+        return Err("`apt update` failed".to_string());
+    }
 
     String::from_utf8(
         Command::new("sh")
             .env("APT_CONFIG", config_path)
             .args(&["-c", "apt list --upgradable"])
             .output()
-            .block_error("apt", "Problem running apt command")?
+            .map_err(|_| "Problem running apt command".to_string())?
             .stdout,
     )
-    .block_error("apt", "Problem capturing apt command output")
+    .map_err(|_| "Problem capturing apt command output".to_string())
 }
 
 fn get_update_count(updates: &str) -> usize {
@@ -197,37 +324,100 @@ fn get_update_count(updates: &str) -> usize {
         .count()
 }
 
+/// Security updates come from a `-security` suite, which is listed after the package name,
+/// e.g. `openssl/jammy-updates,jammy-security 3.0.2-0ubuntu1.10 amd64 [upgradable from: ...]`.
+fn get_security_update_count(updates: &str) -> usize {
+    updates
+        .lines()
+        .filter(|line| line.contains("[upgradable"))
+        .filter(|line| {
+            line.split_whitespace()
+                .next()
+                .and_then(|package| package.split('/').nth(1))
+                .map_or(false, |suites| {
+                    suites.split(',').any(|suite| suite.ends_with("-security"))
+                })
+        })
+        .count()
+}
+
 impl Block for Apt {
     fn id(&self) -> &str {
         &self.id
     }
 
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        vec![&self.output]
+        if self.empty {
+            vec![]
+        } else {
+            vec![&self.output]
+        }
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        let (formatting_map, warning, critical, cum_count) = {
-            let updates_list = get_updates_list(&self.config_path)?;
+        let (formatting_map, warning, critical, cum_count, stale) = {
+            let (updates_list, stale) =
+                match *self.updates.lock().block_error("apt", "mutex poisoned")? {
+                    // The first check isn't done yet, but there may be cached updates
+                    None => match self.last_updates {
+                        Some(ref updates_list) => (updates_list.clone(), false),
+                        None => return Ok(None),
+                    },
+                    Some(Ok(ref updates_list)) => {
+                        self.last_updates = Some(updates_list.clone());
+                        (updates_list.clone(), false)
+                    }
+                    // Keep showing the last known updates if a check fails, e.g. while offline
+                    Some(Err(_)) => match self.last_updates {
+                        Some(ref updates_list) => (updates_list.clone(), true),
+                        // There are none yet, the next check may work
+                        None => {
+                            self.output.set_text(format_stale(&self.stale_format, "?"));
+                            self.output.set_state(State::Idle);
+                            self.empty = false;
+                            return Ok(None);
+                        }
+                    },
+                };
             let count = get_update_count(&updates_list);
-            let formatting_map = map!("{count}" => count);
+            let security_count = get_security_update_count(&updates_list);
+            let formatting_map = map!(
+                "{count}" => count,
+                "{security_count}" => security_count
+            );
 
             let warning = self
                 .warning_updates_regex
                 .as_ref()
-                .map_or(false, |regex| has_warning_update(&updates_list, regex));
+                .map_or(false, |regex| has_warning_update(&updates_list, regex))
+                || self.warning_count.map_or(false, |warning| count >= warning);
             let critical = self
                 .critical_updates_regex
                 .as_ref()
-                .map_or(false, |regex| has_critical_update(&updates_list, regex));
+                .map_or(false, |regex| has_critical_update(&updates_list, regex))
+                || self
+                    .critical_count
+                    .map_or(false, |critical| count >= critical)
+                || security_count > 0;
 
-            (formatting_map, warning, critical, count)
+            (formatting_map, warning, critical, count, stale)
         };
-        self.output.set_text(match cum_count {
+        let text = match cum_count {
             0 => self.format_up_to_date.render_static_str(&formatting_map)?,
             1 => self.format_singular.render_static_str(&formatting_map)?,
             _ => self.format.render_static_str(&formatting_map)?,
+        };
+        self.output.set_text(if stale {
+            format_stale(&self.stale_format, &text)
+        } else {
+            text
         });
+        // A failed check doesn't hide the block, so that it's noticed
+        self.empty = self.hide_when_uptodate && cum_count == 0 && !stale;
         self.output.set_state(match cum_count {
             0 => State::Idle,
             _ => {
@@ -240,14 +430,22 @@ impl Block for Apt {
                 }
             }
         });
-        Ok(Some(self.update_interval.into()))
+        // The checking thread requests the updates
+        Ok(None)
     }
 
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
         if event.name.as_ref().map(|s| s == "apt").unwrap_or(false)
             && event.button == MouseButton::Left
         {
-            self.update()?;
+            match self.on_click {
+                Some(ref cmd) => spawn_child_async("sh", &["-c", cmd])
+                    .block_error("apt", "could not spawn child")?,
+                None => self
+                    .tx_refresh
+                    .send(())
+                    .block_error("apt", "apt thread stopped")?,
+            }
         }
 
         Ok(())
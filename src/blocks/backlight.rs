@@ -5,21 +5,27 @@
 //! levels are read from and written to the `sysfs` filesystem, so this block
 //! does not depend on `xrandr` (and thus it works on Wayland). To set
 //! brightness levels using `xrandr`, see the
-//! [`Xrandr`](../xrandr/struct.Xrandr.html) block.
+//! [`Xrandr`](../xrandr/struct.Xrandr.html) block. External monitors are
+//! controlled over DDC/CI with `ddcutil` instead.
 
 use std::collections::BTreeMap;
 use std::fs::OpenOptions;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use crossbeam_channel::Sender;
+use crossbeam_channel::{unbounded, RecvTimeoutError, Sender};
+use dbus::blocking::Connection;
 use inotify::{EventMask, Inotify, WatchMask};
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::{Config, LogicalDirection, Scrolling};
+use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::input::I3BarEvent;
 use crate::scheduler::Task;
@@ -44,10 +50,15 @@ fn read_brightness(device_file: &Path) -> Result<u64> {
 }
 
 /// Represents a physical backlit device whose brightness level can be queried.
+#[derive(Clone)]
 pub struct BacklitDevice {
     max_brightness: u64,
     device_path: PathBuf,
     root_scaling: f64,
+    driver: BacklightDriver,
+    /// The connection to logind, opened on the first write and shared by the clones of the
+    /// device, since a transition sets the brightness many times a second
+    logind: Arc<Mutex<Option<Connection>>>,
 }
 
 /// Clamp scale root to a safe range. Useful values are 1.0 to 3.0.
@@ -86,6 +97,8 @@ impl BacklitDevice {
             max_brightness,
             device_path: first_device.path(),
             root_scaling: clamp_root_scaling(root_scaling),
+            driver: BacklightDriver::Auto,
+            logind: Arc::new(Mutex::new(None)),
         })
     }
 
@@ -109,44 +122,72 @@ impl BacklitDevice {
             max_brightness,
             device_path,
             root_scaling: clamp_root_scaling(root_scaling),
+            driver: BacklightDriver::Auto,
+            logind: Arc::new(Mutex::new(None)),
         })
     }
 
+    /// Set the brightness through logind or sysfs only, instead of trying logind first.
+    pub fn with_driver(mut self, driver: BacklightDriver) -> Self {
+        self.driver = driver;
+        self
+    }
+
+    /// Query the raw brightness value for this backlit device.
+    pub fn raw_brightness(&self) -> Result<u64> {
+        read_brightness(&self.brightness_file())
+    }
+
     /// Query the brightness value for this backlit device, as a percent.
     pub fn brightness(&self) -> Result<u64> {
-        let raw = read_brightness(&self.brightness_file())?;
+        self.raw_brightness().map(|raw| self.raw_to_percent(raw))
+    }
+
+    /// Convert a raw brightness value to a percent.
+    fn raw_to_percent(&self, raw: u64) -> u64 {
         let brightness_ratio =
             (raw as f64 / self.max_brightness as f64).powf(self.root_scaling.recip());
         let brightness = (brightness_ratio * 100.0).round() as u64;
         match brightness {
-            0..=100 => Ok(brightness),
-            _ => Ok(100),
+            0..=100 => brightness,
+            _ => 100,
         }
     }
 
-    /// Set the brightness value for this backlit device, as a percent.
-    pub fn set_brightness(&self, value: u64) -> Result<()> {
+    /// Convert a percent to a raw brightness value, which is at least 1 to keep the screen on.
+    fn percent_to_raw(&self, value: u64) -> u64 {
         let safe_value = match value {
             0..=100 => value,
             _ => 100,
         };
         let ratio = (safe_value as f64 / 100.0).powf(self.root_scaling);
-        let raw = std::cmp::max(1, (ratio * (self.max_brightness as f64)).round() as u64);
+        std::cmp::max(1, (ratio * (self.max_brightness as f64)).round() as u64)
+    }
 
-        let file = OpenOptions::new()
-            .write(true)
-            .open(self.device_path.join("brightness"));
-        if file.is_err() {
-            // TODO: Find a way to issue a non-fatal error, since this is likely
-            // due to a permissions issue and not the fault of the user. It
-            // should not crash the bar.
-            // Error: "Failed to open brightness file for writing"
-            return self.set_brightness_via_dbus(raw);
+    /// Set the brightness value for this backlit device, as a percent.
+    pub fn set_brightness(&self, value: u64) -> Result<()> {
+        self.set_raw_brightness(self.percent_to_raw(value))
+    }
+
+    /// Set the raw brightness value for this backlit device.
+    pub fn set_raw_brightness(&self, raw: u64) -> Result<()> {
+        match self.driver {
+            BacklightDriver::Sysfs => self.set_brightness_via_sysfs(raw),
+            BacklightDriver::Logind => self.set_brightness_via_dbus(raw),
+            // logind allows the active session to set the brightness, while writing the
+            // file usually requires a udev rule
+            _ => self
+                .set_brightness_via_dbus(raw)
+                .or_else(|_| self.set_brightness_via_sysfs(raw)),
         }
+    }
 
-        // It's safe to unwrap() here because we checked for errors above.
-        file.unwrap()
-            .write_fmt(format_args!("{}", raw))
+    fn set_brightness_via_sysfs(&self, raw_value: u64) -> Result<()> {
+        OpenOptions::new()
+            .write(true)
+            .open(self.device_path.join("brightness"))
+            .block_error("backlight", "Failed to open brightness file for writing")?
+            .write_fmt(format_args!("{}", raw_value))
             .block_error("backlight", "Failed to write into brightness file")
     }
 
@@ -157,21 +198,27 @@ impl BacklitDevice {
             .and_then(|x| x.to_str())
             .block_error("backlight", "Malformed device path")?;
 
-        let con = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::System)
-            .block_error("backlight", "Failed to establish D-Bus connection.")?;
-        let msg = dbus::Message::new_method_call(
+        let mut logind = self.logind.lock().unwrap();
+        // Reconnect if the bus went away, e.g. after logind was restarted
+        if !matches!(*logind, Some(ref con) if con.channel().is_connected()) {
+            *logind = Some(
+                Connection::new_system()
+                    .block_error("backlight", "Failed to establish D-Bus connection.")?,
+            );
+        }
+        let con = logind.as_ref().unwrap();
+
+        con.with_proxy(
             "org.freedesktop.login1",
             "/org/freedesktop/login1/session/auto",
+            Duration::from_secs(1),
+        )
+        .method_call::<(), _, _, _>(
             "org.freedesktop.login1.Session",
             "SetBrightness",
+            ("backlight", device_name, raw_value as u32),
         )
-        .block_error("backlight", "Failed to create D-Bus message")?
-        .append2("backlight", device_name)
-        .append1(raw_value as u32);
-
-        con.send_with_reply_and_block(msg, 1000)
-            .block_error("backlight", "Failed to send D-Bus message")
-            .map(|_| ())
+        .block_error("backlight", "Failed to send D-Bus message")
     }
 
     /// The brightness file itself.
@@ -186,27 +233,327 @@ impl BacklitDevice {
     }
 }
 
+/// Selects the monitor `ddcutil` talks to.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum DdcDisplay {
+    /// The display number, as listed by `ddcutil detect`
+    Number(u32),
+    /// The model name, as listed by `ddcutil detect`
+    Model(String),
+}
+
+/// The brightness of a monitor and its maximum, as raw VCP values.
+type DdcBrightness = std::result::Result<(u64, u64), String>;
+
+/// A monitor whose brightness is controlled over DDC/CI using `ddcutil`. Its commands take
+/// a while, so they are run on a thread and the block shows the last known brightness.
+pub struct DdcDevice {
+    brightness: Arc<Mutex<Option<DdcBrightness>>>,
+    tx_set: Sender<u64>,
+}
+
+/// The VCP feature code of the brightness.
+const DDC_BRIGHTNESS: &str = "10";
+
+impl DdcDevice {
+    /// Starts the thread that re-reads the brightness every `interval` seconds, and after it
+    /// was set. The block `id` is updated if the brightness changes.
+    pub fn new(
+        display: Option<DdcDisplay>,
+        interval: Duration,
+        id: String,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let display_args = match display {
+            None => vec![],
+            Some(DdcDisplay::Number(number)) => vec!["--display".to_string(), number.to_string()],
+            Some(DdcDisplay::Model(model)) => vec!["--model".to_string(), model],
+        };
+        let brightness = Arc::new(Mutex::new(None));
+        let (tx_set, rx_set) = unbounded::<u64>();
+
+        let thread_brightness = brightness.clone();
+        thread::Builder::new()
+            .name("backlight".into())
+            .spawn(move || loop {
+                let read = Self::read(&display_args);
+                let changed = {
+                    let mut brightness = thread_brightness
+                        .lock()
+                        .expect("main thread paniced while holding backlight mutex");
+                    let changed = brightness.as_ref() != Some(&read);
+                    *brightness = Some(read);
+                    changed
+                };
+                if changed {
+                    tx_update_request
+                        .send(Task {
+                            id: id.clone(),
+                            update_time: Instant::now(),
+                        })
+                        .expect("unable to send task from backlight thread");
+                }
+
+                match rx_set.recv_timeout(interval) {
+                    Ok(mut raw) => loop {
+                        // Only the latest of several scroll ticks needs to be written
+                        while let Ok(later) = rx_set.try_recv() {
+                            raw = later;
+                        }
+                        Command::new("ddcutil")
+                            .args(&["setvcp", DDC_BRIGHTNESS, &raw.to_string()])
+                            .args(&display_args)
+                            .output()
+                            .ok();
+                        // Reading before the ticks during the write are written would show
+                        // an outdated value
+                        match rx_set.try_recv() {
+                            Ok(later) => raw = later,
+                            Err(_) => break,
+                        }
+                    },
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            })
+            .block_error("backlight", "failed to start ddcutil thread")?;
+
+        Ok(DdcDevice { brightness, tx_set })
+    }
+
+    /// Reads the brightness with `ddcutil`, whose brief output looks like `VCP 10 C 50 100`.
+    fn read(display_args: &[String]) -> DdcBrightness {
+        let output = Command::new("ddcutil")
+            .args(&["getvcp", DDC_BRIGHTNESS, "--brief"])
+            .args(display_args)
+            .output()
+            .map_err(|_| "failed to run ddcutil".to_string())?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let values: Vec<u64> = stdout
+            .split_whitespace()
+            .skip(3)
+            .filter_map(|value| value.parse().ok())
+            .collect();
+        match values[..] {
+            [current, max] if max > 0 => Ok((current, max)),
+            _ => Err(format!(
+                "failed to read the brightness with ddcutil: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+        }
+    }
+
+    /// The last known brightness as a percent, `None` before it was read the first time.
+    pub fn brightness(&self) -> Result<Option<u64>> {
+        match *self
+            .brightness
+            .lock()
+            .block_error("backlight", "mutex poisoned")?
+        {
+            Some(Ok((current, max))) => Ok(Some(std::cmp::min(
+                100,
+                (current as f64 / max as f64 * 100.0).round() as u64,
+            ))),
+            Some(Err(ref e)) => Err(BlockError("backlight".to_string(), e.clone())),
+            None => Ok(None),
+        }
+    }
+
+    /// Sets the brightness as a percent. It's written in the background, until then the new
+    /// value is shown.
+    pub fn set_brightness(&self, value: u64) -> Result<()> {
+        let mut brightness = self
+            .brightness
+            .lock()
+            .block_error("backlight", "mutex poisoned")?;
+        if let Some(Ok((ref mut current, max))) = *brightness {
+            *current = (std::cmp::min(value, 100) as f64 / 100.0 * max as f64).round() as u64;
+            self.tx_set
+                .send(*current)
+                .block_error("backlight", "ddcutil thread stopped")?;
+        }
+        Ok(())
+    }
+}
+
+/// The time between the writes of a transition.
+const TRANSITION_STEP: Duration = Duration::from_millis(25);
+
+/// Ramps the brightness of a backlit device to the raw values sent to it on a thread, so
+/// that the change is smooth.
+struct Transition {
+    tx_target: Sender<u64>,
+    /// Whether the brightness is still on its way to the last target
+    running: Arc<AtomicBool>,
+}
+
+impl Transition {
+    /// Starts the thread that ramps the brightness over `duration`, which updates the block
+    /// `id` after each write.
+    fn new(
+        device: BacklitDevice,
+        duration: Duration,
+        id: String,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let (tx_target, rx_target) = unbounded::<u64>();
+        let running = Arc::new(AtomicBool::new(false));
+        let steps = std::cmp::max(
+            1,
+            (duration.as_millis() / TRANSITION_STEP.as_millis()) as u64,
+        );
+
+        let thread_running = running.clone();
+        thread::Builder::new()
+            .name("backlight".into())
+            .spawn(move || {
+                while let Ok(mut target) = rx_target.recv() {
+                    // A target sent during the transition cancels it, and the brightness
+                    // goes on from where it is to the new target
+                    'transition: loop {
+                        let start = device.raw_brightness().unwrap_or(target) as f64;
+                        for step in 1..=steps {
+                            let raw = start + (target as f64 - start) * step as f64 / steps as f64;
+                            device.set_raw_brightness(raw.round() as u64).ok();
+                            tx_update_request
+                                .send(Task {
+                                    id: id.clone(),
+                                    update_time: Instant::now(),
+                                })
+                                .expect("unable to send task from backlight thread");
+                            match rx_target.recv_timeout(TRANSITION_STEP) {
+                                Ok(later) => {
+                                    target = later;
+                                    continue 'transition;
+                                }
+                                Err(RecvTimeoutError::Timeout) => {}
+                                Err(RecvTimeoutError::Disconnected) => return,
+                            }
+                        }
+                        break;
+                    }
+                    thread_running.store(false, Ordering::SeqCst);
+                }
+            })
+            .block_error("backlight", "failed to start transition thread")?;
+
+        Ok(Transition { tx_target, running })
+    }
+
+    fn start(&self, raw: u64) -> Result<()> {
+        self.running.store(true, Ordering::SeqCst);
+        self.tx_target
+            .send(raw)
+            .block_error("backlight", "transition thread stopped")
+    }
+
+    fn running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+}
+
+/// Where the brightness is read from and written to.
+enum Device {
+    Sysfs(BacklitDevice),
+    Ddc(DdcDevice),
+}
+
+/// The brightness after an exponential scroll tick. The values are taken from a ladder that
+/// starts at `minimum` and grows by `factor`, so ticks up and down return to the same values.
+fn exponential_step(brightness: u64, minimum: u64, factor: f64, up: bool) -> u64 {
+    let mut ladder = vec![minimum];
+    let mut value = std::cmp::max(minimum, 1) as f64;
+    while let Some(&last) = ladder.last().filter(|&&last| last < 100) {
+        value *= factor;
+        // At the low end the factor would round to the same percent, so go up at least by one
+        let rung = std::cmp::min(100, std::cmp::max(value.round() as u64, last + 1));
+        value = value.max(rung as f64);
+        ladder.push(rung);
+    }
+
+    if up {
+        ladder
+            .into_iter()
+            .find(|&rung| rung > brightness)
+            .unwrap_or(100)
+    } else {
+        ladder
+            .into_iter()
+            .rev()
+            .find(|&rung| rung < brightness)
+            .unwrap_or(minimum)
+    }
+}
+
 /// A block for displaying the brightness of a backlit device.
 pub struct Backlight {
     id: String,
     output: ButtonWidget,
-    device: BacklitDevice,
+    device: Device,
     step_width: u64,
+    exponential: bool,
+    minimum: u64,
+    /// The raw value read after the last change by this block, and the percent it was set to.
+    /// Devices with few raw steps read back a different percent, which would break stepping.
+    last_set: Option<(u64, u64)>,
+    transition: Option<Transition>,
     scrolling: Scrolling,
 }
 
+/// How the brightness is controlled.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BacklightDriver {
+    /// Set the brightness of a backlit device through logind, or else sysfs
+    Auto,
+    Sysfs,
+    Logind,
+    Ddc,
+}
+
+impl Default for BacklightDriver {
+    fn default() -> Self {
+        BacklightDriver::Auto
+    }
+}
+
 /// Configuration for the [`Backlight`](./struct.Backlight.html) block.
 #[derive(Deserialize, Debug, Default, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct BacklightConfig {
+    /// `auto`, `sysfs` or `logind` for backlit devices, `ddc` for external monitors.
+    #[serde(default = "BacklightConfig::default_driver")]
+    pub driver: BacklightDriver,
+
     /// The backlight device in `/sys/class/backlight/` to read brightness from.
     #[serde(default = "BacklightConfig::default_device")]
     pub device: Option<String>,
 
+    /// The monitor of the `ddc` driver, a display number or model name.
+    #[serde(default = "BacklightConfig::default_display")]
+    pub display: Option<DdcDisplay>,
+
+    /// How often the `ddc` driver reads the brightness, in seconds.
+    #[serde(
+        default = "BacklightConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
     /// The steps brightness is in/decreased for the selected screen (When greater than 50 it gets limited to 50)
     #[serde(default = "BacklightConfig::default_step_width")]
     pub step_width: u64,
 
+    /// Multiply the brightness by `1 + step_width / 100` for each step instead of adding
+    /// `step_width`, which suits the perceived brightness better at the low end.
+    #[serde(default = "BacklightConfig::default_exponential")]
+    pub exponential: bool,
+
+    /// The lowest brightness in percent that can be set by scrolling
+    #[serde(default = "BacklightConfig::default_minimum")]
+    pub minimum: u64,
+
     /// Scaling exponent reciprocal (ie. root). Some devices expose raw values
     /// that are best handled with nonlinear scaling. The human perception of
     /// lightness is close to the cube root of relative luminance. Settings
@@ -217,23 +564,52 @@ pub struct BacklightConfig {
     #[serde(default = "BacklightConfig::default_root_scaling")]
     pub root_scaling: f64,
 
+    /// Ramp the brightness over this many milliseconds when scrolling, 0 to change it at once.
+    /// Not used by the `ddc` driver.
+    #[serde(default = "BacklightConfig::default_transition_ms")]
+    pub transition_ms: u64,
+
     #[serde(default = "BacklightConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
 
 impl BacklightConfig {
+    fn default_driver() -> BacklightDriver {
+        BacklightDriver::Auto
+    }
+
     fn default_device() -> Option<String> {
         None
     }
 
+    fn default_display() -> Option<DdcDisplay> {
+        None
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(60)
+    }
+
     fn default_step_width() -> u64 {
         5
     }
 
+    fn default_exponential() -> bool {
+        false
+    }
+
+    fn default_minimum() -> u64 {
+        1
+    }
+
     fn default_root_scaling() -> f64 {
         1f64
     }
 
+    fn default_transition_ms() -> u64 {
+        0
+    }
+
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
         None
     }
@@ -247,21 +623,52 @@ impl ConfigBlock for Backlight {
         config: Config,
         tx_update_request: Sender<Task>,
     ) -> Result<Self> {
-        let device = match block_config.device {
-            Some(path) => BacklitDevice::from_device(path, block_config.root_scaling),
-            None => BacklitDevice::default(block_config.root_scaling),
-        }?;
-
         let id = pseudo_uuid();
-        let brightness_file = device.brightness_file();
+        let device = match block_config.driver {
+            BacklightDriver::Ddc => Device::Ddc(DdcDevice::new(
+                block_config.display,
+                block_config.interval,
+                id.clone(),
+                tx_update_request.clone(),
+            )?),
+            driver => Device::Sysfs(
+                match block_config.device {
+                    Some(path) => BacklitDevice::from_device(path, block_config.root_scaling),
+                    None => BacklitDevice::default(block_config.root_scaling),
+                }?
+                .with_driver(driver),
+            ),
+        };
+        let brightness_file = match device {
+            Device::Sysfs(ref device) => Some(device.brightness_file()),
+            Device::Ddc(_) => None,
+        };
+        let transition = match device {
+            Device::Sysfs(ref device) if block_config.transition_ms > 0 => Some(Transition::new(
+                device.clone(),
+                Duration::from_millis(block_config.transition_ms),
+                id.clone(),
+                tx_update_request.clone(),
+            )?),
+            _ => None,
+        };
 
-        let scrolling = config.scrolling;
         let backlight = Backlight {
-            output: ButtonWidget::new(config, &id),
+            output: ButtonWidget::new(config.clone(), &id),
             id: id.clone(),
             device,
             step_width: block_config.step_width,
-            scrolling,
+            exponential: block_config.exponential,
+            minimum: std::cmp::min(block_config.minimum, 100),
+            last_set: None,
+            transition,
+            scrolling: config.scrolling,
+        };
+
+        // The thread of the DDC device requests its updates itself
+        let brightness_file = match brightness_file {
+            Some(brightness_file) => brightness_file,
+            None => return Ok(backlight),
         };
 
         // Spin up a thread to watch for changes to the brightness file for the
@@ -299,9 +706,57 @@ impl ConfigBlock for Backlight {
     }
 }
 
+impl Backlight {
+    /// The brightness as a percent, or the percent set by this block if it wasn't changed since.
+    /// `None` if the brightness of a DDC device wasn't read yet.
+    fn brightness(&self) -> Result<Option<u64>> {
+        let device = match self.device {
+            Device::Sysfs(ref device) => device,
+            Device::Ddc(ref device) => return device.brightness(),
+        };
+        let raw = device.raw_brightness()?;
+        Ok(Some(match self.last_set {
+            Some((last_raw, percent)) if last_raw == raw => percent,
+            _ => device.raw_to_percent(raw),
+        }))
+    }
+
+    fn set_brightness(&mut self, value: u64) -> Result<()> {
+        match self.device {
+            Device::Sysfs(ref device) => {
+                if let Some(ref transition) = self.transition {
+                    let raw = device.percent_to_raw(value);
+                    transition.start(raw)?;
+                    self.last_set = Some((raw, value));
+                    return Ok(());
+                }
+                device.set_brightness(value)?;
+                self.last_set = Some((device.raw_brightness()?, value));
+                Ok(())
+            }
+            Device::Ddc(ref device) => device.set_brightness(value),
+        }
+    }
+
+    /// The brightness after a scroll tick.
+    fn step(&self, brightness: u64, up: bool) -> u64 {
+        if self.exponential {
+            let factor = 1.0 + self.step_width as f64 / 100.0;
+            exponential_step(brightness, self.minimum, factor, up)
+        } else if up {
+            std::cmp::min(brightness + self.step_width, 100)
+        } else {
+            std::cmp::max(brightness.saturating_sub(self.step_width), self.minimum)
+        }
+    }
+}
+
 impl Block for Backlight {
     fn update(&mut self) -> Result<Option<Update>> {
-        let brightness = self.device.brightness()?;
+        let brightness = match self.brightness()? {
+            Some(brightness) => brightness,
+            None => return Ok(None),
+        };
         self.output.set_text(format!("{}%", brightness));
         match brightness {
             0..=19 => self.output.set_icon("backlight_empty"),
@@ -320,20 +775,25 @@ impl Block for Backlight {
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
         if let Some(ref name) = event.name {
             if name.as_str() == self.id {
-                let brightness = self.device.brightness()?;
+                let target = match (&self.transition, self.last_set) {
+                    // Scrolling again during a transition steps on from its target
+                    (Some(transition), Some((_, percent))) if transition.running() => Some(percent),
+                    _ => self.brightness()?,
+                };
+                let brightness = match target {
+                    Some(brightness) => brightness,
+                    None => return Ok(()),
+                };
                 use LogicalDirection::*;
-                match self.scrolling.to_logical_direction(event.button) {
-                    Some(Up) => {
-                        if brightness < 100 {
-                            self.device.set_brightness(brightness + self.step_width)?;
-                        }
-                    }
-                    Some(Down) => {
-                        if brightness > self.step_width {
-                            self.device.set_brightness(brightness - self.step_width)?;
-                        }
-                    }
-                    None => {}
+                let new_brightness = match self.scrolling.to_logical_direction(event.button) {
+                    Some(Up) if brightness < 100 => self.step(brightness, true),
+                    Some(Down) if brightness > self.minimum => self.step(brightness, false),
+                    _ => return Ok(()),
+                };
+                self.set_brightness(new_brightness)?;
+                if let Device::Ddc(_) = self.device {
+                    // There is no inotify event for the new value
+                    self.update()?;
                 }
             }
         }
@@ -345,3 +805,30 @@ impl Block for Backlight {
         &self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::exponential_step;
+
+    #[test]
+    fn test_exponential_step() {
+        assert_eq!(exponential_step(1, 1, 1.5, true), 2);
+        assert_eq!(exponential_step(2, 1, 1.5, true), 3);
+        assert_eq!(exponential_step(3, 1, 1.5, true), 5);
+        assert_eq!(exponential_step(5, 1, 1.5, false), 3);
+        assert_eq!(exponential_step(4, 1, 1.5, false), 3);
+        assert_eq!(exponential_step(93, 1, 1.5, true), 100);
+        assert_eq!(exponential_step(1, 1, 1.5, false), 1);
+        assert_eq!(exponential_step(1, 0, 1.0, false), 0);
+
+        // Going up and down returns to the same values
+        let mut brightness = 7;
+        for _ in 0..3 {
+            brightness = exponential_step(brightness, 5, 1.2, true);
+        }
+        for _ in 0..3 {
+            brightness = exponential_step(brightness, 5, 1.2, false);
+        }
+        assert_eq!(brightness, 7);
+    }
+}
@@ -4,7 +4,7 @@
 //! display the status, capacity, and time remaining for (dis)charge for an
 //! internal power supply.
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 use std::thread;
 use std::time::{Duration, Instant};
@@ -20,7 +20,7 @@ use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::scheduler::Task;
 use crate::util::{
-    battery_level_to_icon, format_percent_bar, pseudo_uuid, read_file, FormatTemplate,
+    battery_level_to_icon, format_percent_bar, pseudo_uuid, read_file, FormatTemplate, Formats,
 };
 use crate::widget::{I3BarWidget, Spacing, State};
 use crate::widgets::text::TextWidget;
@@ -333,11 +333,15 @@ impl UpowerDevice {
                 "org.freedesktop.UPower",
                 "EnumerateDevices",
             )
-            .unwrap();
-            let dbus_reply = con.send_with_reply_and_block(msg, 2000).unwrap();
+            .block_error("battery", "Failed to create D-Bus message.")?;
+            let dbus_reply = con
+                .send_with_reply_and_block(msg, 2000)
+                .block_error("battery", "Failed to enumerate UPower devices.")?;
 
             // EnumerateDevices returns one argument, which is an array of ObjectPaths (not dbus::tree:ObjectPath).
-            let mut paths: Array<dbus::Path, _> = dbus_reply.get1().unwrap();
+            let mut paths: Array<dbus::Path, _> = dbus_reply
+                .get1()
+                .block_error("battery", "Failed to read UPower devices.")?;
             let path = paths.find(|entry| entry.ends_with(device));
             if path.is_none() {
                 return Err(BlockError(
@@ -406,7 +410,10 @@ impl UpowerDevice {
 
 impl BatteryDevice for UpowerDevice {
     fn is_available(&self) -> bool {
-        true // TODO: has to be implemented for UPower
+        self.con
+            .with_path("org.freedesktop.UPower", &self.device_path, 1000)
+            .get("org.freedesktop.UPower.Device", "IsPresent")
+            .unwrap_or(false)
     }
 
     fn refresh_device_info(&mut self) -> Result<()> {
@@ -490,6 +497,7 @@ pub struct Battery {
     info: u64,
     warning: u64,
     critical: u64,
+    icons: HashMap<String, String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -528,7 +536,7 @@ pub struct BatteryConfig {
     /// Format string for displaying battery information.
     /// placeholders: {percentage}, {bar}, {time} and {power}
     #[serde(default = "BatteryConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
 
     /// Format string for displaying battery information when battery is full.
     /// placeholders: {percentage}, {bar}, {time} and {power}
@@ -545,6 +553,7 @@ pub struct BatteryConfig {
     pub upower: bool,
 
     /// The "driver" to use for powering the block. One of "sysfs" or "upower".
+    /// Defaults to sysfs, or to UPower if the device is only known to UPower.
     pub driver: Option<BatteryDriver>,
 
     /// The threshold above which the remaining capacity is shown as good
@@ -563,7 +572,7 @@ pub struct BatteryConfig {
     #[serde(default = "BatteryConfig::default_critical")]
     pub critical: u64,
 
-    /// If the battery device cannot be found, do not fail and show the block anyway (sysfs only).
+    /// If the battery device cannot be found, do not fail and show the block anyway.
     #[serde(default = "BatteryConfig::default_allow_missing")]
     pub allow_missing: bool,
 
@@ -584,7 +593,7 @@ impl BatteryConfig {
         "BAT0".to_string()
     }
 
-    fn default_format() -> String {
+    fn default_format() -> Formats {
         "{percentage}%".into()
     }
 
@@ -637,6 +646,7 @@ impl ConfigBlock for Battery {
         config: Config,
         update_request: Sender<Task>,
     ) -> Result<Self> {
+        let markup = config.markup;
         // TODO: remove deprecated show types eventually
         let format = match block_config.show {
             Some(show) => match show.as_ref() {
@@ -652,19 +662,36 @@ impl ConfigBlock for Battery {
 
         // TODO: Remove the deprecated upower config eventually.
         let driver = match block_config.driver {
-            Some(val) => val,
-            None if block_config.upower => BatteryDriver::Upower,
-            _ => BatteryDriver::Sysfs,
+            Some(val) => Some(val),
+            None if block_config.upower => Some(BatteryDriver::Upower),
+            _ => None,
         };
 
         let id = pseudo_uuid();
-        let device: Box<dyn BatteryDevice> = match driver {
-            BatteryDriver::Upower => {
-                let out = UpowerDevice::from_device(&block_config.device)?;
+        let upower_device = match driver {
+            Some(BatteryDriver::Upower) => Some(UpowerDevice::from_device(&block_config.device)?),
+            Some(BatteryDriver::Sysfs) => None,
+            // Some batteries (e.g. of bluetooth peripherals) are missing from sysfs but known
+            // to UPower, so fall back to it if the device is missing.
+            None => {
+                if PowerSupplyDevice::from_device(&block_config.device, true)?.is_available() {
+                    None
+                } else {
+                    UpowerDevice::from_device(&block_config.device).ok()
+                }
+            }
+        };
+        let driver = match upower_device {
+            Some(_) => BatteryDriver::Upower,
+            None => BatteryDriver::Sysfs,
+        };
+
+        let device: Box<dyn BatteryDevice> = match upower_device {
+            Some(out) => {
                 out.monitor(id.clone(), update_request);
                 Box::new(out)
             }
-            BatteryDriver::Sysfs => Box::new(PowerSupplyDevice::from_device(
+            None => Box::new(PowerSupplyDevice::from_device(
                 &block_config.device,
                 block_config.allow_missing,
             )?),
@@ -673,11 +700,14 @@ impl ConfigBlock for Battery {
         Ok(Battery {
             id,
             update_interval: block_config.interval,
+            icons: config.icons.clone(),
             output: TextWidget::new(config),
             device,
-            format: FormatTemplate::from_string(&format)?,
-            full_format: FormatTemplate::from_string(&block_config.full_format)?,
-            missing_format: FormatTemplate::from_string(&block_config.missing_format)?,
+            format: FormatTemplate::from_formats(&format)?.with_markup(markup),
+            full_format: FormatTemplate::from_string(&block_config.full_format)?
+                .with_markup(markup),
+            missing_format: FormatTemplate::from_string(&block_config.missing_format)?
+                .with_markup(markup),
             allow_missing: block_config.allow_missing,
             hide_missing: block_config.hide_missing,
             driver,
@@ -689,6 +719,18 @@ impl ConfigBlock for Battery {
     }
 }
 
+impl Battery {
+    /// Uses the icon if the icon set has it, or else the generic battery icon.
+    fn set_icon(&mut self, name: &str) {
+        let name = if self.icons.contains_key(name) {
+            name
+        } else {
+            "bat"
+        };
+        self.output.set_icon(name);
+    }
+}
+
 impl Block for Battery {
     fn update(&mut self) -> Result<Option<Update>> {
         // TODO: Maybe use dbus to immediately signal when the battery state changes.
@@ -706,7 +748,7 @@ impl Block for Battery {
                 "{power}" => "N/A"
             );
 
-            self.output.set_icon("bat_not_available");
+            self.set_icon("bat_not_available");
             self.output
                 .set_text(self.missing_format.render_static_str(&values)?);
             self.output.set_state(State::Warning);
@@ -749,7 +791,7 @@ impl Block for Battery {
                             "{power}" => power);
 
         if status == "Full" || status == "Not charging" {
-            self.output.set_icon("bat_full");
+            self.set_icon("bat_full");
             self.output
                 .set_text(self.full_format.render_static_str(&values)?);
             self.output.set_state(State::Good);
@@ -784,11 +826,10 @@ impl Block for Battery {
                 }
             }
 
-            self.output.set_icon(match status.as_str() {
-                "Discharging" => battery_level_to_icon(capacity),
-                "Charging" => "bat_charging",
-                _ => battery_level_to_icon(capacity),
-            });
+            match status.as_str() {
+                "Charging" => self.set_icon("bat_charging"),
+                _ => self.set_icon(battery_level_to_icon(capacity)),
+            }
             self.output.set_spacing(Spacing::Normal);
         }
 
@@ -810,4 +851,8 @@ impl Block for Battery {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
 }
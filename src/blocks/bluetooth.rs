@@ -16,7 +16,9 @@ use crate::widget::{I3BarWidget, State};
 use crate::widgets::button::ButtonWidget;
 
 pub struct BluetoothDevice {
-    pub path: String,
+    mac: String,
+    /// `None` while the device isn't paired or BlueZ isn't running
+    pub path: Option<String>,
     pub icon: Option<String>,
     pub label: String,
     con: dbus::ffidisp::Connection,
@@ -27,73 +29,84 @@ impl BluetoothDevice {
         let con = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::System)
             .block_error("bluetooth", "Failed to establish D-Bus connection.")?;
 
+        let mut device = BluetoothDevice {
+            mac,
+            path: None,
+            icon: None,
+            label: label.unwrap_or_else(|| "".to_string()),
+            con,
+        };
+        device.find();
+        Ok(device)
+    }
+
+    /// Looks up the object path of the device. A device that isn't paired doesn't have one,
+    /// which isn't an error, the block just waits for it to show up.
+    pub fn find(&mut self) {
         // Bluez does not provide a convenient way to, say, list devices, so we
         // have to employ a rather verbose workaround.
-
-        let objects = con
+        let objects = match self
+            .con
             .with_path("org.bluez", "/", 1000)
             .get_managed_objects()
-            .block_error("bluetooth", "Failed to get managed objects from org.bluez.")?;
+        {
+            Ok(objects) => objects,
+            Err(_) => {
+                self.path = None;
+                return;
+            }
+        };
 
-        let devices: Vec<(dbus::Path, String)> = objects
+        let mac = &self.mac;
+        self.path = objects
             .into_iter()
-            .filter(|(_, interfaces)| interfaces.contains_key("org.bluez.Device1"))
-            .map(|(path, interfaces)| {
-                let props = interfaces.get("org.bluez.Device1").unwrap();
-                // This could be made safer; however, this is the documented
-                // D-Bus API format, so it's not a terrible idea to panic if it
-                // is violated.
-                let address: String = props
-                    .get("Address")
-                    .unwrap()
-                    .0
-                    .as_str()
-                    .unwrap()
-                    .to_string();
-                (path, address)
+            .find(|(_, interfaces)| {
+                interfaces
+                    .get("org.bluez.Device1")
+                    .and_then(|props| props.get("Address"))
+                    .and_then(|address| address.0.as_str().map(|address| address == mac))
+                    .unwrap_or(false)
             })
-            .collect();
-
-        // If we need to suppress errors from missing devices, this is the place
-        // to do it. We could also pick the "default" device here, although that
-        // does not make much sense to me in the context of Bluetooth.
-
-        let path = devices
-            .into_iter()
-            .filter(|(_, address)| address == &mac)
-            .map(|(path, _)| path)
-            .next()
-            .block_error(
-                "bluetooth",
-                "Failed find a device with matching MAC address.",
-            )?
-            .to_string();
+            .map(|(path, _)| path.to_string());
 
         // Swallow errors, since this is optional.
-        let icon: Option<String> = con
-            .with_path("org.bluez", &path, 1000)
-            .get("org.bluez.Device1", "Icon")
-            .ok();
-
-        Ok(BluetoothDevice {
-            path,
-            icon,
-            label: label.unwrap_or_else(|| "".to_string()),
-            con,
-        })
+        self.icon = self.path.as_ref().and_then(|path| {
+            self.con
+                .with_path("org.bluez", path, 1000)
+                .get("org.bluez.Device1", "Icon")
+                .ok()
+        });
+    }
+
+    /// Whether the device is paired, looking it up again if it was removed or not known yet.
+    pub fn available(&mut self) -> bool {
+        let paired = self.path.as_ref().map_or(false, |path| {
+            self.con
+                .with_path("org.bluez", path, 1000)
+                .get::<String>("org.bluez.Device1", "Address")
+                .is_ok()
+        });
+        if !paired {
+            self.find();
+        }
+        self.path.is_some()
     }
 
     pub fn battery(&self) -> Option<u8> {
         // Swallow errors here; not all devices implement this API.
         self.con
-            .with_path("org.bluez", &self.path, 1000)
+            .with_path("org.bluez", self.path.as_ref()?, 1000)
             .get("org.bluez.Battery1", "Percentage")
             .ok()
     }
 
     pub fn connected(&self) -> bool {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return false,
+        };
         self.con
-            .with_path("org.bluez", &self.path, 1000)
+            .with_path("org.bluez", path, 1000)
             .get("org.bluez.Device1", "Connected")
             // In the case that the D-Bus interface missing or responds
             // incorrectly, it seems reasonable to treat the device as "down"
@@ -102,14 +115,17 @@ impl BluetoothDevice {
     }
 
     pub fn toggle(&self) -> Result<()> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
         let method = if self.connected() {
             "Disconnect"
         } else {
             "Connect"
         };
-        let msg =
-            dbus::Message::new_method_call("org.bluez", &self.path, "org.bluez.Device1", method)
-                .block_error("bluetooth", "Failed to build D-Bus method.")?;
+        let msg = dbus::Message::new_method_call("org.bluez", path, "org.bluez.Device1", method)
+            .block_error("bluetooth", "Failed to build D-Bus method.")?;
 
         // Swallow errors rather than nuke the bar.
         let _ = self.con.send(msg);
@@ -117,27 +133,31 @@ impl BluetoothDevice {
     }
 
     /// Monitor Bluetooth property changes in a separate thread and send updates
-    /// via the `update_request` channel.
+    /// via the `update_request` channel. Devices being paired or removed are
+    /// noticed by the signals of the object manager.
     pub fn monitor(&self, id: String, update_request: Sender<Task>) {
-        let path = self.path.clone();
         thread::Builder::new()
             .name("bluetooth".into())
             .spawn(move || {
                 let con = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::System)
                     .expect("Failed to establish D-Bus connection.");
-                let rule = format!(
+                let rules = [
+                    "type='signal',\
+                     sender='org.bluez',\
+                     interface='org.freedesktop.DBus.Properties',\
+                     member='PropertiesChanged'",
                     "type='signal',\
-                 path='{}',\
-                 interface='org.freedesktop.DBus.Properties',\
-                 member='PropertiesChanged'",
-                    path
-                );
+                     sender='org.bluez',\
+                     interface='org.freedesktop.DBus.ObjectManager'",
+                ];
 
                 // Skip the NameAcquired event.
                 con.incoming(10_000).next();
 
-                con.add_match(&rule)
-                    .expect("Failed to add D-Bus match rule.");
+                for rule in &rules {
+                    con.add_match(rule)
+                        .expect("Failed to add D-Bus match rule.");
+                }
 
                 loop {
                     if con.incoming(10_000).next().is_some() {
@@ -154,11 +174,24 @@ impl BluetoothDevice {
     }
 }
 
+/// The icon for the kind of device, or the generic Bluetooth symbol.
+fn device_icon(icon: Option<&str>) -> &'static str {
+    match icon {
+        Some("audio-card") => "headphones",
+        Some("input-gaming") => "joystick",
+        Some("input-keyboard") => "keyboard",
+        Some("input-mouse") => "mouse",
+        _ => "bluetooth",
+    }
+}
+
 pub struct Bluetooth {
     id: String,
     output: ButtonWidget,
     device: BluetoothDevice,
     hide_disconnected: bool,
+    /// Whether the device was connected at the last update
+    connected: bool,
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -192,15 +225,10 @@ impl ConfigBlock for Bluetooth {
 
         Ok(Bluetooth {
             id: id.clone(),
-            output: ButtonWidget::new(config, &id).with_icon(match device.icon {
-                Some(ref icon) if icon == "audio-card" => "headphones",
-                Some(ref icon) if icon == "input-gaming" => "joystick",
-                Some(ref icon) if icon == "input-keyboard" => "keyboard",
-                Some(ref icon) if icon == "input-mouse" => "mouse",
-                _ => "bluetooth",
-            }),
+            output: ButtonWidget::new(config, &id).with_icon(device_icon(device.icon.as_deref())),
             device,
             hide_disconnected: block_config.hide_disconnected,
+            connected: false,
         })
     }
 }
@@ -211,20 +239,26 @@ impl Block for Bluetooth {
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        let connected = self.device.connected();
-        self.output.set_text(self.device.label.to_string());
+        // A device that isn't paired is shown like a disconnected one
+        let available = self.device.available();
         self.output
-            .set_state(if connected { State::Good } else { State::Idle });
+            .set_icon(device_icon(self.device.icon.as_deref()));
+        self.connected = available && self.device.connected();
+        self.output.set_text(self.device.label.to_string());
+        self.output.set_state(if self.connected {
+            State::Good
+        } else {
+            State::Idle
+        });
 
         // Use battery info, when available.
-        if let Some(value) = self.device.battery() {
-            self.output.set_state(match value {
-                0..=15 => State::Critical,
-                16..=30 => State::Warning,
-                31..=60 => State::Info,
-                61..=100 => State::Good,
-                _ => State::Warning,
-            });
+        if let Some(value) = self.device.battery().filter(|_| self.connected) {
+            // Only a low battery changes the state
+            match value {
+                0..=15 => self.output.set_state(State::Critical),
+                16..=30 => self.output.set_state(State::Warning),
+                _ => {}
+            }
             self.output
                 .set_text(format!("{} {}%", self.device.label, value));
         }
@@ -235,7 +269,7 @@ impl Block for Bluetooth {
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
         if let Some(ref name) = event.name {
             if name.as_str() == self.id {
-                if let MouseButton::Right = event.button {
+                if let MouseButton::Left | MouseButton::Right = event.button {
                     self.device.toggle()?;
                 }
             }
@@ -244,7 +278,7 @@ impl Block for Bluetooth {
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        if !self.device.connected() && self.hide_disconnected {
+        if !self.connected && self.hide_disconnected {
             vec![]
         } else {
             vec![&self.output]
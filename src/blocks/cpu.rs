@@ -2,29 +2,37 @@ use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::Config;
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_opt_duration};
 use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::subprocess::spawn_child_async;
-use crate::util::{format_percent_bar, pseudo_uuid, FormatTemplate};
+use crate::util::{format_percent_bar, pseudo_uuid, read_file, FormatTemplate, Formats};
 use crate::widget::{I3BarWidget, State};
 use crate::widgets::button::ButtonWidget;
+use crate::widgets::graph::GraphWidget;
 
 /// Maximum number of CPUs we support.
 const MAX_CPUS: usize = 32;
 
+/// The shortest time the utilization is measured over without a `sampling_window`, so that
+/// the first value right after the start is meaningful.
+const MIN_SAMPLE: Duration = Duration::from_millis(200);
+
 pub struct Cpu {
     output: ButtonWidget,
-    prev_idles: [u64; MAX_CPUS],
-    prev_non_idles: [u64; MAX_CPUS],
+    /// The idle and non-idle time of all CPUs and then each one at the start of the sample
+    prev_times: Vec<(u64, u64)>,
+    /// When the current sample started, `None` until the next one is started
+    sample_start: Option<Instant>,
+    sampling_window: Option<Duration>,
     id: String,
     update_interval: Duration,
     minimum_info: u64,
@@ -34,6 +42,7 @@ pub struct Cpu {
     format: FormatTemplate,
     has_barchart: bool,
     has_frequency: bool,
+    graph: Option<GraphWidget>,
     per_core: bool,
 }
 
@@ -47,6 +56,10 @@ pub struct CpuConfig {
     )]
     pub interval: Duration,
 
+    /// Measure the utilization over this time before each update, instead of since the last one
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub sampling_window: Option<Duration>,
+
     /// Minimum usage, where state is set to info
     #[serde(default = "CpuConfig::default_info")]
     pub info: u64,
@@ -62,13 +75,26 @@ pub struct CpuConfig {
     #[serde(default = "CpuConfig::default_on_click")]
     pub on_click: Option<String>,
 
-    /// Display frequency
+    /// Whether the frequencies are read, by default if the format shows them. `true` is
+    /// deprecated and also sets the format.
     #[serde(default = "CpuConfig::default_frequency")]
-    pub frequency: bool,
+    pub frequency: Option<bool>,
 
     /// Format override
     #[serde(default = "CpuConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
+
+    /// Number of values shown by the `{graph}` placeholder.
+    #[serde(default = "CpuConfig::default_graph_length")]
+    pub graph_length: usize,
+
+    /// Utilization corresponding to the lowest bar of the `{graph}` placeholder.
+    #[serde(default = "CpuConfig::default_graph_min")]
+    pub graph_min: u64,
+
+    /// Utilization corresponding to the highest bar of the `{graph}` placeholder.
+    #[serde(default = "CpuConfig::default_graph_max")]
+    pub graph_max: u64,
 
     /// Compute the metrics (utilization and frequency) per core.
     #[serde(default)]
@@ -79,8 +105,8 @@ pub struct CpuConfig {
 }
 
 impl CpuConfig {
-    fn default_format() -> String {
-        "{utilization}%".to_owned()
+    fn default_format() -> Formats {
+        "{utilization}%".into()
     }
 
     fn default_interval() -> Duration {
@@ -99,8 +125,20 @@ impl CpuConfig {
         90
     }
 
-    fn default_frequency() -> bool {
-        false
+    fn default_frequency() -> Option<bool> {
+        None
+    }
+
+    fn default_graph_length() -> usize {
+        10
+    }
+
+    fn default_graph_min() -> u64 {
+        0
+    }
+
+    fn default_graph_max() -> u64 {
+        100
     }
 
     fn default_on_click() -> Option<String> {
@@ -120,28 +158,67 @@ impl ConfigBlock for Cpu {
         config: Config,
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
-        let format = if block_config.frequency {
+        let markup = config.markup;
+        if let Some(window) = block_config.sampling_window {
+            if window >= block_config.interval {
+                return Err(ConfigurationError(
+                    "cpu".to_string(),
+                    (
+                        "sampling_window must be shorter than interval".to_string(),
+                        "invalid sampling_window".to_string(),
+                    ),
+                ));
+            }
+        }
+
+        if block_config.graph_min >= block_config.graph_max {
+            return Err(ConfigurationError(
+                "cpu".to_string(),
+                (
+                    "graph_min must be less than graph_max".to_string(),
+                    "invalid graph range".to_string(),
+                ),
+            ));
+        }
+
+        let format = if block_config.frequency == Some(true) {
             "{utilization}% {frequency}GHz".into()
         } else if block_config.per_core {
-            "{utilization}".to_owned()
+            "{utilization}".into()
         } else {
             block_config.format
         };
 
         let id = pseudo_uuid();
 
+        let graph = if format.contains("{graph}") {
+            Some(
+                GraphWidget::new(config.clone())
+                    .with_length(block_config.graph_length)
+                    .with_range(Some(block_config.graph_min), Some(block_config.graph_max)),
+            )
+        } else {
+            None
+        };
+
         Ok(Cpu {
             id: id.clone(),
             update_interval: block_config.interval,
             output: ButtonWidget::new(config, &id).with_icon("cpu"),
-            prev_idles: [0; MAX_CPUS],
-            prev_non_idles: [0; MAX_CPUS],
+            graph,
+            // The first sample starts right away, so that the first update doesn't measure
+            // the utilization since boot
+            prev_times: read_cpu_times()?,
+            sample_start: Some(Instant::now()),
+            sampling_window: block_config.sampling_window,
             minimum_info: block_config.info,
             minimum_warning: block_config.warning,
             minimum_critical: block_config.critical,
-            format: FormatTemplate::from_string(&format)
-                .block_error("cpu", "Invalid format specified for cpu")?,
-            has_frequency: format.contains("{frequency}"),
+            format: FormatTemplate::from_formats(&format)
+                .block_error("cpu", "Invalid format specified for cpu")?
+                .with_markup(markup),
+            has_frequency: block_config.frequency != Some(false)
+                && (format.contains("{frequency}") || format.contains("{max_frequency}")),
             has_barchart: format.contains("{barchart}"),
             per_core: block_config.per_core,
             on_click: block_config.on_click,
@@ -151,80 +228,47 @@ impl ConfigBlock for Cpu {
 
 impl Block for Cpu {
     fn update(&mut self) -> Result<Option<Update>> {
-        let f = File::open("/proc/stat")
-            .block_error("cpu", "Your system doesn't support /proc/stat")?;
-        let f = BufReader::new(f);
-
-        let mut cpu_freqs: [f32; MAX_CPUS] = [0.0; MAX_CPUS];
-        let mut n_cpu = 0;
-        if self.has_frequency {
-            let freq_file =
-                File::open("/proc/cpuinfo").block_error("cpu", "failed to read /proc/cpuinfo")?;
-            let freq_file_content = BufReader::new(freq_file);
-            // read frequency of each cpu and calculate the average which we will display
-            for line in freq_file_content.lines().scan((), |_, x| x.ok()) {
-                if line.starts_with("cpu MHz") {
-                    let words = line.split(' ');
-                    let last = words
-                        .last()
-                        .expect("failed to get last word of line while getting cpu frequency");
-                    let numb = last
-                        .parse::<f32>()
-                        .expect("failed to parse String to f32 while getting cpu frequency");
-                    cpu_freqs[n_cpu] = numb;
-                    n_cpu += 1;
-                    if n_cpu >= MAX_CPUS {
-                        break;
-                    };
-                }
+        let window = self.sampling_window.unwrap_or(MIN_SAMPLE);
+        let elapsed = match self.sample_start {
+            Some(start) => start.elapsed(),
+            None => {
+                // Only the time right before the update is measured
+                self.prev_times = read_cpu_times()?;
+                self.sample_start = Some(Instant::now());
+                return Ok(Some(window.into()));
             }
+        };
+        // The scheduler may wake us up slightly before the sample is complete
+        if elapsed + Duration::from_millis(10) < window {
+            return Ok(Some((window - elapsed).into()));
         }
 
+        let times = read_cpu_times()?;
+        let cpu_i = times.len();
         let mut cpu_utilizations: [f64; MAX_CPUS] = [0.0; MAX_CPUS];
-        let mut cpu_i = 0;
-        for line in f.lines().scan((), |_, x| x.ok()) {
-            if line.starts_with("cpu") {
-                let data: Vec<u64> = (&line)
-                    .split(' ')
-                    .collect::<Vec<&str>>()
-                    .iter()
-                    .skip(if cpu_i == 0 { 2 } else { 1 })
-                    .filter_map(|x| x.parse::<u64>().ok())
-                    .collect::<Vec<_>>();
-
-                // idle = idle + iowait
-                let idle = data[3] + data[4];
-                let non_idle = data[0] + // user
-                                data[1] + // nice
-                                data[2] + // system
-                                data[5] + // irq
-                                data[6] + // softirq
-                                data[7]; // steal
-
-                let prev_total = self.prev_idles[cpu_i] + self.prev_non_idles[cpu_i];
-                let total = idle + non_idle;
-
-                // This check is needed because the new values may be reset, for
-                // example after hibernation.
-
-                let (total_delta, idle_delta) =
-                    if prev_total < total && self.prev_idles[cpu_i] <= idle {
-                        (total - prev_total, idle - self.prev_idles[cpu_i])
-                    } else {
-                        (1, 1)
-                    };
-
-                cpu_utilizations[cpu_i] = (total_delta - idle_delta) as f64 / total_delta as f64;
-
-                self.prev_idles[cpu_i] = idle;
-                self.prev_non_idles[cpu_i] = non_idle;
-                cpu_i += 1;
-                if cpu_i >= MAX_CPUS {
-                    break;
-                };
-            }
+        for (i, &(idle, non_idle)) in times.iter().enumerate() {
+            let (prev_idle, prev_non_idle) = self.prev_times.get(i).cloned().unwrap_or((0, 0));
+            let prev_total = prev_idle + prev_non_idle;
+            let total = idle + non_idle;
+
+            // This check is needed because the new values may be reset, for
+            // example after hibernation.
+
+            let (total_delta, idle_delta) = if prev_total < total && prev_idle <= idle {
+                (total - prev_total, idle - prev_idle)
+            } else {
+                (1, 1)
+            };
+
+            cpu_utilizations[i] = (total_delta - idle_delta) as f64 / total_delta as f64;
         }
 
+        let cpu_freqs = if self.has_frequency {
+            read_frequencies()
+        } else {
+            Vec::new()
+        };
+
         let avg_utilization = (100.0 * cpu_utilizations[0]) as u64;
 
         self.output.set_state(match avg_utilization {
@@ -248,15 +292,47 @@ impl Block for Cpu {
                 );
             }
         }
-        let values = map!("{frequency}" => format_frequency(&cpu_freqs, n_cpu, self.per_core),
+        let graph = match self.graph {
+            Some(ref mut graph) => {
+                graph.push_value(avg_utilization);
+                graph.graph()
+            }
+            None => String::new(),
+        };
+
+        let max_frequency = if cpu_freqs.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "{:.1}GHz",
+                cpu_freqs.iter().cloned().fold(0.0, f32::max) / 1000.0
+            )
+        };
+
+        let values = map!("{frequency}" => format_frequency(&cpu_freqs, self.per_core),
+                          "{max_frequency}" => max_frequency,
                           "{barchart}" => barchart,
+                          "{graph}" => graph,
                           "{utilization}" => format_utilization(&cpu_utilizations, cpu_i, self.per_core),
                           "{utilizationbar}" => format_percent_bar(avg_utilization as f32));
 
         self.output
             .set_text(self.format.render_static_str(&values)?);
 
-        Ok(Some(self.update_interval.into()))
+        if self.sampling_window.is_some() {
+            // The next sample starts in time to end with the next update
+            self.sample_start = None;
+            Ok(Some(
+                self.update_interval
+                    .checked_sub(window)
+                    .unwrap_or_default()
+                    .into(),
+            ))
+        } else {
+            self.prev_times = times;
+            self.sample_start = Some(Instant::now());
+            Ok(Some(self.update_interval.into()))
+        }
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
@@ -278,6 +354,44 @@ impl Block for Cpu {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+}
+
+/// Reads the idle and non-idle time of all CPUs together and then of each one.
+fn read_cpu_times() -> Result<Vec<(u64, u64)>> {
+    let f =
+        File::open("/proc/stat").block_error("cpu", "Your system doesn't support /proc/stat")?;
+    let f = BufReader::new(f);
+
+    let mut times = Vec::new();
+    for line in f.lines().scan((), |_, x| x.ok()) {
+        if line.starts_with("cpu") {
+            let data: Vec<u64> = (&line)
+                .split(' ')
+                .collect::<Vec<&str>>()
+                .iter()
+                .skip(if times.is_empty() { 2 } else { 1 })
+                .filter_map(|x| x.parse::<u64>().ok())
+                .collect::<Vec<_>>();
+
+            // idle = idle + iowait
+            let idle = data[3] + data[4];
+            let non_idle = data[0] + // user
+                            data[1] + // nice
+                            data[2] + // system
+                            data[5] + // irq
+                            data[6] + // softirq
+                            data[7]; // steal
+            times.push((idle, non_idle));
+            if times.len() >= MAX_CPUS {
+                break;
+            };
+        }
+    }
+    Ok(times)
 }
 
 #[inline]
@@ -295,17 +409,61 @@ fn format_utilization(values: &[f64], count: usize, per_core: bool) -> String {
     }
 }
 
+/// Reads the current frequency of each core in MHz, preferring cpufreq over /proc/cpuinfo.
+/// Returns no values if neither is available.
+fn read_frequencies() -> Vec<f32> {
+    let mut cores: Vec<(usize, f32)> = std::fs::read_dir("/sys/devices/system/cpu")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let name = entry.file_name();
+                    let core = name.to_str()?.strip_prefix("cpu")?.parse::<usize>().ok()?;
+                    let khz = read_file("cpu", &entry.path().join("cpufreq/scaling_cur_freq"))
+                        .ok()?
+                        .parse::<f32>()
+                        .ok()?;
+                    Some((core, khz / 1000.0))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if cores.is_empty() {
+        // e.g. virtual machines often don't have cpufreq
+        return File::open("/proc/cpuinfo")
+            .map(|file| {
+                BufReader::new(file)
+                    .lines()
+                    .scan((), |_, x| x.ok())
+                    .filter(|line| line.starts_with("cpu MHz"))
+                    .filter_map(|line| line.split(' ').last()?.parse::<f32>().ok())
+                    .take(MAX_CPUS)
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+
+    cores.sort_by_key(|&(core, _)| core);
+    cores
+        .into_iter()
+        .map(|(_, mhz)| mhz)
+        .take(MAX_CPUS)
+        .collect()
+}
+
 #[inline]
-fn format_frequency(cpu_freqs: &[f32], count: usize, per_core: bool) -> String {
-    if per_core {
+fn format_frequency(cpu_freqs: &[f32], per_core: bool) -> String {
+    if cpu_freqs.is_empty() {
+        String::new()
+    } else if per_core {
         cpu_freqs
             .iter()
-            .take(count)
             .map(|v| format!("{0:.1}GHz", v / 1000.0))
             .collect::<Vec<String>>()
             .join(" ")
     } else {
-        let avg = cpu_freqs.iter().take(count).sum::<f32>() / (count as f32) / 1000.0;
+        let avg = cpu_freqs.iter().sum::<f32>() / (cpu_freqs.len() as f32) / 1000.0;
         format!("{:.1}", avg)
     }
 }
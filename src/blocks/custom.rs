@@ -1,25 +1,216 @@
 use std::collections::BTreeMap;
 use std::env;
+use std::io::{BufRead, BufReader, Write};
 use std::iter::{Cycle, Peekable};
-use std::process::Command;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
 use std::time::{Duration, Instant};
 use std::vec;
 
-use crossbeam_channel::Sender;
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender, TryRecvError};
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::Config;
 use crate::de::deserialize_update;
 use crate::errors::*;
-use crate::input::I3BarEvent;
+use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::signals::convert_to_valid_signal;
-use crate::subprocess::spawn_child_async;
-use crate::util::pseudo_uuid;
+use crate::subprocess::spawn_child_async_with_env;
+use crate::util::{expand_tilde, pseudo_uuid, watch_files};
 use crate::widget::{I3BarWidget, State};
 use crate::widgets::button::ButtonWidget;
 
+/// The delay before a persistent command that exited is restarted the first time. It doubles
+/// with every restart up to `MAX_RESTART_DELAY`, until the command keeps running that long.
+const MIN_RESTART_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+
+/// A command that keeps running and prints a new line whenever its output changes. With
+/// `prompt`, the command is sent a newline for every update without new output, so that
+/// commands that read their standard input can print a line without a process being spawned.
+struct PersistentCommand {
+    shell: String,
+    command: String,
+    prompt: bool,
+    id: String,
+    tx_update_request: Sender<Task>,
+    process: Option<RunningCommand>,
+    last_line: String,
+    /// When the command is started again after it exited
+    restart_at: Option<Instant>,
+    restart_delay: Duration,
+}
+
+/// A running persistent command with the lines it printed.
+struct RunningCommand {
+    child: Child,
+    lines: Receiver<String>,
+    /// Asks the writer thread to send the command a newline
+    prompt: Option<Sender<()>>,
+    started: Instant,
+}
+
+impl PersistentCommand {
+    fn spawn(&self) -> Result<RunningCommand> {
+        let mut child = Command::new(&self.shell)
+            .args(&["-c", &self.command])
+            .stdin(if self.prompt {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .block_error("custom", "failed to spawn persistent command")?;
+
+        // Lines are read on a thread so that a silent command can't block the bar, and every
+        // line, as well as the command exiting, updates the block right away
+        let stdout = child
+            .stdout
+            .take()
+            .block_error("custom", "failed to read persistent command")?;
+        let (tx, rx) = unbounded();
+        let id = self.id.clone();
+        let tx_update_request = self.tx_update_request.clone();
+        thread::Builder::new()
+            .name("custom".into())
+            .spawn(move || {
+                let request_update = || {
+                    tx_update_request
+                        .send(Task {
+                            id: id.clone(),
+                            update_time: Instant::now(),
+                        })
+                        .is_ok()
+                };
+                for line in BufReader::new(stdout).lines().scan((), |_, x| x.ok()) {
+                    if tx.send(line).is_err() || !request_update() {
+                        return;
+                    }
+                }
+                drop(tx);
+                request_update();
+            })
+            .block_error("custom", "failed to start reader thread")?;
+
+        // A command that doesn't read its input would block the bar once the pipe is full, so
+        // newlines are written on a thread, and dropped while one is still waiting to be read
+        let prompt = match child.stdin.take() {
+            Some(mut stdin) => {
+                let (tx_prompt, rx_prompt) = bounded::<()>(1);
+                thread::Builder::new()
+                    .name("custom".into())
+                    .spawn(move || {
+                        for () in rx_prompt {
+                            if stdin.write_all(b"\n").and_then(|_| stdin.flush()).is_err() {
+                                return;
+                            }
+                        }
+                    })
+                    .block_error("custom", "failed to start writer thread")?;
+                Some(tx_prompt)
+            }
+            None => None,
+        };
+
+        Ok(RunningCommand {
+            child,
+            lines: rx,
+            prompt,
+            started: Instant::now(),
+        })
+    }
+
+    /// The latest line of output, or how long it takes until the command is restarted after
+    /// it exited.
+    fn query(&mut self) -> Result<std::result::Result<String, Duration>> {
+        if self.process.is_none() {
+            if let Some(restart_at) = self.restart_at {
+                let now = Instant::now();
+                if restart_at > now {
+                    return Ok(Err(restart_at - now));
+                }
+            }
+            self.process = Some(self.spawn()?);
+            // The output of a command that exited is outdated
+            self.last_line.clear();
+        }
+
+        let mut new_line = false;
+        let mut exited = false;
+        if let Some(ref process) = self.process {
+            loop {
+                match process.lines.try_recv() {
+                    Ok(line) => {
+                        self.last_line = line.trim().to_owned();
+                        new_line = true;
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        exited = true;
+                        break;
+                    }
+                }
+            }
+
+            // Without new output, ask the command for a line. Its answer updates the block again.
+            if !new_line && !exited {
+                if let Some(ref prompt) = process.prompt {
+                    prompt.try_send(()).ok();
+                }
+            }
+        }
+
+        if exited {
+            if let Some(ref process) = self.process {
+                if process.started.elapsed() >= MAX_RESTART_DELAY {
+                    self.restart_delay = MIN_RESTART_DELAY;
+                }
+            }
+            self.kill();
+            self.restart_at = Some(Instant::now() + self.restart_delay);
+            let delay = self.restart_delay;
+            self.restart_delay = std::cmp::min(self.restart_delay * 2, MAX_RESTART_DELAY);
+
+            // Updates requested by the reader thread aren't rescheduled, so the restart is
+            // requested separately
+            let id = self.id.clone();
+            let tx_update_request = self.tx_update_request.clone();
+            thread::Builder::new()
+                .name("custom".into())
+                .spawn(move || {
+                    thread::sleep(delay);
+                    tx_update_request
+                        .send(Task {
+                            id,
+                            update_time: Instant::now(),
+                        })
+                        .ok();
+                })
+                .block_error("custom", "failed to start restart thread")?;
+            return Ok(Err(delay));
+        }
+
+        Ok(Ok(self.last_line.clone()))
+    }
+
+    fn kill(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            process.child.kill().ok();
+            process.child.wait().ok();
+        }
+    }
+}
+
+impl Drop for PersistentCommand {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
 pub struct Custom {
     id: String,
     update_interval: Update,
@@ -27,12 +218,17 @@ pub struct Custom {
     command: Option<String>,
     on_click: Option<String>,
     cycle: Option<Peekable<Cycle<vec::IntoIter<String>>>>,
-    signal: Option<i32>,
     tx_update_request: Sender<Task>,
     pub json: bool,
     hide_when_empty: bool,
     is_empty: bool,
+    /// The text that is displayed, passed to `on_click`
+    text: String,
     shell: String,
+    persistent: Option<PersistentCommand>,
+    persistent_error_text: String,
+    /// Without a command, the first watched file is displayed
+    watch_files: Vec<PathBuf>,
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -54,9 +250,6 @@ pub struct CustomConfig {
     /// Commands to execute and change when the button is clicked
     pub cycle: Option<Vec<String>>,
 
-    /// Signal to update upon reception
-    pub signal: Option<i32>,
-
     /// Parse command output if it contains valid bar JSON
     #[serde(default = "CustomConfig::default_json")]
     pub json: bool,
@@ -66,6 +259,22 @@ pub struct CustomConfig {
 
     pub shell: Option<String>,
 
+    /// Run `command` once and show every line it prints
+    #[serde(default = "CustomConfig::default_persistent")]
+    pub persistent: bool,
+
+    /// Send a persistent command a newline for every update without new output
+    #[serde(default = "CustomConfig::default_persistent_prompt")]
+    pub persistent_prompt: bool,
+
+    /// Text shown while a persistent command is restarted after it exited
+    #[serde(default = "CustomConfig::default_persistent_error_text")]
+    pub persistent_error_text: String,
+
+    /// Files whose changes update the block immediately
+    #[serde(default = "CustomConfig::default_watch_files")]
+    pub watch_files: Vec<String>,
+
     #[serde(default = "CustomConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
@@ -83,6 +292,22 @@ impl CustomConfig {
         false
     }
 
+    fn default_persistent() -> bool {
+        false
+    }
+
+    fn default_persistent_prompt() -> bool {
+        false
+    }
+
+    fn default_persistent_error_text() -> String {
+        "command exited".to_owned()
+    }
+
+    fn default_watch_files() -> Vec<String> {
+        Vec::new()
+    }
+
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
         None
     }
@@ -99,28 +324,39 @@ impl ConfigBlock for Custom {
             command: None,
             on_click: None,
             cycle: None,
-            signal: None,
             tx_update_request: tx,
             json: block_config.json,
             hide_when_empty: block_config.hide_when_empty,
             is_empty: true,
+            text: String::new(),
             shell: if let Some(s) = block_config.shell {
                 s
             } else {
                 env::var("SHELL").unwrap_or_else(|_| "sh".to_owned())
             },
+            persistent: None,
+            persistent_error_text: block_config.persistent_error_text,
+            watch_files: block_config
+                .watch_files
+                .iter()
+                .map(|path| expand_tilde(path))
+                .collect(),
         };
         custom.output = ButtonWidget::new(config, &custom.id);
 
+        if !custom.watch_files.is_empty() {
+            watch_files(
+                "custom",
+                &custom.watch_files,
+                custom.id.clone(),
+                custom.tx_update_request.clone(),
+            )?;
+        }
+
         if let Some(on_click) = block_config.on_click {
             custom.on_click = Some(on_click)
         };
 
-        if let Some(signal) = block_config.signal {
-            // If the signal is not in the valid range we return an error
-            custom.signal = Some(convert_to_valid_signal(signal)?);
-        };
-
         if block_config.cycle.is_some() && block_config.command.is_some() {
             return Err(BlockError(
                 "custom".to_string(),
@@ -128,11 +364,38 @@ impl ConfigBlock for Custom {
             ));
         }
 
+        if block_config.cycle.is_some() && block_config.persistent {
+            return Err(ConfigurationError(
+                "custom".to_string(),
+                (
+                    "`persistent` requires a `command`, it can't be used with `cycle`".to_string(),
+                    "invalid persistent".to_string(),
+                ),
+            ));
+        }
+
         if let Some(cycle) = block_config.cycle {
             custom.cycle = Some(cycle.into_iter().cycle().peekable());
             return Ok(custom);
         };
 
+        if block_config.persistent {
+            custom.persistent = Some(PersistentCommand {
+                shell: custom.shell.clone(),
+                command: block_config
+                    .command
+                    .block_error("custom", "`persistent` requires a `command`")?,
+                prompt: block_config.persistent_prompt,
+                id: custom.id.clone(),
+                tx_update_request: custom.tx_update_request.clone(),
+                process: None,
+                last_line: String::new(),
+                restart_at: None,
+                restart_delay: MIN_RESTART_DELAY,
+            });
+            return Ok(custom);
+        }
+
         if let Some(command) = block_config.command {
             custom.command = Some(command)
         };
@@ -141,55 +404,98 @@ impl ConfigBlock for Custom {
     }
 }
 
-fn default_icon() -> String {
-    String::from("")
-}
-
-fn default_state() -> State {
-    State::Idle
-}
-
-#[derive(Deserialize)]
+/// The output of a command in `json` mode. Unknown keys are ignored.
+#[derive(Deserialize, Default)]
+#[serde(default)]
 struct Output {
-    #[serde(default = "default_icon")]
     icon: String,
-    #[serde(default = "default_state")]
-    state: State,
+    state: String,
     text: String,
+    short_text: Option<String>,
+}
+
+/// Parses the name of a state, ignoring case. Unknown states are shown as idle.
+fn parse_state(state: &str) -> State {
+    match state.to_lowercase().as_str() {
+        "info" => State::Info,
+        "good" => State::Good,
+        "warning" => State::Warning,
+        "critical" => State::Critical,
+        _ => State::Idle,
+    }
 }
 
 impl Block for Custom {
     fn update(&mut self) -> Result<Option<Update>> {
-        let command_str = self
-            .cycle
-            .as_mut()
-            .map(|c| c.peek().cloned().unwrap_or_else(|| "".to_owned()))
-            .or_else(|| self.command.clone())
-            .unwrap_or_else(|| "".to_owned());
-
-        let raw_output = Command::new(&self.shell)
-            .args(&["-c", &command_str])
-            .output()
-            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_owned())
-            .unwrap_or_else(|e| e.to_string());
+        let raw_output = match self.persistent {
+            Some(ref mut persistent) => match persistent.query()? {
+                Ok(line) => line,
+                Err(restart_in) => {
+                    if self.json {
+                        self.output.set_icon("");
+                        self.output.set_short_text(None);
+                    }
+                    self.output.set_state(State::Critical);
+                    self.is_empty = self.persistent_error_text.is_empty();
+                    self.text = self.persistent_error_text.clone();
+                    self.output.set_text(self.text.clone());
+                    return Ok(Some(restart_in.into()));
+                }
+            },
+            None if self.cycle.is_none()
+                && self.command.is_none()
+                && !self.watch_files.is_empty() =>
+            {
+                // A missing file is the same as an empty one
+                std::fs::read_to_string(&self.watch_files[0])
+                    .unwrap_or_default()
+                    .trim()
+                    .to_owned()
+            }
+            None => {
+                let command_str = self
+                    .cycle
+                    .as_mut()
+                    .map(|c| c.peek().cloned().unwrap_or_else(|| "".to_owned()))
+                    .or_else(|| self.command.clone())
+                    .unwrap_or_else(|| "".to_owned());
+
+                Command::new(&self.shell)
+                    .args(&["-c", &command_str])
+                    .output()
+                    .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_owned())
+                    .unwrap_or_else(|e| e.to_string())
+            }
+        };
 
         if self.json {
-            let output: Output = match serde_json::from_str(&*raw_output) {
-                Err(e) => {
-                    return Err(BlockError(
-                        "custom".to_string(),
-                        format!("Error parsing JSON: {}", e),
-                    ));
+            match serde_json::from_str::<Output>(&*raw_output) {
+                Ok(output) => {
+                    // Icons that aren't in the icon set are left out
+                    self.output.set_icon(&output.icon);
+                    self.output.set_state(parse_state(&output.state));
+                    self.output.set_short_text(output.short_text);
+                    self.is_empty = output.text.trim().is_empty();
+                    self.text = output.text;
+                    self.output.set_text(self.text.clone());
+                }
+                // Scripts fail, so show what they printed instead of failing the block
+                Err(_) => {
+                    let line = raw_output.lines().next().unwrap_or_default().to_owned();
+                    self.output.set_icon("");
+                    self.output.set_state(State::Warning);
+                    self.output.set_short_text(None);
+                    self.is_empty = line.is_empty();
+                    self.text = line;
+                    self.output.set_text(self.text.clone());
                 }
-                Ok(s) => s,
-            };
-            self.output.set_icon(&output.icon);
-            self.output.set_state(output.state);
-            self.is_empty = output.text.is_empty();
-            self.output.set_text(output.text);
+            }
         } else {
+            // A persistent command may have been restarted after an error
+            self.output.set_state(State::Idle);
             self.is_empty = raw_output.is_empty();
-            self.output.set_text(raw_output);
+            self.text = raw_output;
+            self.output.set_text(self.text.clone());
         }
 
         Ok(Some(self.update_interval.clone()))
@@ -203,18 +509,6 @@ impl Block for Custom {
         }
     }
 
-    fn signal(&mut self, signal: i32) -> Result<()> {
-        if let Some(sig) = self.signal {
-            if sig == signal {
-                self.tx_update_request.send(Task {
-                    id: self.id.clone(),
-                    update_time: Instant::now(),
-                })?;
-            }
-        }
-        Ok(())
-    }
-
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
         if let Some(ref name) = event.name {
             if name != &self.id {
@@ -227,13 +521,27 @@ impl Block for Custom {
         let mut update = false;
 
         if let Some(ref on_click) = self.on_click {
-            spawn_child_async(&self.shell, &["-c", on_click]).ok();
+            // Like in i3blocks, the command can tell what was clicked and how
+            let button = event.button.number().to_string();
+            let (x, y) = (event.x.to_string(), event.y.to_string());
+            let envs = [
+                ("BLOCK_BUTTON", button.as_str()),
+                ("BLOCK_X", x.as_str()),
+                ("BLOCK_Y", y.as_str()),
+                // The block_id if one is set
+                ("BLOCK_NAME", event.instance.as_deref().unwrap_or("custom")),
+                ("BLOCK_TEXT", self.text.as_str()),
+            ];
+            spawn_child_async_with_env(&self.shell, &["-c", on_click], &envs).ok();
             update = true;
         }
 
+        // Only left clicks switch to the next command, so scrolling doesn't run through them
         if let Some(ref mut cycle) = self.cycle {
-            cycle.next();
-            update = true;
+            if event.button == MouseButton::Left {
+                cycle.next();
+                update = true;
+            }
         }
 
         if update {
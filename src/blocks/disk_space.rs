@@ -1,8 +1,10 @@
 use std::collections::BTreeMap;
 use std::path::Path;
+use std::process::Command;
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
+use nix::sys::statfs::statfs;
 use nix::sys::statvfs::statvfs;
 use serde_derive::Deserialize;
 
@@ -11,7 +13,7 @@ use crate::config::Config;
 use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::scheduler::Task;
-use crate::util::{format_percent_bar, pseudo_uuid, FormatTemplate};
+use crate::util::{format_percent_bar, pseudo_uuid, DeltaTracker, FormatTemplate, Formats};
 use crate::widget::{I3BarWidget, State};
 use crate::widgets::text::TextWidget;
 
@@ -49,19 +51,137 @@ pub enum InfoType {
     Used,
 }
 
+/// Where the available space is read from. statvfs doesn't account for e.g. RAID profiles,
+/// so the filesystem's own tools give a better estimate of how much can still be written.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Statvfs,
+    Btrfs,
+    Zfs,
+}
+
+const BTRFS_SUPER_MAGIC: i64 = 0x9123_683e;
+const ZFS_SUPER_MAGIC: i64 = 0x2fc1_2fc1;
+
+impl Backend {
+    /// Makes sure the path is on a filesystem supported by this backend.
+    fn check_path(self, path: &str) -> Result<()> {
+        let (magic, name) = match self {
+            Backend::Statvfs => return Ok(()),
+            Backend::Btrfs => (BTRFS_SUPER_MAGIC, "btrfs"),
+            Backend::Zfs => (ZFS_SUPER_MAGIC, "zfs"),
+        };
+        let fs_type = statfs(path)
+            .block_error(
+                "disk_space",
+                &format!("failed to retrieve statfs for '{}'", path),
+            )?
+            .filesystem_type()
+            .0 as i64;
+        if fs_type == magic {
+            Ok(())
+        } else {
+            Err(BlockError(
+                "disk_space".to_string(),
+                format!("path '{}' is not on a {} filesystem", path, name),
+            ))
+        }
+    }
+
+    /// Estimated available space in bytes, or `None` to use the statvfs value.
+    fn available(self, path: &str) -> Result<Option<u64>> {
+        match self {
+            Backend::Statvfs => Ok(None),
+            // `btrfs filesystem usage` needs root to read the chunk info. Without it, the
+            // estimate may be off, and if the command fails the statvfs value is used instead.
+            Backend::Btrfs => Ok(run_backend("btrfs", &["filesystem", "usage", "-b", path])
+                .ok()
+                .and_then(|output| parse_btrfs_free(&output))),
+            Backend::Zfs => {
+                let output = run_backend("zfs", &["list", "-Hp", "-o", "available", path])?;
+                output
+                    .trim()
+                    .parse::<u64>()
+                    .map(Some)
+                    .block_error("disk_space", "failed to parse zfs list")
+            }
+        }
+    }
+}
+
+/// Runs the tool of a backend and returns its output. If it fails, e.g. without the
+/// permission to read the filesystem, the error it printed is shown.
+fn run_backend(command: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(command)
+        .args(args)
+        .output()
+        .block_error("disk_space", &format!("failed to run {}", command))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(BlockError(
+            "disk_space".to_string(),
+            format!("{} failed: {}", command, stderr.trim()),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Reads the "Free (estimated)" value of `btrfs filesystem usage -b`.
+fn parse_btrfs_free(output: &str) -> Option<u64> {
+    output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Free (estimated):"))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|free| free.parse().ok())
+}
+
+/// A path to collect information from, optionally with its own alias, format and thresholds.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum PathEntry {
+    Path(String),
+    Detailed {
+        path: String,
+        alias: Option<String>,
+        format: Option<String>,
+        warning: Option<f64>,
+        alert: Option<f64>,
+    },
+}
+
+/// Either a single path or a list of paths.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum PathConfig {
+    Single(String),
+    Multiple(Vec<PathEntry>),
+}
+
+struct DiskEntry {
+    path: String,
+    alias: String,
+    /// Overrides the format of the block
+    format: Option<FormatTemplate>,
+    warning: f64,
+    alert: f64,
+    /// Recent values of the info_type in the display unit
+    delta: DeltaTracker,
+}
+
 pub struct DiskSpace {
     disk_space: TextWidget,
     id: String,
     update_interval: Duration,
-    alias: String,
-    path: String,
+    entries: Vec<DiskEntry>,
+    separator: String,
+    backend: Backend,
     unit: Unit,
     info_type: InfoType,
-    warning: f64,
-    alert: f64,
-    show_percentage: bool,
-    show_bar: bool,
-    alert_absolute: bool,
+    alert_unit: Unit,
+    inode_warning: Option<f64>,
+    inode_alert: Option<f64>,
+    delta_warning: Option<f64>,
     format: FormatTemplate,
     icon: String,
 }
@@ -69,14 +189,22 @@ pub struct DiskSpace {
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct DiskSpaceConfig {
-    /// Path to collect information from
-    #[serde(default = "DiskSpaceConfig::default_path")]
-    pub path: String,
+    /// Path (or list of paths) to collect information from
+    #[serde(default = "DiskSpaceConfig::default_path", alias = "paths")]
+    pub path: PathConfig,
 
     /// Alias that is displayed for path
     #[serde(default = "DiskSpaceConfig::default_alias")]
     pub alias: String,
 
+    /// Separator between the entries of multiple paths
+    #[serde(default = "DiskSpaceConfig::default_separator")]
+    pub separator: String,
+
+    /// Where to read the available space from: statvfs, btrfs or zfs
+    #[serde(default = "DiskSpaceConfig::default_backend")]
+    pub backend: Backend,
+
     /// Currently supported options are available, free, total and used
     /// Sets value used for {percentage} calculation
     /// total is the same as used, use format to set format string for output
@@ -85,9 +213,9 @@ pub struct DiskSpaceConfig {
 
     /// Format string for output
     /// placeholders: {percentage}, {bar}, {path}, {alias}, {available}, {free}, {total}, {used},
-    ///               {unit}
+    ///               {unit}, {iused}, {ifree}, {iused_percent}
     #[serde(default = "DiskSpaceConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
 
     /// Unit that is used to display disk space. Options are MB, MiB, GB, GiB, TB and TiB
     #[serde(default = "DiskSpaceConfig::default_unit")]
@@ -102,11 +230,11 @@ pub struct DiskSpaceConfig {
 
     /// Diskspace warning in GiB (yellow)
     #[serde(default = "DiskSpaceConfig::default_warning")]
-    pub warning: f64,
+    pub warning: Option<f64>,
 
     /// Diskspace alert in GiB (red)
     #[serde(default = "DiskSpaceConfig::default_alert")]
-    pub alert: f64,
+    pub alert: Option<f64>,
 
     /// Show percentage - deprecated for format string, kept for previous configs
     #[serde(default = "DiskSpaceConfig::default_show_percentage")]
@@ -120,25 +248,57 @@ pub struct DiskSpaceConfig {
     #[serde(default = "DiskSpaceConfig::default_alert_absolute")]
     pub alert_absolute: bool,
 
+    /// Unit of the warning and alert thresholds, Percent or an absolute unit like GB
+    #[serde(default = "DiskSpaceConfig::default_alert_unit")]
+    pub alert_unit: Option<Unit>,
+
+    /// Inode usage warning as a percentage of used inodes
+    #[serde(default = "DiskSpaceConfig::default_inode_threshold")]
+    pub inode_warning: Option<f64>,
+
+    /// Inode usage alert as a percentage of used inodes
+    #[serde(default = "DiskSpaceConfig::default_inode_threshold")]
+    pub inode_alert: Option<f64>,
+
+    /// Change of the info_type in the display unit within `delta_window`, where state is
+    /// set to warning. For available and free space a decrease counts.
+    #[serde(default = "DiskSpaceConfig::default_delta_warning")]
+    pub delta_warning: Option<f64>,
+
+    /// Time span in seconds over which the change is compared with `delta_warning`
+    #[serde(
+        default = "DiskSpaceConfig::default_delta_window",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub delta_window: Duration,
+
     #[serde(default = "DiskSpaceConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
 
 impl DiskSpaceConfig {
-    fn default_path() -> String {
-        "/".to_owned()
+    fn default_path() -> PathConfig {
+        PathConfig::Single("/".to_owned())
     }
 
     fn default_alias() -> String {
         "/".to_owned()
     }
 
+    fn default_separator() -> String {
+        " ".to_owned()
+    }
+
+    fn default_backend() -> Backend {
+        Backend::Statvfs
+    }
+
     fn default_info_type() -> InfoType {
         InfoType::Available
     }
 
-    fn default_format() -> String {
-        String::from("{alias} {available} {unit}")
+    fn default_format() -> Formats {
+        "{alias} {available} {unit}".into()
     }
 
     fn default_unit() -> Unit {
@@ -149,12 +309,12 @@ impl DiskSpaceConfig {
         Duration::from_secs(20)
     }
 
-    fn default_warning() -> f64 {
-        20.
+    fn default_warning() -> Option<f64> {
+        None
     }
 
-    fn default_alert() -> f64 {
-        10.
+    fn default_alert() -> Option<f64> {
+        None
     }
 
     // Deprecated with format string, kept for previous config support
@@ -171,16 +331,101 @@ impl DiskSpaceConfig {
         false
     }
 
+    fn default_alert_unit() -> Option<Unit> {
+        None
+    }
+
+    fn default_inode_threshold() -> Option<f64> {
+        None
+    }
+
+    fn default_delta_warning() -> Option<f64> {
+        None
+    }
+
+    fn default_delta_window() -> Duration {
+        Duration::from_secs(600)
+    }
+
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
         None
     }
 }
 
+#[derive(Clone, Copy)]
 enum AlertType {
     Above,
     Below,
 }
 
+impl AlertType {
+    /// The warning and alert thresholds used where none are configured.
+    fn default_thresholds(self) -> (f64, f64) {
+        match self {
+            AlertType::Above => (80., 90.),
+            AlertType::Below => (20., 10.),
+        }
+    }
+}
+
+impl InfoType {
+    fn alert_type(self) -> AlertType {
+        match self {
+            InfoType::Available | InfoType::Free => AlertType::Below,
+            InfoType::Total | InfoType::Used => AlertType::Above,
+        }
+    }
+}
+
+/// Makes sure the alert level is reached before the warning level, e.g. for the available
+/// space the alert threshold must be lower than the warning threshold.
+fn validate_thresholds(path: &str, warning: f64, alert: f64, alert_type: AlertType) -> Result<()> {
+    let (valid, relation) = match alert_type {
+        AlertType::Above => (alert >= warning, "greater"),
+        AlertType::Below => (alert <= warning, "less"),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(ConfigurationError(
+            "disk_space".to_string(),
+            (
+                format!(
+                    "alert ({}) must be {} than or equal to warning ({}) for path '{}'",
+                    alert, relation, warning, path
+                ),
+                "invalid thresholds".to_string(),
+            ),
+        ))
+    }
+}
+
+/// The thresholds of a path, where missing ones are the defaults for the direction of the
+/// `info_type`. A single configured threshold has to fit the default of the other one.
+fn resolve_thresholds(
+    path: &str,
+    warning: Option<f64>,
+    alert: Option<f64>,
+    alert_type: AlertType,
+) -> Result<(f64, f64)> {
+    let (default_warning, default_alert) = alert_type.default_thresholds();
+    let warning = warning.unwrap_or(default_warning);
+    let alert = alert.unwrap_or(default_alert);
+    validate_thresholds(path, warning, alert, alert_type)?;
+    Ok((warning, alert))
+}
+
+/// Used to find the most severe state among all paths.
+fn severity(state: State) -> u8 {
+    match state {
+        State::Idle => 0,
+        State::Info => 1,
+        State::Good => 2,
+        State::Warning => 3,
+        State::Critical => 4,
+    }
+}
+
 impl DiskSpace {
     fn compute_state(&self, value: f64, warning: f64, alert: f64, alert_type: AlertType) -> State {
         match alert_type {
@@ -220,91 +465,252 @@ impl ConfigBlock for DiskSpace {
             .cloned()
             .unwrap_or_else(|| "".to_string());
 
+        let (warning, alert) = (block_config.warning, block_config.alert);
+        let info_type = block_config.info_type;
+        let delta = DeltaTracker::new(block_config.delta_window);
+        let entries = match block_config.path {
+            PathConfig::Single(path) => {
+                let (warning, alert) =
+                    resolve_thresholds(&path, warning, alert, info_type.alert_type())?;
+                vec![DiskEntry {
+                    path,
+                    alias: block_config.alias,
+                    format: None,
+                    warning,
+                    alert,
+                    delta,
+                }]
+            }
+            PathConfig::Multiple(paths) => paths
+                .into_iter()
+                .map(|entry| match entry {
+                    PathEntry::Path(path) => {
+                        let (warning, alert) =
+                            resolve_thresholds(&path, warning, alert, info_type.alert_type())?;
+                        Ok(DiskEntry {
+                            alias: path.clone(),
+                            path,
+                            format: None,
+                            warning,
+                            alert,
+                            delta: delta.clone(),
+                        })
+                    }
+                    PathEntry::Detailed {
+                        path,
+                        alias,
+                        format,
+                        warning: entry_warning,
+                        alert: entry_alert,
+                    } => {
+                        let (warning, alert) = resolve_thresholds(
+                            &path,
+                            entry_warning.or(warning),
+                            entry_alert.or(alert),
+                            info_type.alert_type(),
+                        )?;
+                        Ok(DiskEntry {
+                            alias: alias.unwrap_or_else(|| path.clone()),
+                            path,
+                            format: match format {
+                                Some(format) => Some(
+                                    FormatTemplate::from_string(&format)?
+                                        .with_markup(config.markup),
+                                ),
+                                None => None,
+                            },
+                            warning,
+                            alert,
+                            delta: delta.clone(),
+                        })
+                    }
+                })
+                .collect::<Result<Vec<DiskEntry>>>()?,
+        };
+        if entries.is_empty() {
+            return Err(BlockError(
+                "disk_space".to_string(),
+                "at least one path is required".to_string(),
+            ));
+        }
+        for entry in &entries {
+            block_config.backend.check_path(&entry.path)?;
+        }
+        if let (Some(warning), Some(alert)) = (block_config.inode_warning, block_config.inode_alert)
+        {
+            validate_thresholds("inodes", warning, alert, AlertType::Above)?;
+        }
+
+        // alert_absolute is kept for previous configs, it uses the display unit
+        let alert_unit = block_config
+            .alert_unit
+            .unwrap_or(if block_config.alert_absolute {
+                block_config.unit
+            } else {
+                Unit::Percent
+            });
+
+        let mut format = FormatTemplate::from_formats(&block_config.format)?;
+        if let InfoType::Total = block_config.info_type {
+            // Deprecated: Same as Used - use format string to set output format
+            // Kept for back-compatibility
+            // Use format: "{used}/{total} {unit}" for previous format
+            format = FormatTemplate::from_string("{used}/{total} {unit}")?;
+        }
+        if block_config.show_percentage {
+            format = FormatTemplate::from_string("{alias} {result} ({percentage}) {unit}")?;
+        } else if block_config.show_bar {
+            format = FormatTemplate::from_string("{alias} {result} {unit} {bar}")?;
+        }
+        let format = format.with_markup(config.markup);
+
         Ok(DiskSpace {
             id: pseudo_uuid(),
             update_interval: block_config.interval,
             disk_space: TextWidget::new(config),
-            alias: block_config.alias,
-            path: block_config.path,
-            format: FormatTemplate::from_string(&block_config.format)?,
+            entries,
+            separator: block_config.separator,
+            backend: block_config.backend,
+            format,
             info_type: block_config.info_type,
             unit: block_config.unit,
-            warning: block_config.warning,
-            alert: block_config.alert,
-            show_percentage: block_config.show_percentage,
-            show_bar: block_config.show_bar,
-            alert_absolute: block_config.alert_absolute,
+            alert_unit,
+            inode_warning: block_config.inode_warning,
+            inode_alert: block_config.inode_alert,
+            delta_warning: block_config.delta_warning,
             icon,
         })
     }
 }
 
-impl Block for DiskSpace {
-    fn update(&mut self) -> Result<Option<Update>> {
-        let statvfs = statvfs(Path::new(self.path.as_str()))
+impl DiskSpace {
+    /// Renders a single path and computes its state.
+    fn render_entry(&self, entry: &mut DiskEntry) -> Result<(String, State)> {
+        let statvfs = statvfs(Path::new(entry.path.as_str()))
             .block_error("disk_space", "failed to retrieve statvfs")?;
 
-        let result;
         let total = (statvfs.blocks() as u64) * (statvfs.fragment_size() as u64);
         let used = ((statvfs.blocks() as u64) - (statvfs.blocks_free() as u64))
             * (statvfs.fragment_size() as u64);
-        let available = (statvfs.blocks_available() as u64) * (statvfs.block_size() as u64);
+        let available = match self.backend.available(&entry.path)? {
+            Some(available) => available,
+            None => (statvfs.blocks_available() as u64) * (statvfs.block_size() as u64),
+        };
         let free = (statvfs.blocks_free() as u64) * (statvfs.block_size() as u64);
 
-        let alert_type;
-        match self.info_type {
-            InfoType::Available => {
-                result = available;
-                alert_type = AlertType::Below;
-            }
-            InfoType::Free => {
-                result = free;
-                alert_type = AlertType::Below;
-            }
-            InfoType::Total => {
-                // Deprecated: Same as Used - use format string to set output format
-                // Kept for back-compatibility
-                // Use format: "{used}/{total} {unit}" for previous format
-                result = used;
-                alert_type = AlertType::Above;
-                self.format = FormatTemplate::from_string("{used}/{total} {unit}")?;
-            }
-            InfoType::Used => {
-                result = used;
-                alert_type = AlertType::Above;
-            }
-        }
+        let result = match self.info_type {
+            InfoType::Available => available,
+            InfoType::Free => free,
+            InfoType::Total | InfoType::Used => used,
+        };
 
         let percentage = (result as f32) / (total as f32) * 100f32;
-        if self.show_percentage {
-            self.format = FormatTemplate::from_string("{alias} {result} ({percentage}) {unit}")?;
-        } else if self.show_bar {
-            self.format = FormatTemplate::from_string("{alias} {result} {unit} {bar}")?;
-        }
+        let (delta, window_delta) = entry.delta.push(Unit::bytes_in_unit(self.unit, result));
+
+        // Some filesystems (e.g. btrfs) don't have a fixed number of inodes and report zero
+        let inodes_total = statvfs.files() as u64;
+        let inodes = if inodes_total > 0 {
+            let inodes_used = inodes_total - statvfs.files_free() as u64;
+            Some((
+                inodes_used,
+                statvfs.files_available() as u64,
+                (inodes_used as f64) / (inodes_total as f64) * 100.,
+            ))
+        } else {
+            None
+        };
 
         let values = map!("{percentage}" => format!("{:.2}%", percentage),
         "{bar}" => format_percent_bar(percentage),
-        "{alias}" => self.alias.clone(),
+        "{alias}" => entry.alias.clone(),
         "{unit}" => format!("{:?}", self.unit),
-        "{path}" => self.path.clone(),
+        "{path}" => entry.path.clone(),
         "{total}" => format!("{:.2}", Unit::bytes_in_unit(self.unit, total)),
         "{used}" => format!("{:.2}", Unit::bytes_in_unit(self.unit, used)),
         "{available}" => format!("{:.2}", Unit::bytes_in_unit(self.unit, available)),
         "{free}" => format!("{:.2}", Unit::bytes_in_unit(self.unit, free)),
         "{icon}" => self.icon.to_string(),
-        "{result}" => format!("{:.2}", result)
+        "{result}" => format!("{:.2}", result),
+        "{delta}" => format!("{:+.2}", delta),
+        "{iused}" => inodes.map(|(used, _, _)| used.to_string()).unwrap_or_default(),
+        "{ifree}" => inodes.map(|(_, free, _)| free.to_string()).unwrap_or_default(),
+        "{iused_percent}" => inodes
+            .map(|(_, _, percent)| format!("{:.2}%", percent))
+            .unwrap_or_default()
         );
-        self.disk_space
-            .set_text(self.format.render_static_str(&values)?);
+        let text = entry
+            .format
+            .as_ref()
+            .unwrap_or(&self.format)
+            .render_static_str(&values)?;
 
         // Send percentage to alert check if we don't want absolute alerts
-        let alert_val = if !self.alert_absolute {
+        let alert_val = if self.alert_unit == Unit::Percent {
             percentage as f64
         } else {
-            Unit::bytes_in_unit(self.unit, result)
+            Unit::bytes_in_unit(self.alert_unit, result)
         };
 
-        let state = self.compute_state(alert_val, self.warning, self.alert, alert_type);
+        let mut state = self.compute_state(
+            alert_val,
+            entry.warning,
+            entry.alert,
+            self.info_type.alert_type(),
+        );
+
+        // Inode thresholds are only checked if at least one of them is configured
+        if let (Some((_, _, inode_percent)), Some(warning)) =
+            (inodes, self.inode_warning.or(self.inode_alert))
+        {
+            let alert = self.inode_alert.unwrap_or(100.);
+            let inode_state = self.compute_state(inode_percent, warning, alert, AlertType::Above);
+            if severity(inode_state) > severity(state) {
+                state = inode_state;
+            }
+        }
+
+        if let Some(warning) = self.delta_warning {
+            let change = match self.info_type.alert_type() {
+                AlertType::Above => window_delta,
+                AlertType::Below => -window_delta,
+            };
+            if change > warning && severity(State::Warning) > severity(state) {
+                state = State::Warning;
+            }
+        }
+
+        Ok((text, state))
+    }
+}
+
+impl Block for DiskSpace {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut texts = Vec::new();
+        let mut state = State::Idle;
+        let mut error = None;
+        // The entries are taken out so that rendering can update their deltas
+        let mut entries = std::mem::take(&mut self.entries);
+        let single = entries.len() == 1;
+        for entry in &mut entries {
+            match self.render_entry(entry) {
+                Ok((text, entry_state)) => {
+                    texts.push(text);
+                    if severity(entry_state) > severity(state) {
+                        state = entry_state;
+                    }
+                }
+                // With a single path there is nothing else to show, so keep failing the
+                // block; otherwise an unmounted disk shouldn't hide the other paths.
+                Err(e) if single => error = Some(e),
+                Err(_) => texts.push(format!("{} —", entry.alias)),
+            }
+        }
+        self.entries = entries;
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        self.disk_space.set_text(texts.join(&self.separator));
         self.disk_space.set_state(state);
 
         Ok(Some(self.update_interval.into()))
@@ -317,4 +723,47 @@ impl Block for DiskSpace {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_btrfs_free, resolve_thresholds, AlertType};
+
+    #[test]
+    fn test_resolve_thresholds() {
+        let resolve =
+            |warning, alert, alert_type| resolve_thresholds("/", warning, alert, alert_type);
+        assert_eq!(resolve(None, None, AlertType::Below).unwrap(), (20., 10.));
+        assert_eq!(resolve(None, None, AlertType::Above).unwrap(), (80., 90.));
+        // A single threshold is completed with the default for the same direction
+        assert_eq!(
+            resolve(Some(80.), None, AlertType::Above).unwrap(),
+            (80., 90.)
+        );
+        assert_eq!(
+            resolve(None, Some(5.), AlertType::Below).unwrap(),
+            (20., 5.)
+        );
+        // and checked against it
+        assert!(resolve(None, Some(30.), AlertType::Below).is_err());
+        assert!(resolve(Some(95.), None, AlertType::Above).is_err());
+        assert!(resolve(Some(10.), Some(20.), AlertType::Below).is_err());
+    }
+
+    #[test]
+    fn test_parse_btrfs_free() {
+        let output = "Overall:
+    Device size:                      1000204886016
+    Device allocated:                  322149138432
+    Used:                              270561859584
+    Free (estimated):                  364344471552      (min: 364344471552)
+    Data ratio:                                2.00
+";
+        assert_eq!(parse_btrfs_free(output), Some(364_344_471_552));
+        assert_eq!(parse_btrfs_free("ERROR: not a btrfs filesystem"), None);
+    }
 }
@@ -1,5 +1,7 @@
 use std::collections::BTreeMap;
-use std::process::Command;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
@@ -11,15 +13,19 @@ use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::input::I3BarEvent;
 use crate::scheduler::Task;
-use crate::util::{pseudo_uuid, FormatTemplate};
-use crate::widget::I3BarWidget;
+use crate::util::{pseudo_uuid, FormatTemplate, Formats};
+use crate::widget::{I3BarWidget, State};
 use crate::widgets::text::TextWidget;
 
+/// How long the daemon may take to answer, so a hung daemon doesn't stall the bar
+const TIMEOUT: Duration = Duration::from_secs(2);
+
 pub struct Docker {
     text: TextWidget,
     id: String,
     format: FormatTemplate,
     update_interval: Duration,
+    socket_path: PathBuf,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -50,9 +56,14 @@ pub struct DockerConfig {
     )]
     pub interval: Duration,
 
+    /// The unix socket of the Docker daemon
+    #[serde(default = "DockerConfig::default_socket_path")]
+    pub socket_path: PathBuf,
+
     /// Format override
+    /// placeholders: {running}, {paused}, {stopped}, {total}, {images}
     #[serde(default = "DockerConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
 
     #[serde(default = "DockerConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
@@ -63,8 +74,12 @@ impl DockerConfig {
         Duration::from_secs(5)
     }
 
-    fn default_format() -> String {
-        "{running}%".to_owned()
+    fn default_socket_path() -> PathBuf {
+        PathBuf::from("/var/run/docker.sock")
+    }
+
+    fn default_format() -> Formats {
+        "{running}%".into()
     }
 
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
@@ -76,40 +91,55 @@ impl ConfigBlock for Docker {
     type Config = DockerConfig;
 
     fn new(block_config: Self::Config, config: Config, _: Sender<Task>) -> Result<Self> {
+        let markup = config.markup;
         Ok(Docker {
             id: pseudo_uuid(),
-            text: TextWidget::new(config).with_text("N/A").with_icon("docker"),
-            format: FormatTemplate::from_string(&block_config.format)
-                .block_error("docker", "Invalid format specified")?,
+            text: TextWidget::new(config).with_text("docker: n/a"),
+            format: FormatTemplate::from_formats(&block_config.format)
+                .block_error("docker", "Invalid format specified")?
+                .with_markup(markup),
             update_interval: block_config.interval,
+            socket_path: block_config.socket_path,
         })
     }
 }
 
+/// Requests `/info` from the daemon listening on the socket and returns the body of the
+/// response, or `None` if the daemon can't be reached or doesn't answer in time.
+fn request_info(socket_path: &Path) -> Option<String> {
+    let mut stream = UnixStream::connect(socket_path).ok()?;
+    stream.set_read_timeout(Some(TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(TIMEOUT)).ok()?;
+    // With HTTP/1.0 the daemon closes the connection after the response and doesn't use
+    // chunked encoding, so the body is simply the rest of the stream
+    stream
+        .write_all(b"GET /info HTTP/1.0\r\nHost: docker\r\n\r\n")
+        .ok()?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok()?;
+
+    let (head, body) = response.split_at(response.find("\r\n\r\n")?);
+    let status = head.split_whitespace().nth(1)?;
+    if status == "200" {
+        Some(body.trim().to_string())
+    } else {
+        None
+    }
+}
+
 impl Block for Docker {
     fn update(&mut self) -> Result<Option<Update>> {
-        let output = match Command::new("sh")
-            .args(&[
-                "-c",
-                "curl --fail --unix-socket /var/run/docker.sock http:/api/info",
-            ])
-            .output()
-        {
-            Ok(raw_output) => {
-                String::from_utf8(raw_output.stdout).block_error("docker", "Failed to decode")?
-            }
-            Err(_) => {
-                // We don't want the bar to crash if we can't reach the docker daemon.
-                self.text.set_text("N/A".to_string());
+        // The daemon not running isn't an error, it may be stopped while it's not needed
+        let output = match request_info(&self.socket_path) {
+            Some(output) => output,
+            None => {
+                self.text.set_icon("");
+                self.text.set_text("docker: n/a".to_string());
+                self.text.set_state(State::Idle);
                 return Ok(Some(self.update_interval.into()));
             }
         };
 
-        if output.is_empty() {
-            self.text.set_text("N/A".to_string());
-            return Ok(Some(self.update_interval.into()));
-        }
-
         let status: Status = serde_json::from_str(&output)
             .block_error("docker", "Failed to parse JSON response.")?;
 
@@ -121,6 +151,7 @@ impl Block for Docker {
             "{images}" => format!("{}", status.images)
         );
 
+        self.text.set_icon("docker");
         self.text.set_text(self.format.render_static_str(&values)?);
 
         Ok(Some(self.update_interval.into()))
@@ -137,4 +168,8 @@ impl Block for Docker {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
 }
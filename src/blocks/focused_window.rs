@@ -1,18 +1,20 @@
 use std::collections::BTreeMap;
+use std::env;
+use std::os::unix::net::UnixStream;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
-use swayipc::reply::{Event, Node, WindowChange, WorkspaceChange};
+use swayipc::reply::{Event, Node, NodeType, WindowChange, WorkspaceChange};
 use swayipc::{Connection, EventType};
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::Config;
 use crate::errors::*;
 use crate::scheduler::Task;
-use crate::util::pseudo_uuid;
+use crate::util::{pseudo_uuid, truncate_to_width, FormatTemplate, Formats};
 use crate::widget::I3BarWidget;
 use crate::widgets::text::TextWidget;
 
@@ -24,25 +26,184 @@ pub enum MarksType {
     None,
 }
 
+/// `show_marks` is either one of the `MarksType`s or a boolean, where `true` shows the
+/// visible marks.
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ShowMarks {
+    Enabled(bool),
+    Type(MarksType),
+}
+
+impl ShowMarks {
+    fn marks_type(self) -> MarksType {
+        match self {
+            ShowMarks::Enabled(true) => MarksType::Visible,
+            ShowMarks::Enabled(false) => MarksType::None,
+            ShowMarks::Type(marks_type) => marks_type,
+        }
+    }
+}
+
+/// How long to wait before connecting again after the connection to i3 or sway was lost,
+/// e.g. by `sway reload`.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// The focused window.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Window {
+    id: i64,
+    title: String,
+    /// The application id of a Wayland window on sway
+    app_id: String,
+    /// The class of an X11 window
+    class: String,
+    marks: String,
+}
+
+impl Window {
+    fn new(node: &Node, marks_type: MarksType) -> Self {
+        let marks = node
+            .marks
+            .iter()
+            .filter(|mark| match marks_type {
+                MarksType::All => true,
+                MarksType::Visible => !mark.starts_with('_'),
+                MarksType::None => false,
+            })
+            .map(|mark| format!("[{}]", mark))
+            .collect();
+        Window {
+            id: node.id,
+            title: node.name.clone().unwrap_or_default(),
+            app_id: node.app_id.clone().unwrap_or_default(),
+            class: node
+                .window_properties
+                .as_ref()
+                .and_then(|properties| properties.class.clone())
+                .unwrap_or_default(),
+            marks,
+        }
+    }
+}
+
+/// What the block shows, kept up to date by the IPC events.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Focus {
+    window: Option<Window>,
+    /// The name of the focused workspace
+    workspace: String,
+}
+
+impl Focus {
+    /// Reads the focus from the tree and the workspaces.
+    fn query(conn: &mut Connection, marks_type: MarksType) -> Self {
+        let window = conn.get_tree().ok().and_then(|tree| {
+            focused_node(&tree)
+                .filter(|node| node.node_type != NodeType::Workspace)
+                .map(|node| Window::new(node, marks_type))
+        });
+        let workspace = conn
+            .get_workspaces()
+            .ok()
+            .and_then(|workspaces| workspaces.into_iter().find(|workspace| workspace.focused))
+            .map(|workspace| workspace.name)
+            .unwrap_or_default();
+        Focus { window, workspace }
+    }
+
+    /// Applies an event, returns whether the focus changed.
+    fn handle(&mut self, event: Event, marks_type: MarksType) -> bool {
+        let before = self.clone();
+        match event {
+            Event::Window(e) => match e.change {
+                WindowChange::Focus => self.window = Some(Window::new(&e.container, marks_type)),
+                // Windows other than the focused one can be marked by criteria, and the
+                // marks can change without any change of the title
+                WindowChange::Title | WindowChange::Mark if e.container.focused => {
+                    self.window = Some(Window::new(&e.container, marks_type))
+                }
+                WindowChange::Close
+                    if self.window.as_ref().map(|window| window.id) == Some(e.container.id) =>
+                {
+                    self.window = None
+                }
+                _ => {}
+            },
+            Event::Workspace(e) => match (e.change, e.current) {
+                (WorkspaceChange::Init, _) => self.window = None,
+                (WorkspaceChange::Focus, Some(current)) => {
+                    // There is no window event when switching to an empty workspace,
+                    // otherwise the focus event of its window follows
+                    if current.nodes.is_empty() && current.floating_nodes.is_empty() {
+                        self.window = None;
+                    }
+                    self.workspace = current.name.unwrap_or_default();
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        *self != before
+    }
+}
+
+/// The focused node of the tree.
+fn focused_node(node: &Node) -> Option<&Node> {
+    if node.focused {
+        return Some(node);
+    }
+    node.nodes
+        .iter()
+        .chain(node.floating_nodes.iter())
+        .find_map(focused_node)
+}
+
+/// Connects to sway's own socket if running on sway, else to i3's.
+fn connect() -> Result<Connection> {
+    match env::var_os("SWAYSOCK") {
+        Some(path) => UnixStream::connect(path)
+            .map(Connection::from)
+            .block_error("focused_window", "failed to connect to the sway socket"),
+        None => Connection::new().block_error("focused_window", "failed to acquire connect to IPC"),
+    }
+}
+
 pub struct FocusedWindow {
     text: TextWidget,
-    title: Arc<Mutex<String>>,
-    marks: Arc<Mutex<String>>,
-    show_marks: MarksType,
+    focus: Arc<Mutex<Focus>>,
+    format: FormatTemplate,
+    /// Wayland windows on sway may have no title, but an application id
+    sway: bool,
     max_width: usize,
+    autohide: bool,
+    empty_text: Option<String>,
     id: String,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct FocusedWindowConfig {
-    /// Truncates titles if longer than max-width
+    /// Truncates the title if it takes up more columns than max-width
     #[serde(default = "FocusedWindowConfig::default_max_width")]
     pub max_width: usize,
 
-    /// Show marks in place of title (if exist)
+    /// Format override
+    /// placeholders: {title}, {app_id}, {class}
+    #[serde(default = "FocusedWindowConfig::default_format")]
+    pub format: Formats,
+
+    /// Show the marks of the window in front of the title
     #[serde(default = "FocusedWindowConfig::default_show_marks")]
-    pub show_marks: MarksType,
+    pub show_marks: ShowMarks,
+
+    /// Hide the block if no window is focused, instead of showing the `empty_text`
+    #[serde(default = "FocusedWindowConfig::default_autohide")]
+    pub autohide: bool,
+
+    /// Shown if no window is focused and `autohide` is off, the workspace name if not set
+    #[serde(default = "FocusedWindowConfig::default_empty_text")]
+    pub empty_text: Option<String>,
 
     #[serde(default = "FocusedWindowConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
@@ -53,8 +214,20 @@ impl FocusedWindowConfig {
         21
     }
 
-    fn default_show_marks() -> MarksType {
-        MarksType::None
+    fn default_format() -> Formats {
+        "{title}".into()
+    }
+
+    fn default_show_marks() -> ShowMarks {
+        ShowMarks::Type(MarksType::None)
+    }
+
+    fn default_autohide() -> bool {
+        true
+    }
+
+    fn default_empty_text() -> Option<String> {
+        None
     }
 
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
@@ -66,122 +239,72 @@ impl ConfigBlock for FocusedWindow {
     type Config = FocusedWindowConfig;
 
     fn new(block_config: Self::Config, config: Config, tx: Sender<Task>) -> Result<Self> {
+        let markup = config.markup;
         let id = pseudo_uuid();
         let id_clone = id.clone();
+        let marks_type = block_config.show_marks.marks_type();
 
-        let title = Arc::new(Mutex::new(String::from("")));
-        let marks = Arc::new(Mutex::new(String::from("")));
-        let marks_type = block_config.show_marks;
-
-        let update_window = {
-            let title = title.clone();
-
-            move |new_title| {
-                let mut title = title
-                    .lock()
-                    .expect("lock has been poisoned in `window` block");
-
-                let changed = *title != new_title;
-                *title = new_title;
-                changed
-            }
-        };
-
-        let close_window = {
-            let title = title.clone();
-
-            move |closed_title: String| {
-                let mut title = title
-                    .lock()
-                    .expect("lock has been poisoned in `window` block");
-
-                if *title == closed_title {
-                    *title = "".to_string();
-                    true
-                } else {
-                    false
-                }
-            }
-        };
-
-        let update_marks = {
-            let marks = marks.clone();
-
-            move |new_marks: Vec<String>| {
-                let mut new_marks_str = String::from("");
-
-                for mark in new_marks {
-                    match marks_type {
-                        MarksType::All => {
-                            new_marks_str.push_str(&format!("[{}]", mark));
-                        }
-                        MarksType::Visible if !mark.starts_with('_') => {
-                            new_marks_str.push_str(&format!("[{}]", mark));
-                        }
-                        _ => {}
-                    }
-                }
-
-                let mut marks = marks
-                    .lock()
-                    .expect("lock has been poisoned in `window` block");
-
-                let changed = *marks != new_marks_str;
-                *marks = new_marks_str;
-                changed
-            }
-        };
-
-        let _test_conn =
-            Connection::new().block_error("focused_window", "failed to acquire connect to IPC")?;
+        let mut conn = connect()?;
+        let focus = Arc::new(Mutex::new(Focus::query(&mut conn, marks_type)));
 
+        let thread_focus = focus.clone();
         thread::Builder::new()
             .name("focused_window".into())
             .spawn(move || {
-                let conn = Connection::new().expect("failed to open connection with swayipc");
-
-                let events = conn
-                    .subscribe(&[EventType::Window, EventType::Workspace])
-                    .expect("could not subscribe to window events");
-
-                for event in events {
-                    let updated = match event.expect("could not read event in `window` block") {
-                        Event::Window(e) => match (e.change, e.container) {
-                            (WindowChange::Mark, Node { marks, .. }) => update_marks(marks),
-                            (WindowChange::Focus, Node { name, marks, .. }) => {
-                                let updated_for_window = name.map(&update_window).unwrap_or(false);
-                                let updated_for_marks = update_marks(marks);
-                                updated_for_window || updated_for_marks
+                let mut conn = Some(conn);
+                loop {
+                    let conn = match conn.take() {
+                        Some(conn) => conn,
+                        // The focus may have changed while the connection was lost
+                        None => match connect() {
+                            Ok(mut conn) => {
+                                let focus = Focus::query(&mut conn, marks_type);
+                                *thread_focus
+                                    .lock()
+                                    .expect("lock has been poisoned in `window` block") = focus;
+                                tx.send(Task {
+                                    id: id_clone.clone(),
+                                    update_time: Instant::now(),
+                                })
+                                .expect("could not communicate with channel in `window` block");
+                                conn
+                            }
+                            Err(_) => {
+                                thread::sleep(RECONNECT_DELAY);
+                                continue;
                             }
-                            (
-                                WindowChange::Title,
-                                Node {
-                                    focused: true,
-                                    name: Some(name),
-                                    ..
-                                },
-                            ) => update_window(name),
-                            (
-                                WindowChange::Close,
-                                Node {
-                                    name: Some(name), ..
-                                },
-                            ) => close_window(name),
-                            _ => false,
                         },
-                        Event::Workspace(e) if e.change == WorkspaceChange::Init => {
-                            update_window("".to_string())
+                    };
+
+                    // The window events include `mark`, which is sent when the marks of a
+                    // window change without any change of its title
+                    let events = match conn.subscribe(&[EventType::Window, EventType::Workspace]) {
+                        Ok(events) => events,
+                        Err(_) => {
+                            thread::sleep(RECONNECT_DELAY);
+                            continue;
                         }
-                        _ => false,
                     };
 
-                    if updated {
-                        tx.send(Task {
-                            id: id_clone.clone(),
-                            update_time: Instant::now(),
-                        })
-                        .expect("could not communicate with channel in `window` block");
+                    // Reading fails once the connection is lost
+                    for event in events {
+                        let event = match event {
+                            Ok(event) => event,
+                            Err(_) => break,
+                        };
+                        let updated = thread_focus
+                            .lock()
+                            .expect("lock has been poisoned in `window` block")
+                            .handle(event, marks_type);
+                        if updated {
+                            tx.send(Task {
+                                id: id_clone.clone(),
+                                update_time: Instant::now(),
+                            })
+                            .expect("could not communicate with channel in `window` block");
+                        }
                     }
+                    thread::sleep(RECONNECT_DELAY);
                 }
             })
             .expect("failed to start watching thread for `window` block");
@@ -189,50 +312,74 @@ impl ConfigBlock for FocusedWindow {
         Ok(FocusedWindow {
             id,
             text: TextWidget::new(config),
+            focus,
+            format: FormatTemplate::from_formats(&block_config.format)
+                .block_error("focused_window", "Invalid format specified")?
+                .with_markup(markup),
+            sway: env::var_os("SWAYSOCK").is_some(),
             max_width: block_config.max_width,
-            show_marks: block_config.show_marks,
-            title,
-            marks,
+            autohide: block_config.autohide,
+            empty_text: block_config.empty_text,
         })
     }
 }
 
+impl FocusedWindow {
+    /// The title of the window, or on sway its application id if it has no title.
+    fn title(&self, window: &Window) -> String {
+        if window.title.is_empty() && self.sway {
+            window.app_id.clone()
+        } else {
+            window.title.clone()
+        }
+    }
+}
+
 impl Block for FocusedWindow {
     fn update(&mut self) -> Result<Option<Update>> {
-        let mut marks_string = (*self
-            .marks
-            .lock()
-            .block_error("focused_window", "failed to acquire lock")?)
-        .clone();
-        marks_string = marks_string.chars().take(self.max_width).collect();
-        let mut title_string = (*self
-            .title
+        let focus = self
+            .focus
             .lock()
-            .block_error("focused_window", "failed to acquire lock")?)
-        .clone();
-        title_string = title_string.chars().take(self.max_width).collect();
-        let out_str = match self.show_marks {
-            MarksType::None => title_string,
-            _ => {
-                if !marks_string.is_empty() {
-                    marks_string
+            .block_error("focused_window", "failed to acquire lock")?
+            .clone();
+        let window = focus.window.filter(|window| !self.title(window).is_empty());
+        let text = match window {
+            Some(window) => {
+                let values = map!(
+                    // Truncated before rendering, so that no markup of the format is cut
+                    "{title}" => truncate_to_width(&self.title(&window), self.max_width),
+                    "{app_id}" => window.app_id.clone(),
+                    "{class}" => window.class.clone()
+                );
+                let text = self.format.render_static_str(&values)?;
+                // The marks are empty unless `show_marks` is set
+                if window.marks.is_empty() {
+                    text
                 } else {
-                    title_string
+                    format!("{} {}", window.marks, text)
                 }
             }
+            None => truncate_to_width(
+                &self.empty_text.clone().unwrap_or(focus.workspace),
+                self.max_width,
+            ),
         };
-        self.text.set_text(out_str);
+        self.text.set_text(text);
 
         Ok(None)
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        let title = &*self
-            .title
+        let focus = self
+            .focus
             .lock()
             .expect("lock has been poisoned in `window` block");
+        let has_window = focus
+            .window
+            .as_ref()
+            .map_or(false, |window| !self.title(window).is_empty());
 
-        if title.is_empty() {
+        if !has_window && self.autohide {
             vec![]
         } else {
             vec![&self.text]
@@ -242,4 +389,8 @@ impl Block for FocusedWindow {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
 }
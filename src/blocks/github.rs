@@ -8,24 +8,41 @@ use regex::Regex;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
+use crate::cache::{Cache, Cached};
 use crate::config::Config;
 use crate::de::deserialize_duration;
 use crate::errors::*;
-use crate::input::I3BarEvent;
+use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::{pseudo_uuid, FormatTemplate};
-use crate::widget::I3BarWidget;
-use crate::widgets::text::TextWidget;
+use crate::subprocess::spawn_child_async;
+use crate::util::{expand_tilde, format_stale, pseudo_uuid, read_file, FormatTemplate, Formats};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
 
 const GITHUB_TOKEN_ENV: &str = "I3RS_GITHUB_TOKEN";
 
 pub struct Github {
-    text: TextWidget,
+    text: ButtonWidget,
     id: String,
     update_interval: Duration,
     api_server: String,
     token: String,
     format: FormatTemplate,
+    hide_if_total_is_zero: bool,
+    on_click: Option<String>,
+    /// The counts of the last successful update
+    aggregations: Option<HashMap<String, u64>>,
+    /// The `Last-Modified` header of the last successful update
+    last_modified: Option<String>,
+    /// The minimum interval between updates the API asks for
+    poll_interval: Duration,
+    /// The last update failed, the counts are from an earlier one
+    stale: bool,
+    /// Template for the text while the data is outdated
+    stale_format: String,
+    cache: Cache,
+    /// The counts cached before the last restart, until the first update
+    cached: Option<Cached<HashMap<String, u64>>>,
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -41,9 +58,25 @@ pub struct GithubConfig {
     #[serde(default = "GithubConfig::default_api_server")]
     pub api_server: String,
 
+    /// The personal access token, instead of the I3RS_GITHUB_TOKEN environment variable
+    #[serde(default = "GithubConfig::default_token")]
+    pub token: Option<String>,
+
+    /// A file containing the personal access token
+    #[serde(default = "GithubConfig::default_token_file")]
+    pub token_file: Option<String>,
+
     /// Format override
     #[serde(default = "GithubConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
+
+    /// Hide the block while there are no notifications
+    #[serde(default = "GithubConfig::default_hide_if_total_is_zero")]
+    pub hide_if_total_is_zero: bool,
+
+    /// Shell command run on a left click
+    #[serde(default = "GithubConfig::default_on_click")]
+    pub on_click: Option<String>,
 
     #[serde(default = "GithubConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
@@ -58,8 +91,24 @@ impl GithubConfig {
         "https://api.github.com".to_owned()
     }
 
-    fn default_format() -> String {
-        "{total}".to_owned()
+    fn default_token() -> Option<String> {
+        None
+    }
+
+    fn default_token_file() -> Option<String> {
+        None
+    }
+
+    fn default_format() -> Formats {
+        "{total}".into()
+    }
+
+    fn default_hide_if_total_is_zero() -> bool {
+        false
+    }
+
+    fn default_on_click() -> Option<String> {
+        Some("xdg-open https://github.com/notifications".to_owned())
     }
 
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
@@ -71,49 +120,52 @@ impl ConfigBlock for Github {
     type Config = GithubConfig;
 
     fn new(block_config: Self::Config, config: Config, _: Sender<Task>) -> Result<Self> {
-        let token = match std::env::var(GITHUB_TOKEN_ENV).ok() {
-            Some(v) => v,
-            None => {
-                return Err(BlockError(
-                    "github".to_owned(),
-                    "missing I3RS_GITHUB_TOKEN environment variable".to_owned(),
-                ))
-            }
+        let markup = config.markup;
+        let token = match (block_config.token, block_config.token_file) {
+            (Some(token), _) => token,
+            (None, Some(path)) => read_file("github", &expand_tilde(&path))?.trim().to_owned(),
+            (None, None) => match std::env::var(GITHUB_TOKEN_ENV).ok() {
+                Some(v) => v,
+                None => {
+                    return Err(BlockError(
+                        "github".to_owned(),
+                        "missing token, token_file or I3RS_GITHUB_TOKEN environment variable"
+                            .to_owned(),
+                    ))
+                }
+            },
         };
 
+        let id = pseudo_uuid();
+        let cache = Cache::new("github", &block_config.api_server, config.cache_ttl);
         Ok(Github {
-            id: pseudo_uuid(),
+            cached: cache.load(),
+            cache,
             update_interval: block_config.interval,
-            text: TextWidget::new(config).with_text("x").with_icon("github"),
+            stale_format: config.stale_format.clone(),
+            text: ButtonWidget::new(config, &id)
+                .with_text("x")
+                .with_icon("github"),
+            id,
             api_server: block_config.api_server,
             token,
-            format: FormatTemplate::from_string(&block_config.format)
-                .block_error("github", "Invalid format specified")?,
+            format: FormatTemplate::from_formats(&block_config.format)
+                .block_error("github", "Invalid format specified")?
+                .with_markup(markup),
+            hide_if_total_is_zero: block_config.hide_if_total_is_zero,
+            on_click: block_config.on_click.filter(|cmd| !cmd.is_empty()),
+            aggregations: None,
+            last_modified: None,
+            poll_interval: Duration::from_secs(0),
+            stale: false,
         })
     }
 }
 
-impl Block for Github {
-    fn update(&mut self) -> Result<Option<Update>> {
-        let aggregations = match Notifications::new(&self.api_server, &self.token).try_fold(
-            map!("total".to_owned() => 0),
-            |mut acc,
-             notif|
-             -> std::result::Result<HashMap<String, u64>, Box<dyn std::error::Error>> {
-                let n = notif?;
-                acc.entry(n.reason).and_modify(|v| *v += 1).or_insert(1);
-                acc.entry("total".to_owned()).and_modify(|v| *v += 1);
-                Ok(acc)
-            },
-        ) {
-            Ok(v) => v,
-            Err(_) => {
-                // If there is a error reported, set the value to x
-                self.text.set_text("x".to_owned());
-                return Ok(Some(self.update_interval.into()));
-            }
-        };
-
+impl Github {
+    /// Shows the number of notifications for each reason.
+    fn show(&mut self, aggregations: &HashMap<String, u64>) -> Result<()> {
+        self.aggregations = Some(aggregations.clone());
         let default: u64 = 0;
         let values = map!(
             "{total}" => format!("{}", aggregations.get("total").unwrap_or(&default)),
@@ -132,22 +184,113 @@ impl Block for Github {
             "{team_mention}" => format!("{}", aggregations.get("team_mention").unwrap_or(&default))
         );
 
-        self.text.set_text(self.format.render_static_str(&values)?);
+        let text = self.format.render_static_str(&values)?;
+        if self.stale {
+            self.text.set_text(format_stale(&self.stale_format, &text));
+        } else {
+            self.text.set_text(text);
+        }
+        self.text
+            .set_state(if aggregations.get("total").unwrap_or(&default) > &0 {
+                State::Info
+            } else {
+                State::Idle
+            });
+        Ok(())
+    }
+}
+
+impl Block for Github {
+    fn update(&mut self) -> Result<Option<Update>> {
+        // After a restart, show the cached counts until they're due to be fetched again
+        let cached = self.cached.take();
+        if let Some(ref cached) = cached {
+            if let Some(remaining) = cached.remaining(self.update_interval) {
+                self.show(&cached.value)?;
+                return Ok(Some(remaining.into()));
+            }
+        }
+
+        let mut notifications =
+            Notifications::new(&self.api_server, &self.token, self.last_modified.as_deref());
+        let aggregations = notifications.by_ref().try_fold(
+            map!("total".to_owned() => 0),
+            |mut acc,
+             notif|
+             -> std::result::Result<HashMap<String, u64>, Box<dyn std::error::Error>> {
+                let n = notif?;
+                acc.entry(n.reason).and_modify(|v| *v += 1).or_insert(1);
+                acc.entry("total".to_owned()).and_modify(|v| *v += 1);
+                Ok(acc)
+            },
+        );
+        // GitHub asks clients not to poll more often than X-Poll-Interval
+        if let Some(poll_interval) = notifications.poll_interval {
+            self.poll_interval = poll_interval;
+        }
+        let interval = self.update_interval.max(self.poll_interval);
+
+        match aggregations {
+            // Nothing changed since the last update
+            Ok(_) if notifications.not_modified => {
+                self.stale = false;
+                if let Some(aggregations) = self.aggregations.take() {
+                    self.show(&aggregations)?;
+                }
+            }
+            Ok(aggregations) => {
+                self.stale = false;
+                self.last_modified = notifications.last_modified;
+                self.cache.store(&aggregations);
+                self.show(&aggregations)?;
+            }
+            Err(_) => {
+                // Show the last known counts, or x if there are none
+                self.stale = true;
+                match self
+                    .aggregations
+                    .take()
+                    .or_else(|| cached.map(|cached| cached.value))
+                {
+                    Some(aggregations) => self.show(&aggregations)?,
+                    None => self.text.set_text("x".to_owned()),
+                }
+                return Ok(Some(self.update_interval.into()));
+            }
+        }
 
-        Ok(Some(self.update_interval.into()))
+        Ok(Some(interval.into()))
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        vec![&self.text]
+        let total = self
+            .aggregations
+            .as_ref()
+            .and_then(|aggregations| aggregations.get("total"));
+        if self.hide_if_total_is_zero && total == Some(&0) {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
     }
 
-    fn click(&mut self, _: &I3BarEvent) -> Result<()> {
+    fn click(&mut self, e: &I3BarEvent) -> Result<()> {
+        if e.matches_name(self.id()) && e.button == MouseButton::Left {
+            if let Some(ref cmd) = self.on_click {
+                spawn_child_async("sh", &["-c", cmd])
+                    .block_error("github", "could not spawn child")?;
+            }
+        }
         Ok(())
     }
 
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
 }
 
 #[derive(Deserialize)]
@@ -159,6 +302,16 @@ struct Notifications<'a> {
     notifications: <Vec<Notification> as IntoIterator>::IntoIter,
     token: &'a str,
     next_page_url: String,
+    /// Only fetch the notifications if they changed since then
+    if_modified_since: Option<&'a str>,
+    /// The next page is the first one
+    first_page: bool,
+    /// The notifications haven't changed since `if_modified_since`
+    not_modified: bool,
+    /// The `Last-Modified` header of the first page
+    last_modified: Option<String>,
+    /// The `X-Poll-Interval` header of the first page
+    poll_interval: Option<Duration>,
 }
 
 impl<'a> Iterator for Notifications<'a> {
@@ -174,11 +327,20 @@ impl<'a> Iterator for Notifications<'a> {
 }
 
 impl<'a> Notifications<'a> {
-    fn new(api_server: &'a str, token: &'a str) -> Notifications<'a> {
+    fn new(
+        api_server: &'a str,
+        token: &'a str,
+        if_modified_since: Option<&'a str>,
+    ) -> Notifications<'a> {
         Notifications {
             next_page_url: format!("{}/notifications", api_server),
             token,
             notifications: vec![].into_iter(),
+            if_modified_since,
+            first_page: true,
+            not_modified: false,
+            last_modified: None,
+            poll_interval: None,
         }
     }
 
@@ -193,12 +355,19 @@ impl<'a> Notifications<'a> {
             return Ok(None);
         }
 
+        // Only the first page is conditional, the following ones are always requested
+        let first_page = std::mem::replace(&mut self.first_page, false);
+        let condition = match self.if_modified_since {
+            Some(date) if first_page => format!(" --header \"If-Modified-Since: {}\"", date),
+            _ => String::new(),
+        };
         let result = Command::new("sh")
             .args(&[
                 "-c",
                 &format!(
-                    "curl --silent --dump-header - --header \"Authorization: Bearer {token}\" -m 3 \"{next_page_url}\"",
+                    "curl --silent --dump-header - --header \"Authorization: Bearer {token}\"{condition} -m 3 \"{next_page_url}\"",
                     token = self.token,
+                    condition = condition,
                     next_page_url = self.next_page_url,
                 ),
             ])
@@ -225,7 +394,25 @@ impl<'a> Notifications<'a> {
 
         let (meta, body) = (split[0], split[1]);
 
-        let next = match meta.lines().find(|&l| l.starts_with("Link:")) {
+        if first_page {
+            self.last_modified = header(meta, "Last-Modified").map(str::to_owned);
+            self.poll_interval = header(meta, "X-Poll-Interval")
+                .and_then(|secs| secs.parse().ok())
+                .map(Duration::from_secs);
+        }
+        let status = meta.split_whitespace().nth(1).unwrap_or_default();
+        if status == "304" {
+            self.not_modified = true;
+            self.next_page_url = String::new();
+            return Ok(None);
+        } else if status != "200" {
+            return Err(Box::new(BlockError(
+                "github".to_owned(),
+                format!("unexpected response status {}", status),
+            )));
+        }
+
+        let next = match header(meta, "Link") {
             Some(v) => match parse_links_header(v).get("next") {
                 Some(next) => next,
                 None => "",
@@ -241,6 +428,18 @@ impl<'a> Notifications<'a> {
     }
 }
 
+/// The value of a response header, whose name is lower case with HTTP/2.
+fn header<'a>(meta: &'a str, name: &str) -> Option<&'a str> {
+    meta.lines().find_map(|line| {
+        let (key, value) = line.split_at(line.find(':')?);
+        if key.eq_ignore_ascii_case(name) {
+            Some(value[1..].trim())
+        } else {
+            None
+        }
+    })
+}
+
 fn parse_links_header(raw_links: &str) -> HashMap<&str, &str> {
     lazy_static! {
         static ref LINKS_REGEX: Regex =
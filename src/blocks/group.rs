@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use toml::value::{Table, Value};
+
+use crate::blocks::{create_block, Block, ConfigBlock, Update, WIDGET_OPTIONS};
+use crate::config::Config;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::pseudo_uuid;
+use crate::widget::I3BarWidget;
+use crate::widgets::button::ButtonWidget;
+
+struct Child {
+    block: Box<dyn Block>,
+    /// When the block is due for its next scheduled update
+    next_update: Option<Instant>,
+    /// The block requested an update itself
+    requested: Arc<AtomicBool>,
+}
+
+pub struct Group {
+    id: String,
+    children: Vec<Child>,
+    collapsed_button: ButtonWidget,
+    collapsible: bool,
+    collapsed: bool,
+    toggle_button: MouseButton,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GroupConfig {
+    /// The blocks of the group, configured like any other block
+    pub blocks: Vec<Table>,
+
+    /// Whether clicking the group collapses and expands it
+    #[serde(default = "GroupConfig::default_collapsible")]
+    pub collapsible: bool,
+
+    /// Whether a collapsible group starts out collapsed
+    #[serde(default = "GroupConfig::default_collapsed")]
+    pub collapsed: bool,
+
+    /// Text shown instead of the blocks while the group is collapsed
+    #[serde(default = "GroupConfig::default_collapsed_text")]
+    pub collapsed_text: String,
+
+    /// The mouse button which collapses and expands the group
+    #[serde(default = "GroupConfig::default_toggle_button")]
+    pub toggle_button: MouseButton,
+
+    #[serde(default = "GroupConfig::default_color_overrides")]
+    pub color_overrides: Option<BTreeMap<String, String>>,
+}
+
+impl GroupConfig {
+    fn default_collapsible() -> bool {
+        false
+    }
+
+    fn default_collapsed() -> bool {
+        false
+    }
+
+    fn default_collapsed_text() -> String {
+        "…".to_owned()
+    }
+
+    fn default_toggle_button() -> MouseButton {
+        MouseButton::Right
+    }
+
+    fn default_color_overrides() -> Option<BTreeMap<String, String>> {
+        None
+    }
+}
+
+/// Checks the blocks of a group and splits them into their names and configurations.
+pub fn group_blocks(blocks: Vec<Table>) -> Result<Vec<(String, Value)>> {
+    if blocks.is_empty() {
+        return Err(BlockError(
+            "group".to_string(),
+            "at least one block is required".to_string(),
+        ));
+    }
+
+    let mut named = Vec::with_capacity(blocks.len());
+    for mut block in blocks {
+        let name = match block.remove("block") {
+            Some(Value::String(name)) => name,
+            _ => {
+                return Err(ConfigurationError(
+                    "group".to_string(),
+                    (
+                        "each block of a group needs a block name".to_string(),
+                        "missing block name".to_string(),
+                    ),
+                ))
+            }
+        };
+        // Clicks are recognized by the names of the widgets, which only the group has
+        if let Some(key) = WIDGET_OPTIONS
+            .iter()
+            .chain(&["click_command"])
+            .find(|key| block.contains_key(**key))
+        {
+            return Err(ConfigurationError(
+                "group".to_string(),
+                (
+                    format!("{} can only be set for the group, not its blocks", key),
+                    "invalid block in group".to_string(),
+                ),
+            ));
+        }
+
+        named.push((name, Value::Table(block)));
+    }
+    Ok(named)
+}
+
+impl ConfigBlock for Group {
+    type Config = GroupConfig;
+
+    fn new(
+        block_config: Self::Config,
+        config: Config,
+        update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let blocks = group_blocks(block_config.blocks)?;
+        let id = pseudo_uuid();
+        let mut children = Vec::with_capacity(blocks.len());
+        for (name, block) in blocks {
+            // The scheduler only knows the group, so the blocks' update requests are sent
+            // for the group, which then updates the blocks that requested one
+            let requested = Arc::new(AtomicBool::new(false));
+            let (tx, rx) = crossbeam_channel::unbounded::<Task>();
+            let forward_to = update_request.clone();
+            let forward_id = id.clone();
+            let forward_requested = requested.clone();
+            thread::Builder::new()
+                .name("group".into())
+                .spawn(move || {
+                    for task in rx {
+                        forward_requested.store(true, Ordering::SeqCst);
+                        let task = Task {
+                            id: forward_id.clone(),
+                            ..task
+                        };
+                        if forward_to.send(task).is_err() {
+                            break;
+                        }
+                    }
+                })
+                .block_error("group", "failed to start update forwarding thread")?;
+
+            children.push(Child {
+                block: create_block(&name, block, config.clone(), tx)?,
+                next_update: Some(Instant::now()),
+                requested,
+            });
+        }
+
+        Ok(Group {
+            collapsed_button: ButtonWidget::new(config, &id)
+                .with_text(&block_config.collapsed_text),
+            id,
+            children,
+            collapsible: block_config.collapsible,
+            collapsed: block_config.collapsible && block_config.collapsed,
+            toggle_button: block_config.toggle_button,
+        })
+    }
+}
+
+impl Block for Group {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        let widgets: Vec<&dyn I3BarWidget> = self
+            .children
+            .iter()
+            .flat_map(|child| child.block.view())
+            .collect();
+        // A group without anything to show is hidden even while collapsed
+        if self.collapsed && !widgets.is_empty() {
+            vec![&self.collapsed_button]
+        } else {
+            widgets
+        }
+    }
+
+    fn update(&mut self) -> Result<Option<Update>> {
+        let now = Instant::now();
+        for child in &mut self.children {
+            let requested = child.requested.swap(false, Ordering::SeqCst);
+            // The scheduler may wake us up slightly before a block is due
+            let due = child
+                .next_update
+                .map_or(false, |time| time <= now + Duration::from_millis(10));
+            if !requested && !due {
+                continue;
+            }
+
+            let update = child.block.update()?;
+            // Like the scheduler, only the scheduled updates determine the next one
+            if due {
+                child.next_update = match update {
+                    Some(Update::Every(interval)) => Some(now + interval),
+                    _ => None,
+                };
+            }
+        }
+
+        Ok(self
+            .children
+            .iter()
+            .filter_map(|child| child.next_update)
+            .min()
+            .map(|time| time.saturating_duration_since(Instant::now()).into()))
+    }
+
+    fn signal(&mut self, signal: i32) -> Result<()> {
+        for child in &mut self.children {
+            child.block.signal(signal)?;
+        }
+        Ok(())
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if self.collapsible && event.button == self.toggle_button {
+            let on_group = event.matches_name(&self.id)
+                || self
+                    .children
+                    .iter()
+                    .any(|child| event.matches_name(child.block.id()));
+            if on_group {
+                self.collapsed = !self.collapsed;
+                return Ok(());
+            }
+        }
+
+        if !self.collapsed {
+            for child in &mut self.children {
+                child.block.click(event)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,392 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::value::Value;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{escape_pango_value, pseudo_uuid, FormatTemplate, Formats};
+use crate::widget::{I3BarWidget, Markup, State};
+use crate::widgets::text::TextWidget;
+
+/// Exit code of curl if the request timed out
+const CURL_TIMEOUT: i32 = 28;
+
+pub struct Http {
+    text: TextWidget,
+    id: String,
+    update_interval: Duration,
+    url: String,
+    timeout: Duration,
+    auth: Option<String>,
+    headers: BTreeMap<String, String>,
+    fields: BTreeMap<String, String>,
+    state_field: Option<String>,
+    states: BTreeMap<String, State>,
+    format: FormatTemplate,
+    format_error: FormatTemplate,
+    markup: Option<Markup>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HttpConfig {
+    /// The URL that is requested
+    pub url: String,
+
+    /// Update interval in seconds
+    #[serde(
+        default = "HttpConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Maximum time the request may take, in seconds
+    #[serde(
+        default = "HttpConfig::default_timeout",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub timeout: Duration,
+
+    /// User name for basic authentication
+    #[serde(default = "HttpConfig::default_username")]
+    pub username: Option<String>,
+
+    /// Password for basic authentication
+    #[serde(default = "HttpConfig::default_password")]
+    pub password: Option<String>,
+
+    /// Additional request headers
+    #[serde(default = "HttpConfig::default_headers")]
+    pub headers: BTreeMap<String, String>,
+
+    /// Placeholders that show fields of the response, e.g. `{ healthy = "status.healthy" }`
+    #[serde(default = "HttpConfig::default_fields")]
+    pub fields: BTreeMap<String, String>,
+
+    /// Field of the response that determines the state of the block
+    #[serde(default = "HttpConfig::default_state_field")]
+    pub state_field: Option<String>,
+
+    /// States for the values of `state_field`
+    #[serde(default = "HttpConfig::default_states")]
+    pub states: BTreeMap<String, State>,
+
+    /// Format override
+    /// placeholders: {status_code} and the keys of `fields`
+    #[serde(default = "HttpConfig::default_format")]
+    pub format: Formats,
+
+    /// Format used if the request fails
+    /// placeholders: {status_code}
+    #[serde(default = "HttpConfig::default_format_error")]
+    pub format_error: String,
+
+    #[serde(default = "HttpConfig::default_color_overrides")]
+    pub color_overrides: Option<BTreeMap<String, String>>,
+}
+
+impl HttpConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn default_timeout() -> Duration {
+        Duration::from_secs(3)
+    }
+
+    fn default_username() -> Option<String> {
+        None
+    }
+
+    fn default_password() -> Option<String> {
+        None
+    }
+
+    fn default_headers() -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
+    fn default_fields() -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
+    fn default_state_field() -> Option<String> {
+        None
+    }
+
+    fn default_states() -> BTreeMap<String, State> {
+        BTreeMap::new()
+    }
+
+    fn default_format() -> Formats {
+        "{status_code}".into()
+    }
+
+    fn default_format_error() -> String {
+        "{status_code}".to_owned()
+    }
+
+    fn default_color_overrides() -> Option<BTreeMap<String, String>> {
+        None
+    }
+}
+
+/// Looks up a dotted path like `status.checks.0.name` in a JSON value.
+fn lookup<'a>(json: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(json, |value, key| match value {
+        Value::Array(array) => array.get(key.parse::<usize>().ok()?),
+        _ => value.get(key),
+    })
+}
+
+/// Strings are shown without quotes, other values as JSON.
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+impl ConfigBlock for Http {
+    type Config = HttpConfig;
+
+    fn new(
+        block_config: Self::Config,
+        config: Config,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let markup = config.markup;
+        if block_config.fields.contains_key("status_code") {
+            return Err(ConfigurationError(
+                "http".to_string(),
+                (
+                    "the field name 'status_code' is reserved".to_string(),
+                    "invalid fields".to_string(),
+                ),
+            ));
+        }
+
+        let auth = match (block_config.username, block_config.password) {
+            (Some(username), password) => {
+                Some(format!("{}:{}", username, password.unwrap_or_default()))
+            }
+            (None, Some(_)) => {
+                return Err(ConfigurationError(
+                    "http".to_string(),
+                    (
+                        "password requires a username".to_string(),
+                        "invalid authentication".to_string(),
+                    ),
+                ))
+            }
+            (None, None) => None,
+        };
+
+        Ok(Http {
+            text: TextWidget::new(config),
+            id: pseudo_uuid(),
+            update_interval: block_config.interval,
+            url: block_config.url,
+            timeout: block_config.timeout,
+            auth,
+            headers: block_config.headers,
+            fields: block_config.fields,
+            state_field: block_config.state_field,
+            states: block_config.states,
+            format: FormatTemplate::from_formats(&block_config.format)
+                .block_error("http", "Invalid format specified")?
+                .with_markup(markup),
+            format_error: FormatTemplate::from_string(&block_config.format_error)
+                .block_error("http", "Invalid format_error specified")?
+                .with_markup(markup),
+            markup,
+        })
+    }
+}
+
+/// Quotes a value for a curl config file.
+fn curl_config_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl Http {
+    /// Requests the URL, returns the status code and the body of the response. Without a
+    /// response the status code is "timeout" or "unreachable", and without curl there is no
+    /// result.
+    fn request(&self) -> Result<Option<(String, String)>> {
+        // Credentials and headers would be visible to everyone in the process list as
+        // arguments, so curl reads them, and the URL, from its standard input instead
+        let mut config = format!("url = {}\n", curl_config_quote(&self.url));
+        if let Some(ref auth) = self.auth {
+            config.push_str(&format!("user = {}\n", curl_config_quote(auth)));
+        }
+        for (name, value) in &self.headers {
+            let header = format!("{}: {}", name, value);
+            config.push_str(&format!("header = {}\n", curl_config_quote(&header)));
+        }
+
+        let child = Command::new("curl")
+            .arg("--silent")
+            .arg("--max-time")
+            .arg(format!("{}", self.timeout.as_secs_f64()))
+            .arg("--write-out")
+            .arg("%{stderr}%{http_code}")
+            .arg("--config")
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).block_error("http", "failed to run curl"),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(config.as_bytes())
+                .block_error("http", "failed to pass the request to curl")?;
+        }
+        let output = child
+            .wait_with_output()
+            .block_error("http", "failed to run curl")?;
+
+        match output.status.code() {
+            Some(0) => Ok(Some((
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                String::from_utf8_lossy(&output.stdout).to_string(),
+            ))),
+            Some(CURL_TIMEOUT) => Ok(Some(("timeout".to_string(), String::new()))),
+            _ => Ok(Some(("unreachable".to_string(), String::new()))),
+        }
+    }
+}
+
+impl Block for Http {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let (status_code, body) = match self.request()? {
+            Some(response) => response,
+            None => {
+                self.text
+                    .set_text("http: curl is not installed".to_string());
+                self.text.set_state(State::Critical);
+                return Ok(Some(self.update_interval.into()));
+            }
+        };
+        // Placeholders only need a JSON body if fields are used
+        let json = if !status_code.starts_with('2') {
+            None
+        } else if self.fields.is_empty() && self.state_field.is_none() {
+            Some(Value::Null)
+        } else {
+            serde_json::from_str::<Value>(&body).ok()
+        };
+        let json = match json {
+            Some(json) => json,
+            None => {
+                let values = map!("{status_code}" => status_code);
+                self.text
+                    .set_text(self.format_error.render_static_str(&values)?);
+                self.text.set_state(State::Critical);
+                return Ok(Some(self.update_interval.into()));
+            }
+        };
+
+        let keys: Vec<String> = self
+            .fields
+            .keys()
+            .map(|name| format!("{{{}}}", name))
+            .collect();
+        let mut values: HashMap<&str, String> = keys
+            .iter()
+            .zip(self.fields.values())
+            .map(|(key, path)| {
+                let value = lookup(&json, path).map(display_value).unwrap_or_default();
+                (key.as_str(), escape_pango_value(value, self.markup))
+            })
+            .collect();
+        values.insert("{status_code}", status_code);
+
+        let state = self
+            .state_field
+            .as_ref()
+            .and_then(|path| lookup(&json, path))
+            .and_then(|value| self.states.get(&display_value(value)))
+            .cloned()
+            .unwrap_or(State::Idle);
+
+        self.text.set_text(self.format.render_static_str(&values)?);
+        self.text.set_state(state);
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let json: Value = serde_json::from_str(
+            r#"{"status": {"healthy": true, "checks": [{"name": "db"}]}, "version": "1.2"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            lookup(&json, "status.healthy").map(display_value),
+            Some("true".to_string())
+        );
+        assert_eq!(
+            lookup(&json, "status.checks.0.name").map(display_value),
+            Some("db".to_string())
+        );
+        assert_eq!(
+            lookup(&json, "version").map(display_value),
+            Some("1.2".to_string())
+        );
+        assert!(lookup(&json, "status.missing").is_none());
+        assert!(lookup(&json, "status.checks.1").is_none());
+    }
+
+    #[test]
+    fn test_curl_config_quote() {
+        assert_eq!(curl_config_quote("user:pass"), r#""user:pass""#);
+        assert_eq!(curl_config_quote("a \"b\" \\ c\n"), r#""a \"b\" \\ c\n""#);
+    }
+}
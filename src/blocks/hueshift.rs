@@ -1,6 +1,9 @@
 use std::collections::BTreeMap;
-use std::process::Command;
-use std::time::Duration;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
@@ -18,13 +21,16 @@ use crate::widgets::button::ButtonWidget;
 pub struct Hueshift {
     text: ButtonWidget,
     id: String,
-    update_interval: Duration,
     step: u16,
     current_temp: u16,
     max_temp: u16,
     min_temp: u16,
     hue_shifter: Option<HueShifter>,
     click_temp: u16,
+    /// The temperature last read from a running redshift or gammastep
+    queried_temp: Arc<Mutex<Option<u16>>>,
+    /// The temperature was set with the block, so the scheduled one isn't shown
+    manual: bool,
 
     //useful, but optional
     #[allow(dead_code)]
@@ -38,6 +44,7 @@ pub struct Hueshift {
 pub enum HueShifter {
     Redshift,
     Sct,
+    Gammastep,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -55,8 +62,9 @@ pub struct HueshiftConfig {
     #[serde(default = "HueshiftConfig::default_min_temp")]
     pub min_temp: u16,
 
-    // TODO: Detect currently defined temperature
     /// Currently defined temperature default to 6500K.
+    /// If redshift or gammastep is running, the temperature is read from it every `interval`,
+    /// until it's set with the block.
     #[serde(default = "HueshiftConfig::default_current_temp")]
     pub current_temp: u16,
 
@@ -99,11 +107,13 @@ impl HueshiftConfig {
 
     /// Prefer any installed shifter, redshift is preferred though.
     fn default_hue_shifter() -> Option<HueShifter> {
-        let (redshift, sct) = what_is_supported();
+        let (redshift, sct, gammastep) = what_is_supported();
         if redshift {
             return Some(HueShifter::Redshift);
         } else if sct {
             return Some(HueShifter::Sct);
+        } else if gammastep {
+            return Some(HueShifter::Gammastep);
         }
 
         None
@@ -126,7 +136,6 @@ impl ConfigBlock for Hueshift {
         config: Config,
         tx_update_request: Sender<Task>,
     ) -> Result<Self> {
-        let current_temp = block_config.current_temp;
         let mut step = block_config.step;
         let id = pseudo_uuid();
         let mut max_temp = block_config.max_temp;
@@ -141,9 +150,49 @@ impl ConfigBlock for Hueshift {
         if block_config.min_temp < 1000 || block_config.min_temp > block_config.max_temp {
             min_temp = 1000;
         }
+        let current_temp = block_config.current_temp.max(min_temp).min(max_temp);
+
+        // Something else (e.g. automatic day/night transitions) may change the temperature,
+        // which is queried on a thread since the programs may take a while to answer
+        let queried_temp = Arc::new(Mutex::new(None));
+        let program = match block_config.hue_shifter {
+            Some(HueShifter::Redshift) => Some("redshift"),
+            Some(HueShifter::Gammastep) => Some("gammastep"),
+            _ => None,
+        };
+        if let Some(program) = program {
+            let queried = queried_temp.clone();
+            let interval = block_config.interval;
+            let update_id = id.clone();
+            let update_request = tx_update_request.clone();
+            thread::Builder::new()
+                .name("hueshift".into())
+                .spawn(move || loop {
+                    let temp = query_temp(program);
+                    let changed = {
+                        let mut queried = queried.lock().unwrap();
+                        let changed = temp.is_some() && temp != *queried;
+                        if changed {
+                            *queried = temp;
+                        }
+                        changed
+                    };
+                    if changed {
+                        let task = Task {
+                            id: update_id.clone(),
+                            update_time: Instant::now(),
+                        };
+                        if update_request.send(task).is_err() {
+                            break;
+                        }
+                    }
+                    thread::sleep(interval);
+                })
+                .block_error("hueshift", "failed to start temperature query thread")?;
+        }
+
         Ok(Hueshift {
             id: id.clone(),
-            update_interval: block_config.interval,
             text: ButtonWidget::new(config.clone(), &id).with_text(&current_temp.to_string()),
             tx_update_request,
             step,
@@ -151,7 +200,9 @@ impl ConfigBlock for Hueshift {
             min_temp,
             current_temp,
             hue_shifter: block_config.hue_shifter,
-            click_temp: block_config.click_temp,
+            click_temp: block_config.click_temp.max(min_temp).min(max_temp),
+            queried_temp,
+            manual: false,
             config,
         })
     }
@@ -159,8 +210,15 @@ impl ConfigBlock for Hueshift {
 
 impl Block for Hueshift {
     fn update(&mut self) -> Result<Option<Update>> {
+        // redshift and gammastep report the scheduled temperature, not one set with the block
+        if !self.manual {
+            if let Some(temp) = *self.queried_temp.lock().unwrap() {
+                self.current_temp = temp;
+            }
+        }
         self.text.set_text(&self.current_temp.to_string());
-        Ok(Some(self.update_interval.into()))
+        // The query thread requests an update when the temperature changes
+        Ok(None)
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
@@ -173,14 +231,18 @@ impl Block for Hueshift {
                 match event.button {
                     MouseButton::Left => {
                         self.current_temp = self.click_temp;
+                        self.manual = true;
                         update_hue(&self.hue_shifter, self.current_temp);
                     }
                     MouseButton::Right => {
                         if self.max_temp > 6500 {
+                            // The reset hands the temperature back to the schedule
                             self.current_temp = 6500;
+                            self.manual = false;
                             reset_hue(&self.hue_shifter);
                         } else {
                             self.current_temp = self.max_temp;
+                            self.manual = true;
                             update_hue(&self.hue_shifter, self.current_temp);
                         }
                     }
@@ -189,17 +251,25 @@ impl Block for Hueshift {
                         let new_temp: u16;
                         match self.config.scrolling.to_logical_direction(mb) {
                             Some(Up) => {
-                                new_temp = self.current_temp + self.step;
-                                if new_temp <= self.max_temp {
+                                new_temp = self
+                                    .current_temp
+                                    .saturating_add(self.step)
+                                    .min(self.max_temp);
+                                if new_temp != self.current_temp {
                                     update_hue(&self.hue_shifter, new_temp);
                                     self.current_temp = new_temp;
+                                    self.manual = true;
                                 }
                             }
                             Some(Down) => {
-                                new_temp = self.current_temp - self.step;
-                                if new_temp >= self.min_temp {
+                                new_temp = self
+                                    .current_temp
+                                    .saturating_sub(self.step)
+                                    .max(self.min_temp);
+                                if new_temp != self.current_temp {
                                     update_hue(&self.hue_shifter, new_temp);
                                     self.current_temp = new_temp;
+                                    self.manual = true;
                                 }
                             }
                             None => {}
@@ -216,9 +286,9 @@ impl Block for Hueshift {
     }
 }
 
-/// Currently, detects whether sct and redshift are installed.
+/// Currently, detects whether redshift, sct and gammastep are installed.
 #[inline]
-fn what_is_supported() -> (bool, bool) {
+fn what_is_supported() -> (bool, bool, bool) {
     let has_redshift = match has_command("hueshift", "redshift") {
         Ok(has_redshift) => has_redshift,
         Err(_) => false,
@@ -229,7 +299,52 @@ fn what_is_supported() -> (bool, bool) {
         Err(_) => false,
     };
 
-    (has_redshift, has_sct)
+    let has_gammastep = has_command("hueshift", "gammastep").unwrap_or(false);
+
+    (has_redshift, has_sct, has_gammastep)
+}
+
+/// Reads the "Color temperature: 6500K" line printed by `redshift -p` and `gammastep -p`.
+fn parse_temp(output: &str) -> Option<u16> {
+    output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Color temperature:"))
+        .and_then(|temp| temp.trim().trim_end_matches('K').parse().ok())
+}
+
+/// How long the commands that query the temperature may run before they're killed.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Runs a command and returns its output, or `None` if it fails or doesn't finish in time.
+fn output_within_timeout(command: &mut Command) -> Option<String> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let deadline = Instant::now() + QUERY_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => break,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+            _ => {
+                child.kill().ok();
+                child.wait().ok();
+                return None;
+            }
+        }
+    }
+    let mut output = String::new();
+    child.stdout?.read_to_string(&mut output).ok()?;
+    Some(output)
+}
+
+/// Queries the temperature of a running redshift or gammastep instance. Without a running
+/// instance nothing else changes the temperature, so `None` is returned then.
+fn query_temp(program: &str) -> Option<u16> {
+    output_within_timeout(Command::new("pgrep").args(&["-x", program]))?;
+    parse_temp(&output_within_timeout(Command::new(program).arg("-p"))?)
 }
 
 #[inline]
@@ -250,6 +365,15 @@ fn update_hue(hue_shifter: &Option<HueShifter>, new_temp: u16) {
                 .spawn()
                 .expect("Failed to set new color temperature using sct.");
         }
+        Some(HueShifter::Gammastep) => {
+            Command::new("sh")
+                .args(&[
+                    "-c",
+                    format!("gammastep -O {} -P >/dev/null 2>&1", new_temp).as_str(),
+                ])
+                .spawn()
+                .expect("Failed to set new color temperature using gammastep.");
+        }
         None => {}
     }
 }
@@ -269,6 +393,12 @@ fn reset_hue(hue_shifter: &Option<HueShifter>) {
                 .spawn()
                 .expect("Failed to set new color temperature using sct.");
         }
+        Some(HueShifter::Gammastep) => {
+            Command::new("sh")
+                .args(&["-c", "gammastep -x >/dev/null 2>&1"])
+                .spawn()
+                .expect("Failed to set new color temperature using gammastep.");
+        }
         None => {}
     }
 }
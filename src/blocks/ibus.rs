@@ -23,7 +23,7 @@ use crate::config::Config;
 use crate::errors::*;
 use crate::input::I3BarEvent;
 use crate::scheduler::Task;
-use crate::util::{pseudo_uuid, xdg_config_home, FormatTemplate};
+use crate::util::{pseudo_uuid, xdg_config_home, FormatTemplate, Formats};
 use crate::widget::I3BarWidget;
 use crate::widgets::text::TextWidget;
 
@@ -42,7 +42,7 @@ pub struct IBusConfig {
     pub mappings: Option<BTreeMap<String, String>>,
 
     #[serde(default = "IBusConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
 
     #[serde(default = "IBusConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
@@ -53,7 +53,7 @@ impl IBusConfig {
         None
     }
 
-    fn default_format() -> String {
+    fn default_format() -> Formats {
         "{engine}".into()
     }
 
@@ -67,6 +67,7 @@ impl ConfigBlock for IBus {
 
     #[allow(clippy::many_single_char_names)]
     fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let markup = config.markup;
         let id: String = pseudo_uuid();
         let id_copy = id.clone();
         let id_copy2 = id.clone();
@@ -222,7 +223,7 @@ impl ConfigBlock for IBus {
             text: TextWidget::new(config).with_text("IBus"),
             engine: engine_original,
             mappings: block_config.mappings,
-            format: FormatTemplate::from_string(&block_config.format)?,
+            format: FormatTemplate::from_formats(&block_config.format)?.with_markup(markup),
         })
     }
 }
@@ -232,6 +233,10 @@ impl Block for IBus {
         &self.id
     }
 
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+
     // Updates the internal state of the block.
     fn update(&mut self) -> Result<Option<Update>> {
         let engine = (*self
@@ -14,7 +14,7 @@ use crate::config::Config;
 use crate::errors::*;
 use crate::input::I3BarEvent;
 use crate::scheduler::Task;
-use crate::util::{battery_level_to_icon, pseudo_uuid, FormatTemplate};
+use crate::util::{battery_level_to_icon, pseudo_uuid, FormatTemplate, Formats};
 use crate::widget::{I3BarWidget, State};
 use crate::widgets::button::ButtonWidget;
 
@@ -44,6 +44,10 @@ pub struct KDEConnectConfig {
     #[serde(default = "KDEConnectConfig::default_device_id")]
     pub device_id: Option<String>,
 
+    /// Name of the device, used if no `device_id` is given
+    #[serde(default = "KDEConnectConfig::default_device_name")]
+    pub device_name: Option<String>,
+
     /// The threshold above which the remaining capacity is shown as good
     #[serde(default = "KDEConnectConfig::default_bat_good")]
     pub bat_good: i32,
@@ -62,7 +66,7 @@ pub struct KDEConnectConfig {
 
     /// Format string for displaying phone information.
     #[serde(default = "KDEConnectConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
 
     /// Format string for displaying phone information when it is disconnected.
     #[serde(default = "KDEConnectConfig::default_format_disconnected")]
@@ -77,6 +81,10 @@ impl KDEConnectConfig {
         None
     }
 
+    fn default_device_name() -> Option<String> {
+        None
+    }
+
     fn default_bat_critical() -> i32 {
         15
     }
@@ -93,7 +101,7 @@ impl KDEConnectConfig {
         60
     }
 
-    fn default_format() -> String {
+    fn default_format() -> Formats {
         "{name} {bat_icon}{bat_charge}% {notif_icon}{notif_count}".into()
     }
 
@@ -131,8 +139,11 @@ impl ConfigBlock for KDEConnect {
             &"Failed to establish D-Bus connection".to_string(),
         )?;
 
-        let device_id = if block_config.device_id.is_none() {
-            // If none specified in block config, just grab the first device found.
+        let device_id = if let Some(device_id) = block_config.device_id {
+            device_id
+        } else {
+            // If none specified in block config, grab the device with the configured name or
+            // just the first device found.
             let p1 = c.with_proxy(
                 "org.kde.kdeconnect",
                 "/modules/kdeconnect",
@@ -145,15 +156,25 @@ impl ConfigBlock for KDEConnect {
                     "kdeconnect",
                     &"Couldn't connect to KDE Connect daemon".to_string(),
                 )?;
-            if devices.is_empty() {
-                return Err(BlockError(
-                    "kdeconnect".to_owned(),
-                    "No devices found.".to_owned(),
-                ));
-            }
-            devices[0].clone()
-        } else {
-            block_config.device_id.unwrap()
+            let device = match block_config.device_name {
+                Some(ref name) => devices.into_iter().find(|device| {
+                    c.with_proxy(
+                        "org.kde.kdeconnect",
+                        format!("/modules/kdeconnect/devices/{}", device),
+                        Duration::from_millis(5000),
+                    )
+                    .get::<String>("org.kde.kdeconnect.device", "name")
+                    .map_or(false, |device_name| device_name == *name)
+                }),
+                None => devices.into_iter().next(),
+            };
+            device.block_error(
+                "kdeconnect",
+                &match block_config.device_name {
+                    Some(name) => format!("No paired device named '{}' found.", name),
+                    None => "No devices found.".to_string(),
+                },
+            )?
         };
 
         let p2 = c.with_proxy(
@@ -168,7 +189,7 @@ impl ConfigBlock for KDEConnect {
 
         let (initial_charge,): (i32,) = p2
             .method_call("org.kde.kdeconnect.device.battery", "charge", ())
-            .unwrap_or((0,));
+            .unwrap_or((-1,));
 
         let (initial_charging,): (bool,) = p2
             .method_call("org.kde.kdeconnect.device.battery", "isCharging", ())
@@ -180,7 +201,7 @@ impl ConfigBlock for KDEConnect {
                 "activeNotifications",
                 (),
             )
-            .unwrap_or((vec![String::from("")],));
+            .unwrap_or((Vec::new(),));
 
         let initial_reachable: bool = p2
             .get("org.kde.kdeconnect.device", "isReachable")
@@ -412,8 +433,9 @@ impl ConfigBlock for KDEConnect {
             bat_info: block_config.bat_info,
             bat_warning: block_config.bat_warning,
             bat_critical: block_config.bat_critical,
-            format: FormatTemplate::from_string(&block_config.format)?,
-            format_disconnected: FormatTemplate::from_string(&block_config.format_disconnected)?,
+            format: FormatTemplate::from_formats(&block_config.format)?.with_markup(config.markup),
+            format_disconnected: FormatTemplate::from_string(&block_config.format_disconnected)?
+                .with_markup(config.markup),
             output: ButtonWidget::new(config.clone(), "kdeconnect").with_icon("phone"),
             config,
         })
@@ -425,6 +447,10 @@ impl Block for KDEConnect {
         &self.id
     }
 
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+
     fn update(&mut self) -> Result<Option<Update>> {
         let charge = (*self
             .battery_charge
@@ -478,8 +504,11 @@ impl Block for KDEConnect {
             "{bat_icon}" => bat_icon.trim().to_string(),
             "{bat_charge}" => if charge < 0 { "x".to_string() } else { charge.to_string() },
             "{bat_state}" => charging.to_string(),
+            "{battery}" => if charge < 0 { "x".to_string() } else { charge.to_string() },
+            "{charging}" => charging.to_string(),
             "{notif_icon}" => self.config.icons.get("notification").cloned().unwrap_or_else(|| "".to_string()).trim().to_string(),
             "{notif_count}" => notif_count.to_string(),
+            "{notifications}" => notif_count.to_string(),
             // TODO
             //"{notif_text}" => notif_text,
             "{name}" => name,
@@ -499,6 +528,9 @@ impl Block for KDEConnect {
             })
         } else if charging {
             self.output.set_state(State::Good);
+        } else if charge < 0 {
+            // The charge is unknown, e.g. if the battery plugin is disabled
+            self.output.set_state(State::Idle);
         } else {
             self.output.set_state(if charge <= self.bat_critical {
                 State::Critical
@@ -6,10 +6,7 @@ use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use dbus::ffidisp::stdintf::org_freedesktop_dbus::Properties;
-use dbus::{
-    ffidisp::{MsgHandlerResult, MsgHandlerType},
-    Message,
-};
+use dbus::Message;
 use serde_derive::Deserialize;
 use swayipc::reply::Event;
 use swayipc::reply::InputChange;
@@ -20,7 +17,7 @@ use crate::config::Config;
 use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::scheduler::Task;
-use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::util::{pseudo_uuid, FormatTemplate, Formats};
 use crate::widget::I3BarWidget;
 use crate::widgets::text::TextWidget;
 
@@ -46,6 +43,12 @@ pub trait KeyboardLayoutMonitor {
     /// Retrieve the current keyboard variant.
     fn keyboard_variant(&self) -> Result<String>;
 
+    /// Whether the layout already includes the variant, so that it's looked up in the
+    /// `mappings` as is.
+    fn layout_includes_variant(&self) -> bool {
+        false
+    }
+
     /// Specify that the monitor does not send update requests and must be
     /// polled manually.
     fn must_poll(&self) -> bool;
@@ -64,33 +67,32 @@ impl SetXkbMap {
     }
 }
 
-fn setxkbmap_layouts() -> Result<String> {
-    let output = Command::new("setxkbmap")
+fn setxkbmap_query() -> Result<String> {
+    Command::new("setxkbmap")
         .args(&["-query"])
         .output()
         .block_error("keyboard_layout", "Failed to execute setxkbmap.")
         .and_then(|raw| {
             String::from_utf8(raw.stdout).block_error("keyboard_layout", "Non-UTF8 input.")
-        })?;
+        })
+}
 
-    // Find the "layout:    xxxx" entry.
-    let layout = output
+/// Finds an entry like "layout:    xxxx" in the output of `setxkbmap -query`.
+fn setxkbmap_entry<'a>(output: &'a str, name: &str) -> Option<&'a str> {
+    output
         .split('\n')
-        .find(|line| line.starts_with("layout"))
-        .ok_or_else(|| {
-            BlockError(
-                "keyboard_layout".to_string(),
-                "Could not find the layout entry from setxkbmap.".to_string(),
-            )
-        })?
+        .find(|line| line.starts_with(name))?
         .split(char::is_whitespace)
-        .last();
+        .last()
+}
 
-    match layout {
+fn setxkbmap_layouts() -> Result<String> {
+    let output = setxkbmap_query()?;
+    match setxkbmap_entry(&output, "layout") {
         Some(layout) => Ok(layout.to_string()),
         None => Err(BlockError(
             "keyboard_layout".to_string(),
-            "Could not read the layout entry from setxkbmap.".to_string(),
+            "Could not find the layout entry from setxkbmap.".to_string(),
         )),
     }
 }
@@ -100,9 +102,16 @@ impl KeyboardLayoutMonitor for SetXkbMap {
         setxkbmap_layouts()
     }
 
-    // Not implemented (TODO?)
+    /// The entry is missing if no variant is set, and it's e.g. ",intl" if there are several
+    /// layouts of which only the second one has a variant.
     fn keyboard_variant(&self) -> Result<String> {
-        Ok("N/A".to_string())
+        let output = setxkbmap_query()?;
+        let variant = setxkbmap_entry(&output, "variant").unwrap_or("");
+        if variant.chars().all(|c| c == ',') {
+            Ok(String::new())
+        } else {
+            Ok(variant.to_string())
+        }
     }
 
     fn must_poll(&self) -> bool {
@@ -188,112 +197,117 @@ impl KeyboardLayoutMonitor for LocaleBus {
 // KbdDaemonBus - use this option if you have kbdd running (https://github.com/qnikst/kbdd,
 // also available in AUR and Debian) running, which enables per window keyboard layout,
 // really handy for dual-language typists who often change window focus
+#[derive(Default)]
 pub struct KbdDaemonBus {
-    // extracted from kbdd dbus message
-    kbdd_layout_id: Arc<Mutex<u32>>,
+    // extracted from kbdd dbus messages, None until kbdd is found on the bus
+    kbdd_layout: Arc<Mutex<Option<String>>>,
 }
 
 impl KbdDaemonBus {
-    pub fn new() -> Result<Self> {
-        Command::new("setxkbmap")
-            .arg("-version")
-            .output()
-            .block_error("kbddaemonbus", "setxkbmap not found")?;
-
-        // also verifies that kbdd daemon is registered in dbus
-        let layout_id = KbdDaemonBus::get_initial_layout_id()?;
-
-        Ok(KbdDaemonBus {
-            kbdd_layout_id: Arc::new(Mutex::new(layout_id)),
-        })
-    }
-
-    fn get_initial_layout_id() -> Result<u32> {
+    /// Connects to the session bus and gets the name of the current layout from kbdd.
+    fn connect() -> Result<(dbus::ffidisp::Connection, String)> {
         let c = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::Session)
             .block_error("kbddaemonbus", "can't connect to dbus")?;
-
-        let send_msg = Message::new_method_call(
-            "ru.gentoo.KbddService",
-            "/ru/gentoo/KbddService",
-            "ru.gentoo.kbdd",
-            "getCurrentLayout",
+        // added before asking for the layout so that no change is missed
+        c.add_match(
+            "interface='ru.gentoo.kbdd',\
+             member='layoutNameChanged',\
+             path='/ru/gentoo/KbddService'",
         )
-        .block_error("kbddaemonbus", "Create get-layout-id message failure")?;
+        .block_error("kbddaemonbus", "Failed to add D-Bus match rule")?;
 
-        let repl_msg = c
-            .send_with_reply_and_block(send_msg, 5000)
-            .block_error("kbddaemonbus", "Is kbdd running?")?;
-
-        let current_layout_id: u32 = repl_msg
-            .get1()
-            .ok_or("")
-            .block_error("kbddaemonbus", "dbus kbdd response error")?;
+        let call = |method: &str, msg: Message| -> Result<Message> {
+            c.send_with_reply_and_block(msg, 5000).block_error(
+                "kbddaemonbus",
+                &format!("{} failed, is kbdd running?", method),
+            )
+        };
+        let layout_id: u32 = call(
+            "getCurrentLayout",
+            Message::new_method_call(
+                "ru.gentoo.KbddService",
+                "/ru/gentoo/KbddService",
+                "ru.gentoo.kbdd",
+                "getCurrentLayout",
+            )
+            .block_error("kbddaemonbus", "Create get-layout-id message failure")?,
+        )?
+        .get1()
+        .block_error("kbddaemonbus", "dbus kbdd response error")?;
+        let layout: String = call(
+            "getLayoutName",
+            Message::new_method_call(
+                "ru.gentoo.KbddService",
+                "/ru/gentoo/KbddService",
+                "ru.gentoo.kbdd",
+                "getLayoutName",
+            )
+            .block_error("kbddaemonbus", "Create get-layout-name message failure")?
+            .append1(layout_id),
+        )?
+        .get1()
+        .block_error("kbddaemonbus", "dbus kbdd response error")?;
 
-        Ok(current_layout_id)
+        Ok((c, layout))
     }
 }
 
 impl KeyboardLayoutMonitor for KbdDaemonBus {
     fn keyboard_layout(&self) -> Result<String> {
-        let layouts_str = setxkbmap_layouts()?;
-        let idx = *self.kbdd_layout_id.lock().unwrap();
-
-        let split = layouts_str.split(',').nth(idx as usize);
-
-        match split {
-            //sometimes (after keyboard attach/detach) setxkbmap reports variant in the layout line,
-            //e.g. 'layout:     us,bg:bas_phonetic,' instead of printing it in its own line,
-            //there is no need to waste space for it, because in most cases there will be only one
-            //variant per layout. TODO - add configuration option to show the variant?!
-            Some(s) => Ok(s.split(':').next().unwrap().to_string()),
-
-            //'None' may happen only if keyboard map is being toggled (by window focus or keyboard)
-            //and keyboard layout replaced (by calling setxkmap) at almost the same time,
-            //frankly I can't reproduce this without thread sleep in monitor function, so instead
-            //of block_error I think showing all layouts will be better until the next
-            //toggling happens
-            None => Ok(layouts_str),
-        }
+        Ok(self
+            .kbdd_layout
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| "N/A".to_string()))
     }
 
     fn keyboard_variant(&self) -> Result<String> {
         // Not implemented (TODO?)
-        Ok("N/A".to_string())
+        Ok(String::new())
     }
 
     fn must_poll(&self) -> bool {
         false
     }
 
-    // Monitor KbdDaemon 'layoutChanged' property in a separate thread and send updates
-    // via the `update_request` channel.
+    // Monitor the KbdDaemon 'layoutNameChanged' signal in a separate thread and send updates
+    // via the `update_request` channel. Until kbdd shows up on the bus, keep trying to
+    // connect to it.
     fn monitor(&self, id: String, update_request: Sender<Task>) {
-        let arc = Arc::clone(&self.kbdd_layout_id);
+        let arc = Arc::clone(&self.kbdd_layout);
+        let send_update = move || {
+            update_request
+                .send(Task {
+                    id: id.clone(),
+                    update_time: Instant::now(),
+                })
+                .unwrap();
+        };
         thread::Builder::new()
             .name("keyboard_layout".into())
             .spawn(move || {
-                let c = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::Session)
-                    .unwrap();
-                c.add_match(
-                    "interface='ru.gentoo.kbdd',\
-                 member='layoutChanged',\
-                 path='/ru/gentoo/KbddService'",
-                )
-                .expect("Failed to add D-Bus match rule, is kbdd started?");
-
-                // skip NameAcquired
-                c.incoming(10_000).next();
-
-                c.add_handler(KbddMessageHandler(arc));
+                let c = loop {
+                    match KbdDaemonBus::connect() {
+                        Ok((c, layout)) => {
+                            *arc.lock().unwrap() = Some(layout);
+                            send_update();
+                            break c;
+                        }
+                        Err(_) => thread::sleep(Duration::from_secs(5)),
+                    }
+                };
+
                 loop {
                     for ci in c.iter(100_000) {
-                        if let dbus::ffidisp::ConnectionItem::Signal(_) = ci {
-                            update_request
-                                .send(Task {
-                                    id: id.clone(),
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
+                        if let dbus::ffidisp::ConnectionItem::Signal(msg) = ci {
+                            let is_layout_name = msg
+                                .member()
+                                .map_or(false, |member| &*member == "layoutNameChanged");
+                            if let (true, Some(layout)) = (is_layout_name, msg.get1::<String>()) {
+                                *arc.lock().unwrap() = Some(layout);
+                                send_update();
+                            }
                         }
                     }
                 }
@@ -302,64 +316,38 @@ impl KeyboardLayoutMonitor for KbdDaemonBus {
     }
 }
 
-struct KbddMessageHandler(Arc<Mutex<u32>>);
-
-impl dbus::ffidisp::MsgHandler for KbddMessageHandler {
-    fn handler_type(&self) -> MsgHandlerType {
-        dbus::ffidisp::MsgHandlerType::MsgType(dbus::MessageType::Signal)
-    }
-
-    fn handle_msg(&mut self, msg: &Message) -> Option<MsgHandlerResult> {
-        let layout: Option<u32> = msg.get1();
-        if let Some(idx) = layout {
-            let mut val = self.0.lock().unwrap();
-            *val = idx;
-        }
-        //handled=false - because we still need to call update_request.send in monitor
-        Some(MsgHandlerResult {
-            handled: false,
-            done: false,
-            reply: vec![],
-        })
-    }
-}
-
 pub struct Sway {
+    sway_kb_identifier: String,
     sway_kb_layout: Arc<Mutex<String>>,
 }
 
 impl Sway {
     pub fn new(sway_kb_identifier: String) -> Result<Self> {
-        let layout = if sway_kb_identifier.is_empty() {
-            swayipc::Connection::new()
-                .unwrap()
-                .get_inputs()
-                .unwrap()
-                .into_iter()
-                .find(|input| input.input_type == "keyboard")
-                .and_then(|input| input.xkb_active_layout_name)
-                .ok_or_else(|| "".to_string())
-                .block_error("sway", "Failed to get xkb_active_layout_name.")?
-        } else {
-            swayipc::Connection::new()
-                .unwrap()
-                .get_inputs()
-                .unwrap()
-                .into_iter()
-                .find(|input| {
-                    input.identifier == sway_kb_identifier && input.input_type == "keyboard"
-                })
-                .and_then(|input| input.xkb_active_layout_name)
-                .ok_or_else(|| "".to_string())
-                .block_error("sway", "Failed to get xkb_active_layout_name.")?
-        };
+        let layout = Connection::new()
+            .block_error("sway", "Failed to connect to the sway IPC socket.")
+            .and_then(|connection| sway_layout(connection, &sway_kb_identifier))?;
 
         Ok(Sway {
+            sway_kb_identifier,
             sway_kb_layout: Arc::new(Mutex::new(layout)),
         })
     }
 }
 
+/// The active layout of the keyboard with the identifier, or of the first keyboard.
+fn sway_layout(mut connection: Connection, sway_kb_identifier: &str) -> Result<String> {
+    connection
+        .get_inputs()
+        .block_error("sway", "Failed to get the inputs.")?
+        .into_iter()
+        .find(|input| {
+            input.input_type == "keyboard"
+                && (sway_kb_identifier.is_empty() || input.identifier == sway_kb_identifier)
+        })
+        .and_then(|input| input.xkb_active_layout_name)
+        .block_error("sway", "Failed to get xkb_active_layout_name.")
+}
+
 impl KeyboardLayoutMonitor for Sway {
     fn keyboard_layout(&self) -> Result<String> {
         let layout = self.sway_kb_layout.lock().unwrap();
@@ -367,8 +355,21 @@ impl KeyboardLayoutMonitor for Sway {
     }
 
     fn keyboard_variant(&self) -> Result<String> {
-        // Not implemented (TODO? Doesn't look like sway IPC exposes this anyway)
-        Ok("N/A".to_string())
+        // sway only reports the description of the layout, e.g. "English (US, intl., with
+        // dead keys)", in which the variant is the part in parentheses
+        let layout = self.sway_kb_layout.lock().unwrap();
+        Ok(layout
+            .find('(')
+            .and_then(|start| {
+                layout[start + 1..]
+                    .strip_suffix(')')
+                    .map(|variant| variant.to_string())
+            })
+            .unwrap_or_default())
+    }
+
+    fn layout_includes_variant(&self) -> bool {
+        true
     }
 
     fn must_poll(&self) -> bool {
@@ -379,43 +380,61 @@ impl KeyboardLayoutMonitor for Sway {
     /// via the `update_request` channel.
     fn monitor(&self, id: String, update_request: Sender<Task>) {
         let arc = Arc::clone(&self.sway_kb_layout);
+        let sway_kb_identifier = self.sway_kb_identifier.clone();
+        let send_update = move || {
+            update_request
+                .send(Task {
+                    id: id.clone(),
+                    update_time: Instant::now(),
+                })
+                .unwrap();
+        };
         thread::Builder::new()
             .name("keyboard_layout".into())
             .spawn(move || {
-                for event in Connection::new()
-                    .unwrap()
-                    .subscribe(&[EventType::Input])
-                    .unwrap()
-                {
-                    match event.unwrap() {
-                        Event::Input(e) => match e.change {
-                            InputChange::XkbLayout => {
-                                if let Some(name) = e.input.xkb_active_layout_name {
-                                    let mut layout = arc.lock().unwrap();
-                                    *layout = name;
-                                }
-                                update_request
-                                    .send(Task {
-                                        id: id.clone(),
-                                        update_time: Instant::now(),
-                                    })
-                                    .unwrap();
+                let mut reconnect = false;
+                loop {
+                    // The socket goes away when sway is reloaded or restarted, and the
+                    // layout may have changed meanwhile
+                    if reconnect {
+                        thread::sleep(Duration::from_secs(1));
+                        match Connection::new()
+                            .block_error("sway", "Failed to connect to the sway IPC socket.")
+                            .and_then(|connection| sway_layout(connection, &sway_kb_identifier))
+                        {
+                            Ok(name) => {
+                                *arc.lock().unwrap() = name;
+                                send_update();
                             }
-                            InputChange::XkbKeymap => {
+                            Err(_) => continue,
+                        }
+                    }
+                    reconnect = true;
+
+                    let events = match Connection::new()
+                        .and_then(|connection| connection.subscribe(&[EventType::Input]))
+                    {
+                        Ok(events) => events,
+                        Err(_) => continue,
+                    };
+                    for event in events {
+                        let e = match event {
+                            Ok(Event::Input(e)) => e,
+                            Ok(_) => continue,
+                            Err(_) => break,
+                        };
+                        let for_keyboard = e.input.input_type == "keyboard"
+                            && (sway_kb_identifier.is_empty()
+                                || e.input.identifier == sway_kb_identifier);
+                        match e.change {
+                            InputChange::XkbLayout | InputChange::XkbKeymap if for_keyboard => {
                                 if let Some(name) = e.input.xkb_active_layout_name {
-                                    let mut layout = arc.lock().unwrap();
-                                    *layout = name;
+                                    *arc.lock().unwrap() = name;
                                 }
-                                update_request
-                                    .send(Task {
-                                        id: id.clone(),
-                                        update_time: Instant::now(),
-                                    })
-                                    .unwrap();
+                                send_update();
                             }
                             _ => {}
-                        },
-                        _ => unreachable!(),
+                        }
                     }
                 }
             })
@@ -427,7 +446,7 @@ impl KeyboardLayoutMonitor for Sway {
 #[serde(default, deny_unknown_fields)]
 pub struct KeyboardLayoutConfig {
     #[serde(default = "KeyboardLayoutConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
 
     driver: KeyboardLayoutDriver,
     #[serde(
@@ -438,13 +457,17 @@ pub struct KeyboardLayoutConfig {
 
     sway_kb_identifier: String,
 
+    /// Text shown instead of a layout, keyed by "layout (variant)" or the layout alone,
+    /// e.g. `{ "us (colemak)" = "CO", "ru" = "РУ" }`
+    mappings: BTreeMap<String, String>,
+
     #[serde(default = "KeyboardLayoutConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
 
 impl KeyboardLayoutConfig {
-    fn default_format() -> String {
-        "{layout}".to_owned()
+    fn default_format() -> Formats {
+        "{layout}".into()
     }
 
     fn default_interval() -> Duration {
@@ -462,6 +485,7 @@ pub struct KeyboardLayout {
     monitor: Box<dyn KeyboardLayoutMonitor>,
     update_interval: Option<Duration>,
     format: FormatTemplate,
+    mappings: BTreeMap<String, String>,
 }
 
 impl ConfigBlock for KeyboardLayout {
@@ -477,7 +501,7 @@ impl ConfigBlock for KeyboardLayout {
                 Box::new(monitor)
             }
             KeyboardLayoutDriver::KbddBus => {
-                let monitor = KbdDaemonBus::new()?;
+                let monitor = KbdDaemonBus::default();
                 monitor.monitor(id.clone(), send);
                 Box::new(monitor)
             }
@@ -492,15 +516,30 @@ impl ConfigBlock for KeyboardLayout {
         } else {
             None
         };
+        let format = FormatTemplate::from_formats(&block_config.format)
+            .block_error(
+                "keyboard_layout",
+                "Invalid format specified for keyboard_layout",
+            )?
+            .with_markup(config.markup);
+        // Fail right away instead of at an update if any of the formats has other placeholders
+        for format in block_config.format.all() {
+            FormatTemplate::from_string(format)
+                .and_then(|format| {
+                    format.render_static_str(&map!("{layout}" => "", "{variant}" => ""))
+                })
+                .block_error(
+                    "keyboard_layout",
+                    "Invalid format specified for keyboard_layout, only {layout} and {variant} are available",
+                )?;
+        }
         Ok(KeyboardLayout {
             id,
             output: TextWidget::new(config),
             monitor,
             update_interval,
-            format: FormatTemplate::from_string(&block_config.format).block_error(
-                "keyboard_layout",
-                "Invalid format specified for keyboard_layout",
-            )?,
+            format,
+            mappings: block_config.mappings,
         })
     }
 }
@@ -510,16 +549,35 @@ impl Block for KeyboardLayout {
         &self.id
     }
 
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+
     fn update(&mut self) -> Result<Option<Update>> {
-        let layout = self.monitor.keyboard_layout()?;
+        let mut layout = self.monitor.keyboard_layout()?;
         let variant = self.monitor.keyboard_variant()?;
+        let with_variant = format!("{} ({})", layout, variant);
+        let mapped = if variant.is_empty() || self.monitor.layout_includes_variant() {
+            None
+        } else {
+            self.mappings.get(&with_variant)
+        };
+        if let Some(mapped) = mapped.or_else(|| self.mappings.get(&layout)) {
+            layout = mapped.clone();
+        }
         let values = map!(
             "{layout}" => layout,
             "{variant}" => variant
         );
 
-        self.output
-            .set_text(self.format.render_static_str(&values)?);
+        let text = self.format.render_static_str(&values)?;
+        // e.g. a layout mapped to "" shows only an icon instead of an empty block
+        if text.trim().is_empty() {
+            self.output.set_icon("keyboard");
+        } else {
+            self.output.set_icon("");
+        }
+        self.output.set_text(text);
         Ok(self.update_interval.map(|d| d.into()))
     }
 
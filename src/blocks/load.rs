@@ -11,7 +11,7 @@ use crate::config::Config;
 use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::scheduler::Task;
-use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::util::{pseudo_uuid, FormatTemplate, Formats};
 use crate::widget::{I3BarWidget, State};
 use crate::widgets::text::TextWidget;
 
@@ -30,7 +30,7 @@ pub struct Load {
 #[serde(deny_unknown_fields)]
 pub struct LoadConfig {
     #[serde(default = "LoadConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
     #[serde(
         default = "LoadConfig::default_interval",
         deserialize_with = "deserialize_duration"
@@ -54,8 +54,8 @@ pub struct LoadConfig {
 }
 
 impl LoadConfig {
-    fn default_format() -> String {
-        "{1m}".to_owned()
+    fn default_format() -> Formats {
+        "{1m}".into()
     }
 
     fn default_interval() -> Duration {
@@ -87,6 +87,7 @@ impl ConfigBlock for Load {
         config: Config,
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let markup = config.markup;
         let text = TextWidget::new(config)
             .with_icon("cogs")
             .with_state(State::Info);
@@ -106,8 +107,9 @@ impl ConfigBlock for Load {
             minimum_info: block_config.info,
             minimum_warning: block_config.warning,
             minimum_critical: block_config.critical,
-            format: FormatTemplate::from_string(&block_config.format)
-                .block_error("load", "Invalid format specified for load")?,
+            format: FormatTemplate::from_formats(&block_config.format)
+                .block_error("load", "Invalid format specified for load")?
+                .with_markup(markup),
             text,
         })
     }
@@ -156,4 +158,8 @@ impl Block for Load {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
 }
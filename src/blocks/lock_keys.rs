@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, read_file, FormatTemplate, Formats};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+const LEDS_PATH: &str = "/sys/class/leds";
+
+/// The LEDs of all keyboards for one lock key.
+struct Led {
+    paths: Vec<PathBuf>,
+}
+
+impl Led {
+    /// Finds the LEDs named e.g. `input3::capslock`.
+    fn find(name: &str) -> Self {
+        let suffix = format!("::{}", name);
+        let mut paths: Vec<PathBuf> = Path::new(LEDS_PATH)
+            .read_dir()
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.file_name().to_string_lossy().ends_with(&suffix))
+                    .map(|entry| entry.path().join("brightness"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        paths.sort();
+        Led { paths }
+    }
+
+    /// The lock is active if the LED of any keyboard is on. Fails if a keyboard is gone.
+    fn on(&self) -> Result<bool> {
+        let mut on = false;
+        for path in &self.paths {
+            on |= read_file("lock_keys", path)? != "0";
+        }
+        Ok(on)
+    }
+}
+
+pub struct LockKeys {
+    text: TextWidget,
+    id: String,
+    update_interval: Duration,
+    format: FormatTemplate,
+    caps: Led,
+    num: Led,
+    scroll: Led,
+    empty: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LockKeysConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "LockKeysConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format override
+    /// placeholders: {caps}, {num}, {scroll}
+    #[serde(default = "LockKeysConfig::default_format")]
+    pub format: Formats,
+
+    #[serde(default = "LockKeysConfig::default_color_overrides")]
+    pub color_overrides: Option<BTreeMap<String, String>>,
+}
+
+impl LockKeysConfig {
+    fn default_interval() -> Duration {
+        Duration::from_millis(500)
+    }
+
+    fn default_format() -> Formats {
+        "{caps}".into()
+    }
+
+    fn default_color_overrides() -> Option<BTreeMap<String, String>> {
+        None
+    }
+}
+
+impl ConfigBlock for LockKeys {
+    type Config = LockKeysConfig;
+
+    fn new(
+        block_config: Self::Config,
+        config: Config,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let markup = config.markup;
+        let mut lock_keys = LockKeys {
+            text: TextWidget::new(config),
+            id: pseudo_uuid(),
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_formats(&block_config.format)
+                .block_error("lock_keys", "Invalid format specified")?
+                .with_markup(markup),
+            caps: Led { paths: Vec::new() },
+            num: Led { paths: Vec::new() },
+            scroll: Led { paths: Vec::new() },
+            empty: true,
+        };
+        lock_keys.discover();
+        if lock_keys.paths().is_empty() {
+            return Err(BlockError(
+                "lock_keys".to_string(),
+                format!("no keyboard LEDs found in {}", LEDS_PATH),
+            ));
+        }
+        Ok(lock_keys)
+    }
+}
+
+impl LockKeys {
+    /// The brightness files of all LEDs.
+    fn paths(&self) -> Vec<PathBuf> {
+        [&self.caps, &self.num, &self.scroll]
+            .iter()
+            .flat_map(|led| led.paths.iter().cloned())
+            .collect()
+    }
+
+    /// Looks for the LEDs, e.g. again after a keyboard was plugged in or out.
+    fn discover(&mut self) {
+        self.caps = Led::find("capslock");
+        self.num = Led::find("numlock");
+        self.scroll = Led::find("scrolllock");
+    }
+}
+
+impl Block for LockKeys {
+    fn update(&mut self) -> Result<Option<Update>> {
+        if self.paths().is_empty() {
+            self.discover();
+        }
+        let (caps, num, scroll) = match (self.caps.on(), self.num.on(), self.scroll.on()) {
+            (Ok(caps), Ok(num), Ok(scroll)) => (caps, num, scroll),
+            _ => {
+                // A keyboard is gone
+                self.discover();
+                (
+                    self.caps.on().unwrap_or(false),
+                    self.num.on().unwrap_or(false),
+                    self.scroll.on().unwrap_or(false),
+                )
+            }
+        };
+        let label = |on: bool, label: &str| if on { label.to_string() } else { String::new() };
+        let values = map!(
+            "{caps}" => label(caps, "CAPS"),
+            "{num}" => label(num, "NUM"),
+            "{scroll}" => label(scroll, "SCROLL")
+        );
+
+        let text = self.format.render_static_str(&values)?;
+        self.empty = text.trim().is_empty();
+        self.text.set_text(text);
+        self.text
+            .set_state(if caps { State::Warning } else { State::Idle });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.empty {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+}
@@ -165,6 +165,8 @@ pub struct Memory {
     tx_update_request: Sender<Task>,
     warning: (f64, f64),
     critical: (f64, f64),
+    delta: (DeltaTracker, DeltaTracker),
+    delta_warning: (Option<f64>, Option<f64>),
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -212,6 +214,21 @@ pub struct MemoryConfig {
     #[serde(default = "MemoryConfig::default_critical_swap")]
     pub critical_swap: f64,
 
+    /// Growth of the used memory in MiB within `delta_window`, where state is set to warning
+    #[serde(default = "MemoryConfig::default_delta_warning")]
+    pub delta_warning_mem: Option<f64>,
+
+    /// Growth of the used swap in MiB within `delta_window`, where state is set to warning
+    #[serde(default = "MemoryConfig::default_delta_warning")]
+    pub delta_warning_swap: Option<f64>,
+
+    /// Time span in seconds over which the growth is compared with the delta warnings
+    #[serde(
+        default = "MemoryConfig::default_delta_window",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub delta_window: Duration,
+
     #[serde(default = "MemoryConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
@@ -257,6 +274,14 @@ impl MemoryConfig {
         95.0
     }
 
+    fn default_delta_warning() -> Option<f64> {
+        None
+    }
+
+    fn default_delta_window() -> Duration {
+        Duration::from_secs(60)
+    }
+
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
         None
     }
@@ -275,6 +300,14 @@ impl Memory {
         let mem_used = Unit::KiB(mem_total_used.n() - (buffers.n() + cached.n()));
         let mem_avail = Unit::KiB(mem_total.n() - mem_used.n());
 
+        // Both are tracked so that switching the view doesn't lose the history
+        let mem_delta = self.delta.0.push(mem_used.n() as f64 / 1024.);
+        let swap_delta = self.delta.1.push(swap_used.n() as f64 / 1024.);
+        let (delta, window_delta, delta_warning) = match self.memtype {
+            Memtype::Memory => (mem_delta.0, mem_delta.1, self.delta_warning.0),
+            Memtype::Swap => (swap_delta.0, swap_delta.1, self.delta_warning.1),
+        };
+
         let values = map!(
             "{MTg}" => format!("{:.1}", mem_total.gib()),
             "{MTm}" => format!("{}", mem_total.mib()),
@@ -319,12 +352,14 @@ impl Memory {
             "{Cm}" => format!("{}", cached.mib()),
             "{Cp}" => format!("{:.2}", cached.percent(mem_total)),
             "{Cpi}" => format!("{:02}", cached.percent(mem_total) as i32),
-            "{Cpb}" => format_percent_bar(cached.percent(mem_total)));
+            "{Cpb}" => format_percent_bar(cached.percent(mem_total)),
+            "{delta}" => format!("{:+}", delta.round() as i64));
 
+        let growing = delta_warning.map_or(false, |warning| window_delta > warning);
         match self.memtype {
             Memtype::Memory => self.output.0.set_state(match mem_used.percent(mem_total) {
                 x if f64::from(x) > self.critical.0 => State::Critical,
-                x if f64::from(x) > self.warning.0 => State::Warning,
+                x if f64::from(x) > self.warning.0 || growing => State::Warning,
                 _ => State::Idle,
             }),
             Memtype::Swap => self
@@ -332,7 +367,7 @@ impl Memory {
                 .1
                 .set_state(match swap_used.percent(swap_total) {
                     x if f64::from(x) > self.critical.1 => State::Critical,
-                    x if f64::from(x) > self.warning.1 => State::Warning,
+                    x if f64::from(x) > self.warning.1 || growing => State::Warning,
                     _ => State::Idle,
                 }),
         };
@@ -356,6 +391,7 @@ impl ConfigBlock for Memory {
     type Config = MemoryConfig;
 
     fn new(block_config: Self::Config, config: Config, tx: Sender<Task>) -> Result<Self> {
+        let markup = config.markup;
         let icons: bool = block_config.icons;
         let widget = ButtonWidget::new(config, "memory").with_text("");
         Ok(Memory {
@@ -371,13 +407,21 @@ impl ConfigBlock for Memory {
             },
             clickable: block_config.clickable,
             format: (
-                FormatTemplate::from_string(&block_config.format_mem)?,
-                FormatTemplate::from_string(&block_config.format_swap)?,
+                FormatTemplate::from_string(&block_config.format_mem)?.with_markup(markup),
+                FormatTemplate::from_string(&block_config.format_swap)?.with_markup(markup),
             ),
             update_interval: block_config.interval,
             tx_update_request: tx,
             warning: (block_config.warning_mem, block_config.warning_swap),
             critical: (block_config.critical_mem, block_config.critical_swap),
+            delta: (
+                DeltaTracker::new(block_config.delta_window),
+                DeltaTracker::new(block_config.delta_window),
+            ),
+            delta_warning: (
+                block_config.delta_warning_mem,
+                block_config.delta_warning_swap,
+            ),
         })
     }
 }
@@ -12,7 +12,7 @@ use crate::errors::*;
 use crate::input::I3BarEvent;
 use crate::input::MouseButton::*;
 use crate::scheduler::Task;
-use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::util::{pseudo_uuid, FormatTemplate, Formats};
 use crate::widget::I3BarWidget;
 use crate::widgets::button::ButtonWidget;
 use mpd::status::State::{Pause, Play};
@@ -47,7 +47,7 @@ pub struct MpdConfig {
     pub interval: Duration,
 
     #[serde(default = "MpdConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
 
     #[serde(default = "MpdConfig::default_ip")]
     pub ip: String,
@@ -60,8 +60,8 @@ impl MpdConfig {
     fn default_interval() -> Duration {
         Duration::from_secs(1)
     }
-    fn default_format() -> String {
-        String::from("{artist} - {title} [{playback_info}]{repeat}{random}{single}{consume}")
+    fn default_format() -> Formats {
+        "{artist} - {title} [{playback_info}]{repeat}{random}{single}{consume}".into()
     }
 
     fn default_ip() -> String {
@@ -89,8 +89,9 @@ impl ConfigBlock for Mpd {
             update_interval: block_config.interval,
             mpd_conn: Cell::new(Client::connect(&block_config.ip).unwrap()),
             ip: block_config.ip,
-            format: FormatTemplate::from_string(&block_config.format)
-                .block_error("Mpd", "Invalid format for mpd format")?,
+            format: FormatTemplate::from_formats(&block_config.format)
+                .block_error("Mpd", "Invalid format for mpd format")?
+                .with_markup(config.markup),
             tx_update_request,
             config,
         })
@@ -212,4 +213,8 @@ impl Block for Mpd {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
 }
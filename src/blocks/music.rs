@@ -23,7 +23,7 @@ use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::subprocess::spawn_child_async;
-use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::util::{pseudo_uuid, FormatTemplate, Formats};
 use crate::widget::{I3BarWidget, Spacing, State};
 use crate::widgets::button::ButtonWidget;
 use crate::widgets::rotatingtext::RotatingTextWidget;
@@ -216,7 +216,7 @@ pub struct MusicConfig {
 
     /// Format string for displaying music player info.
     #[serde(default = "MusicConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
 
     #[serde(default = "MusicConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
@@ -275,8 +275,8 @@ impl MusicConfig {
         false
     }
 
-    fn default_format() -> String {
-        "{combo}".to_string()
+    fn default_format() -> Formats {
+        "{combo}".into()
     }
 
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
@@ -288,6 +288,7 @@ impl ConfigBlock for Music {
     type Config = MusicConfig;
 
     fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let markup = config.markup;
         let id: String = pseudo_uuid();
         let id_copy = id.clone();
         let id_copy2 = id.clone();
@@ -545,7 +546,7 @@ impl ConfigBlock for Music {
             players: players_copy,
             hide_when_empty: block_config.hide_when_empty,
             send: send3,
-            format: FormatTemplate::from_string(&block_config.format)?,
+            format: FormatTemplate::from_formats(&block_config.format)?.with_markup(markup),
         })
     }
 }
@@ -555,6 +556,10 @@ impl Block for Music {
         &self.id
     }
 
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+
     fn update(&mut self) -> Result<Option<Update>> {
         let (rotation_in_progress, time_to_next_rotation) = if self.marquee {
             self.current_song_widget.next()?
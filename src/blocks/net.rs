@@ -1,15 +1,19 @@
 use std::collections::BTreeMap;
 use std::ffi::OsStr;
 use std::fmt;
-use std::fs::{read_to_string, OpenOptions};
+use std::fs::{read_dir, read_to_string, OpenOptions};
 use std::io::prelude::*;
 use std::io::ErrorKind;
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::{Duration, Instant};
 
+use chrono::{Local, NaiveDate};
 use crossbeam_channel::Sender;
 use lazy_static::lazy_static;
+use nix::ifaddrs::getifaddrs;
+use nix::sys::socket::SockAddr;
 use regex::bytes::Regex;
 use serde_derive::Deserialize;
 
@@ -21,10 +25,10 @@ use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::subprocess::spawn_child_async;
 use crate::util::{
-    escape_pango_text, format_percent_bar, format_speed, format_vec_to_bar_graph, pseudo_uuid,
-    FormatTemplate,
+    escape_pango_value, format_percent_bar, format_speed, format_vec_to_bar_graph, pseudo_uuid,
+    wildcard_to_regex, FormatTemplate, Formats,
 };
-use crate::widget::{I3BarWidget, Spacing};
+use crate::widget::{I3BarWidget, Spacing, State};
 use crate::widgets::button::ButtonWidget;
 
 lazy_static! {
@@ -197,11 +201,14 @@ impl NetworkDevice {
             ));
         }
 
-        let iw_output = Command::new("iw")
+        // iw uses nl80211, /proc/net/wireless is the fallback if it isn't installed
+        let iw_output = match Command::new("iw")
             .args(&["dev", &self.device, "link"])
             .output()
-            .block_error("net", "Failed to execute signal strength query.")?
-            .stdout;
+        {
+            Ok(output) => output.stdout,
+            Err(_) => return Ok(self.proc_signal_strength()),
+        };
 
         if let Some(raw) = IW_SIGNAL_REGEX
             .captures_iter(&iw_output)
@@ -216,10 +223,27 @@ impl NetworkDevice {
                 })
                 .map(Some)
         } else {
-            Ok(None)
+            Ok(self.proc_signal_strength())
         }
     }
 
+    /// Reads the signal level in dBm from `/proc/net/wireless`, where a line looks like
+    /// `wlan0: 0000   54.  -56.  -256        0      0      0      0      0        0`.
+    fn proc_signal_strength(&self) -> Option<i32> {
+        let wireless = read_to_string("/proc/net/wireless").ok()?;
+        let line = wireless
+            .lines()
+            .find(|line| line.trim_start().starts_with(&format!("{}:", self.device)))?;
+        let level = line
+            .split_whitespace()
+            .nth(3)?
+            .trim_end_matches('.')
+            .parse::<i32>()
+            .ok()?;
+        // Some drivers report the level as an unsigned byte
+        Some(if level > 0 { level - 256 } else { level })
+    }
+
     fn relative_signal_strength(&self) -> Result<Option<u32>> {
         let xbm = if let Some(xbm) = self.absolute_signal_strength()? {
             xbm as f64
@@ -227,79 +251,55 @@ impl NetworkDevice {
             return Ok(None);
         };
 
-        // Code inspired by https://github.com/NetworkManager/NetworkManager/blob/master/src/platform/wifi/nm-wifi-utils-nl80211.c
-        const NOISE_FLOOR_DBM: f64 = -90.;
-        const SIGNAL_MAX_DBM: f64 = -20.;
+        Ok(Some(signal_percent(xbm)))
+    }
 
-        let xbm = if xbm < NOISE_FLOOR_DBM {
-            NOISE_FLOOR_DBM
-        } else if xbm > SIGNAL_MAX_DBM {
-            SIGNAL_MAX_DBM
-        } else {
-            xbm
-        };
+    /// The addresses assigned to this device.
+    fn addresses(&self) -> Result<Vec<IpAddr>> {
+        Ok(getifaddrs()
+            .block_error(
+                "net",
+                "Failed to query the addresses of the network devices",
+            )?
+            .filter(|ifaddr| ifaddr.interface_name == self.device)
+            .filter_map(|ifaddr| match ifaddr.address {
+                Some(SockAddr::Inet(addr)) => Some(addr.to_std().ip()),
+                _ => None,
+            })
+            .collect())
+    }
 
-        let result = 100. - 70. * ((SIGNAL_MAX_DBM - xbm) / (SIGNAL_MAX_DBM - NOISE_FLOOR_DBM));
-        let result = result as u32;
-        Ok(Some(result))
+    /// Checks whether this device has any address assigned.
+    pub fn has_address(&self) -> Result<bool> {
+        Ok(!self.addresses()?.is_empty())
     }
 
-    /// Queries the inet IP of this device (using `ip`).
+    /// Queries the inet IP of this device.
     pub fn ip_addr(&self) -> Result<Option<String>> {
-        if !self.is_up()? {
-            return Ok(None);
-        }
-        let output = Command::new("ip")
-            .args(&["-json", "-family", "inet", "address", "show", &self.device])
-            .output()
-            .block_error("net", "Failed to execute IP address query.")
-            .and_then(|raw_output| {
-                String::from_utf8(raw_output.stdout)
-                    .block_error("net", "Response contained non-UTF8 characters.")
-            })?;
-
-        let ip_devs: Vec<IpDev> =
-            serde_json::from_str(&output).block_error("net", "Failed to parse JSON response")?;
-
-        if ip_devs.is_empty() {
-            return Ok(Some("".to_string()));
-        }
-
-        let ip = ip_devs
-            .iter()
-            .flat_map(|dev| &dev.addr_info)
-            .filter_map(|addr| addr.local.clone())
-            .next();
-        Ok(ip)
+        self.address(false)
     }
 
-    /// Queries the inet IPv6 of this device (using `ip`).
+    /// Queries the inet IPv6 of this device.
     pub fn ipv6_addr(&self) -> Result<Option<String>> {
+        self.address(true)
+    }
+
+    /// Queries the address of the given version, preferring addresses that aren't link-local.
+    fn address(&self, ipv6: bool) -> Result<Option<String>> {
         if !self.is_up()? {
             return Ok(None);
         }
-        let output = Command::new("ip")
-            .args(&["-json", "-family", "inet6", "address", "show", &self.device])
-            .output()
-            .block_error("net", "Failed to execute IP address query.")
-            .and_then(|raw_output| {
-                String::from_utf8(raw_output.stdout)
-                    .block_error("net", "Response contained non-UTF8 characters.")
-            })?;
-
-        let ip_devs: Vec<IpDev> =
-            serde_json::from_str(&output).block_error("net", "Failed to parse JSON response")?;
-
-        if ip_devs.is_empty() {
-            return Ok(Some("".to_string()));
-        }
-
-        let ip = ip_devs
+        let addresses: Vec<IpAddr> = self
+            .addresses()?
+            .into_iter()
+            .filter(|addr| addr.is_ipv6() == ipv6)
+            .collect();
+        let ip = addresses
             .iter()
-            .flat_map(|dev| &dev.addr_info)
-            .filter_map(|addr| addr.local.clone())
-            .next();
-        Ok(ip)
+            .find(|addr| !is_link_local(addr))
+            .or_else(|| addresses.first())
+            .map(IpAddr::to_string);
+        Ok(Some(ip.unwrap_or_default()))
     }
 
     /// Queries the bitrate of this device
@@ -356,13 +356,23 @@ pub struct Net {
     max_ssid_width: usize,
     signal_strength: Option<String>,
     signal_strength_bar: Option<String>,
+    signal_warning: Option<u32>,
     ip_addr: Option<String>,
     ipv6_addr: Option<String>,
+    /// Whether the format uses `{ip}` or `{ip_version}`, which need both addresses
+    uses_ip: bool,
+    ip_version: IpVersion,
     bitrate: Option<String>,
     output_tx: Option<String>,
     graph_tx: Option<String>,
     output_rx: Option<String>,
     graph_rx: Option<String>,
+    vpn: Option<String>,
+    vpn_name: Option<String>,
+    vpn_interfaces: Vec<regex::Regex>,
+    vpn_symbol: String,
+    vpn_indicator: Option<ButtonWidget>,
+    vpn_up: bool,
     id: String,
     update_interval: Duration,
     device: NetworkDevice,
@@ -371,6 +381,10 @@ pub struct Net {
     rx_buff: Vec<u64>,
     tx_bytes: u64,
     rx_bytes: u64,
+    total_tx: Option<u64>,
+    total_rx: Option<u64>,
+    reset: Reset,
+    reset_date: NaiveDate,
     use_bits: bool,
     speed_min_unit: Unit,
     speed_digits: usize,
@@ -403,6 +417,36 @@ impl fmt::Display for Unit {
     }
 }
 
+/// Which address `{ip}` shows.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IpVersion {
+    Ipv4,
+    Ipv6,
+}
+
+impl Default for IpVersion {
+    fn default() -> Self {
+        IpVersion::Ipv4
+    }
+}
+
+/// When the `{total_up}` and `{total_down}` counters are reset.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Reset {
+    /// Count since the block was started
+    Never,
+    /// Count since local midnight
+    Daily,
+}
+
+impl Default for Reset {
+    fn default() -> Self {
+        Reset::Never
+    }
+}
+
 #[derive(Deserialize, Debug, Default, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct NetConfig {
@@ -414,7 +458,7 @@ pub struct NetConfig {
     pub interval: Duration,
 
     #[serde(default = "NetConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
 
     /// Which interface in /sys/class/net/ to read from.
     #[serde(default = "NetConfig::default_device")]
@@ -439,6 +483,10 @@ pub struct NetConfig {
     #[serde(default = "NetConfig::default_signal_strength_bar")]
     pub signal_strength_bar: bool,
 
+    /// Signal strength in percent below which the state is set to warning.
+    #[serde(default = "NetConfig::default_signal_warning")]
+    pub signal_warning: Option<u32>,
+
     /// Whether to show the bitrate of active wireless networks.
     #[serde(default = "NetConfig::default_bitrate")]
     pub bitrate: bool,
@@ -451,6 +499,10 @@ pub struct NetConfig {
     #[serde(default = "NetConfig::default_ipv6")]
     pub ipv6: bool,
 
+    /// The address `{ip}` shows if the device has both an IPv4 and an IPv6 address
+    #[serde(default = "NetConfig::default_ip_version")]
+    pub ip_version: IpVersion,
+
     /// Whether to hide networks that are down/inactive completely.
     #[serde(default = "NetConfig::default_hide_inactive")]
     pub hide_inactive: bool,
@@ -487,6 +539,22 @@ pub struct NetConfig {
     #[serde(default = "NetConfig::default_graph_down")]
     pub graph_down: bool,
 
+    /// When to reset the transferred bytes counters.
+    #[serde(default = "NetConfig::default_reset")]
+    pub reset: Reset,
+
+    /// Interface name patterns (`*` matches anything) that are considered VPN tunnels.
+    #[serde(default = "NetConfig::default_vpn_interfaces")]
+    pub vpn_interfaces: Vec<String>,
+
+    /// Text shown by `{vpn}` while a VPN tunnel is up.
+    #[serde(default = "NetConfig::default_vpn_symbol")]
+    pub vpn_symbol: String,
+
+    /// Whether to show a separate VPN icon while a VPN tunnel is up.
+    #[serde(default = "NetConfig::default_vpn_indicator")]
+    pub vpn_indicator: bool,
+
     #[serde(default = "NetConfig::default_on_click")]
     pub on_click: Option<String>,
 
@@ -499,8 +567,8 @@ impl NetConfig {
         Duration::from_secs(1)
     }
 
-    fn default_format() -> String {
-        "{speed_up} {speed_down}".to_owned()
+    fn default_format() -> Formats {
+        "{speed_up} {speed_down}".into()
     }
 
     fn default_device() -> String {
@@ -538,6 +606,10 @@ impl NetConfig {
         false
     }
 
+    fn default_signal_warning() -> Option<u32> {
+        None
+    }
+
     fn default_bitrate() -> bool {
         false
     }
@@ -550,6 +622,10 @@ impl NetConfig {
         false
     }
 
+    fn default_ip_version() -> IpVersion {
+        IpVersion::default()
+    }
+
     fn default_speed_up() -> bool {
         true
     }
@@ -578,6 +654,22 @@ impl NetConfig {
         3
     }
 
+    fn default_reset() -> Reset {
+        Reset::Never
+    }
+
+    fn default_vpn_interfaces() -> Vec<String> {
+        vec!["tun*".to_string(), "wg*".to_string(), "ppp*".to_string()]
+    }
+
+    fn default_vpn_symbol() -> String {
+        "VPN".to_string()
+    }
+
+    fn default_vpn_indicator() -> bool {
+        false
+    }
+
     fn default_on_click() -> Option<String> {
         None
     }
@@ -613,17 +705,30 @@ impl ConfigBlock for Net {
             block_config.format
         } else if let Some(format) = old_format(&net_config) {
             // Only choose those deprecated options which are true
-            format
+            format.into()
         } else {
             // Default format
             block_config.format
         };
 
+        let vpn_interfaces = block_config
+            .vpn_interfaces
+            .iter()
+            .map(|pattern| {
+                wildcard_to_regex(pattern).block_error(
+                    "net",
+                    &format!("invalid VPN interface pattern: {}", pattern),
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let uses_ip = format.contains("{ip}") || format.contains("{ip_version}");
+
         Ok(Net {
             id: id.clone(),
             update_interval: block_config.interval,
-            format: FormatTemplate::from_string(&format)
-                .block_error("net", "Invalid format specified")?,
+            format: FormatTemplate::from_formats(&format)
+                .block_error("net", "Invalid format specified")?
+                .with_markup(config.markup),
             output: ButtonWidget::new(config.clone(), "")
                 .with_text("")
                 .with_spacing(Spacing::Inline),
@@ -631,6 +736,11 @@ impl ConfigBlock for Net {
             use_bits: block_config.use_bits,
             speed_min_unit: block_config.speed_min_unit,
             speed_digits: block_config.speed_digits,
+            vpn_indicator: if block_config.vpn_indicator {
+                Some(ButtonWidget::new(config.clone(), &id).with_icon("net_vpn"))
+            } else {
+                None
+            },
             network: ButtonWidget::new(config, &id).with_icon(if wireless {
                 "net_wireless"
             } else if vpn {
@@ -641,13 +751,13 @@ impl ConfigBlock for Net {
             // Might want to signal an error if the user wants the SSID of a
             // wired connection instead.
             ssid: if wireless && format.contains("{ssid}") {
-                Some(" ".to_string())
+                Some("".to_string())
             } else {
                 None
             },
             max_ssid_width: block_config.max_ssid_width,
             signal_strength: if wireless && format.contains("{signal_strength}") {
-                Some(0.to_string())
+                Some("".to_string())
             } else {
                 None
             },
@@ -656,32 +766,60 @@ impl ConfigBlock for Net {
             } else {
                 None
             },
+            signal_warning: block_config.signal_warning,
             // TODO: a better way to deal with this?
             bitrate: if format.contains("{bitrate}") {
                 Some("".to_string())
             } else {
                 None
             },
-            ip_addr: if format.contains("{ip}") {
+            ip_addr: if uses_ip || format.contains("{ipv4}") {
                 Some("".to_string())
             } else {
                 None
             },
-            ipv6_addr: if format.contains("{ipv6}") {
+            ipv6_addr: if uses_ip || format.contains("{ipv6}") {
                 Some("".to_string())
             } else {
                 None
             },
+            uses_ip,
+            ip_version: block_config.ip_version,
             output_tx: Some("".to_string()),
             output_rx: Some("".to_string()),
             graph_tx: Some("".to_string()),
             graph_rx: Some("".to_string()),
+            vpn: if format.contains("{vpn}") {
+                Some("".to_string())
+            } else {
+                None
+            },
+            vpn_name: if format.contains("{vpn_name}") {
+                Some("".to_string())
+            } else {
+                None
+            },
+            vpn_interfaces,
+            vpn_symbol: block_config.vpn_symbol,
+            vpn_up: false,
             device,
             auto_device: block_config.auto_device,
             rx_buff: vec![0; 10],
             tx_buff: vec![0; 10],
             rx_bytes: init_rx_bytes,
             tx_bytes: init_tx_bytes,
+            total_tx: if format.contains("{total_up}") {
+                Some(0)
+            } else {
+                None
+            },
+            total_rx: if format.contains("{total_down}") {
+                Some(0)
+            } else {
+                None
+            },
+            reset: block_config.reset,
+            reset_date: Local::now().date().naive_local(),
             active: true,
             exists: true,
             hide_inactive: block_config.hide_inactive,
@@ -727,6 +865,35 @@ fn old_format(net_config: &toml::Value) -> Option<String> {
     Some(result)
 }
 
+/// How close to its maximum a 32-bit counter has to be for a decrease to be taken as a
+/// wraparound.
+const COUNTER_WRAP_MARGIN: u64 = 1 << 30;
+
+/// Computes how many bytes were transferred between two readings of a statistics counter.
+/// Old drivers expose 32-bit counters, which wrap around. Any other decrease of a counter
+/// means it was reset, e.g. because the interface was recreated, and nothing is counted.
+fn counter_delta(previous: u64, current: u64) -> u64 {
+    let max = u64::from(u32::MAX);
+    if current >= previous {
+        current - previous
+    } else if previous <= max && previous >= max - COUNTER_WRAP_MARGIN {
+        max - previous + current + 1
+    } else {
+        0
+    }
+}
+
+/// Maps a signal level in dBm to a percentage, from the noise floor at 0% to a very good
+/// signal at 100%.
+fn signal_percent(dbm: f64) -> u32 {
+    // Code inspired by https://github.com/NetworkManager/NetworkManager/blob/master/src/platform/wifi/nm-wifi-utils-nl80211.c
+    const NOISE_FLOOR_DBM: f64 = -90.;
+    const SIGNAL_MAX_DBM: f64 = -20.;
+
+    let dbm = dbm.max(NOISE_FLOOR_DBM).min(SIGNAL_MAX_DBM);
+    (100. * (dbm - NOISE_FLOOR_DBM) / (SIGNAL_MAX_DBM - NOISE_FLOOR_DBM)).round() as u32
+}
+
 fn read_file(path: &Path) -> Result<String> {
     let mut f = OpenOptions::new().read(true).open(path).block_error(
         "net",
@@ -746,6 +913,12 @@ impl Net {
             let dev = NetConfig::default_device();
             if self.device.device() != dev {
                 self.device = NetworkDevice::from_device(dev);
+                // Start counting from scratch for the new device, so that its counters
+                // are not compared with those of the previous one
+                self.tx_bytes = self.device.tx_bytes().unwrap_or(0);
+                self.rx_bytes = self.device.rx_bytes().unwrap_or(0);
+                self.total_tx = self.total_tx.map(|_| 0);
+                self.total_rx = self.total_rx.map(|_| 0);
                 self.network.set_icon(if self.device.is_wireless() {
                     "net_wireless"
                 } else if self.device.is_vpn() {
@@ -770,34 +943,62 @@ impl Net {
     fn update_ssid(&mut self) -> Result<()> {
         if let Some(ref mut ssid_string) = self.ssid {
             let ssid = self.device.ssid()?;
-            if let Some(s) = ssid {
-                let mut truncated = s;
-                truncated.truncate(self.max_ssid_width);
-                // SSID names can contain chars that need escaping
-                *ssid_string = escape_pango_text(truncated);
-            }
+            *ssid_string = match ssid {
+                Some(s) => {
+                    let mut truncated = s;
+                    truncated.truncate(self.max_ssid_width);
+                    // SSID names can contain chars that need escaping
+                    escape_pango_value(truncated, self.config.markup)
+                }
+                None => "".to_string(),
+            };
         }
         Ok(())
     }
 
     fn update_signal_strength(&mut self) -> Result<()> {
-        if self.signal_strength.is_some() || self.signal_strength_bar.is_some() {
+        let wanted = self.signal_strength.is_some() || self.signal_strength_bar.is_some();
+        if wanted || self.signal_warning.is_some() && self.device.is_wireless() {
             let value = self.device.relative_signal_strength()?;
             if let Some(ref mut signal_strength_string) = self.signal_strength {
-                if let Some(v) = value {
-                    *signal_strength_string = format!("{}%", v);
-                };
+                *signal_strength_string = value.map(|v| format!("{}%", v)).unwrap_or_default();
             }
 
             if let Some(ref mut signal_strength_bar_string) = self.signal_strength_bar {
-                if let Some(v) = value {
-                    *signal_strength_bar_string = format_percent_bar(v as f32);
-                };
+                *signal_strength_bar_string = value
+                    .map(|v| format_percent_bar(v as f32))
+                    .unwrap_or_default();
             }
+
+            let weak = match (value, self.signal_warning) {
+                (Some(v), Some(warning)) => v < warning,
+                _ => false,
+            };
+            let state = if weak { State::Warning } else { State::Idle };
+            self.network.set_state(state);
+            self.output.set_state(state);
         }
         Ok(())
     }
 
+    /// The address `{ip}` shows and its version: the address of the preferred version, or the
+    /// other one if the device has none.
+    fn ip(&self) -> (&str, &str) {
+        let ipv4 = (self.ip_addr.as_deref().unwrap_or(""), "4");
+        let ipv6 = (self.ipv6_addr.as_deref().unwrap_or(""), "6");
+        let (preferred, other) = match self.ip_version {
+            IpVersion::Ipv4 => (ipv4, ipv6),
+            IpVersion::Ipv6 => (ipv6, ipv4),
+        };
+        if !preferred.0.is_empty() {
+            preferred
+        } else if !other.0.is_empty() {
+            other
+        } else {
+            ("", "")
+        }
+    }
+
     fn update_ip_addr(&mut self) -> Result<()> {
         if let Some(ref mut ip_addr_string) = self.ip_addr {
             let ip_addr = self.device.ip_addr()?;
@@ -814,16 +1015,76 @@ impl Net {
         Ok(())
     }
 
+    fn update_vpn(&mut self) -> Result<()> {
+        if self.vpn.is_none() && self.vpn_name.is_none() && self.vpn_indicator.is_none() {
+            return Ok(());
+        }
+
+        let mut names = read_dir("/sys/class/net")
+            .block_error("net", "failed to read /sys/class/net")?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| self.vpn_interfaces.iter().any(|regex| regex.is_match(name)))
+            .collect::<Vec<String>>();
+        names.sort();
+
+        let mut active_vpn = None;
+        for name in names {
+            let device = NetworkDevice::from_device(name.clone());
+            // A tunnel may go away while it's checked, which only means it's not up
+            if device.is_up().unwrap_or(false) && device.has_address().unwrap_or(false) {
+                active_vpn = Some(name);
+                break;
+            }
+        }
+
+        self.vpn_up = active_vpn.is_some();
+        if let Some(ref mut vpn) = self.vpn {
+            *vpn = if self.vpn_up {
+                self.vpn_symbol.clone()
+            } else {
+                "".to_string()
+            };
+        }
+        if let Some(ref mut vpn_name) = self.vpn_name {
+            *vpn_name = active_vpn.unwrap_or_else(|| "".to_string());
+        }
+        Ok(())
+    }
+
+    /// Forgets the speed history of the device, e.g. when it comes back after it was down
+    /// or missing, so that stale values are not displayed and no bogus spike is computed
+    /// from counters read before it went away.
+    fn reset_speeds(&mut self) {
+        self.tx_bytes = self.device.tx_bytes().unwrap_or(0);
+        self.rx_bytes = self.device.rx_bytes().unwrap_or(0);
+        self.tx_buff = vec![0; self.tx_buff.len()];
+        self.rx_buff = vec![0; self.rx_buff.len()];
+    }
+
     fn update_tx_rx(&mut self) -> Result<()> {
         // TODO: consider using `as_nanos`
         let update_interval = (self.update_interval.as_secs() as f64)
             // Update the throughput/graph widgets if they are enabled
             + (self.update_interval.subsec_nanos() as f64 / 1_000_000_000.0);
-        if self.output_tx.is_some() || self.graph_tx.is_some() {
+        if self.reset == Reset::Daily {
+            let today = Local::now().date().naive_local();
+            if today != self.reset_date {
+                self.reset_date = today;
+                self.total_tx = self.total_tx.map(|_| 0);
+                self.total_rx = self.total_rx.map(|_| 0);
+            }
+        }
+        if self.output_tx.is_some() || self.graph_tx.is_some() || self.total_tx.is_some() {
             let current_tx = self.device.tx_bytes()?;
-            let tx_bytes = ((current_tx - self.tx_bytes) as f64 / update_interval) as u64;
+            let delta = counter_delta(self.tx_bytes, current_tx);
+            let tx_bytes = (delta as f64 / update_interval) as u64;
             self.tx_bytes = current_tx;
 
+            if let Some(ref mut total) = self.total_tx {
+                *total += delta;
+            }
+
             if let Some(ref mut tx) = self.output_tx {
                 *tx = format_speed(
                     tx_bytes,
@@ -839,11 +1100,16 @@ impl Net {
                 *graph_tx = format_vec_to_bar_graph(&self.tx_buff, None, None);
             }
         }
-        if self.output_rx.is_some() || self.graph_rx.is_some() {
+        if self.output_rx.is_some() || self.graph_rx.is_some() || self.total_rx.is_some() {
             let current_rx = self.device.rx_bytes()?;
-            let rx_bytes = ((current_rx - self.rx_bytes) as f64 / update_interval) as u64;
+            let delta = counter_delta(self.rx_bytes, current_rx);
+            let rx_bytes = (delta as f64 / update_interval) as u64;
             self.rx_bytes = current_rx;
 
+            if let Some(ref mut total) = self.total_rx {
+                *total += delta;
+            }
+
             if let Some(ref mut rx) = self.output_rx {
                 *rx = format_speed(
                     rx_bytes,
@@ -866,12 +1132,28 @@ impl Net {
 impl Block for Net {
     fn update(&mut self) -> Result<Option<Update>> {
         self.update_device();
+        self.update_vpn()?;
 
         // skip updating if device is not up.
+        let was_active = self.active;
         self.exists = self.device.exists()?;
         self.active = self.exists && self.device.is_up()?;
+        if self.active && !was_active {
+            self.reset_speeds();
+        }
         if !self.active {
             self.network.set_text("×".to_string());
+            self.network.set_state(State::Idle);
+            self.output.set_state(State::Idle);
+            // Shown again once they are known for the new connection
+            for value in self
+                .ssid
+                .iter_mut()
+                .chain(self.signal_strength.iter_mut())
+                .chain(self.signal_strength_bar.iter_mut())
+            {
+                value.clear();
+            }
             if let Some(ref mut tx) = self.output_tx {
                 *tx = "×".to_string();
             };
@@ -890,19 +1172,18 @@ impl Block for Net {
             self.update_bitrate()?;
         }
 
-        let waiting_for_ip = match self.ip_addr.as_deref() {
-            None => false,
-            Some("") => true,
-            Some(_) => false,
-        };
-
-        let waiting_for_ipv6 = match self.ipv6_addr.as_deref() {
-            None => false,
-            Some("") => true,
-            Some(_) => false,
+        // `{ip}` falls back on the other address, so it only waits for either
+        let (waiting_for_ip, waiting_for_ipv6) = if self.uses_ip {
+            (self.ip().0.is_empty(), false)
+        } else {
+            (
+                self.ip_addr.as_deref() == Some(""),
+                self.ipv6_addr.as_deref() == Some(""),
+            )
         };
 
         if (now.duration_since(self.last_update).as_secs() > 30)
+            || !was_active
             || waiting_for_ip
             || waiting_for_ipv6
         {
@@ -915,6 +1196,8 @@ impl Block for Net {
         self.update_tx_rx()?;
 
         let empty_string = "".to_string();
+        let (ip, ip_version) = self.ip();
+        let (ip, ip_version) = (ip.to_string(), ip_version.to_string());
         let s_up = format!(
             "{} {}",
             self.config
@@ -934,17 +1217,32 @@ impl Block for Net {
             self.output_rx.as_ref().unwrap_or(&empty_string)
         );
 
+        let total_up = self
+            .total_tx
+            .map(|total| format_speed(total, self.speed_digits, "B", false))
+            .unwrap_or_else(|| "".to_string());
+        let total_down = self
+            .total_rx
+            .map(|total| format_speed(total, self.speed_digits, "B", false))
+            .unwrap_or_else(|| "".to_string());
+
         let values = map!(
             "{ssid}" => self.ssid.as_ref().unwrap_or(&empty_string),
             "{signal_strength}" => self.signal_strength.as_ref().unwrap_or(&empty_string),
             "{signal_strength_bar}" => self.signal_strength_bar.as_ref().unwrap_or(&empty_string),
             "{bitrate}" =>  self.bitrate.as_ref().unwrap_or(&empty_string),
-            "{ip}" =>  self.ip_addr.as_ref().unwrap_or(&empty_string),
-            "{ipv6}" =>  self.ipv6_addr.as_ref().unwrap_or(&empty_string),
+            "{ip}" => &ip,
+            "{ip_version}" => &ip_version,
+            "{ipv4}" => self.ip_addr.as_ref().unwrap_or(&empty_string),
+            "{ipv6}" => self.ipv6_addr.as_ref().unwrap_or(&empty_string),
             "{speed_up}" =>  &s_up,
             "{speed_down}" => &s_dn,
             "{graph_up}" =>  self.graph_tx.as_ref().unwrap_or(&empty_string),
-            "{graph_down}" =>  self.graph_rx.as_ref().unwrap_or(&empty_string)
+            "{graph_down}" =>  self.graph_rx.as_ref().unwrap_or(&empty_string),
+            "{total_up}" => &total_up,
+            "{total_down}" => &total_down,
+            "{vpn}" => self.vpn.as_ref().unwrap_or(&empty_string),
+            "{vpn_name}" => self.vpn_name.as_ref().unwrap_or(&empty_string)
         );
 
         self.output
@@ -954,13 +1252,19 @@ impl Block for Net {
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        if self.active {
+        let mut widgets: Vec<&dyn I3BarWidget> = if self.active {
             vec![&self.network, &self.output]
         } else if self.hide_inactive || !self.exists && self.hide_missing {
-            vec![]
+            return vec![];
         } else {
             vec![&self.network]
+        };
+        if let Some(ref indicator) = self.vpn_indicator {
+            if self.vpn_up {
+                widgets.push(indicator);
+            }
         }
+        widgets
     }
 
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
@@ -981,16 +1285,18 @@ impl Block for Net {
     fn id(&self) -> &str {
         &self.id
     }
-}
 
-#[derive(Deserialize)]
-struct IpDev {
-    addr_info: Vec<IpAddrInfo>,
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
 }
 
-#[derive(Deserialize)]
-struct IpAddrInfo {
-    local: Option<String>,
+/// Whether the address is only valid on its link, i.e. in 169.254.0.0/16 or fe80::/10.
+fn is_link_local(addr: &IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(addr) => addr.is_link_local(),
+        IpAddr::V6(addr) => addr.segments()[0] & 0xffc0 == 0xfe80,
+    }
 }
 
 fn get_ssid(dev: &NetworkDevice) -> Result<Option<String>> {
@@ -1144,3 +1450,41 @@ fn maybe_ssid_convert(raw: Option<&[u8]>) -> Result<Option<String>> {
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::blocks::net::{counter_delta, is_link_local, signal_percent};
+
+    #[test]
+    fn test_counter_delta() {
+        assert_eq!(counter_delta(100, 150), 50);
+        assert_eq!(counter_delta(150, 150), 0);
+    }
+
+    #[test]
+    fn test_signal_percent() {
+        assert_eq!(signal_percent(-95.), 0);
+        assert_eq!(signal_percent(-90.), 0);
+        assert_eq!(signal_percent(-55.), 50);
+        assert_eq!(signal_percent(-20.), 100);
+        assert_eq!(signal_percent(-10.), 100);
+    }
+
+    #[test]
+    fn test_counter_delta_wraparound() {
+        // 32-bit counter wrapped around
+        assert_eq!(counter_delta(u64::from(u32::MAX) - 9, 5), 15);
+        // 64-bit counter was reset
+        assert_eq!(counter_delta(u64::from(u32::MAX) + 100, 5), 0);
+        // Counter was reset long before it could wrap around
+        assert_eq!(counter_delta(1_000_000, 5), 0);
+    }
+
+    #[test]
+    fn test_is_link_local() {
+        assert!(is_link_local(&"169.254.1.2".parse().unwrap()));
+        assert!(!is_link_local(&"192.168.1.2".parse().unwrap()));
+        assert!(is_link_local(&"fe80::1".parse().unwrap()));
+        assert!(!is_link_local(&"2001:db8::1".parse().unwrap()));
+    }
+}
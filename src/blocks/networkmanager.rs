@@ -280,6 +280,28 @@ impl<'a> NmConnection<'a> {
         Ok(id.0)
     }
 
+    fn conn_type(&self, c: &Connection) -> Result<String> {
+        let m = ConnectionManager::get(
+            c,
+            self.path.clone(),
+            "org.freedesktop.NetworkManager.Connection.Active",
+            "Type",
+        )
+        .block_error("networkmanager", "Failed to retrieve connection type")?;
+
+        let conn_type: Variant<String> = m
+            .get1()
+            .block_error("networkmanager", "Failed to read connection type")?;
+        Ok(conn_type.0)
+    }
+
+    fn is_vpn(&self, c: &Connection) -> bool {
+        match self.conn_type(c) {
+            Ok(t) => t == "vpn" || t == "wireguard",
+            Err(_) => false,
+        }
+    }
+
     fn devices(&self, c: &Connection) -> Result<Vec<NmDevice>> {
         let m = ConnectionManager::get(
             c,
@@ -456,6 +478,7 @@ pub struct NetworkManager {
     ap_format: FormatTemplate,
     device_format: FormatTemplate,
     connection_format: FormatTemplate,
+    vpn_separator: String,
     interface_name_exclude_regexps: Vec<Regex>,
     interface_name_include_regexps: Vec<Regex>,
 }
@@ -486,6 +509,10 @@ pub struct NetworkManagerConfig {
     #[serde(default = "NetworkManagerConfig::default_connection_format")]
     pub connection_format: String,
 
+    /// Separator used to join the names of multiple active VPN connections.
+    #[serde(default = "NetworkManagerConfig::default_vpn_separator")]
+    pub vpn_separator: String,
+
     /// Interface name regex patterns to include.
     #[serde(default = "NetworkManagerConfig::default_interface_name_include_patterns")]
     pub interface_name_exclude: Vec<String>,
@@ -523,6 +550,10 @@ impl NetworkManagerConfig {
         "{devices}".to_string()
     }
 
+    fn default_vpn_separator() -> String {
+        ", ".to_string()
+    }
+
     fn default_interface_name_include_patterns() -> Vec<String> {
         vec![]
     }
@@ -540,6 +571,7 @@ impl ConfigBlock for NetworkManager {
     type Config = NetworkManagerConfig;
 
     fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let markup = config.markup;
         let id: String = pseudo_uuid();
         let id_copy = id.clone();
         let dbus_conn = Connection::get_private(BusType::System)
@@ -589,9 +621,12 @@ impl ConfigBlock for NetworkManager {
             on_click: block_config.on_click,
             primary_only: block_config.primary_only,
             max_ssid_width: block_config.max_ssid_width,
-            ap_format: FormatTemplate::from_string(&block_config.ap_format)?,
-            device_format: FormatTemplate::from_string(&block_config.device_format)?,
-            connection_format: FormatTemplate::from_string(&block_config.connection_format)?,
+            ap_format: FormatTemplate::from_string(&block_config.ap_format)?.with_markup(markup),
+            device_format: FormatTemplate::from_string(&block_config.device_format)?
+                .with_markup(markup),
+            connection_format: FormatTemplate::from_string(&block_config.connection_format)?
+                .with_markup(markup),
+            vpn_separator: block_config.vpn_separator,
             interface_name_exclude_regexps: compile_regexps(block_config.interface_name_exclude)
                 .block_error("networkmanager", "failed to parse exclude patterns")?,
             interface_name_include_regexps: compile_regexps(block_config.interface_name_include)
@@ -636,6 +671,20 @@ impl Block for NetworkManager {
                     _ => State::Idle,
                 };
 
+                let active = self
+                    .manager
+                    .active_connections(&self.dbus_conn)
+                    .unwrap_or_else(|_| Vec::new());
+
+                // VPN names are collected from all active connections, so that they can be
+                // displayed even when only the primary connection is shown
+                let vpn = active
+                    .iter()
+                    .filter(|conn| conn.is_vpn(&self.dbus_conn))
+                    .filter_map(|conn| conn.id(&self.dbus_conn).ok())
+                    .collect::<Vec<String>>()
+                    .join(&self.vpn_separator);
+
                 let connections = if self.primary_only {
                     match self.manager.primary_connection(&self.dbus_conn) {
                         Ok(conn) => vec![conn],
@@ -643,10 +692,6 @@ impl Block for NetworkManager {
                     }
                 } else {
                     // We sort things so that the primary connection comes first
-                    let active = self
-                        .manager
-                        .active_connections(&self.dbus_conn)
-                        .unwrap_or_else(|_| Vec::new());
                     match self.manager.primary_connection(&self.dbus_conn) {
                         Ok(conn) => vec![conn.clone()]
                             .into_iter()
@@ -670,6 +715,8 @@ impl Block for NetworkManager {
                             ActiveConnectionState::Unknown.to_state(good_state)
                         });
 
+                        let is_vpn = conn.is_vpn(&self.dbus_conn);
+
                         // Get all devices for this connection
                         let mut devicevec: Vec<String> = Vec::new();
                         if let Ok(devices) = conn.devices(&self.dbus_conn) {
@@ -702,7 +749,14 @@ impl Block for NetworkManager {
                                 let (icon, type_name) = if let Ok(dev_type) =
                                     device.device_type(&self.dbus_conn)
                                 {
-                                    match dev_type.to_icon_name() {
+                                    // Devices of a VPN connection (e.g. the TUN device of an
+                                    // OpenVPN connection) always get the VPN icon
+                                    let icon_name = if is_vpn {
+                                        Some("net_vpn".to_string())
+                                    } else {
+                                        dev_type.to_icon_name()
+                                    };
+                                    match icon_name {
                                         Some(icon_name) => {
                                             let i = self
                                                 .config
@@ -790,7 +844,8 @@ impl Block for NetworkManager {
                         };
 
                         let values = map!("{devices}" => devicevec.join(" "),
-                                          "{id}" => id);
+                                          "{id}" => id,
+                                          "{vpn}" => vpn.clone());
 
                         if let Ok(s) = self.connection_format.render_static_str(&values) {
                             widget.set_text(s);
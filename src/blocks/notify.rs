@@ -14,7 +14,7 @@ use crate::config::Config;
 use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::util::{pseudo_uuid, FormatTemplate, Formats};
 use crate::widget::I3BarWidget;
 use crate::widgets::button::ButtonWidget;
 
@@ -33,14 +33,14 @@ pub struct Notify {
 pub struct NotifyConfig {
     /// Format string for displaying phone information.
     #[serde(default = "NotifyConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
 
     #[serde(default = "NotifyConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
 
 impl NotifyConfig {
-    fn default_format() -> String {
+    fn default_format() -> Formats {
         // display just the bell icon
         "".into()
     }
@@ -113,7 +113,7 @@ impl ConfigBlock for Notify {
         Ok(Notify {
             id,
             paused: state,
-            format: FormatTemplate::from_string(&block_config.format)?,
+            format: FormatTemplate::from_formats(&block_config.format)?.with_markup(config.markup),
             output: ButtonWidget::new(config, "notify").with_icon(icon),
         })
     }
@@ -124,6 +124,10 @@ impl Block for Notify {
         &self.id
     }
 
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+
     fn update(&mut self) -> Result<Option<Update>> {
         let paused = *self
             .paused
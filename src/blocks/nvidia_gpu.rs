@@ -11,7 +11,7 @@ use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::pseudo_uuid;
+use crate::util::{pseudo_uuid, FormatTemplate, Formats};
 use crate::widget::{I3BarWidget, Spacing, State};
 use crate::widgets::button::ButtonWidget;
 use crate::widgets::text::TextWidget;
@@ -24,6 +24,8 @@ pub struct NvidiaGpu {
 
     gpu_enabled: bool,
     gpu_id: u64,
+    /// `gpu_id` was checked against the number of GPUs
+    gpu_id_checked: bool,
 
     name_widget: ButtonWidget,
     name_widget_mode: NameWidgetMode,
@@ -41,6 +43,9 @@ pub struct NvidiaGpu {
     scrolling: Scrolling,
 
     show_clocks: Option<TextWidget>,
+
+    format: Option<(FormatTemplate, TextWidget)>,
+
     maximum_idle: u64,
     maximum_good: u64,
     maximum_info: u64,
@@ -72,9 +77,13 @@ pub struct NvidiaGpuConfig {
     pub label: Option<String>,
 
     /// GPU id in system
-    #[serde(default = "NvidiaGpuConfig::default_gpu_id")]
+    #[serde(default = "NvidiaGpuConfig::default_gpu_id", alias = "gpu")]
     pub gpu_id: u64,
 
+    /// Format string for the GPU stats. When set, the `show_*` options are ignored.
+    #[serde(default = "NvidiaGpuConfig::default_format")]
+    pub format: Option<Formats>,
+
     /// GPU utilization. In percent.
     #[serde(default = "NvidiaGpuConfig::default_show_utilization")]
     pub show_utilization: bool,
@@ -128,6 +137,10 @@ impl NvidiaGpuConfig {
         0
     }
 
+    fn default_format() -> Option<Formats> {
+        None
+    }
+
     fn default_show_utilization() -> bool {
         true
     }
@@ -181,6 +194,16 @@ impl ConfigBlock for NvidiaGpu {
         let id_memory = pseudo_uuid();
         let id_fans = pseudo_uuid();
 
+        let format = match block_config.format {
+            Some(ref format) => Some((
+                FormatTemplate::from_formats(format)
+                    .block_error("nvidia_gpu", "Invalid format specified")?
+                    .with_markup(config.markup),
+                TextWidget::new(config.clone()).with_spacing(Spacing::Inline),
+            )),
+            None => None,
+        };
+
         Ok(NvidiaGpu {
             id: id.clone(),
             id_fans: id_fans.clone(),
@@ -188,6 +211,7 @@ impl ConfigBlock for NvidiaGpu {
             update_interval: block_config.interval,
             gpu_enabled: false,
             gpu_id: block_config.gpu_id,
+            gpu_id_checked: false,
 
             name_widget: ButtonWidget::new(config.clone(), &id)
                 .with_icon("gpu")
@@ -237,6 +261,8 @@ impl ConfigBlock for NvidiaGpu {
                 None
             },
 
+            format,
+
             maximum_idle: block_config.idle,
             maximum_good: block_config.good,
             maximum_info: block_config.info,
@@ -245,23 +271,83 @@ impl ConfigBlock for NvidiaGpu {
     }
 }
 
+/// Fields queried from `nvidia-smi` when a `format` is configured, in order.
+const FORMAT_PARAMS: &str = "name,memory.total,utilization.gpu,memory.used,temperature.gpu,\
+                             fan.speed,clocks.current.graphics,power.draw,";
+
+impl NvidiaGpu {
+    /// Fails if there's no GPU with `gpu_id`. If nvidia-smi fails (e.g. the GPU is disabled),
+    /// the GPU is checked again on the next update, which reports the error.
+    fn check_gpu_id(&mut self) -> Result<()> {
+        let output = match Command::new("nvidia-smi")
+            .args(&["--query-gpu=count", "--format=csv,noheader"])
+            .output()
+        {
+            Ok(output) if output.status.success() => output,
+            _ => return Ok(()),
+        };
+        // The number of GPUs is printed once for every GPU
+        let count = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()
+            .and_then(|line| line.trim().parse::<u64>().ok())
+            .unwrap_or(0);
+        if self.gpu_id >= count {
+            return Err(BlockError(
+                "nvidia_gpu".to_string(),
+                format!(
+                    "GPU {} does not exist, {} GPU(s) detected",
+                    self.gpu_id, count
+                ),
+            ));
+        }
+        self.gpu_id_checked = true;
+        Ok(())
+    }
+
+    fn temperature_state(&self, temp: u64) -> State {
+        match temp {
+            t if t <= self.maximum_idle => State::Idle,
+            t if t <= self.maximum_good => State::Good,
+            t if t <= self.maximum_info => State::Info,
+            t if t <= self.maximum_warning => State::Warning,
+            _ => State::Critical,
+        }
+    }
+}
+
+/// Values that the GPU doesn't support are reported as e.g. "[Not Supported]" or "[N/A]".
+fn supported_value(value: Option<&&str>) -> String {
+    match value {
+        Some(v) if !v.starts_with('[') => v.trim().to_string(),
+        _ => "".to_string(),
+    }
+}
+
 impl Block for NvidiaGpu {
     fn update(&mut self) -> Result<Option<Update>> {
+        if !self.gpu_id_checked {
+            self.check_gpu_id()?;
+        }
         let mut params = String::from("name,memory.total,");
-        if self.show_utilization.is_some() {
+        if self.format.is_some() {
+            params = FORMAT_PARAMS.to_string();
+        } else if self.show_utilization.is_some() {
             params += "utilization.gpu,";
         }
-        if self.show_memory.is_some() {
-            params += "memory.used,";
-        }
-        if self.show_temperature.is_some() {
-            params += "temperature.gpu,";
-        }
-        if self.show_fan.is_some() {
-            params += "fan.speed,";
-        }
-        if self.show_clocks.is_some() {
-            params += "clocks.current.graphics,";
+        if self.format.is_none() {
+            if self.show_memory.is_some() {
+                params += "memory.used,";
+            }
+            if self.show_temperature.is_some() {
+                params += "temperature.gpu,";
+            }
+            if self.show_fan.is_some() {
+                params += "fan.speed,";
+            }
+            if self.show_clocks.is_some() {
+                params += "clocks.current.graphics,";
+            }
         }
 
         let handle = Command::new("nvidia-smi")
@@ -316,6 +402,25 @@ impl Block for NvidiaGpu {
                 }
             }
 
+            if self.format.is_some() {
+                let temp = result.get(4).and_then(|t| t.parse::<u64>().ok());
+                let state = temp.map(|t| self.temperature_state(t));
+                let values = map!(
+                    "{utilization}" => supported_value(result.get(2)),
+                    "{memory_used}" => supported_value(result.get(3)),
+                    "{memory_total}" => memory_total,
+                    "{temperature}" => supported_value(result.get(4)),
+                    "{fan_speed}" => supported_value(result.get(5)),
+                    "{clocks}" => supported_value(result.get(6)),
+                    "{power}" => supported_value(result.get(7))
+                );
+                if let Some((ref template, ref mut widget)) = self.format {
+                    widget.set_text(template.render_static_str(&values)?);
+                    widget.set_state(state.unwrap_or(State::Idle));
+                }
+                return Ok(Some(self.update_interval.into()));
+            }
+
             let mut count: usize = 2;
             if let Some(ref mut utilization_widget) = self.show_utilization {
                 utilization_widget.set_text(format!("{:02}%", result[count]));
@@ -364,6 +469,10 @@ impl Block for NvidiaGpu {
         widgets.push(&self.name_widget);
 
         if self.gpu_enabled {
+            if let Some((_, ref widget)) = self.format {
+                widgets.push(widget);
+                return widgets;
+            }
             if let Some(ref utilization_widget) = self.show_utilization {
                 widgets.push(utilization_widget);
             }
@@ -492,4 +601,10 @@ impl Block for NvidiaGpu {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        if let Some((ref mut template, _)) = self.format {
+            template.select(index);
+        }
+    }
 }
@@ -2,12 +2,15 @@ use std::collections::BTreeMap;
 use std::env;
 use std::ffi::OsString;
 use std::fs;
+use std::io::Read;
 use std::os::unix::fs::symlink;
 use std::path::Path;
-use std::process::Command;
-use std::time::Duration;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crossbeam_channel::Sender;
+use crossbeam_channel::{unbounded, RecvTimeoutError, Sender};
 use regex::Regex;
 use serde_derive::Deserialize;
 
@@ -17,23 +20,45 @@ use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::{has_command, pseudo_uuid, FormatTemplate};
+use crate::util::{format_stale, has_command, pseudo_uuid, FormatTemplate, Formats};
 use crate::widget::{I3BarWidget, State};
 use crate::widgets::button::ButtonWidget;
 
+/// The updates found by the checking thread.
+#[derive(Debug, Clone)]
+struct Updates {
+    /// Empty if pacman isn't watched
+    pacman: String,
+    /// `None` if the AUR isn't watched or the `aur_command` failed
+    aur: Option<String>,
+}
+
+/// The updates, or why pacman couldn't be checked.
+type CheckedUpdates = std::result::Result<Updates, String>;
+
 pub struct Pacman {
     output: ButtonWidget,
     id: String,
-    update_interval: Duration,
     format: FormatTemplate,
     format_singular: FormatTemplate,
     format_up_to_date: FormatTemplate,
     warning_updates_regex: Option<Regex>,
     critical_updates_regex: Option<Regex>,
+    warning_count: Option<usize>,
+    critical_count: Option<usize>,
+    hide_when_uptodate: bool,
     watched: Watched,
+    /// The last result of the checking thread, `None` until the first check is done
+    updates: Arc<Mutex<Option<CheckedUpdates>>>,
+    /// The updates of the last successful check, shown as stale while checks fail
+    last_updates: Option<Updates>,
+    /// Template for the text while the data is outdated
+    stale_format: String,
+    empty: bool,
+    tx_refresh: Sender<()>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Watched {
     Pacman,
     /// cf `Pacman::aur_command`
@@ -53,8 +78,8 @@ pub struct PacmanConfig {
     pub interval: Duration,
 
     /// Format override
-    #[serde(default = "PacmanConfig::default_format")]
-    pub format: String,
+    #[serde(default = "PacmanConfig::default_formats")]
+    pub format: Formats,
 
     /// Alternative format override for when exactly 1 update is available
     #[serde(default = "PacmanConfig::default_format")]
@@ -75,10 +100,37 @@ pub struct PacmanConfig {
     #[serde(default = "PacmanConfig::default_critical_updates_regex")]
     pub critical_updates_regex: Option<String>,
 
+    /// Indicate a `warning` state for the block if at least this many updates are available,
+    /// counting both pacman and AUR updates
+    #[serde(
+        default = "PacmanConfig::default_warning_count",
+        alias = "warning_updates"
+    )]
+    pub warning_count: Option<usize>,
+
+    /// Indicate a `critical` state for the block if at least this many updates are available,
+    /// counting both pacman and AUR updates
+    #[serde(
+        default = "PacmanConfig::default_critical_count",
+        alias = "critical_updates"
+    )]
+    pub critical_count: Option<usize>,
+
+    /// Hide the block while no updates are available
+    #[serde(default = "PacmanConfig::default_hide_when_uptodate")]
+    pub hide_when_uptodate: bool,
+
     /// Optional AUR command, listing available updates
     #[serde()]
     pub aur_command: Option<String>,
 
+    /// Maximum time the AUR command may take, in seconds
+    #[serde(
+        default = "PacmanConfig::default_aur_timeout",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub aur_timeout: Duration,
+
     #[serde(default = "PacmanConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
@@ -92,6 +144,10 @@ impl PacmanConfig {
         "{pacman}".to_owned()
     }
 
+    fn default_formats() -> Formats {
+        Self::default_format().into()
+    }
+
     fn default_warning_updates_regex() -> Option<String> {
         None
     }
@@ -100,6 +156,22 @@ impl PacmanConfig {
         None
     }
 
+    fn default_warning_count() -> Option<usize> {
+        None
+    }
+
+    fn default_critical_count() -> Option<usize> {
+        None
+    }
+
+    fn default_hide_when_uptodate() -> bool {
+        false
+    }
+
+    fn default_aur_timeout() -> Duration {
+        Duration::from_secs(30)
+    }
+
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
         None
     }
@@ -153,23 +225,68 @@ impl ConfigBlock for Pacman {
     fn new(
         block_config: Self::Config,
         config: Config,
-        _tx_update_request: Sender<Task>,
+        tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let watched = PacmanConfig::watched(
+            &block_config.format.all().concat(),
+            &block_config.format_singular,
+            &block_config.format_up_to_date,
+            block_config.aur_command,
+        )?;
+
+        // Checking for updates takes a while, especially in the AUR, so it's done on a thread
+        // which requests an update of the block whenever it's done
+        let id = pseudo_uuid();
+        let updates = Arc::new(Mutex::new(None));
+        let (tx_refresh, rx_refresh) = unbounded::<()>();
+        let thread_updates = updates.clone();
+        let thread_id = id.clone();
+        let thread_watched = watched.clone();
+        let interval = block_config.interval;
+        let aur_timeout = block_config.aur_timeout;
+        thread::Builder::new()
+            .name("pacman".into())
+            .spawn(move || loop {
+                let result = check_updates(&thread_watched, aur_timeout);
+                *thread_updates
+                    .lock()
+                    .expect("main thread paniced while holding pacman mutex") = Some(result);
+                tx_update_request
+                    .send(Task {
+                        id: thread_id.clone(),
+                        update_time: Instant::now(),
+                    })
+                    .expect("unable to send task from pacman thread");
+
+                match rx_refresh.recv_timeout(interval) {
+                    Ok(()) => {
+                        // Several clicks during a check only need one more check
+                        while rx_refresh.try_recv().is_ok() {}
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            })
+            .block_error("pacman", "failed to start pacman thread")?;
+
         Ok(Pacman {
-            id: pseudo_uuid(),
-            update_interval: block_config.interval,
-            format: FormatTemplate::from_string(&block_config.format)
-                .block_error("pacman", "Invalid format specified for pacman::format")?,
+            id,
+            format: FormatTemplate::from_formats(&block_config.format)
+                .block_error("pacman", "Invalid format specified for pacman::format")?
+                .with_markup(config.markup),
             format_singular: FormatTemplate::from_string(&block_config.format_singular)
                 .block_error(
                     "pacman",
                     "Invalid format specified for pacman::format_singular",
-                )?,
+                )?
+                .with_markup(config.markup),
             format_up_to_date: FormatTemplate::from_string(&block_config.format_up_to_date)
                 .block_error(
                     "pacman",
                     "Invalid format specified for pacman::format_up_to_date",
-                )?,
+                )?
+                .with_markup(config.markup),
+            stale_format: config.stale_format.clone(),
             output: ButtonWidget::new(config, "pacman").with_icon("update"),
             warning_updates_regex: match block_config.warning_updates_regex {
                 None => None, // no regex configured
@@ -201,12 +318,14 @@ impl ConfigBlock for Pacman {
                     Some(regex)
                 }
             },
-            watched: PacmanConfig::watched(
-                &block_config.format,
-                &block_config.format_singular,
-                &block_config.format_up_to_date,
-                block_config.aur_command,
-            )?,
+            warning_count: block_config.warning_count,
+            critical_count: block_config.critical_count,
+            hide_when_uptodate: block_config.hide_when_uptodate,
+            watched,
+            updates,
+            last_updates: None,
+            empty: false,
+            tx_refresh,
         })
     }
 }
@@ -296,33 +415,104 @@ fn get_pacman_available_updates() -> Result<String> {
     )
 }
 
-fn get_aur_available_updates(aur_command: &str) -> Result<String> {
-    String::from_utf8(
-        Command::new("sh")
-            .args(&["-c", aur_command])
-            .output()
-            .block_error("pacman", &format!("aur command: {} failed", aur_command))?
-            .stdout,
-    )
-    .block_error(
+/// Fails if the command exits unsuccessfully, e.g. because there's no network, or doesn't
+/// finish within the `timeout`.
+fn get_aur_available_updates(aur_command: &str, timeout: Duration) -> Result<String> {
+    let mut child = Command::new("sh")
+        .args(&["-c", aur_command])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .block_error("pacman", &format!("aur command: {} failed", aur_command))?;
+
+    let mut stdout = child
+        .stdout
+        .take()
+        .block_error("pacman", "failed to read the aur command output")?;
+    let (tx, rx) = unbounded();
+    thread::Builder::new()
+        .name("pacman_aur".into())
+        .spawn(move || {
+            let mut output = Vec::new();
+            let _ = stdout.read_to_end(&mut output);
+            let _ = tx.send(output);
+        })
+        .block_error("pacman", "failed to start aur command reader thread")?;
+
+    let output = match rx.recv_timeout(timeout) {
+        Ok(output) => output,
+        Err(_) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(BlockError(
+                "pacman".to_string(),
+                format!("aur command: {} timed out", aur_command),
+            ));
+        }
+    };
+    let status = child
+        .wait()
+        .block_error("pacman", &format!("aur command: {} failed", aur_command))?;
+    if !status.success() {
+        return Err(BlockError(
+            "pacman".to_string(),
+            format!("aur command: {} failed", aur_command),
+        ));
+    }
+
+    String::from_utf8(output).block_error(
         "pacman",
         "There was a problem while converting the aur command output to a string",
     )
 }
 
-fn get_update_count(updates: &str) -> usize {
+/// A failing `aur_command` only fails the check if the AUR is watched on its own, otherwise
+/// its updates are shown as unknown.
+fn check_updates(watched: &Watched, aur_timeout: Duration) -> CheckedUpdates {
+    let pacman = match watched {
+        Watched::AUR(_) => String::new(),
+        _ => check_fakeroot_command_exists()
+            .and_then(|_| get_pacman_available_updates())
+            .map_err(|e| match e {
+                BlockError(_, message) => message,
+                e => e.to_string(),
+            })?,
+    };
+    let aur = match watched {
+        Watched::Pacman => None,
+        Watched::AUR(aur_command) => Some(
+            get_aur_available_updates(aur_command, aur_timeout).map_err(|e| match e {
+                BlockError(_, message) => message,
+                e => e.to_string(),
+            })?,
+        ),
+        Watched::Both(aur_command) => get_aur_available_updates(aur_command, aur_timeout).ok(),
+    };
+    Ok(Updates { pacman, aur })
+}
+
+/// The pending updates as lines of the form `name old -> new`, along with the package name.
+/// Blank lines and updates of ignored packages are skipped, and a repository prefix like
+/// `aur/`, which some AUR helpers print, is removed from the name.
+fn pending_updates(updates: &str) -> impl Iterator<Item = (&str, &str)> {
     updates
         .lines()
-        .filter(|line| !line.contains("[ignored]"))
-        .count()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.contains("[ignored]"))
+        .filter_map(|line| {
+            let name = line.split_whitespace().next()?;
+            Some((line, name.rsplit('/').next().unwrap_or(name)))
+        })
 }
 
-fn has_warning_update(updates: &str, regex: &Regex) -> bool {
-    updates.lines().filter(|line| regex.is_match(line)).count() > 0
+fn get_update_count(updates: &str) -> usize {
+    pending_updates(updates).count()
 }
 
-fn has_critical_update(updates: &str, regex: &Regex) -> bool {
-    updates.lines().filter(|line| regex.is_match(line)).count() > 0
+/// Regexes like `^linux$` are matched against the package names, but whole lines are also
+/// matched, as that is what the regexes were matched against before.
+fn has_matching_update(updates: &str, regex: &Regex) -> bool {
+    pending_updates(updates).any(|(line, name)| regex.is_match(name) || regex.is_match(line))
 }
 
 impl Block for Pacman {
@@ -330,66 +520,86 @@ impl Block for Pacman {
         &self.id
     }
 
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        vec![&self.output]
+        if self.empty {
+            vec![]
+        } else {
+            vec![&self.output]
+        }
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        let (formatting_map, warning, critical, cum_count) = match &self.watched {
-            Watched::Pacman => {
-                check_fakeroot_command_exists()?;
-                let pacman_available_updates = get_pacman_available_updates()?;
-                let pacman_count = get_update_count(&pacman_available_updates);
-                let formatting_map = map!("{count}" => pacman_count, "{pacman}" => pacman_count);
-
-                let warning = self.warning_updates_regex.as_ref().map_or(false, |regex| {
-                    has_warning_update(&pacman_available_updates, regex)
-                });
-                let critical = self.critical_updates_regex.as_ref().map_or(false, |regex| {
-                    has_critical_update(&pacman_available_updates, regex)
-                });
-
-                (formatting_map, warning, critical, pacman_count)
-            }
-            Watched::AUR(aur_command) => {
-                let aur_available_updates = get_aur_available_updates(&aur_command)?;
-                let aur_count = get_update_count(&aur_available_updates);
-                let formatting_map = map!("{aur}" => aur_count);
-
-                let warning = self.warning_updates_regex.as_ref().map_or(false, |regex| {
-                    has_warning_update(&aur_available_updates, regex)
-                });
-                let critical = self.critical_updates_regex.as_ref().map_or(false, |regex| {
-                    has_critical_update(&aur_available_updates, regex)
-                });
-
-                (formatting_map, warning, critical, aur_count)
-            }
-            Watched::Both(aur_command) => {
-                check_fakeroot_command_exists()?;
-                let pacman_available_updates = get_pacman_available_updates()?;
-                let aur_available_updates = get_aur_available_updates(&aur_command)?;
-                let pacman_count = get_update_count(&pacman_available_updates);
-                let aur_count = get_update_count(&aur_available_updates);
-                let formatting_map = map!("{count}" => pacman_count, "{pacman}" => pacman_count, "{aur}" => aur_count, "{both}" => pacman_count + aur_count);
-
-                let warning = self.warning_updates_regex.as_ref().map_or(false, |regex| {
-                    has_warning_update(&aur_available_updates, regex)
-                        || has_warning_update(&pacman_available_updates, regex)
-                });
-                let critical = self.critical_updates_regex.as_ref().map_or(false, |regex| {
-                    has_critical_update(&aur_available_updates, regex)
-                        || has_critical_update(&pacman_available_updates, regex)
-                });
-
-                (formatting_map, warning, critical, pacman_count + aur_count)
+        let (updates, stale) = match *self
+            .updates
+            .lock()
+            .block_error("pacman", "mutex poisoned")?
+        {
+            // The first check isn't done yet
+            None => return Ok(None),
+            Some(Ok(ref updates)) => {
+                self.last_updates = Some(updates.clone());
+                (updates.clone(), false)
             }
+            // Keep showing the last known updates if a check fails
+            Some(Err(_)) => match self.last_updates {
+                Some(ref updates) => (updates.clone(), true),
+                // There are none yet, the next check may work
+                None => {
+                    self.output.set_text(format_stale(&self.stale_format, "?"));
+                    self.output.set_state(State::Idle);
+                    self.empty = false;
+                    return Ok(None);
+                }
+            },
         };
-        self.output.set_text(match cum_count {
+
+        let Updates {
+            pacman: pacman_updates,
+            aur: aur_updates,
+        } = updates;
+        let pacman_count = get_update_count(&pacman_updates);
+        let aur_count = aur_updates.as_deref().map(get_update_count);
+        // The AUR updates are unknown if the `aur_command` failed, so it may not be up to date
+        let stale = stale || (self.watched != Watched::Pacman && aur_count.is_none());
+        let show = |count: Option<usize>| count.map_or_else(|| "?".to_string(), |c| c.to_string());
+        let formatting_map = map!(
+            "{count}" => show(Some(pacman_count)),
+            "{pacman}" => show(Some(pacman_count)),
+            "{aur}" => show(aur_count),
+            "{both}" => show(aur_count.map(|aur_count| pacman_count + aur_count))
+        );
+        let cum_count = match self.watched {
+            Watched::Pacman => pacman_count,
+            Watched::AUR(_) => aur_count.unwrap_or(0),
+            Watched::Both(_) => pacman_count + aur_count.unwrap_or(0),
+        };
+
+        let aur_updates = aur_updates.unwrap_or_default();
+        let warning = self.warning_updates_regex.as_ref().map_or(false, |regex| {
+            has_matching_update(&aur_updates, regex) || has_matching_update(&pacman_updates, regex)
+        }) || self.warning_count.map_or(false, |count| cum_count >= count);
+        let critical = self.critical_updates_regex.as_ref().map_or(false, |regex| {
+            has_matching_update(&aur_updates, regex) || has_matching_update(&pacman_updates, regex)
+        }) || self
+            .critical_count
+            .map_or(false, |count| cum_count >= count);
+
+        let text = match cum_count {
             0 => self.format_up_to_date.render_static_str(&formatting_map)?,
             1 => self.format_singular.render_static_str(&formatting_map)?,
             _ => self.format.render_static_str(&formatting_map)?,
+        };
+        self.output.set_text(if stale {
+            format_stale(&self.stale_format, &text)
+        } else {
+            text
         });
+        // A failed check doesn't hide the block, so that it's noticed
+        self.empty = self.hide_when_uptodate && cum_count == 0 && !stale;
         self.output.set_state(match cum_count {
             0 => State::Idle,
             _ => {
@@ -402,14 +612,17 @@ impl Block for Pacman {
                 }
             }
         });
-        Ok(Some(self.update_interval.into()))
+        // The checking thread requests the updates
+        Ok(None)
     }
 
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
         if event.name.as_ref().map(|s| s == "pacman").unwrap_or(false)
             && event.button == MouseButton::Left
         {
-            self.update()?;
+            self.tx_refresh
+                .send(())
+                .block_error("pacman", "pacman thread stopped")?;
         }
 
         Ok(())
@@ -418,8 +631,12 @@ impl Block for Pacman {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
+    use regex::Regex;
+
     use crate::blocks::pacman::{
-        get_aur_available_updates, get_update_count, PacmanConfig, Watched,
+        get_aur_available_updates, get_update_count, has_matching_update, PacmanConfig, Watched,
     };
 
     #[test]
@@ -431,6 +648,34 @@ mod tests {
             "systemd-libs 245.4-2 -> 245.5-1\n"
         );
         assert_eq!(get_update_count(two_updates_available), 2);
+        let with_ignored_and_blank_lines = concat!(
+            "linux 5.9.1-1 -> 5.9.2-1\n",
+            "\n",
+            "firefox 81.0-1 -> 82.0-1 [ignored]\n"
+        );
+        assert_eq!(get_update_count(with_ignored_and_blank_lines), 1);
+    }
+
+    #[test]
+    fn test_has_matching_update() {
+        let updates = concat!(
+            "linux-firmware 20201005-1 -> 20201016-1\n",
+            "aur/systemd-git 246-1 -> 247-1\n",
+            "linux-lts 5.4.70-1 -> 5.4.72-1 [ignored]\n"
+        );
+        let regex = Regex::new("^linux$|^systemd$").unwrap();
+        assert!(!has_matching_update(updates, &regex));
+        assert!(has_matching_update(
+            "  linux 5.9.1-1 -> 5.9.2-1  \n",
+            &regex
+        ));
+        assert!(has_matching_update("aur/systemd 246-1 -> 247-1", &regex));
+        // matches lines as well
+        let regex = Regex::new("(linux |systemd-git )").unwrap();
+        assert!(has_matching_update(updates, &regex));
+        // ignored updates don't count
+        let regex = Regex::new("^linux-(lts|zen)$").unwrap();
+        assert!(!has_matching_update(updates, &regex));
     }
 
     #[test]
@@ -481,8 +726,11 @@ mod tests {
         // aur_command should behave as echo -ne "foo x.x -> y.y\n"
         let updates = "foo x.x -> y.y\nbar x.x -> y.y\n";
         let aur_command = format!("printf '{}'", updates);
-        let available_updates = get_aur_available_updates(&aur_command);
+        let available_updates = get_aur_available_updates(&aur_command, Duration::from_secs(5));
         assert!(available_updates.is_ok());
         assert_eq!(available_updates.unwrap(), updates);
+        // e.g. the AUR helper isn't installed
+        assert!(get_aur_available_updates("exit 127", Duration::from_secs(5)).is_err());
+        assert!(get_aur_available_updates("sleep 5", Duration::from_millis(100)).is_err());
     }
 }
@@ -1,8 +1,10 @@
-use std::collections::BTreeMap;
-use std::fmt;
+use std::collections::{BTreeMap, HashMap};
 use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
+use dbus::arg::Variant;
+use dbus::ffidisp::{BusType, Connection};
+use dbus::Message;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
@@ -11,8 +13,8 @@ use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::subprocess::spawn_child_async;
-use crate::util::pseudo_uuid;
-use crate::widget::I3BarWidget;
+use crate::util::{pseudo_uuid, shell_quote};
+use crate::widget::{I3BarWidget, State as WidgetState};
 use crate::widgets::button::ButtonWidget;
 
 enum State {
@@ -33,30 +35,12 @@ impl State {
     }
 }
 
-impl fmt::Display for State {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            State::Stopped => write!(f, "\u{25a0} 0:00"),
-            State::Started(_) => write!(
-                f,
-                "\u{f04b} {}:{:02}",
-                self.elapsed().as_secs() / 60,
-                self.elapsed().as_secs() % 60
-            ),
-            State::OnBreak(_) => write!(
-                f,
-                "\u{2615} {}:{:02}",
-                self.elapsed().as_secs() / 60,
-                self.elapsed().as_secs() % 60
-            ),
-            State::Paused(duration) => write!(
-                f,
-                "\u{f04c} {}:{:02}",
-                duration.as_secs() / 60,
-                duration.as_secs() % 60
-            ),
-        }
-    }
+/// Shows e.g. `24:59` for the time left of `duration`.
+fn remaining(duration: Duration, elapsed: Duration) -> String {
+    let left = duration.checked_sub(elapsed).unwrap_or_default();
+    // Rounded up, so that the timer ends right when it shows 0:00
+    let secs = (left.as_millis() + 999) / 1000;
+    format!("{}:{:02}", secs / 60, secs % 60)
 }
 
 pub struct Pomodoro {
@@ -71,12 +55,54 @@ pub struct Pomodoro {
     count: usize,
     use_nag: bool,
     nag_path: std::path::PathBuf,
+    notify_cmd: Option<String>,
+    use_notification: bool,
+    tx_update_request: Sender<Task>,
 }
 
 impl Pomodoro {
     fn set_text(&mut self) {
-        self.time
-            .set_text(format!("{} | {}", self.count, self.state));
+        let (text, state) = match self.state {
+            State::Stopped => (
+                format!(
+                    "\u{25a0} {}",
+                    remaining(self.length, Duration::from_secs(0))
+                ),
+                WidgetState::Idle,
+            ),
+            State::Started(_) => (
+                format!("\u{f04b} {}", remaining(self.length, self.state.elapsed())),
+                WidgetState::Good,
+            ),
+            State::OnBreak(_) => (
+                format!(
+                    "\u{2615} {}",
+                    remaining(self.break_length, self.state.elapsed())
+                ),
+                WidgetState::Info,
+            ),
+            State::Paused(elapsed) => (
+                format!("\u{f04c} {}", remaining(self.length, elapsed)),
+                WidgetState::Idle,
+            ),
+        };
+        self.time.set_text(format!("{} | {}", self.count, text));
+        self.time.set_state(state);
+    }
+
+    /// Tells the user that an interval is over, in all the configured ways.
+    fn notify(&self, message: &str, level: &str) {
+        if self.use_nag {
+            self.nag(message, level);
+        }
+        if let Some(ref cmd) = self.notify_cmd {
+            let command = format!("{} {}", cmd, shell_quote(message));
+            spawn_child_async("sh", &["-c", &command]).ok();
+        }
+        if self.use_notification {
+            // Not being able to notify shouldn't stop the timer
+            send_notification(message).ok();
+        }
     }
 
     fn nag(&self, message: &str, level: &str) {
@@ -86,6 +112,38 @@ impl Pomodoro {
         )
         .expect("Failed to start i3-nagbar");
     }
+
+    /// Starts the updates every second, which stop by themselves once the timer isn't running.
+    fn request_updates(&self) -> Result<()> {
+        self.tx_update_request
+            .send(Task {
+                id: self.id.clone(),
+                update_time: Instant::now(),
+            })
+            .block_error("pomodoro", "failed to request an update")
+    }
+}
+
+/// Shows a notification following the desktop notifications specification.
+fn send_notification(summary: &str) -> Result<()> {
+    let con = Connection::get_private(BusType::Session)
+        .block_error("pomodoro", "failed to establish D-Bus connection")?;
+    let msg = Message::new_method_call(
+        "org.freedesktop.Notifications",
+        "/org/freedesktop/Notifications",
+        "org.freedesktop.Notifications",
+        "Notify",
+    )
+    .block_error("pomodoro", "failed to create D-Bus message")?
+    // app name, id of the replaced notification, icon
+    .append3("i3status-rs", 0u32, "pomodoro")
+    // summary, body, actions
+    .append3(summary, "", Vec::<String>::new())
+    // hints, timeout chosen by the server
+    .append2(HashMap::<String, Variant<String>>::new(), -1i32);
+    con.send_with_reply_and_block(msg, 1000)
+        .block_error("pomodoro", "failed to send notification")?;
+    Ok(())
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -103,6 +161,12 @@ pub struct PomodoroConfig {
     pub use_nag: bool,
     #[serde(default = "PomodoroConfig::default_nag_path")]
     pub nag_path: std::path::PathBuf,
+    /// Shell command run with the message when an interval is over, e.g. `notify-send`
+    #[serde(default = "PomodoroConfig::default_notify_cmd")]
+    pub notify_cmd: Option<String>,
+    /// Show the message as a desktop notification over D-Bus
+    #[serde(default = "PomodoroConfig::default_use_notification")]
+    pub use_notification: bool,
     #[serde(default = "PomodoroConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
@@ -132,6 +196,14 @@ impl PomodoroConfig {
         std::path::PathBuf::from("i3-nagbar")
     }
 
+    fn default_notify_cmd() -> Option<String> {
+        None
+    }
+
+    fn default_use_notification() -> bool {
+        false
+    }
+
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
         None
     }
@@ -140,7 +212,11 @@ impl PomodoroConfig {
 impl ConfigBlock for Pomodoro {
     type Config = PomodoroConfig;
 
-    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+    fn new(
+        block_config: Self::Config,
+        config: Config,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
         let id: String = pseudo_uuid();
 
         Ok(Pomodoro {
@@ -155,6 +231,9 @@ impl ConfigBlock for Pomodoro {
             use_nag: block_config.use_nag,
             count: 0,
             nag_path: block_config.nag_path,
+            notify_cmd: block_config.notify_cmd,
+            use_notification: block_config.use_notification,
+            tx_update_request,
         })
     }
 }
@@ -165,58 +244,56 @@ impl Block for Pomodoro {
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        self.set_text();
-        match &self.state {
-            State::Started(_) => {
-                if self.state.elapsed() >= self.length {
-                    if self.use_nag {
-                        self.nag(&self.message, "error");
-                    }
-
-                    self.state = State::OnBreak(Instant::now());
-                }
+        match self.state {
+            State::Started(_) if self.state.elapsed() >= self.length => {
+                self.notify(&self.message, "error");
+                self.state = State::OnBreak(Instant::now());
             }
-            State::OnBreak(_) => {
-                if self.state.elapsed() >= self.break_length {
-                    if self.use_nag {
-                        self.nag(&self.break_message, "warning");
-                    }
-                    self.state = State::Stopped;
-                    self.count += 1;
-                }
+            State::OnBreak(_) if self.state.elapsed() >= self.break_length => {
+                self.notify(&self.break_message, "warning");
+                self.state = State::Stopped;
+                self.count += 1;
             }
             _ => {}
         }
+        self.set_text();
 
-        Ok(Some(self.update_interval.into()))
+        // Only a running timer needs updates
+        match self.state {
+            State::Started(_) | State::OnBreak(_) => Ok(Some(self.update_interval.into())),
+            State::Stopped | State::Paused(_) => Ok(None),
+        }
     }
 
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
-        if let Some(ref name) = event.name {
-            if name.as_str() == self.id {
-                match event.button {
-                    MouseButton::Right => {
-                        self.state = State::Stopped;
-                        self.count = 0;
-                    }
-                    _ => match &self.state {
-                        State::Stopped => {
-                            self.state = State::Started(Instant::now());
-                        }
-                        State::Started(_) => {
-                            self.state = State::Paused(self.state.elapsed());
-                        }
-                        State::Paused(duration) => {
-                            self.state = State::Started(
-                                Instant::now().checked_sub(duration.to_owned()).unwrap(),
-                            );
-                        }
-                        State::OnBreak(_) => {
-                            self.state = State::Started(Instant::now());
-                        }
-                    },
+        if !event.matches_name(&self.id) {
+            return Ok(());
+        }
+        match event.button {
+            MouseButton::Left => match self.state {
+                State::Stopped | State::OnBreak(_) => {
+                    self.state = State::Started(Instant::now());
+                    self.request_updates()?;
+                }
+                State::Paused(elapsed) => {
+                    self.state = State::Started(Instant::now() - elapsed);
+                    self.request_updates()?;
                 }
+                State::Started(_) => {}
+            },
+            MouseButton::Middle => match self.state {
+                State::Started(_) => self.state = State::Paused(self.state.elapsed()),
+                State::Paused(elapsed) => {
+                    self.state = State::Started(Instant::now() - elapsed);
+                    self.request_updates()?;
+                }
+                State::Stopped | State::OnBreak(_) => {}
+            },
+            MouseButton::Right => {
+                self.state = State::Stopped;
+                self.count = 0;
             }
+            _ => {}
         }
 
         self.set_text();
@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{
+    escape_pango_value, pseudo_uuid, watch_files, xdg_runtime_dir, FormatTemplate, Formats,
+};
+use crate::widget::{I3BarWidget, Markup};
+use crate::widgets::button::ButtonWidget;
+
+pub struct Scratchpad {
+    text: ButtonWidget,
+    id: String,
+    path: PathBuf,
+    format: FormatTemplate,
+    clear_on_click: bool,
+    empty: bool,
+    markup: Option<Markup>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScratchpadConfig {
+    /// The file the text is read from
+    #[serde(default = "ScratchpadConfig::default_path")]
+    pub path: PathBuf,
+
+    /// Format override
+    /// placeholders: {text}
+    #[serde(default = "ScratchpadConfig::default_format")]
+    pub format: Formats,
+
+    /// Clear the text when the block is left clicked
+    #[serde(default = "ScratchpadConfig::default_clear_on_click")]
+    pub clear_on_click: bool,
+
+    #[serde(default = "ScratchpadConfig::default_color_overrides")]
+    pub color_overrides: Option<BTreeMap<String, String>>,
+}
+
+impl ScratchpadConfig {
+    fn default_path() -> PathBuf {
+        xdg_runtime_dir().join("i3status-rust-scratchpad")
+    }
+
+    fn default_format() -> Formats {
+        "{text}".into()
+    }
+
+    fn default_clear_on_click() -> bool {
+        true
+    }
+
+    fn default_color_overrides() -> Option<BTreeMap<String, String>> {
+        None
+    }
+}
+
+impl ConfigBlock for Scratchpad {
+    type Config = ScratchpadConfig;
+
+    fn new(
+        block_config: Self::Config,
+        config: Config,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let markup = config.markup;
+        let id = pseudo_uuid();
+        let path = block_config.path;
+
+        // The file is usually written by something like `echo text > file`
+        watch_files(
+            "scratchpad",
+            std::slice::from_ref(&path),
+            id.clone(),
+            tx_update_request,
+        )?;
+
+        Ok(Scratchpad {
+            text: ButtonWidget::new(config, &id),
+            id,
+            path,
+            format: FormatTemplate::from_formats(&block_config.format)
+                .block_error("scratchpad", "Invalid format specified")?
+                .with_markup(markup),
+            clear_on_click: block_config.clear_on_click,
+            empty: true,
+            markup,
+        })
+    }
+}
+
+impl Block for Scratchpad {
+    fn update(&mut self) -> Result<Option<Update>> {
+        // A missing file is the same as an empty one
+        let text = std::fs::read_to_string(&self.path).unwrap_or_default();
+        let text = text.trim();
+        self.empty = text.is_empty();
+
+        let values = map!("{text}" => escape_pango_value(text.to_string(), self.markup));
+        self.text.set_text(self.format.render_static_str(&values)?);
+
+        // The watcher thread requests updates when the file changes
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.empty {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn click(&mut self, e: &I3BarEvent) -> Result<()> {
+        if e.matches_name(self.id()) && self.clear_on_click {
+            if let MouseButton::Left = e.button {
+                std::fs::write(&self.path, "")
+                    .block_error("scratchpad", "failed to clear the file")?;
+                self.update()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+}
@@ -28,6 +28,7 @@ use std::thread;
 use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
+use regex::Regex;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
@@ -36,17 +37,41 @@ use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::subprocess::spawn_child_async;
-use crate::util::{format_percent_bar, pseudo_uuid, FormatTemplate};
+use crate::util::{format_percent_bar, pseudo_uuid, wildcard_to_regex, FormatTemplate, Formats};
 use crate::widget::{I3BarWidget, Spacing, State};
 use crate::widgets::button::ButtonWidget;
 
+/// Where the audio of a device goes, used to pick the icon.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+// Only PulseAudio tells where the audio goes
+#[cfg_attr(not(feature = "pulseaudio"), allow(dead_code))]
+enum OutputType {
+    Speaker,
+    Headphones,
+    Bluetooth,
+    Hdmi,
+}
+
+impl OutputType {
+    fn icon(self) -> Option<&'static str> {
+        match self {
+            OutputType::Speaker => None,
+            OutputType::Headphones => Some("volume_headphones"),
+            OutputType::Bluetooth => Some("volume_bluetooth"),
+            OutputType::Hdmi => Some("volume_hdmi"),
+        }
+    }
+}
+
 trait SoundDevice {
     fn volume(&self) -> u32;
     fn muted(&self) -> bool;
     fn output_name(&self) -> String;
+    fn output_type(&self) -> OutputType;
+    fn present(&self) -> bool;
 
     fn get_info(&mut self) -> Result<()>;
-    fn set_volume(&mut self, step: i32, max_vol: Option<u32>) -> Result<()>;
+    fn set_volume(&mut self, step: i32, max_vol: u32) -> Result<()>;
     fn toggle(&mut self) -> Result<()>;
     fn monitor(&mut self, id: String, tx_update_request: Sender<Task>) -> Result<()>;
 }
@@ -84,6 +109,13 @@ impl SoundDevice for AlsaSoundDevice {
     fn output_name(&self) -> String {
         self.name.clone()
     }
+    fn output_type(&self) -> OutputType {
+        // amixer doesn't tell where the audio goes
+        OutputType::Speaker
+    }
+    fn present(&self) -> bool {
+        true
+    }
 
     fn get_info(&mut self) -> Result<()> {
         let mut args = Vec::new();
@@ -120,13 +152,10 @@ impl SoundDevice for AlsaSoundDevice {
         Ok(())
     }
 
-    fn set_volume(&mut self, step: i32, max_vol: Option<u32>) -> Result<()> {
+    fn set_volume(&mut self, step: i32, max_vol: u32) -> Result<()> {
         let new_vol = max(0, self.volume as i32 + step) as u32;
-        let capped_volume = if let Some(vol_cap) = max_vol {
-            min(new_vol, vol_cap)
-        } else {
-            new_vol
-        };
+        // A volume that was set above the cap elsewhere isn't lowered by scrolling up
+        let capped_volume = min(new_vol, max(max_vol, self.volume));
         let mut args = Vec::new();
         if self.natural_mapping {
             args.push("-M")
@@ -213,10 +242,16 @@ struct PulseAudioClient {
 #[cfg(feature = "pulseaudio")]
 struct PulseAudioSoundDevice {
     name: Option<String>,
+    /// Binds to the first device whose description matches
+    description: Option<Regex>,
+    /// The device found by the description, or instead of a default monitor source
+    bound: Option<String>,
+    present: bool,
     device_kind: DeviceKind,
     volume: Option<ChannelVolumes>,
     volume_avg: u32,
     muted: bool,
+    output_type: OutputType,
 }
 
 #[cfg(feature = "pulseaudio")]
@@ -225,6 +260,38 @@ struct PulseAudioVolInfo {
     volume: ChannelVolumes,
     mute: bool,
     name: String,
+    index: u32,
+    description: Option<String>,
+    /// Monitor sources record the output of a sink
+    monitor: bool,
+    output_type: OutputType,
+}
+
+#[cfg(feature = "pulseaudio")]
+impl OutputType {
+    fn from_sink(sink_info: &SinkInfo) -> Self {
+        let port = sink_info
+            .active_port
+            .as_ref()
+            .and_then(|port| port.name.as_ref())
+            .map(|name| name.to_lowercase())
+            .unwrap_or_default();
+        let property = |key| sink_info.proplist.get_str(key).unwrap_or_default();
+        let form_factor = property(properties::DEVICE_FORM_FACTOR);
+
+        if property(properties::DEVICE_BUS) == "bluetooth" {
+            OutputType::Bluetooth
+        } else if port.contains("headphones")
+            || form_factor == "headphone"
+            || form_factor == "headset"
+        {
+            OutputType::Headphones
+        } else if port.contains("hdmi") {
+            OutputType::Hdmi
+        } else {
+            OutputType::Speaker
+        }
+    }
 }
 
 #[cfg(feature = "pulseaudio")]
@@ -238,6 +305,10 @@ impl TryFrom<&SourceInfo<'_>> for PulseAudioVolInfo {
                 volume: source_info.volume,
                 mute: source_info.mute,
                 name: name.to_string(),
+                index: source_info.index,
+                description: source_info.description.as_ref().map(|d| d.to_string()),
+                monitor: source_info.monitor_of_sink.is_some(),
+                output_type: OutputType::Speaker,
             }),
         }
     }
@@ -254,6 +325,10 @@ impl TryFrom<&SinkInfo<'_>> for PulseAudioVolInfo {
                 volume: sink_info.volume,
                 mute: sink_info.mute,
                 name: name.to_string(),
+                index: sink_info.index,
+                description: sink_info.description.as_ref().map(|d| d.to_string()),
+                monitor: false,
+                output_type: OutputType::from_sink(sink_info),
             }),
         }
     }
@@ -265,6 +340,7 @@ enum PulseAudioClientRequest {
     GetDefaultDevice,
     GetInfoByIndex(DeviceKind, u32),
     GetInfoByName(DeviceKind, String),
+    GetInfoList(DeviceKind),
     SetVolumeByName(DeviceKind, String, ChannelVolumes),
     SetMuteByName(DeviceKind, String, bool),
 }
@@ -331,6 +407,77 @@ impl PulseAudioConnection {
         Ok(connection)
     }
 
+    /// Connects to the server, retrying until it's available (again).
+    fn reconnect() -> Self {
+        loop {
+            match PulseAudioConnection::new() {
+                Ok(connection) => return connection,
+                Err(_) => thread::sleep(Duration::from_secs(1)),
+            }
+        }
+    }
+
+    fn is_ready(&self) -> bool {
+        self.context.borrow().get_state() == PulseState::Ready
+    }
+
+    fn request(&mut self, req: &PulseAudioClientRequest) -> Result<()> {
+        if !self.is_ready() {
+            return Err(BlockError(
+                "sound".into(),
+                "pulseaudio context is not ready".into(),
+            ));
+        }
+
+        {
+            use PulseAudioClientRequest::*;
+            let mut introspector = self.context.borrow_mut().introspect();
+
+            match req {
+                GetDefaultDevice => {
+                    introspector.get_server_info(PulseAudioClient::server_info_callback);
+                }
+                GetInfoByIndex(DeviceKind::Sink, index) => {
+                    introspector
+                        .get_sink_info_by_index(*index, PulseAudioClient::sink_info_callback);
+                }
+                GetInfoByIndex(DeviceKind::Source, index) => {
+                    introspector
+                        .get_source_info_by_index(*index, PulseAudioClient::source_info_callback);
+                }
+                GetInfoByName(DeviceKind::Sink, name) => {
+                    introspector.get_sink_info_by_name(name, PulseAudioClient::sink_info_callback);
+                }
+                GetInfoByName(DeviceKind::Source, name) => {
+                    introspector
+                        .get_source_info_by_name(name, PulseAudioClient::source_info_callback);
+                }
+                GetInfoList(DeviceKind::Sink) => {
+                    introspector.get_sink_info_list(PulseAudioClient::sink_info_callback);
+                }
+                GetInfoList(DeviceKind::Source) => {
+                    introspector.get_source_info_list(PulseAudioClient::source_info_callback);
+                }
+                SetVolumeByName(DeviceKind::Sink, name, volumes) => {
+                    introspector.set_sink_volume_by_name(name, volumes, None);
+                }
+                SetVolumeByName(DeviceKind::Source, name, volumes) => {
+                    introspector.set_source_volume_by_name(name, volumes, None);
+                }
+                SetMuteByName(DeviceKind::Sink, name, mute) => {
+                    introspector.set_sink_mute_by_name(name, *mute, None);
+                }
+                SetMuteByName(DeviceKind::Source, name, mute) => {
+                    introspector.set_source_mute_by_name(name, *mute, None);
+                }
+            };
+        }
+
+        // send request and receive response
+        self.iterate(true)?;
+        self.iterate(true)
+    }
+
     fn iterate(&mut self, blocking: bool) -> Result<()> {
         match self.mainloop.borrow_mut().iterate(blocking) {
             IterateResult::Quit(_) | IterateResult::Err(_) => Err(BlockError(
@@ -378,63 +525,19 @@ impl PulseAudioClient {
                 let mut connection = new_connection(send_result);
 
                 loop {
-                    // make sure mainloop dispatched everything
+                    // make sure mainloop dispatched everything, a lost connection is noticed
+                    // when the next request is sent
                     for _ in 0..10 {
-                        connection.iterate(false).unwrap();
+                        if connection.iterate(false).is_err() {
+                            break;
+                        }
                     }
 
-                    match recv_req.recv() {
-                        Err(_) => {}
-                        Ok(req) => {
-                            use PulseAudioClientRequest::*;
-                            let mut introspector = connection.context.borrow_mut().introspect();
-
-                            match req {
-                                GetDefaultDevice => {
-                                    introspector
-                                        .get_server_info(PulseAudioClient::server_info_callback);
-                                }
-                                GetInfoByIndex(DeviceKind::Sink, index) => {
-                                    introspector.get_sink_info_by_index(
-                                        index,
-                                        PulseAudioClient::sink_info_callback,
-                                    );
-                                }
-                                GetInfoByIndex(DeviceKind::Source, index) => {
-                                    introspector.get_source_info_by_index(
-                                        index,
-                                        PulseAudioClient::source_info_callback,
-                                    );
-                                }
-                                GetInfoByName(DeviceKind::Sink, name) => {
-                                    introspector.get_sink_info_by_name(
-                                        &name,
-                                        PulseAudioClient::sink_info_callback,
-                                    );
-                                }
-                                GetInfoByName(DeviceKind::Source, name) => {
-                                    introspector.get_source_info_by_name(
-                                        &name,
-                                        PulseAudioClient::source_info_callback,
-                                    );
-                                }
-                                SetVolumeByName(DeviceKind::Sink, name, volumes) => {
-                                    introspector.set_sink_volume_by_name(&name, &volumes, None);
-                                }
-                                SetVolumeByName(DeviceKind::Source, name, volumes) => {
-                                    introspector.set_source_volume_by_name(&name, &volumes, None);
-                                }
-                                SetMuteByName(DeviceKind::Sink, name, mute) => {
-                                    introspector.set_sink_mute_by_name(&name, mute, None);
-                                }
-                                SetMuteByName(DeviceKind::Source, name, mute) => {
-                                    introspector.set_source_mute_by_name(&name, mute, None);
-                                }
-                            };
-
-                            // send request and receive response
-                            connection.iterate(true).unwrap();
-                            connection.iterate(true).unwrap();
+                    if let Ok(req) = recv_req.recv() {
+                        if connection.request(&req).is_err() {
+                            // The server went away, e.g. because it was restarted
+                            connection = PulseAudioConnection::reconnect();
+                            connection.request(&req).ok();
                         }
                     }
                 }
@@ -446,21 +549,31 @@ impl PulseAudioClient {
         thread::Builder::new()
             .name("sound_pulseaudio_sub".into())
             .spawn(move || {
-                let connection = new_connection(send_result2);
-
-                // subcribe for events
-                connection
-                    .context
-                    .borrow_mut()
-                    .set_subscribe_callback(Some(Box::new(PulseAudioClient::subscribe_callback)));
-                connection.context.borrow_mut().subscribe(
-                    subscription_masks::SERVER
-                        | subscription_masks::SINK
-                        | subscription_masks::SOURCE,
-                    |_| {},
-                );
-
-                connection.mainloop.borrow_mut().run().unwrap();
+                let mut connection = new_connection(send_result2);
+
+                loop {
+                    // subcribe for events
+                    connection
+                        .context
+                        .borrow_mut()
+                        .set_subscribe_callback(Some(Box::new(
+                            PulseAudioClient::subscribe_callback,
+                        )));
+                    connection.context.borrow_mut().subscribe(
+                        subscription_masks::SERVER
+                            | subscription_masks::SINK
+                            | subscription_masks::SOURCE,
+                        |_| {},
+                    );
+
+                    // dispatch events until the connection is lost
+                    while connection.iterate(true).is_ok() && connection.is_ready() {}
+
+                    // The server went away, e.g. because it was restarted. Subscribe again once
+                    // it's back and fetch the devices, the default ones may have changed.
+                    connection = PulseAudioConnection::reconnect();
+                    PulseAudioClient::refresh();
+                }
             })
             .unwrap();
         thread_result()?;
@@ -481,13 +594,32 @@ impl PulseAudioClient {
         }
     }
 
+    /// Forgets the known devices and requests the default and all present ones again.
+    fn refresh() {
+        PULSEAUDIO_DEVICES.lock().unwrap().clear();
+        PulseAudioClient::send(PulseAudioClientRequest::GetDefaultDevice).ok();
+        PulseAudioClient::send(PulseAudioClientRequest::GetInfoList(DeviceKind::Sink)).ok();
+        PulseAudioClient::send(PulseAudioClientRequest::GetInfoList(DeviceKind::Source)).ok();
+    }
+
     fn server_info_callback(server_info: &ServerInfo) {
         if let Some(default_sink) = server_info.default_sink_name.as_ref() {
             *PULSEAUDIO_DEFAULT_SINK.lock().unwrap() = default_sink.to_string();
+            // the default sink may have changed, its state may not be known yet
+            PulseAudioClient::send(PulseAudioClientRequest::GetInfoByName(
+                DeviceKind::Sink,
+                default_sink.to_string(),
+            ))
+            .ok();
         }
 
         if let Some(default_source) = server_info.default_source_name.as_ref() {
             *PULSEAUDIO_DEFAULT_SOURCE.lock().unwrap() = default_source.to_string();
+            PulseAudioClient::send(PulseAudioClientRequest::GetInfoByName(
+                DeviceKind::Source,
+                default_source.to_string(),
+            ))
+            .ok();
         }
 
         PulseAudioClient::send_update_event();
@@ -526,12 +658,18 @@ impl PulseAudioClient {
 
     fn subscribe_callback(
         facility: Option<Facility>,
-        _operation: Option<SubscribeOperation>,
+        operation: Option<SubscribeOperation>,
         index: u32,
     ) {
-        match facility {
-            None => {}
-            Some(facility) => match facility {
+        match (facility, operation) {
+            (Some(Facility::Sink), Some(SubscribeOperation::Removed)) => {
+                PulseAudioClient::remove_device(DeviceKind::Sink, index);
+            }
+            (Some(Facility::Source), Some(SubscribeOperation::Removed)) => {
+                PulseAudioClient::remove_device(DeviceKind::Source, index);
+            }
+            (None, _) => {}
+            (Some(facility), _) => match facility {
                 Facility::Server => {
                     PulseAudioClient::send(PulseAudioClientRequest::GetDefaultDevice).ok();
                 }
@@ -554,6 +692,15 @@ impl PulseAudioClient {
         }
     }
 
+    fn remove_device(device_kind: DeviceKind, index: u32) {
+        PULSEAUDIO_DEVICES
+            .lock()
+            .unwrap()
+            .retain(|&(kind, _), info| kind != device_kind || info.index != index);
+
+        PulseAudioClient::send_update_event();
+    }
+
     fn send_update_event() {
         for (id, tx_update_request) in &*PULSEAUDIO_EVENT_LISTENER.lock().unwrap() {
             tx_update_request
@@ -571,20 +718,20 @@ impl PulseAudioSoundDevice {
     fn new(device_kind: DeviceKind) -> Result<Self> {
         PulseAudioClient::send(PulseAudioClientRequest::GetDefaultDevice)?;
 
-        let device = PulseAudioSoundDevice {
+        // The device may be a named one or, for sources, the first one that is no monitor
+        PulseAudioClient::send(PulseAudioClientRequest::GetInfoList(device_kind))?;
+
+        Ok(PulseAudioSoundDevice {
             name: None,
+            description: None,
+            bound: None,
+            present: false,
             device_kind,
             volume: None,
             volume_avg: 0,
             muted: false,
-        };
-
-        PulseAudioClient::send(PulseAudioClientRequest::GetInfoByName(
-            device_kind,
-            device.name(),
-        ))?;
-
-        Ok(device)
+            output_type: OutputType::Speaker,
+        })
     }
 
     fn with_name(mut self, name: String) -> Self {
@@ -592,12 +739,48 @@ impl PulseAudioSoundDevice {
         self
     }
 
+    fn with_description(mut self, description: Regex) -> Self {
+        self.description = Some(description);
+        self
+    }
+
     fn name(&self) -> String {
-        self.name
+        self.bound
             .clone()
+            .or_else(|| self.name.clone())
             .unwrap_or_else(|| self.device_kind.default_name())
     }
 
+    /// Finds the device to bind to if no name is given. Returns `None` if there is none.
+    fn resolve(
+        &self,
+        devices: &HashMap<(DeviceKind, String), PulseAudioVolInfo>,
+    ) -> Option<String> {
+        // The device with the lowest name is used if several match, so the choice is stable.
+        // Monitors are never picked, they only loop back the output of a sink.
+        let first = |matches: &dyn Fn(&PulseAudioVolInfo) -> bool| {
+            devices
+                .iter()
+                .filter(|((kind, _), info)| {
+                    *kind == self.device_kind && !info.monitor && matches(info)
+                })
+                .map(|((_, name), _)| name.clone())
+                .min()
+        };
+
+        match self.description {
+            Some(ref description) => first(&|info| {
+                info.description
+                    .as_ref()
+                    .map_or(false, |d| description.is_match(d))
+            }),
+            None => match devices.get(&(self.device_kind, self.device_kind.default_name())) {
+                Some(info) if info.monitor => first(&|_| true),
+                _ => Some(self.device_kind.default_name()),
+            },
+        }
+    }
+
     fn volume(&mut self, volume: ChannelVolumes) {
         self.volume = Some(volume);
         self.volume_avg = (volume.avg().0 as f32 / VOLUME_NORM.0 as f32 * 100.0).round() as u32;
@@ -618,18 +801,39 @@ impl SoundDevice for PulseAudioSoundDevice {
         self.name()
     }
 
+    fn output_type(&self) -> OutputType {
+        self.output_type
+    }
+
+    fn present(&self) -> bool {
+        self.present
+    }
+
     fn get_info(&mut self) -> Result<()> {
         let devices = PULSEAUDIO_DEVICES.lock().unwrap();
 
-        if let Some(info) = devices.get(&(self.device_kind, self.name())) {
-            self.volume(info.volume);
-            self.muted = info.mute;
+        if self.name.is_none() {
+            self.bound = self.resolve(&devices);
+            if self.bound.is_none() {
+                self.present = false;
+                return Ok(());
+            }
+        }
+
+        match devices.get(&(self.device_kind, self.name())) {
+            Some(info) => {
+                self.volume(info.volume);
+                self.muted = info.mute;
+                self.output_type = info.output_type;
+                self.present = true;
+            }
+            None => self.present = false,
         }
 
         Ok(())
     }
 
-    fn set_volume(&mut self, step: i32, max_vol: Option<u32>) -> Result<()> {
+    fn set_volume(&mut self, step: i32, max_vol: u32) -> Result<()> {
         let mut volume = match self.volume {
             Some(volume) => volume,
             None => return Err(BlockError("sound".into(), "volume unknown".into())),
@@ -637,16 +841,11 @@ impl SoundDevice for PulseAudioSoundDevice {
 
         // apply step to volumes
         let step = (step as f32 * VOLUME_NORM.0 as f32 / 100.0).round() as i32;
+        let vol_cap = (max_vol as f32 * VOLUME_NORM.0 as f32 / 100.0).round() as u32;
         for vol in volume.get_mut().iter_mut() {
             let uncapped_vol = max(0, vol.0 as i32 + step) as u32;
-            let capped_vol = if let Some(vol_cap) = max_vol {
-                min(
-                    uncapped_vol,
-                    (vol_cap as f32 * VOLUME_NORM.0 as f32 / 100.0).round() as u32,
-                )
-            } else {
-                uncapped_vol
-            };
+            // A volume that was set above the cap elsewhere isn't lowered by scrolling up
+            let capped_vol = min(uncapped_vol, max(vol_cap, vol.0));
             vol.0 = min(capped_vol, VOLUME_MAX.0);
         }
 
@@ -692,10 +891,14 @@ pub struct Sound {
     format: FormatTemplate,
     config: Config,
     on_click: Option<String>,
+    mute_button: MouseButton,
+    format_missing: String,
     show_volume_when_muted: bool,
     bar: bool,
-    mappings: Option<BTreeMap<String, String>>,
-    max_vol: Option<u32>,
+    /// Patterns of device names and their labels, exact names first
+    mappings: Vec<(Regex, String)>,
+    mappings_use_regex: bool,
+    max_vol: u32,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq)]
@@ -737,6 +940,10 @@ pub struct SoundConfig {
     #[serde(default = "SoundConfig::default_device")]
     pub device: Option<String>,
 
+    /// Regex matched against the descriptions of the PulseAudio devices, if no name is given
+    #[serde(default = "SoundConfig::default_description")]
+    pub description: Option<String>,
+
     /// Type of device: sink or source (default is "sink")
     #[serde(default)]
     pub device_kind: DeviceKind,
@@ -746,17 +953,25 @@ pub struct SoundConfig {
     pub natural_mapping: bool,
 
     /// The steps volume is in/decreased for the selected audio device (When greater than 50 it gets limited to 50)
-    #[serde(default = "SoundConfig::default_step_width")]
+    #[serde(default = "SoundConfig::default_step_width", alias = "step")]
     pub step_width: u32,
 
     /// Format string for displaying sound information.
     /// placeholders: {volume}
     #[serde(default = "SoundConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
 
     #[serde(default = "SoundConfig::default_on_click")]
     pub on_click: Option<String>,
 
+    /// Text shown while the PulseAudio device is not present
+    #[serde(default = "SoundConfig::default_format_missing")]
+    pub format_missing: String,
+
+    /// The button that toggles mute, left for sources and right for sinks by default
+    #[serde(default = "SoundConfig::default_mute_button")]
+    pub mute_button: Option<MouseButton>,
+
     #[serde(default = "SoundConfig::default_show_volume_when_muted")]
     pub show_volume_when_muted: bool,
 
@@ -767,8 +982,13 @@ pub struct SoundConfig {
     #[serde(default = "SoundConfig::default_mappings")]
     pub mappings: Option<BTreeMap<String, String>>,
 
+    /// Treat the names of `mappings` as regexes, the labels may refer to their capture groups
+    #[serde(default = "SoundConfig::default_mappings_use_regex")]
+    pub mappings_use_regex: bool,
+
+    /// Max volume in percent that can be set by scrolling
     #[serde(default = "SoundConfig::default_max_vol")]
-    pub max_vol: Option<u32>,
+    pub max_vol: u32,
 
     #[serde(default = "SoundConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
@@ -806,7 +1026,7 @@ impl SoundConfig {
         5
     }
 
-    fn default_format() -> String {
+    fn default_format() -> Formats {
         "{volume}%".into()
     }
 
@@ -814,6 +1034,18 @@ impl SoundConfig {
         None
     }
 
+    fn default_description() -> Option<String> {
+        None
+    }
+
+    fn default_format_missing() -> String {
+        "×".to_owned()
+    }
+
+    fn default_mute_button() -> Option<MouseButton> {
+        None
+    }
+
     fn default_show_volume_when_muted() -> bool {
         false
     }
@@ -826,8 +1058,12 @@ impl SoundConfig {
         None
     }
 
-    fn default_max_vol() -> Option<u32> {
-        None
+    fn default_mappings_use_regex() -> bool {
+        false
+    }
+
+    fn default_max_vol() -> u32 {
+        100
     }
 
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
@@ -837,6 +1073,15 @@ impl SoundConfig {
 
 impl Sound {
     fn icon(&self, volume: u32) -> String {
+        // Headphones etc. have one icon for all volumes, if the icon set has it
+        if volume > 0 {
+            if let Some(icon) = self.device.output_type().icon() {
+                if self.config.icons.contains_key(icon) {
+                    return icon.to_string();
+                }
+            }
+        }
+
         let prefix = match self.device_kind {
             DeviceKind::Source => "microphone",
             DeviceKind::Sink => "volume",
@@ -855,15 +1100,26 @@ impl Sound {
     fn display(&mut self) -> Result<()> {
         self.device.get_info()?;
 
+        if !self.device.present() {
+            self.text.set_icon(&self.icon(0));
+            self.text.set_text(self.format_missing.clone());
+            self.text.set_spacing(Spacing::Normal);
+            self.text.set_state(State::Idle);
+            return Ok(());
+        }
+
         let volume = self.device.volume();
         let output_name = self.device.output_name();
-        let mapped_output_name = if let Some(m) = &self.mappings {
-            match m.get(&output_name) {
-                Some(mapping) => mapping.to_string(),
-                None => output_name,
+        let mapped_output_name = match self
+            .mappings
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(&output_name))
+        {
+            Some((pattern, label)) if self.mappings_use_regex => {
+                pattern.replace(&output_name, label.as_str()).into_owned()
             }
-        } else {
-            output_name
+            Some((_, label)) => label.clone(),
+            None => output_name,
         };
         let values = map!("{volume}" => format!("{:02}", volume),
                           "{output_name}" => mapped_output_name
@@ -922,9 +1178,26 @@ impl ConfigBlock for Sound {
             SoundDriver::Auto | SoundDriver::PulseAudio => {
                 let sound_device = PulseAudioSoundDevice::new(block_config.device_kind);
 
-                match block_config.name.as_ref() {
-                    None => sound_device,
-                    Some(name) => sound_device.map(|device| device.with_name(name.to_string())),
+                match (
+                    block_config.name.as_ref(),
+                    block_config.description.as_ref(),
+                ) {
+                    (Some(name), _) => {
+                        sound_device.map(|device| device.with_name(name.to_string()))
+                    }
+                    (None, Some(description)) => {
+                        let description = Regex::new(description).map_err(|e| {
+                            ConfigurationError(
+                                "sound".to_string(),
+                                (
+                                    format!("invalid description regex: {}", e),
+                                    "invalid description".to_string(),
+                                ),
+                            )
+                        })?;
+                        sound_device.map(|device| device.with_description(description))
+                    }
+                    (None, None) => sound_device,
                 }
             }
             _ => Err(BlockError(
@@ -943,18 +1216,47 @@ impl ConfigBlock for Sound {
             )?),
         };
 
+        let mappings_use_regex = block_config.mappings_use_regex;
+        let mut mappings = block_config
+            .mappings
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, label)| {
+                let pattern = if mappings_use_regex {
+                    Regex::new(&name)
+                } else {
+                    wildcard_to_regex(&name)
+                };
+                pattern
+                    .map(|pattern| (pattern, label))
+                    .block_error("sound", &format!("invalid mapping: {}", name))
+            })
+            .collect::<Result<Vec<(Regex, String)>>>()?;
+        // Exact names take precedence over patterns matching them as well
+        if !mappings_use_regex {
+            mappings.sort_by_key(|(pattern, _)| pattern.as_str().contains(".*"));
+        }
+
         let mut sound = Self {
             text: ButtonWidget::new(config.clone(), &id).with_icon("volume_empty"),
             id: id.clone(),
             device,
             device_kind: block_config.device_kind,
-            format: FormatTemplate::from_string(&block_config.format)?,
+            format: FormatTemplate::from_formats(&block_config.format)?.with_markup(config.markup),
             step_width,
             config,
             on_click: block_config.on_click,
+            mute_button: block_config
+                .mute_button
+                .unwrap_or(match block_config.device_kind {
+                    DeviceKind::Source => MouseButton::Left,
+                    DeviceKind::Sink => MouseButton::Right,
+                }),
+            format_missing: block_config.format_missing,
             show_volume_when_muted: block_config.show_volume_when_muted,
             bar: block_config.bar,
-            mappings: block_config.mappings,
+            mappings,
+            mappings_use_regex,
             max_vol: block_config.max_vol,
         };
 
@@ -980,15 +1282,17 @@ impl Block for Sound {
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
         if let Some(ref name) = e.name {
             if name.as_str() == self.id {
+                // Without a device there is nothing to control
+                let present = self.device.present();
                 match e.button {
-                    MouseButton::Right => self.device.toggle()?,
+                    button if button == self.mute_button && present => self.device.toggle()?,
                     MouseButton::Left => {
                         if let Some(ref cmd) = self.on_click {
                             spawn_child_async("sh", &["-c", cmd])
                                 .block_error("sound", "could not spawn child")?;
                         }
                     }
-                    _ => {
+                    _ if present => {
                         use LogicalDirection::*;
                         match self.config.scrolling.to_logical_direction(e.button) {
                             Some(Up) => self
@@ -1000,6 +1304,7 @@ impl Block for Sound {
                             None => (),
                         }
                     }
+                    _ => {}
                 }
                 self.display()?;
             }
@@ -1011,4 +1316,8 @@ impl Block for Sound {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
 }
@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 use std::fmt;
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
@@ -9,18 +9,38 @@ use crossbeam_channel::{unbounded, Receiver, Sender};
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
+use crate::cache::Cache;
 use crate::config::Config;
 use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::{format_speed, pseudo_uuid};
+use crate::util::{format_speed, pseudo_uuid, FormatTemplate, Formats};
 use crate::widget::{I3BarWidget, State};
 use crate::widgets::button::ButtonWidget;
 
+/// Frames of the spinner that is shown while a test is running.
+const SPINNER: &[&str] = &["\u{25d0}", "\u{25d3}", "\u{25d1}", "\u{25d2}"];
+const SPINNER_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Shared between the block and its measurement thread.
+#[derive(Default)]
+struct Measurement {
+    /// A test finished since the last update
+    updated: bool,
+    /// A test is currently in progress
+    running: bool,
+    /// Ping, download and upload of the last successful test
+    values: Vec<f32>,
+    /// Time of the last successful test
+    last_run: Option<Instant>,
+}
+
 pub struct SpeedTest {
-    vals: Arc<Mutex<(bool, Vec<f32>)>>,
+    vals: Arc<Mutex<Measurement>>,
     text: Vec<ButtonWidget>,
+    format: Option<FormatTemplate>,
+    spinner_frame: usize,
     id: String,
     config: SpeedTestConfig,
     send: Sender<()>,
@@ -69,6 +89,15 @@ pub struct SpeedTestConfig {
     #[serde(default = "SpeedTestConfig::default_speed_min_unit")]
     pub speed_min_unit: Unit,
 
+    /// Only run a test when the block is clicked.
+    #[serde(default = "SpeedTestConfig::default_on_click_only")]
+    pub on_click_only: bool,
+
+    /// Format override, showing all values in a single widget.
+    /// placeholders: {ping}, {download}, {upload}, {age}
+    #[serde(default = "SpeedTestConfig::default_format")]
+    pub format: Option<Formats>,
+
     #[serde(default = "SpeedTestConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
@@ -90,19 +119,44 @@ impl SpeedTestConfig {
         3
     }
 
+    fn default_on_click_only() -> bool {
+        false
+    }
+
+    fn default_format() -> Option<Formats> {
+        None
+    }
+
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
         None
     }
 }
 
-fn get_values(bytes: bool) -> Result<String> {
+/// Runs speedtest-cli, calling `tick` periodically until it finishes. A failed test keeps the
+/// last result, so its errors aren't needed and would end up in the bar's stderr.
+fn get_values<F: Fn()>(bytes: bool, tick: F) -> Result<String> {
     let mut cmd = Command::new("speedtest-cli");
-    cmd.arg("--simple");
+    cmd.arg("--simple")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
     if bytes {
         cmd.arg("--bytes");
     }
+    let mut child = cmd
+        .spawn()
+        .block_error("speedtest", "could not run speedtest-cli")?;
+    while child
+        .try_wait()
+        .block_error("speedtest", "could not get speedtest-cli status")?
+        .is_none()
+    {
+        tick();
+        thread::sleep(SPINNER_INTERVAL);
+    }
     String::from_utf8(
-        cmd.output()
+        child
+            .wait_with_output()
             .block_error("speedtest", "could not get speedtest-cli output")?
             .stdout,
     )
@@ -129,53 +183,105 @@ fn parse_values(output: &str) -> Result<Vec<f32>> {
 fn make_thread(
     recv: Receiver<()>,
     done: Sender<Task>,
-    values: Arc<Mutex<(bool, Vec<f32>)>>,
+    values: Arc<Mutex<Measurement>>,
     config: SpeedTestConfig,
     id: String,
+    cache: Cache,
 ) {
     thread::Builder::new()
         .name("speedtest".into())
         .spawn(move || loop {
             if recv.recv().is_ok() {
-                if let Ok(output) = get_values(config.bytes) {
-                    if let Ok(vals) = parse_values(&output) {
-                        if vals.len() == 3 {
-                            let (ref mut update, ref mut values) = *values
-                                .lock()
-                                .expect("main thread paniced while holding speedtest-values mutex");
-                            *values = vals;
-
-                            *update = true;
-
-                            done.send(Task {
-                                id: id.clone(),
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-                        }
-                    }
+                let request_update = || {
+                    done.send(Task {
+                        id: id.clone(),
+                        update_time: Instant::now(),
+                    })
+                    .unwrap();
+                };
+
+                let vals = get_values(config.bytes, &request_update)
+                    .and_then(|output| parse_values(&output))
+                    .ok()
+                    .filter(|vals| vals.len() == 3);
+
+                let mut measurement = values
+                    .lock()
+                    .expect("main thread paniced while holding speedtest-values mutex");
+                if let Some(vals) = vals {
+                    cache.store(&vals);
+                    measurement.values = vals;
+                    measurement.last_run = Some(Instant::now());
                 }
+                // Also set on failure so the previous result is shown again
+                measurement.updated = true;
+                measurement.running = false;
+                drop(measurement);
+
+                request_update();
             }
         })
         .unwrap();
 }
 
+/// Formats the time elapsed since the last test, e.g. "5m".
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 60 * 60 {
+        format!("{}m", secs / 60)
+    } else if secs < 24 * 60 * 60 {
+        format!("{}h", secs / (60 * 60))
+    } else {
+        format!("{}d", secs / (24 * 60 * 60))
+    }
+}
+
 impl ConfigBlock for SpeedTest {
     type Config = SpeedTestConfig;
 
     fn new(block_config: Self::Config, config: Config, done: Sender<Task>) -> Result<Self> {
         // Create all the things we are going to send and take for ourselves.
         let (send, recv): (Sender<()>, Receiver<()>) = unbounded();
-        let vals = Arc::new(Mutex::new((false, vec![])));
         let id = pseudo_uuid();
 
+        // After a restart, the cached result is shown until the next test is due. The result
+        // is measured either in bytes or in bits.
+        let cache_key = if block_config.bytes { "bytes" } else { "bits" };
+        let cache = Cache::new("speedtest", cache_key, config.cache_ttl);
+        let mut measurement = Measurement::default();
+        if let Some(cached) = cache
+            .load::<Vec<f32>>()
+            .filter(|cached| cached.value.len() == 3)
+        {
+            measurement.updated = cached.remaining(block_config.interval).is_some();
+            measurement.last_run = Instant::now().checked_sub(cached.age);
+            measurement.values = cached.value;
+        }
+        let vals = Arc::new(Mutex::new(measurement));
+
         // Make the update thread
-        make_thread(recv, done, vals.clone(), block_config.clone(), id.clone());
+        make_thread(
+            recv,
+            done,
+            vals.clone(),
+            block_config.clone(),
+            id.clone(),
+            cache,
+        );
 
         let ty = if block_config.bytes { "MB/s" } else { "Mb/s" };
-        Ok(SpeedTest {
-            vals,
-            text: vec![
+        let format = match block_config.format {
+            Some(ref format) => {
+                Some(FormatTemplate::from_formats(format)?.with_markup(config.markup))
+            }
+            None => None,
+        };
+        let text = if format.is_some() {
+            vec![ButtonWidget::new(config, &id).with_icon("ping")]
+        } else {
+            vec![
                 ButtonWidget::new(config.clone(), &id)
                     .with_icon("ping")
                     .with_text("0ms"),
@@ -185,7 +291,14 @@ impl ConfigBlock for SpeedTest {
                 ButtonWidget::new(config, &id)
                     .with_icon("net_up")
                     .with_text(&format!("0{}", ty)),
-            ],
+            ]
+        };
+
+        Ok(SpeedTest {
+            vals,
+            text,
+            format,
+            spinner_frame: 0,
             id,
             send,
             config: block_config,
@@ -193,63 +306,116 @@ impl ConfigBlock for SpeedTest {
     }
 }
 
+impl SpeedTest {
+    fn format_throughput(&self, value: f32) -> String {
+        let bytes = if self.config.bytes {
+            value * 1_000_000.0
+        } else {
+            value * 125_000.0
+        };
+        format!(
+            "{}/s",
+            format_speed(
+                bytes as u64,
+                self.config.speed_digits,
+                &self.config.speed_min_unit.to_string(),
+                !self.config.bytes
+            )
+        )
+    }
+
+    fn show_spinner(&mut self) {
+        self.spinner_frame = (self.spinner_frame + 1) % SPINNER.len();
+        self.text[0].set_text(SPINNER[self.spinner_frame]);
+    }
+
+    fn show_values(&mut self, measurement: &Measurement) -> Result<()> {
+        let vals = &measurement.values;
+
+        if let Some(ref format) = self.format {
+            let (ping, download, upload) = if vals.len() == 3 {
+                (
+                    format!("{}ms", vals[0]),
+                    self.format_throughput(vals[1]),
+                    self.format_throughput(vals[2]),
+                )
+            } else {
+                (String::new(), String::new(), String::new())
+            };
+            let age = measurement
+                .last_run
+                .map(|last_run| format_age(last_run.elapsed()))
+                .unwrap_or_default();
+            let text = format.render_static_str(&map!(
+                "{ping}" => ping,
+                "{download}" => download,
+                "{upload}" => upload,
+                "{age}" => age
+            ))?;
+            self.text[0].set_text(text);
+        } else if vals.len() == 3 {
+            self.text[0].set_text(format!("{}ms", vals[0]));
+            let download = self.format_throughput(vals[1]);
+            let upload = self.format_throughput(vals[2]);
+            self.text[1].set_text(download);
+            self.text[2].set_text(upload);
+        }
+
+        if vals.len() == 3 {
+            // TODO: remove clippy workaround
+            #[allow(clippy::unknown_clippy_lints)]
+            #[allow(clippy::match_on_vec_items)]
+            self.text[0].set_state(match_range!(vals[0], default: (State::Critical) {
+                        0.0 ; 25.0 => State::Good,
+                        25.0 ; 60.0 => State::Info,
+                        60.0 ; 100.0 => State::Warning
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Starts a new test unless one is already running.
+    fn start_test(&mut self, measurement: &mut Measurement) -> Result<()> {
+        if !measurement.running {
+            measurement.running = true;
+            self.send.send(())?;
+            self.show_spinner();
+        }
+        Ok(())
+    }
+}
+
 impl Block for SpeedTest {
     fn update(&mut self) -> Result<Option<Update>> {
-        let (ref mut updated, ref vals) = *self
-            .vals
-            .lock()
-            .block_error("speedtest", "mutext poisoned")?;
-
-        if *updated {
-            *updated = false;
-
-            if vals.len() == 3 {
-                self.text[0].set_text(format!("{}ms", vals[0]));
-                let (down_bytes, up_bytes) = if self.config.bytes {
-                    (vals[1] * 1_000_000.0, vals[2] * 1_000_000.0)
-                } else {
-                    (vals[1] * 125_000.0, vals[2] * 125_000.0)
-                };
-                self.text[1].set_text(format!(
-                    "{}/s",
-                    format_speed(
-                        down_bytes as u64,
-                        self.config.speed_digits,
-                        &self.config.speed_min_unit.to_string(),
-                        !self.config.bytes
-                    )
-                ));
-                self.text[2].set_text(format!(
-                    "{}/s",
-                    format_speed(
-                        up_bytes as u64,
-                        self.config.speed_digits,
-                        &self.config.speed_min_unit.to_string(),
-                        !self.config.bytes
-                    )
-                ));
-
-                // TODO: remove clippy workaround
-                #[allow(clippy::unknown_clippy_lints)]
-                #[allow(clippy::match_on_vec_items)]
-                self.text[0].set_state(match_range!(vals[0], default: (State::Critical) {
-                            0.0 ; 25.0 => State::Good,
-                            25.0 ; 60.0 => State::Info,
-                            60.0 ; 100.0 => State::Warning
-                }));
-            }
-
-            Ok(None)
+        let vals = self.vals.clone();
+        let mut measurement = vals.lock().block_error("speedtest", "mutext poisoned")?;
+
+        if measurement.running {
+            self.show_spinner();
+        } else if measurement.updated || self.config.on_click_only {
+            measurement.updated = false;
+            self.show_values(&measurement)?;
         } else {
-            self.send.send(())?;
+            self.start_test(&mut measurement)?;
+        }
+
+        if !self.config.on_click_only {
             Ok(Some(self.config.interval.into()))
+        } else if self.format.is_some() {
+            // Keep {age} up to date
+            Ok(Some(Duration::from_secs(60).into()))
+        } else {
+            Ok(None)
         }
     }
 
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
         if let Some(ref name) = e.name {
             if name.as_str() == self.id && e.button == MouseButton::Left {
-                self.send.send(())?;
+                let vals = self.vals.clone();
+                let mut measurement = vals.lock().block_error("speedtest", "mutext poisoned")?;
+                self.start_test(&mut measurement)?;
             }
         }
         Ok(())
@@ -266,4 +432,10 @@ impl Block for SpeedTest {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        if let Some(ref mut format) = self.format {
+            format.select(index);
+        }
+    }
 }
@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::pseudo_uuid;
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+pub struct StaticText {
+    text: TextWidget,
+    id: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct StaticTextConfig {
+    /// The text to show
+    #[serde(default = "StaticTextConfig::default_text")]
+    pub text: String,
+
+    /// The name of an icon to show before the text
+    #[serde(default = "StaticTextConfig::default_icon")]
+    pub icon: Option<String>,
+
+    /// The state the text is shown in
+    #[serde(default = "StaticTextConfig::default_state")]
+    pub state: State,
+
+    #[serde(default = "StaticTextConfig::default_color_overrides")]
+    pub color_overrides: Option<BTreeMap<String, String>>,
+}
+
+impl StaticTextConfig {
+    fn default_text() -> String {
+        "".to_owned()
+    }
+
+    fn default_icon() -> Option<String> {
+        None
+    }
+
+    fn default_state() -> State {
+        State::Idle
+    }
+
+    fn default_color_overrides() -> Option<BTreeMap<String, String>> {
+        None
+    }
+}
+
+impl ConfigBlock for StaticText {
+    type Config = StaticTextConfig;
+
+    fn new(
+        block_config: Self::Config,
+        config: Config,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let mut text = TextWidget::new(config)
+            .with_text(&block_config.text)
+            .with_state(block_config.state);
+        if let Some(ref icon) = block_config.icon {
+            text = text.with_icon(icon);
+        }
+
+        Ok(StaticText {
+            text,
+            id: pseudo_uuid(),
+        })
+    }
+}
+
+impl Block for StaticText {
+    fn update(&mut self) -> Result<Option<Update>> {
+        // The text never changes
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
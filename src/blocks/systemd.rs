@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use dbus::ffidisp::stdintf::org_freedesktop_dbus::Properties;
+use dbus::ffidisp::{BusType, Connection};
+use dbus::Message;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate, Formats};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+const SYSTEMD_SERVICE: &str = "org.freedesktop.systemd1";
+const SYSTEMD_PATH: &str = "/org/freedesktop/systemd1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.systemd1.Manager";
+const UNIT_INTERFACE: &str = "org.freedesktop.systemd1.Unit";
+
+pub struct Systemd {
+    id: String,
+    text: ButtonWidget,
+    con: Connection,
+    units: Vec<String>,
+    update_interval: Duration,
+    format: FormatTemplate,
+    separator: String,
+    restart_on_click: bool,
+    /// Units that weren't active at the last update
+    not_active: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SystemdConfig {
+    /// The units to watch, e.g. `["syncthing.service"]`
+    pub units: Vec<String>,
+
+    /// Watch the units of the user's service manager instead of the system's
+    #[serde(default = "SystemdConfig::default_user")]
+    pub user: bool,
+
+    /// Update interval in seconds
+    #[serde(
+        default = "SystemdConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format override, rendered for each unit
+    /// placeholders: {unit}, {active_state}, {sub_state}, {failed_count}
+    #[serde(default = "SystemdConfig::default_format")]
+    pub format: Formats,
+
+    /// Separator between the units
+    #[serde(default = "SystemdConfig::default_separator")]
+    pub separator: String,
+
+    /// Restart the units that aren't active when the block is left clicked
+    #[serde(default = "SystemdConfig::default_restart_on_click")]
+    pub restart_on_click: bool,
+
+    #[serde(default = "SystemdConfig::default_color_overrides")]
+    pub color_overrides: Option<BTreeMap<String, String>>,
+}
+
+impl SystemdConfig {
+    fn default_user() -> bool {
+        false
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn default_format() -> Formats {
+        "{unit} {active_state}".into()
+    }
+
+    fn default_separator() -> String {
+        " ".to_owned()
+    }
+
+    fn default_restart_on_click() -> bool {
+        false
+    }
+
+    fn default_color_overrides() -> Option<BTreeMap<String, String>> {
+        None
+    }
+}
+
+impl ConfigBlock for Systemd {
+    type Config = SystemdConfig;
+
+    fn new(
+        block_config: Self::Config,
+        config: Config,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let markup = config.markup;
+        if block_config.units.is_empty() {
+            return Err(BlockError(
+                "systemd".to_string(),
+                "at least one unit is required".to_string(),
+            ));
+        }
+
+        let con = Connection::get_private(if block_config.user {
+            BusType::Session
+        } else {
+            BusType::System
+        })
+        .block_error("systemd", "Failed to establish D-Bus connection.")?;
+
+        let id = pseudo_uuid();
+        Ok(Systemd {
+            text: ButtonWidget::new(config, &id),
+            id,
+            con,
+            units: block_config.units,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_formats(&block_config.format)
+                .block_error("systemd", "Invalid format specified")?
+                .with_markup(markup),
+            separator: block_config.separator,
+            restart_on_click: block_config.restart_on_click,
+            not_active: Vec::new(),
+        })
+    }
+}
+
+impl Systemd {
+    /// Calls a method of the service manager with the name of a unit and the given mode.
+    fn call_manager(&self, method: &str, unit: &str, mode: Option<&str>) -> Result<Message> {
+        let msg =
+            Message::new_method_call(SYSTEMD_SERVICE, SYSTEMD_PATH, MANAGER_INTERFACE, method)
+                .block_error("systemd", "Failed to create D-Bus message")?;
+        let msg = match mode {
+            Some(mode) => msg.append2(unit, mode),
+            None => msg.append1(unit),
+        };
+        self.con
+            .send_with_reply_and_block(msg, 1000)
+            .block_error("systemd", &format!("{} of {} failed", method, unit))
+    }
+
+    /// The active state and sub state of a unit, e.g. "active" and "running".
+    fn unit_state(&self, unit: &str) -> Result<(String, String)> {
+        // Unlike GetUnit, LoadUnit also works for units that aren't loaded because they are
+        // inactive, which is where a failed unit can end up after some time
+        let path: dbus::Path = self
+            .call_manager("LoadUnit", unit, None)?
+            .get1()
+            .block_error("systemd", "Invalid reply of LoadUnit")?;
+        let unit_path = self.con.with_path(SYSTEMD_SERVICE, path, 1000);
+        let active_state: String = unit_path
+            .get(UNIT_INTERFACE, "ActiveState")
+            .block_error("systemd", "Failed to get the ActiveState of the unit")?;
+        let sub_state: String = unit_path
+            .get(UNIT_INTERFACE, "SubState")
+            .block_error("systemd", "Failed to get the SubState of the unit")?;
+        Ok((active_state, sub_state))
+    }
+}
+
+impl Block for Systemd {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut states = Vec::with_capacity(self.units.len());
+        for unit in &self.units {
+            states.push((unit, self.unit_state(unit)?));
+        }
+
+        let failed_count = states
+            .iter()
+            .filter(|(_, (active_state, _))| active_state == "failed")
+            .count();
+        let mut texts = Vec::with_capacity(states.len());
+        for (unit, (active_state, sub_state)) in &states {
+            let values = map!(
+                "{unit}" => unit.to_string(),
+                "{active_state}" => active_state.clone(),
+                "{sub_state}" => sub_state.clone(),
+                "{failed_count}" => failed_count.to_string()
+            );
+            texts.push(self.format.render_static_str(&values)?);
+        }
+
+        let state_of = |active_state: &str| match active_state {
+            "failed" => State::Critical,
+            "activating" | "deactivating" | "reloading" => State::Warning,
+            "active" => State::Good,
+            _ => State::Idle,
+        };
+        // The most critical unit determines the state
+        let state = [State::Critical, State::Warning, State::Idle, State::Good]
+            .iter()
+            .find(|state| {
+                states
+                    .iter()
+                    .any(|(_, (active_state, _))| state_of(active_state) == **state)
+            })
+            .cloned()
+            .unwrap_or(State::Idle);
+
+        self.not_active = states
+            .iter()
+            .filter(|(_, (active_state, _))| active_state != "active")
+            .map(|(unit, _)| unit.to_string())
+            .collect();
+        self.text.set_text(texts.join(&self.separator));
+        self.text.set_state(state);
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn click(&mut self, e: &I3BarEvent) -> Result<()> {
+        if e.matches_name(self.id()) && self.restart_on_click {
+            if let MouseButton::Left = e.button {
+                for unit in &self.not_active {
+                    self.call_manager("RestartUnit", unit, Some("replace"))?;
+                }
+                self.update()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use regex::Regex;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{escape_pango_value, pseudo_uuid, FormatTemplate, Formats};
+use crate::widget::{I3BarWidget, Markup, State};
+use crate::widgets::text::TextWidget;
+
+/// How much of an existing file is read when the block starts.
+const INITIAL_READ_BYTES: u64 = 64 * 1024;
+
+/// The latest line that matched one of the patterns.
+struct Match {
+    line: String,
+    captures: Vec<String>,
+    state: State,
+}
+
+pub struct Tail {
+    text: TextWidget,
+    id: String,
+    format: FormatTemplate,
+    /// The highest number of capture groups of all patterns
+    max_captures: usize,
+    latest: Arc<Mutex<Option<Match>>>,
+    markup: Option<Markup>,
+}
+
+/// A regex, optionally with the state that is used if it matches.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum PatternConfig {
+    Regex(String),
+    WithState { regex: String, state: State },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TailConfig {
+    /// The file to follow
+    pub path: PathBuf,
+
+    /// Regexes that lines are matched against, the first matching one is used
+    pub patterns: Vec<PatternConfig>,
+
+    /// Format override
+    /// placeholders: {line}, {0}, {1}, ... for the capture groups
+    #[serde(default = "TailConfig::default_format")]
+    pub format: Formats,
+
+    /// How often the file is checked for new lines
+    #[serde(
+        default = "TailConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    #[serde(default = "TailConfig::default_color_overrides")]
+    pub color_overrides: Option<BTreeMap<String, String>>,
+}
+
+impl TailConfig {
+    fn default_format() -> Formats {
+        "{line}".into()
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    fn default_color_overrides() -> Option<BTreeMap<String, String>> {
+        None
+    }
+}
+
+/// Follows a file like `tail -F`, handling truncation and rotation.
+struct Follower {
+    path: PathBuf,
+    file: Option<File>,
+    inode: u64,
+    offset: u64,
+    /// An incomplete line at the end of the last read
+    partial: String,
+    /// The first read started in the middle of a line
+    skip_line: bool,
+}
+
+impl Follower {
+    fn new(path: PathBuf) -> Self {
+        Follower {
+            path,
+            file: None,
+            inode: 0,
+            offset: 0,
+            partial: String::new(),
+            skip_line: false,
+        }
+    }
+
+    /// Returns the complete lines that were appended since the last call.
+    fn read_lines(&mut self) -> Vec<String> {
+        let metadata = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // The file may be missing for a moment while it's rotated
+            Err(_) => return vec![],
+        };
+
+        if self.file.is_none() || metadata.ino() != self.inode {
+            // The file was rotated (or not opened yet); only the first time a file is opened,
+            // skip all but the end of it as it may be huge.
+            let first_open = self.file.is_none() && self.inode == 0;
+            self.file = File::open(&self.path).ok();
+            self.inode = metadata.ino();
+            self.offset = if first_open {
+                metadata.len().saturating_sub(INITIAL_READ_BYTES)
+            } else {
+                0
+            };
+            self.partial.clear();
+            self.skip_line = self.offset > 0;
+        } else if metadata.len() < self.offset {
+            // The file was truncated
+            self.offset = 0;
+            self.partial.clear();
+        }
+
+        let file = match self.file {
+            Some(ref mut file) => file,
+            None => return vec![],
+        };
+        let mut buffer = Vec::new();
+        if file.seek(SeekFrom::Start(self.offset)).is_err()
+            || file.read_to_end(&mut buffer).is_err()
+        {
+            return vec![];
+        }
+        self.offset += buffer.len() as u64;
+
+        self.partial.push_str(&String::from_utf8_lossy(&buffer));
+        let mut lines: Vec<String> = self.partial.split('\n').map(String::from).collect();
+        // The last element is either empty or an incomplete line
+        self.partial = lines.pop().unwrap_or_default();
+        if self.skip_line && !lines.is_empty() {
+            lines.remove(0);
+            self.skip_line = false;
+        }
+        lines
+    }
+}
+
+impl ConfigBlock for Tail {
+    type Config = TailConfig;
+
+    fn new(
+        block_config: Self::Config,
+        config: Config,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let markup = config.markup;
+        if block_config.patterns.is_empty() {
+            return Err(BlockError(
+                "tail".to_string(),
+                "at least one pattern is required".to_string(),
+            ));
+        }
+        let patterns = block_config
+            .patterns
+            .iter()
+            .map(|pattern| {
+                let (regex, state) = match pattern {
+                    PatternConfig::Regex(regex) => (regex, State::Idle),
+                    PatternConfig::WithState { regex, state } => (regex, *state),
+                };
+                Regex::new(regex)
+                    .block_error("tail", &format!("invalid pattern: {}", regex))
+                    .map(|regex| (regex, state))
+            })
+            .collect::<Result<Vec<(Regex, State)>>>()?;
+        let max_captures = patterns
+            .iter()
+            .map(|(regex, _)| regex.captures_len())
+            .max()
+            .unwrap_or(1);
+
+        // A placeholder of a capture group that no pattern has would fail every render
+        let placeholder = Regex::new(r"\{(\d+)\}").unwrap();
+        for format in block_config.format.all() {
+            for captures in placeholder.captures_iter(format) {
+                if captures[1]
+                    .parse::<usize>()
+                    .map_or(true, |i| i >= max_captures)
+                {
+                    return Err(ConfigurationError(
+                        "tail".to_string(),
+                        (
+                            format!(
+                                "{} is out of range, the patterns have {} capture groups",
+                                &captures[0],
+                                max_captures - 1
+                            ),
+                            "invalid format".to_string(),
+                        ),
+                    ));
+                }
+            }
+        }
+
+        let id = pseudo_uuid();
+        let latest = Arc::new(Mutex::new(None));
+
+        let thread_latest = latest.clone();
+        let thread_id = id.clone();
+        let interval = block_config.interval;
+        let mut follower = Follower::new(block_config.path);
+        thread::Builder::new()
+            .name("tail".into())
+            .spawn(move || loop {
+                let found = follower.read_lines().iter().rev().find_map(|line| {
+                    patterns.iter().find_map(|(regex, state)| {
+                        regex.captures(line).map(|captures| Match {
+                            line: line.clone(),
+                            captures: captures
+                                .iter()
+                                .map(|c| c.map(|m| m.as_str().to_string()).unwrap_or_default())
+                                .collect(),
+                            state: *state,
+                        })
+                    })
+                });
+
+                if let Some(found) = found {
+                    *thread_latest
+                        .lock()
+                        .expect("main thread paniced while holding tail mutex") = Some(found);
+                    tx_update_request
+                        .send(Task {
+                            id: thread_id.clone(),
+                            update_time: Instant::now(),
+                        })
+                        .expect("unable to send task from tail reader");
+                }
+
+                thread::sleep(interval);
+            })
+            .block_error("tail", "failed to start reader thread")?;
+
+        Ok(Tail {
+            text: TextWidget::new(config),
+            id,
+            format: FormatTemplate::from_formats(&block_config.format)?.with_markup(markup),
+            max_captures,
+            latest,
+            markup,
+        })
+    }
+}
+
+impl Block for Tail {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let latest = self.latest.lock().block_error("tail", "mutex poisoned")?;
+        if let Some(ref latest) = *latest {
+            let keys: Vec<String> = (0..self.max_captures)
+                .map(|i| format!("{{{}}}", i))
+                .collect();
+            let mut values: HashMap<&str, String> = keys
+                .iter()
+                .enumerate()
+                .map(|(i, key)| {
+                    let capture = latest.captures.get(i).cloned().unwrap_or_default();
+                    (key.as_str(), escape_pango_value(capture, self.markup))
+                })
+                .collect();
+            values.insert(
+                "{line}",
+                escape_pango_value(latest.line.clone(), self.markup),
+            );
+
+            self.text.set_text(self.format.render_static_str(&values)?);
+            self.text.set_state(latest.state);
+        }
+
+        // The reader thread requests updates when a new line matches
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self
+            .latest
+            .lock()
+            .map(|latest| latest.is_some())
+            .unwrap_or(false)
+        {
+            vec![&self.text]
+        } else {
+            vec![]
+        }
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+}
@@ -1,8 +1,11 @@
 use std::collections::BTreeMap;
-use std::process::Command;
-use std::time::Duration;
+use std::io::{self, Read};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crossbeam_channel::Sender;
+use chrono::{NaiveDateTime, Utc};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
@@ -11,10 +14,17 @@ use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::{pseudo_uuid, FormatTemplate};
-use crate::widget::{I3BarWidget, State};
+use crate::util::{escape_pango_value, has_command, pseudo_uuid, FormatTemplate, Formats};
+use crate::widget::{I3BarWidget, Markup, State};
 use crate::widgets::button::ButtonWidget;
 
+/// How long an update waits for `task`, which may e.g. run slow hooks. If it takes longer,
+/// the block keeps showing the last count until it's done.
+const TASK_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long `task` may run at most before it's considered hung and killed.
+const TASK_KILL_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub struct Taskwarrior {
     output: ButtonWidget,
     id: String,
@@ -22,7 +32,10 @@ pub struct Taskwarrior {
     warning_threshold: u32,
     critical_threshold: u32,
     filter_tags: Vec<String>,
+    filters: Vec<Filter>,
     block_mode: TaskwarriorBlockMode,
+    /// The `task` that is still running
+    pending: Option<PendingExport>,
     format: FormatTemplate,
     format_singular: FormatTemplate,
     format_everything_done: FormatTemplate,
@@ -32,6 +45,7 @@ pub struct Taskwarrior {
     config: Config,
     #[allow(dead_code)]
     tx_update_request: Sender<Task>,
+    markup: Option<Markup>,
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -56,9 +70,17 @@ pub struct TaskwarriorConfig {
     #[serde(default = "TaskwarriorConfig::default_filter_tags")]
     pub filter_tags: Vec<String>,
 
+    /// A filter that is passed to taskwarrior, e.g. "project:work"
+    #[serde(default = "TaskwarriorConfig::default_filter")]
+    pub filter: String,
+
+    /// A list of filters to cycle through with the left mouse button, replaces `filter`
+    #[serde(default = "TaskwarriorConfig::default_filters")]
+    pub filters: Vec<Filter>,
+
     /// Format override
-    #[serde(default = "TaskwarriorConfig::default_format")]
-    pub format: String,
+    #[serde(default = "TaskwarriorConfig::default_formats")]
+    pub format: Formats,
 
     /// Format override if exactly one task is pending
     #[serde(default = "TaskwarriorConfig::default_format")]
@@ -72,9 +94,41 @@ pub struct TaskwarriorConfig {
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
 
+/// A filter, optionally with a name that is shown instead of the filter itself.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Filter {
+    Named(NamedFilter),
+    Plain(String),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct NamedFilter {
+    pub name: String,
+    pub filter: String,
+}
+
+impl Filter {
+    fn name(&self) -> &str {
+        match self {
+            Filter::Named(named) => &named.name,
+            Filter::Plain(filter) => filter,
+        }
+    }
+
+    fn filter(&self) -> &str {
+        match self {
+            Filter::Named(named) => &named.filter,
+            Filter::Plain(filter) => filter,
+        }
+    }
+}
+
 enum TaskwarriorBlockMode {
-    // Show only the tasks which are filtered by the set tags and which are not completed.
-    OnlyFilteredPendingTasks,
+    // Show only the tasks which are filtered by the set tags and the filter with the given index
+    // and which are not completed.
+    OnlyFilteredPendingTasks(usize),
     // Show all pending tasks and ignore the filtering tags.
     AllPendingTasks,
 }
@@ -96,10 +150,22 @@ impl TaskwarriorConfig {
         vec![]
     }
 
+    fn default_filter() -> String {
+        "".to_owned()
+    }
+
+    fn default_filters() -> Vec<Filter> {
+        vec![]
+    }
+
     fn default_format() -> String {
         "{count}".to_owned()
     }
 
+    fn default_formats() -> Formats {
+        Self::default_format().into()
+    }
+
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
         None
     }
@@ -113,110 +179,228 @@ impl ConfigBlock for Taskwarrior {
         config: Config,
         tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let markup = config.markup;
+        if !has_command("taskwarrior", "task")? {
+            return Err(BlockError(
+                "taskwarrior".to_string(),
+                "the `task` command of taskwarrior isn't installed".to_string(),
+            ));
+        }
+
+        let filters = if block_config.filters.is_empty() {
+            vec![Filter::Plain(block_config.filter)]
+        } else {
+            block_config.filters
+        };
+
         Ok(Taskwarrior {
             id: pseudo_uuid(),
             update_interval: block_config.interval,
             warning_threshold: block_config.warning_threshold,
             critical_threshold: block_config.critical_threshold,
             filter_tags: block_config.filter_tags,
-            block_mode: TaskwarriorBlockMode::OnlyFilteredPendingTasks,
+            filters,
+            block_mode: TaskwarriorBlockMode::OnlyFilteredPendingTasks(0),
+            pending: None,
             output: ButtonWidget::new(config.clone(), "taskwarrior")
                 .with_icon("tasks")
                 .with_text("-"),
-            format: FormatTemplate::from_string(&block_config.format).block_error(
-                "taskwarrior",
-                "Invalid format specified for taskwarrior::format",
-            )?,
+            format: FormatTemplate::from_formats(&block_config.format)
+                .block_error(
+                    "taskwarrior",
+                    "Invalid format specified for taskwarrior::format",
+                )?
+                .with_markup(markup),
             format_singular: FormatTemplate::from_string(&block_config.format_singular)
                 .block_error(
                     "taskwarrior",
                     "Invalid format specified for taskwarrior::format_singular",
-                )?,
+                )?
+                .with_markup(markup),
             format_everything_done: FormatTemplate::from_string(
                 &block_config.format_everything_done,
             )
             .block_error(
                 "taskwarrior",
                 "Invalid format specified for taskwarrior::format_everything_done",
-            )?,
+            )?
+            .with_markup(markup),
             tx_update_request,
             config,
+            markup,
         })
     }
 }
 
-fn has_taskwarrior() -> Result<bool> {
-    Ok(String::from_utf8(
-        Command::new("sh")
-            .args(&["-c", "type -P task"])
-            .output()
-            .block_error(
-                "taskwarrior",
-                "failed to start command to check for taskwarrior",
-            )?
-            .stdout,
-    )
-    .block_error("taskwarrior", "failed to check for taskwarrior")?
-    .trim()
-        != "")
+/// The arguments of `task export`. The filter is put in parentheses, so that e.g. an `or` in
+/// it doesn't also bring back the completed and deleted tasks.
+fn export_args(tags: &[String], filter: &str) -> Vec<String> {
+    let mut args: Vec<String> = [
+        "rc.gc=off",
+        "rc.color=off",
+        "rc.json.array=on",
+        "-COMPLETED",
+        "-DELETED",
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect();
+    args.extend(tags.iter().map(|tag| format!("+{}", tag)));
+    if !filter.trim().is_empty() {
+        args.push(format!("( {} )", filter));
+    }
+    args.push("export".to_string());
+    args
 }
 
-fn tags_to_filter(tags: &[String]) -> String {
-    tags.iter()
-        .map(|element| format!("+{}", element))
-        .collect::<Vec<String>>()
-        .join(" ")
+/// The fields of `task export` used by this block.
+#[derive(Deserialize, Debug)]
+struct TaskEntry {
+    description: String,
+    #[serde(default)]
+    urgency: f64,
+    due: Option<String>,
 }
 
-fn get_number_of_pending_tasks(tags: &[String]) -> Result<u32> {
-    String::from_utf8(
-        Command::new("sh")
-            .args(&[
-                "-c",
-                &format!(
-                    "task rc.gc=off -COMPLETED -DELETED {} count",
-                    tags_to_filter(tags)
-                ),
-            ])
-            .output()
-            .block_error(
-                "taskwarrior",
-                "failed to run taskwarrior for getting the number of pending tasks",
-            )?
-            .stdout,
-    )
+impl TaskEntry {
+    fn is_overdue(&self) -> bool {
+        self.due
+            .as_ref()
+            .and_then(|due| NaiveDateTime::parse_from_str(due, "%Y%m%dT%H%M%SZ").ok())
+            .map(|due| due < Utc::now().naive_utc())
+            .unwrap_or(false)
+    }
+}
+
+/// A run of `task` that exports the pending tasks. It is killed if it's dropped before it's
+/// done, e.g. after switching to another filter or once it hung, and waited for in any case.
+struct PendingExport {
+    child: Child,
+    /// The standard output, sent once `task` is done
+    output: Receiver<io::Result<Vec<u8>>>,
+    started: Instant,
+}
+
+impl Drop for PendingExport {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+/// Starts `task` to export the pending tasks. Its output is read on a thread, so that the
+/// bar doesn't have to wait for it.
+fn export_pending_tasks(tags: &[String], filter: &str) -> Result<PendingExport> {
+    let mut child = Command::new("task")
+        .args(export_args(tags, filter))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .block_error("taskwarrior", "failed to run taskwarrior")?;
+    let mut stdout = child
+        .stdout
+        .take()
+        .block_error("taskwarrior", "failed to read the output of taskwarrior")?;
+
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    thread::Builder::new()
+        .name("taskwarrior".into())
+        .spawn(move || {
+            let mut output = Vec::new();
+            let result = stdout.read_to_end(&mut output);
+            tx.send(result.map(|_| output)).ok();
+        })
+        .block_error("taskwarrior", "failed to start taskwarrior thread")?;
+
+    Ok(PendingExport {
+        child,
+        output: rx,
+        started: Instant::now(),
+    })
+}
+
+fn parse_pending_tasks(output: io::Result<Vec<u8>>) -> Result<Vec<TaskEntry>> {
+    let output = String::from_utf8(output.block_error(
+        "taskwarrior",
+        "failed to run taskwarrior for getting the pending tasks",
+    )?)
     .block_error(
         "taskwarrior",
-        "failed to get the number of pending tasks from taskwarrior",
-    )?
-    .trim()
-    .parse::<u32>()
-    .block_error("taskwarrior", "could not parse the result of taskwarrior")
+        "failed to get the pending tasks from taskwarrior",
+    )?;
+
+    serde_json::from_str(&output)
+        .block_error("taskwarrior", "could not parse the result of taskwarrior")
 }
 
 impl Block for Taskwarrior {
     fn update(&mut self) -> Result<Option<Update>> {
-        if !has_taskwarrior()? {
-            self.output.set_text("?")
-        } else {
-            let filter_tags = match self.block_mode {
-                TaskwarriorBlockMode::OnlyFilteredPendingTasks => self.filter_tags.clone(),
-                TaskwarriorBlockMode::AllPendingTasks => vec![],
-            };
-            let number_of_pending_tasks = get_number_of_pending_tasks(&filter_tags)?;
-            let values = map!("{count}" => number_of_pending_tasks);
-            self.output.set_text(match number_of_pending_tasks {
-                0 => self.format_everything_done.render_static_str(&values)?,
-                1 => self.format_singular.render_static_str(&values)?,
-                _ => self.format.render_static_str(&values)?,
-            });
-            if number_of_pending_tasks >= self.critical_threshold {
+        let (filter_tags, filter, filter_name) = match self.block_mode {
+            TaskwarriorBlockMode::OnlyFilteredPendingTasks(i) => (
+                self.filter_tags.clone(),
+                self.filters[i].filter(),
+                self.filters[i].name(),
+            ),
+            TaskwarriorBlockMode::AllPendingTasks => (vec![], "", "all"),
+        };
+
+        // A `task` that is still running from an earlier update is only checked on, so that
+        // slow runs don't keep blocking the bar
+        let (pending, timeout) = match self.pending.take() {
+            Some(pending) => (pending, Duration::from_secs(0)),
+            None => (export_pending_tasks(&filter_tags, filter)?, TASK_TIMEOUT),
+        };
+        let output = match pending.output.recv_timeout(timeout) {
+            Ok(output) => output,
+            Err(RecvTimeoutError::Timeout) if pending.started.elapsed() >= TASK_KILL_TIMEOUT => {
+                // Dropping the run kills it, the next update tries again
+                drop(pending);
+                self.output
+                    .set_text("taskwarrior: task timed out".to_string());
                 self.output.set_state(State::Critical);
-            } else if number_of_pending_tasks >= self.warning_threshold {
-                self.output.set_state(State::Warning);
-            } else {
-                self.output.set_state(State::Idle);
+                return Ok(Some(self.update_interval.into()));
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                self.pending = Some(pending);
+                return Ok(Some(TASK_TIMEOUT.into()));
             }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(BlockError(
+                    "taskwarrior".to_string(),
+                    "taskwarrior thread stopped".to_string(),
+                ))
+            }
+        };
+
+        let tasks = parse_pending_tasks(output)?;
+        let number_of_pending_tasks = tasks.len() as u32;
+        let next = tasks
+            .iter()
+            .max_by(|a, b| {
+                a.urgency
+                    .partial_cmp(&b.urgency)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|task| escape_pango_value(task.description.clone(), self.markup))
+            .unwrap_or_default();
+        let values = map!("{count}" => number_of_pending_tasks.to_string(),
+                          "{next}" => next,
+                          "{filter}" => filter.to_string(),
+                          "{filter_name}" => filter_name.to_string());
+        self.output.set_text(match number_of_pending_tasks {
+            0 => self.format_everything_done.render_static_str(&values)?,
+            1 => self.format_singular.render_static_str(&values)?,
+            _ => self.format.render_static_str(&values)?,
+        });
+        if number_of_pending_tasks >= self.critical_threshold {
+            self.output.set_state(State::Critical);
+        } else if number_of_pending_tasks >= self.warning_threshold
+            || tasks.iter().any(TaskEntry::is_overdue)
+        {
+            self.output.set_state(State::Warning);
+        } else {
+            self.output.set_state(State::Idle);
         }
 
         // continue updating the block in the configured interval
@@ -235,18 +419,25 @@ impl Block for Taskwarrior {
             .unwrap_or(false)
         {
             match event.button {
-                MouseButton::Left => {
+                MouseButton::Right => {
                     self.update()?;
                 }
-                MouseButton::Right => {
-                    match self.block_mode {
-                        TaskwarriorBlockMode::OnlyFilteredPendingTasks => {
-                            self.block_mode = TaskwarriorBlockMode::AllPendingTasks
+                MouseButton::Left => {
+                    // The count of the filter before doesn't matter anymore
+                    self.pending = None;
+                    self.block_mode = match self.block_mode {
+                        TaskwarriorBlockMode::OnlyFilteredPendingTasks(i)
+                            if i + 1 < self.filters.len() =>
+                        {
+                            TaskwarriorBlockMode::OnlyFilteredPendingTasks(i + 1)
+                        }
+                        TaskwarriorBlockMode::OnlyFilteredPendingTasks(_) => {
+                            TaskwarriorBlockMode::AllPendingTasks
                         }
                         TaskwarriorBlockMode::AllPendingTasks => {
-                            self.block_mode = TaskwarriorBlockMode::OnlyFilteredPendingTasks
+                            TaskwarriorBlockMode::OnlyFilteredPendingTasks(0)
                         }
-                    }
+                    };
                     self.update()?;
                 }
                 _ => {}
@@ -259,4 +450,26 @@ impl Block for Taskwarrior {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_args() {
+        assert_eq!(
+            export_args(&[], "").join(" "),
+            "rc.gc=off rc.color=off rc.json.array=on -COMPLETED -DELETED export"
+        );
+        assert_eq!(
+            export_args(&["work".to_string()], "+PENDING +OVERDUE or due:today").join(" "),
+            "rc.gc=off rc.color=off rc.json.array=on -COMPLETED -DELETED +work \
+             ( +PENDING +OVERDUE or due:today ) export"
+        );
+    }
 }
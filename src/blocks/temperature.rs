@@ -3,6 +3,7 @@ use std::process::Command;
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
+use regex::Regex;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
@@ -11,7 +12,7 @@ use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::util::{pseudo_uuid, wildcard_to_regex, FormatTemplate, Formats};
 use crate::widget::{I3BarWidget, Spacing, State};
 use crate::widgets::button::ButtonWidget;
 
@@ -28,6 +29,16 @@ impl Default for TemperatureScale {
     }
 }
 
+impl TemperatureScale {
+    /// Converts a temperature in degrees Celsius to this scale.
+    fn convert_celsius(self, celsius: f64) -> f64 {
+        match self {
+            TemperatureScale::Celsius => celsius,
+            TemperatureScale::Fahrenheit => celsius * 9. / 5. + 32.,
+        }
+    }
+}
+
 pub struct Temperature {
     text: ButtonWidget,
     output: String,
@@ -35,13 +46,15 @@ pub struct Temperature {
     id: String,
     update_interval: Duration,
     scale: TemperatureScale,
+    precision: usize,
     maximum_good: i64,
     maximum_idle: i64,
     maximum_info: i64,
     maximum_warning: i64,
     format: FormatTemplate,
-    chip: Option<String>,
-    inputs: Option<Vec<String>>,
+    chip: Option<Regex>,
+    inputs: Option<Vec<Regex>>,
+    fans: Option<Vec<Regex>>,
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -62,6 +75,10 @@ pub struct TemperatureConfig {
     #[serde(default)]
     pub scale: TemperatureScale,
 
+    /// Number of decimals of the temperatures
+    #[serde(default = "TemperatureConfig::default_precision")]
+    pub precision: usize,
+
     /// Maximum temperature, below which state is set to good
     #[serde(default)]
     pub good: Option<i64>,
@@ -80,29 +97,38 @@ pub struct TemperatureConfig {
 
     /// Format override
     #[serde(default = "TemperatureConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
 
-    /// Chip override
+    /// Chip override, matched against the chip name (e.g. "k10temp-pci-00c3") or the hwmon name
+    /// (e.g. "k10temp")
     #[serde(default = "TemperatureConfig::default_chip")]
     pub chip: Option<String>,
 
-    /// Inputs whitelist
-    #[serde(default = "TemperatureConfig::default_inputs")]
+    /// Inputs whitelist, matched against the input labels (e.g. "Tctl" or "Package id 0")
+    #[serde(default = "TemperatureConfig::default_inputs", alias = "labels")]
     pub inputs: Option<Vec<String>>,
 
+    /// Fans whitelist, matched against the fan labels (e.g. "cpu_fan" or "fan1")
+    #[serde(default = "TemperatureConfig::default_inputs")]
+    pub fans: Option<Vec<String>>,
+
     #[serde(default = "TemperatureConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
 
 impl TemperatureConfig {
-    fn default_format() -> String {
-        "{average}° avg, {max}° max".to_owned()
+    fn default_format() -> Formats {
+        "{average}° avg, {max}° max".into()
     }
 
     fn default_interval() -> Duration {
         Duration::from_secs(5)
     }
 
+    fn default_precision() -> usize {
+        0
+    }
+
     fn default_collapsed() -> bool {
         true
     }
@@ -120,6 +146,62 @@ impl TemperatureConfig {
     }
 }
 
+fn pattern_to_regex(pattern: &str) -> Result<Regex> {
+    wildcard_to_regex(pattern).block_error(
+        "temperature",
+        &format!("invalid sensor pattern: {}", pattern),
+    )
+}
+
+/// The maximum temperatures of the good, idle, info and warning states. The configured ones
+/// must be in order, and the defaults are moved in between them where needed, so that e.g.
+/// `warning` can be set below the default of `info`.
+fn thresholds(block_config: &TemperatureConfig) -> Result<[i64; 4]> {
+    let defaults = match block_config.scale {
+        TemperatureScale::Celsius => [20, 45, 60, 80],
+        TemperatureScale::Fahrenheit => [68, 113, 140, 176],
+    };
+    let configured = [
+        ("good", block_config.good),
+        ("idle", block_config.idle),
+        ("info", block_config.info),
+        ("warning", block_config.warning),
+    ];
+
+    let set: Vec<(&str, i64)> = configured
+        .iter()
+        .filter_map(|&(name, value)| value.map(|value| (name, value)))
+        .collect();
+    for pair in set.windows(2) {
+        if pair[0].1 > pair[1].1 {
+            return Err(BlockError(
+                "temperature".to_string(),
+                format!(
+                    "'{}' ({}) must not be greater than '{}' ({})",
+                    pair[0].0, pair[0].1, pair[1].0, pair[1].1
+                ),
+            ));
+        }
+    }
+
+    let mut thresholds = defaults;
+    for (i, threshold) in thresholds.iter_mut().enumerate() {
+        *threshold = match configured[i].1 {
+            Some(value) => value,
+            None => {
+                let below = configured[..i].iter().filter_map(|&(_, value)| value).max();
+                let above = configured[i + 1..]
+                    .iter()
+                    .filter_map(|&(_, value)| value)
+                    .min();
+                let threshold = below.map_or(*threshold, |below| (*threshold).max(below));
+                above.map_or(threshold, |above| threshold.min(above))
+            }
+        };
+    }
+    Ok(thresholds)
+}
+
 impl ConfigBlock for Temperature {
     type Config = TemperatureConfig;
 
@@ -128,8 +210,11 @@ impl ConfigBlock for Temperature {
         config: Config,
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let markup = config.markup;
         let id = pseudo_uuid();
-        Ok(Temperature {
+        let [maximum_good, maximum_idle, maximum_info, maximum_warning] =
+            thresholds(&block_config)?;
+        let temperature = Temperature {
             update_interval: block_config.interval,
             text: ButtonWidget::new(config, &id)
                 .with_icon("thermometer")
@@ -142,35 +227,38 @@ impl ConfigBlock for Temperature {
             collapsed: block_config.collapsed,
             id,
             scale: block_config.scale,
-            maximum_good: block_config
-                .good
-                .unwrap_or_else(|| match block_config.scale {
-                    TemperatureScale::Celsius => 20,
-                    TemperatureScale::Fahrenheit => 68,
-                }),
-            maximum_idle: block_config
-                .idle
-                .unwrap_or_else(|| match block_config.scale {
-                    TemperatureScale::Celsius => 45,
-                    TemperatureScale::Fahrenheit => 113,
-                }),
-            maximum_info: block_config
-                .info
-                .unwrap_or_else(|| match block_config.scale {
-                    TemperatureScale::Celsius => 60,
-                    TemperatureScale::Fahrenheit => 140,
-                }),
-            maximum_warning: block_config
-                .warning
-                .unwrap_or_else(|| match block_config.scale {
-                    TemperatureScale::Celsius => 80,
-                    TemperatureScale::Fahrenheit => 176,
-                }),
-            format: FormatTemplate::from_string(&block_config.format)
-                .block_error("temperature", "Invalid format specified for temperature")?,
-            chip: block_config.chip,
-            inputs: block_config.inputs,
-        })
+            precision: block_config.precision,
+            maximum_good,
+            maximum_idle,
+            maximum_info,
+            maximum_warning,
+            format: FormatTemplate::from_formats(&block_config.format)
+                .block_error("temperature", "Invalid format specified for temperature")?
+                .with_markup(markup),
+            chip: match block_config.chip {
+                Some(ref chip) => Some(pattern_to_regex(chip)?),
+                None => None,
+            },
+            inputs: match block_config.inputs {
+                Some(ref inputs) => Some(
+                    inputs
+                        .iter()
+                        .map(|input| pattern_to_regex(input))
+                        .collect::<Result<Vec<Regex>>>()?,
+                ),
+                None => None,
+            },
+            fans: match block_config.fans {
+                Some(ref fans) => Some(
+                    fans.iter()
+                        .map(|fan| pattern_to_regex(fan))
+                        .collect::<Result<Vec<Regex>>>()?,
+                ),
+                None => None,
+            },
+        };
+
+        Ok(temperature)
     }
 }
 
@@ -179,15 +267,9 @@ type InputReadings = HashMap<String, f64>;
 
 impl Block for Temperature {
     fn update(&mut self) -> Result<Option<Update>> {
-        let mut args = vec!["-j"];
-        if let TemperatureScale::Fahrenheit = self.scale {
-            args.push("-f");
-        }
-        if let Some(ref chip) = &self.chip {
-            args.push(chip);
-        }
+        // Always read Celsius, the temperatures are converted to the configured scale below
         let output = Command::new("sensors")
-            .args(&args)
+            .args(&["-j"])
             .output()
             .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_owned())
             .unwrap_or_else(|e| e.to_string());
@@ -195,13 +277,26 @@ impl Block for Temperature {
         let parsed: SensorsOutput = serde_json::from_str(&output)
             .block_error("temperature", "sensors output is invalid")?;
 
-        let mut temperatures: Vec<i64> = Vec::new();
-        for (_chip, inputs) in parsed {
+        // The label of each reading, which `sensors` takes from `tempN_label` if there is one
+        let mut temperatures: Vec<(String, f64)> = Vec::new();
+        let mut fan_speeds: Vec<(String, u64)> = Vec::new();
+        for (chip_name, inputs) in parsed {
+            // Chips are matched by name as the hwmonN numbering may change across boots
+            if let Some(ref chip) = self.chip {
+                let hwmon_name = chip_name.split('-').next().unwrap_or_default();
+                if !chip.is_match(&chip_name) && !chip.is_match(hwmon_name) {
+                    continue;
+                }
+            }
+
             for (input_name, input_values) in inputs {
-                if let Some(ref whitelist) = self.inputs {
-                    if !whitelist.contains(&input_name) {
-                        continue;
-                    }
+                let matches = |whitelist: &Option<Vec<Regex>>| match whitelist {
+                    Some(whitelist) => whitelist.iter().any(|input| input.is_match(&input_name)),
+                    None => true,
+                };
+                let (temperature_input, fan_input) = (matches(&self.inputs), matches(&self.fans));
+                if !temperature_input && !fan_input {
+                    continue;
                 }
 
                 let values_parsed: InputReadings = match serde_json::from_value(input_values) {
@@ -210,50 +305,104 @@ impl Block for Temperature {
                 };
 
                 for (value_name, value) in values_parsed {
-                    if !value_name.starts_with("temp") || !value_name.ends_with("input") {
+                    if !value_name.ends_with("input") {
+                        continue;
+                    }
+                    if value_name.starts_with("fan") {
+                        // A stopped fan reports 0, which is meaningful
+                        if fan_input && value >= 0. {
+                            fan_speeds
+                                .push((format!("{}/{}", chip_name, value_name), value as u64));
+                        }
+                        continue;
+                    }
+                    if !temperature_input || !value_name.starts_with("temp") {
                         continue;
                     }
 
-                    if value > -101f64 && value < 151f64 {
-                        temperatures.push(value as i64);
+                    if value > -101. && value < 151. {
+                        temperatures.push((input_name.clone(), self.scale.convert_celsius(value)));
                     } else {
                         // This error is recoverable and therefore should not stop the program
-                        eprintln!("Temperature ({}) outside of range ([-100, 150])", value);
+                        eprintln!("Temperature ({}) outside of range ([-101, 151])", value);
                     }
                 }
             }
         }
 
         if !temperatures.is_empty() {
-            let max: i64 = *temperatures
+            // The first of several equally hot sensors, as the chips are in no particular order
+            temperatures.sort_by(|a, b| a.0.cmp(&b.0));
+            let (max_sensor, max) =
+                temperatures
+                    .iter()
+                    .fold((String::new(), f64::MIN), |hottest, (sensor, value)| {
+                        if *value > hottest.1 {
+                            (sensor.clone(), *value)
+                        } else {
+                            hottest
+                        }
+                    });
+            let min = temperatures
+                .iter()
+                .map(|(_, value)| *value)
+                .fold(f64::MAX, f64::min);
+            let avg = temperatures.iter().map(|(_, value)| value).sum::<f64>()
+                / temperatures.len() as f64;
+
+            // Keep the order of the fans stable across updates
+            fan_speeds.sort();
+            // Empty if there are no (readable) fans
+            let fan_speed = fan_speeds
                 .iter()
+                .map(|(_, speed)| speed)
                 .max()
-                .block_error("temperature", "failed to get max temperature")?;
-            let min: i64 = *temperatures
+                .map(|speed| speed.to_string())
+                .unwrap_or_default();
+            let fan_speeds = fan_speeds
                 .iter()
-                .min()
-                .block_error("temperature", "failed to get min temperature")?;
-            let avg: i64 = (temperatures.iter().sum::<i64>() as f64 / temperatures.len() as f64)
-                .round() as i64;
-
-            let values = map!("{average}" => avg,
-                              "{min}" => min,
-                              "{max}" => max);
+                .map(|(_, speed)| speed.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            let shown_max = format!("{:.*}", self.precision, max);
+            let values = map!("{temp}" => shown_max.clone(),
+                              "{average}" => format!("{:.*}", self.precision, avg),
+                              "{min}" => format!("{:.*}", self.precision, min),
+                              "{max}" => shown_max.clone(),
+                              "{max_sensor}" => max_sensor,
+                              "{fan_speed}" => fan_speed,
+                              "{fan_speeds}" => fan_speeds);
 
             self.output = self.format.render_static_str(&values)?;
-            if !self.collapsed {
+            if self.collapsed {
+                self.text.set_text(String::new());
+                self.text.set_spacing(Spacing::Hidden);
+            } else {
                 self.text.set_text(self.output.clone());
             }
 
-            let state = match max {
-                m if m <= self.maximum_good => State::Good,
-                m if m <= self.maximum_idle => State::Idle,
-                m if m <= self.maximum_info => State::Info,
-                m if m <= self.maximum_warning => State::Warning,
+            // The hottest sensor, so that a single hot core isn't hidden by the average. The
+            // state goes with the shown value, e.g. 60.4° shown as 60° is still at most 60°.
+            let state = match shown_max.parse::<f64>().unwrap_or(max) {
+                m if m <= self.maximum_good as f64 => State::Good,
+                m if m <= self.maximum_idle as f64 => State::Idle,
+                m if m <= self.maximum_info as f64 => State::Info,
+                m if m <= self.maximum_warning as f64 => State::Warning,
                 _ => State::Critical,
             };
 
             self.text.set_state(state);
+        } else {
+            // Don't silently show nothing (or 0°) if the filters are wrong
+            self.output = if self.chip.is_some() || self.inputs.is_some() {
+                "no sensors match chip/inputs".to_string()
+            } else {
+                "no sensors found".to_string()
+            };
+            self.text.set_text(self.output.clone());
+            self.text.set_spacing(Spacing::Normal);
+            self.text.set_state(State::Critical);
         }
 
         Ok(Some(self.update_interval.into()))
@@ -283,4 +432,41 @@ impl Block for Temperature {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thresholds() {
+        let config = |good, idle, info, warning| TemperatureConfig {
+            good,
+            idle,
+            info,
+            warning,
+            ..Default::default()
+        };
+        assert_eq!(
+            thresholds(&config(None, None, None, None)).unwrap(),
+            [20, 45, 60, 80]
+        );
+        assert_eq!(
+            thresholds(&config(None, None, None, Some(55))).unwrap(),
+            [20, 45, 55, 55]
+        );
+        assert_eq!(
+            thresholds(&config(Some(50), None, None, None)).unwrap(),
+            [50, 50, 60, 80]
+        );
+        assert_eq!(
+            thresholds(&config(None, Some(60), Some(75), Some(90))).unwrap(),
+            [20, 60, 75, 90]
+        );
+        assert!(thresholds(&config(None, Some(70), None, Some(65))).is_err());
+    }
 }
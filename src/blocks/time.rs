@@ -1,23 +1,26 @@
 use std::collections::BTreeMap;
 use std::convert::TryInto;
-use std::time::Duration;
+use std::fmt::Display;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use chrono::{
+    format::{Item, StrftimeItems},
     offset::{Local, Utc},
-    Locale,
+    DateTime, Locale, TimeZone,
 };
 use chrono_tz::Tz;
-use crossbeam_channel::Sender;
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::Config;
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_opt_duration};
 use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::subprocess::spawn_child_async;
-use crate::util::pseudo_uuid;
+use crate::util::{pseudo_uuid, Formats};
 use crate::widget::I3BarWidget;
 use crate::widgets::button::ButtonWidget;
 
@@ -25,10 +28,56 @@ pub struct Time {
     time: ButtonWidget,
     id: String,
     update_interval: Duration,
-    format: String,
+    format: Formats,
+    /// Index of the shown format in `format`
+    format_index: usize,
+    format_long: Option<String>,
+    /// Since when the long format is shown
+    long_since: Option<Instant>,
+    toggle_button: MouseButton,
+    auto_revert: Option<Duration>,
     on_click: Option<String>,
-    timezone: Option<Tz>,
-    locale: Option<String>,
+    timezones: Vec<TimezoneEntry>,
+    /// Index of the displayed timezone in `timezones`
+    current_timezone: usize,
+    locale: Option<Locale>,
+    /// Tells the revert thread when the short format is due again, if ever
+    revert_at: Option<Sender<Option<Instant>>>,
+}
+
+/// A timezone, optionally with a label used for the `{timezone}` placeholder.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum TimezoneEntry {
+    Name(Tz),
+    Labeled {
+        timezone: Tz,
+        #[serde(default)]
+        label: Option<String>,
+    },
+}
+
+impl TimezoneEntry {
+    fn timezone(&self) -> Tz {
+        match *self {
+            TimezoneEntry::Name(tz) => tz,
+            TimezoneEntry::Labeled { timezone, .. } => timezone,
+        }
+    }
+
+    fn label(&self) -> Option<&str> {
+        match self {
+            TimezoneEntry::Name(_) => None,
+            TimezoneEntry::Labeled { label, .. } => label.as_deref(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum TimezoneConfig {
+    Single(Tz),
+    Multiple(Vec<TimezoneEntry>),
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -36,7 +85,27 @@ pub struct Time {
 pub struct TimeConfig {
     /// Format string.<br/> See [chrono docs](https://docs.rs/chrono/0.3.0/chrono/format/strftime/index.html#specifiers) for all options.
     #[serde(default = "TimeConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
+
+    /// Overrides `format`
+    #[serde(default = "TimeConfig::default_format_short")]
+    pub format_short: Option<String>,
+
+    /// Format that is shown instead of the short format after clicking the block
+    #[serde(default = "TimeConfig::default_format_long")]
+    pub format_long: Option<String>,
+
+    /// Mouse button that toggles between the short and long format, right by default since
+    /// left clicks cycle through the timezones
+    #[serde(default = "TimeConfig::default_toggle_button")]
+    pub toggle_button: MouseButton,
+
+    /// Seconds after which the short format is shown again
+    #[serde(
+        default = "TimeConfig::default_auto_revert_secs",
+        deserialize_with = "deserialize_opt_duration"
+    )]
+    pub auto_revert_secs: Option<Duration>,
 
     /// Update interval in seconds
     #[serde(
@@ -48,9 +117,11 @@ pub struct TimeConfig {
     #[serde(default = "TimeConfig::default_on_click")]
     pub on_click: Option<String>,
 
+    /// A timezone or a list of timezones, cycled through by clicking the block
     #[serde(default = "TimeConfig::default_timezone")]
-    pub timezone: Option<Tz>,
+    pub timezone: Option<TimezoneConfig>,
 
+    /// Locale used for the names of days and months, defaults to LC_TIME
     #[serde(default = "TimeConfig::default_locale")]
     pub locale: Option<String>,
 
@@ -59,8 +130,24 @@ pub struct TimeConfig {
 }
 
 impl TimeConfig {
-    fn default_format() -> String {
-        "%a %d/%m %R".to_owned()
+    fn default_format() -> Formats {
+        "%a %d/%m %R".into()
+    }
+
+    fn default_format_short() -> Option<String> {
+        None
+    }
+
+    fn default_format_long() -> Option<String> {
+        None
+    }
+
+    fn default_toggle_button() -> MouseButton {
+        MouseButton::Right
+    }
+
+    fn default_auto_revert_secs() -> Option<Duration> {
+        None
     }
 
     fn default_interval() -> Duration {
@@ -71,7 +158,7 @@ impl TimeConfig {
         None
     }
 
-    fn default_timezone() -> Option<Tz> {
+    fn default_timezone() -> Option<TimezoneConfig> {
         None
     }
 
@@ -90,56 +177,131 @@ impl ConfigBlock for Time {
     fn new(
         block_config: Self::Config,
         config: Config,
-        _tx_update_request: Sender<Task>,
+        tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let format = block_config
+            .format_short
+            .map(Formats::from)
+            .unwrap_or(block_config.format);
+        for format in format.all() {
+            validate_format(format)?;
+        }
+        if let Some(ref format_long) = block_config.format_long {
+            validate_format(format_long)?;
+        }
+
+        let timezones = match block_config.timezone {
+            None => vec![],
+            Some(TimezoneConfig::Single(tz)) => vec![TimezoneEntry::Name(tz)],
+            Some(TimezoneConfig::Multiple(timezones)) => timezones,
+        };
+
+        let locale = match block_config.locale {
+            Some(locale) => Some(locale.as_str().try_into().map_err(|_| {
+                ConfigurationError(
+                    "time".to_string(),
+                    (
+                        format!("invalid locale '{}'", locale),
+                        "invalid locale".to_string(),
+                    ),
+                )
+            })?),
+            None => env_locale(),
+        };
+
         let i = pseudo_uuid();
+        let revert_at = match block_config.auto_revert_secs {
+            Some(_) => Some(start_revert_thread(i.clone(), tx_update_request)?),
+            None => None,
+        };
         Ok(Time {
             id: i.clone(),
-            format: block_config.format,
+            format,
+            format_index: 0,
+            format_long: block_config.format_long,
+            long_since: None,
+            toggle_button: block_config.toggle_button,
+            auto_revert: block_config.auto_revert_secs,
             time: ButtonWidget::new(config, i.as_str())
                 .with_text("")
                 .with_icon("time"),
             update_interval: block_config.interval,
             on_click: block_config.on_click,
-            timezone: block_config.timezone,
-            locale: block_config.locale,
+            timezones,
+            current_timezone: 0,
+            locale,
+            revert_at,
         })
     }
 }
 
 impl Block for Time {
     fn update(&mut self) -> Result<Option<Update>> {
-        let time = match &self.locale {
-            Some(l) => {
-                let locale: Locale = l
-                    .as_str()
-                    .try_into()
-                    .block_error("time", "invalid locale")?;
-                match self.timezone {
-                    Some(tz) => Utc::now()
-                        .with_timezone(&tz)
-                        .format_localized(&self.format, locale),
-                    None => Local::now().format_localized(&self.format, locale),
-                }
+        let mut next_update = self.update_interval;
+        if let (Some(since), Some(auto_revert)) = (self.long_since, self.auto_revert) {
+            let elapsed = since.elapsed();
+            if elapsed >= auto_revert {
+                self.long_since = None;
+            } else {
+                next_update = next_update.min(auto_revert - elapsed);
             }
-            None => match self.timezone {
-                Some(tz) => Utc::now().with_timezone(&tz).format(&self.format),
-                None => Local::now().format(&self.format),
-            },
+        }
+        let format = match (self.long_since, &self.format_long) {
+            (Some(_), Some(format_long)) => format_long,
+            _ => self.format.get(self.format_index),
         };
-        self.time.set_text(format!("{}", time));
-        Ok(Some(self.update_interval.into()))
+
+        let time = match self.timezones.get(self.current_timezone) {
+            Some(entry) => render_time(
+                &Utc::now().with_timezone(&entry.timezone()),
+                format,
+                self.locale,
+                entry.label(),
+            ),
+            None => render_time(&Local::now(), format, self.locale, None),
+        };
+
+        self.time.set_text(time);
+        Ok(Some(next_update.into()))
     }
 
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
         if let Some(ref name) = e.name {
             if name.as_str() == self.id {
-                if let MouseButton::Left = e.button {
+                let toggles = self.format_long.is_some() && e.button == self.toggle_button;
+                if toggles {
+                    self.long_since = match self.long_since {
+                        Some(_) => None,
+                        None => Some(Instant::now()),
+                    };
+                    if let (Some(revert_at), Some(auto_revert)) =
+                        (&self.revert_at, self.auto_revert)
+                    {
+                        revert_at
+                            .send(self.long_since.map(|since| since + auto_revert))
+                            .block_error("time", "revert thread stopped")?;
+                    }
+                }
+
+                if e.button == MouseButton::Left {
                     if let Some(ref cmd) = self.on_click {
                         spawn_child_async("sh", &["-c", cmd])
                             .block_error("time", "could not spawn child")?;
                     }
                 }
+
+                // Left clicks cycle through the timezones, unless they're configured to toggle
+                // the format, the wheel always does
+                let timezones = self.timezones.len().max(1);
+                self.current_timezone = match e.button {
+                    MouseButton::Left if !toggles => (self.current_timezone + 1) % timezones,
+                    MouseButton::WheelUp => (self.current_timezone + 1) % timezones,
+                    MouseButton::WheelDown => (self.current_timezone + timezones - 1) % timezones,
+                    MouseButton::Middle => 0,
+                    _ => self.current_timezone,
+                };
+
+                self.update()?;
             }
         }
         Ok(())
@@ -152,4 +314,182 @@ impl Block for Time {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format_index = index;
+    }
+}
+
+/// Starts a thread that updates the block when the short format is due again. Updates
+/// requested by clicks aren't scheduled, so the block wouldn't revert before its next update
+/// otherwise. Every deadline sent replaces the previous one, `None` cancels it.
+fn start_revert_thread(
+    id: String,
+    tx_update_request: Sender<Task>,
+) -> Result<Sender<Option<Instant>>> {
+    let (tx, rx): (Sender<Option<Instant>>, Receiver<Option<Instant>>) = unbounded();
+    thread::Builder::new()
+        .name("time".into())
+        .spawn(move || {
+            let mut deadline: Option<Instant> = None;
+            loop {
+                let received = match deadline {
+                    Some(deadline) => {
+                        rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    }
+                    None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match received {
+                    Ok(new_deadline) => deadline = new_deadline,
+                    Err(RecvTimeoutError::Timeout) => {
+                        deadline = None;
+                        let task = Task {
+                            id: id.clone(),
+                            update_time: Instant::now(),
+                        };
+                        if tx_update_request.send(task).is_err() {
+                            return;
+                        }
+                    }
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+        })
+        .block_error("time", "failed to start revert thread")?;
+    Ok(tx)
+}
+
+/// Returns the locale configured by the environment for formatting times, if chrono supports it.
+/// `C` and `POSIX` result in no locale, so that the output of chrono's default formatting is kept.
+fn env_locale() -> Option<Locale> {
+    let value = ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())?;
+    // Strip the encoding and modifier, e.g. "de_DE.UTF-8@euro" becomes "de_DE"
+    let name = value.split(|c| c == '.' || c == '@').next()?;
+    if name == "C" || name == "POSIX" {
+        return None;
+    }
+    name.try_into().ok()
+}
+
+/// Formats the time and replaces the `{timezone}` placeholder with the label, or else the
+/// abbreviation of the timezone, and `{utc_offset}` with e.g. "+02:00".
+fn render_time<T: TimeZone>(
+    time: &DateTime<T>,
+    format: &str,
+    locale: Option<Locale>,
+    label: Option<&str>,
+) -> String
+where
+    T::Offset: Display,
+{
+    let text = match locale {
+        Some(locale) => time.format_localized(format, locale).to_string(),
+        None => time.format(format).to_string(),
+    };
+    let text = if text.contains("{timezone}") {
+        let label = label.map_or_else(|| time.format("%Z").to_string(), str::to_string);
+        text.replace("{timezone}", &label)
+    } else {
+        text
+    };
+    if text.contains("{utc_offset}") {
+        text.replace("{utc_offset}", &time.format("%:z").to_string())
+    } else {
+        text
+    }
+}
+
+/// Makes sure that chrono can render the format, it would panic otherwise.
+fn validate_format(format: &str) -> Result<()> {
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        Err(ConfigurationError(
+            "time".to_string(),
+            (
+                format!("invalid format '{}'", format),
+                "invalid format".to_string(),
+            ),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_time, Time, TimeConfig};
+    use crate::blocks::{Block, ConfigBlock};
+    use crate::config::Config;
+    use crate::input::{I3BarEvent, MouseButton};
+    use chrono::{TimeZone, Utc};
+    use chrono_tz::Europe::Berlin;
+
+    #[test]
+    fn test_click_with_format_long() {
+        let block_config: TimeConfig = toml::from_str(
+            r#"
+            format_long = "%c"
+            timezone = ["Europe/Berlin", "Asia/Tokyo"]
+            "#,
+        )
+        .unwrap();
+        let (tx, _rx) = crossbeam_channel::unbounded();
+        let mut time = Time::new(block_config, Config::default(), tx).unwrap();
+        let click = |time: &mut Time, button| {
+            let event = I3BarEvent {
+                name: Some(time.id().to_string()),
+                instance: None,
+                x: 0,
+                y: 0,
+                button,
+            };
+            time.click(&event).unwrap();
+        };
+
+        // Left clicks cycle through the timezones, right clicks toggle the format
+        click(&mut time, MouseButton::Left);
+        assert_eq!(time.current_timezone, 1);
+        assert!(time.long_since.is_none());
+        click(&mut time, MouseButton::Right);
+        assert_eq!(time.current_timezone, 1);
+        assert!(time.long_since.is_some());
+        click(&mut time, MouseButton::Left);
+        assert_eq!(time.current_timezone, 0);
+        assert!(time.long_since.is_some());
+    }
+
+    #[test]
+    fn test_render_time_across_dst() {
+        let format = "%R {timezone} {utc_offset}";
+        // Berlin springs forward from 02:00 CET to 03:00 CEST on 2021-03-28 at 01:00 UTC
+        let before = Utc
+            .ymd(2021, 3, 28)
+            .and_hms(0, 59, 0)
+            .with_timezone(&Berlin);
+        let after = Utc.ymd(2021, 3, 28).and_hms(1, 0, 0).with_timezone(&Berlin);
+        assert_eq!(render_time(&before, format, None, None), "01:59 CET +01:00");
+        assert_eq!(render_time(&after, format, None, None), "03:00 CEST +02:00");
+
+        // And falls back on 2021-10-31 at 01:00 UTC
+        let before = Utc
+            .ymd(2021, 10, 31)
+            .and_hms(0, 59, 0)
+            .with_timezone(&Berlin);
+        let after = Utc
+            .ymd(2021, 10, 31)
+            .and_hms(1, 0, 0)
+            .with_timezone(&Berlin);
+        assert_eq!(
+            render_time(&before, format, None, None),
+            "02:59 CEST +02:00"
+        );
+        assert_eq!(render_time(&after, format, None, None), "02:00 CET +01:00");
+
+        assert_eq!(
+            render_time(&after, format, None, Some("Berlin")),
+            "02:00 Berlin +01:00"
+        );
+    }
 }
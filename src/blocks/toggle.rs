@@ -1,14 +1,16 @@
 use std::collections::BTreeMap;
 use std::env;
-use std::process::Command;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::thread;
 use std::time::Duration;
 
-use crossbeam_channel::Sender;
+use crossbeam_channel::{unbounded, Sender};
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::Config;
-use crate::de::deserialize_opt_duration;
+use crate::de::{deserialize_duration, deserialize_opt_duration};
 use crate::errors::*;
 use crate::input::I3BarEvent;
 use crate::scheduler::Task;
@@ -21,14 +23,39 @@ pub struct Toggle {
     command_on: String,
     command_off: String,
     command_state: String,
+    state_from: StateFrom,
     icon_on: String,
     icon_off: String,
+    icon_unknown: Option<String>,
+    text_on: Option<String>,
+    text_off: Option<String>,
+    text_unknown: Option<String>,
+    state_on: State,
+    state_off: State,
+    state_unknown: State,
     update_interval: Option<Duration>,
+    state_timeout: Duration,
     toggled: bool,
     id: String,
 }
 
-#[derive(Deserialize, Debug, Default, Clone)]
+/// How the output of `command_state` is interpreted.
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StateFrom {
+    /// Empty output => off. Any output => on.
+    Output,
+    /// Zero exit status => on. Non-zero exit status => off.
+    ExitCode,
+}
+
+impl Default for StateFrom {
+    fn default() -> Self {
+        StateFrom::Output
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct ToggleConfig {
     /// Update interval in seconds
@@ -42,8 +69,20 @@ pub struct ToggleConfig {
     pub command_off: String,
 
     /// Shell Command to determine toggle state. <br/>Empty output => off. Any output => on.
+    #[serde(alias = "state_command")]
     pub command_state: String,
 
+    /// Whether the toggle state is determined from the output or the exit status of `command_state`
+    #[serde(default = "ToggleConfig::default_state_from")]
+    pub state_from: StateFrom,
+
+    /// How long `command_state` may take before the last known state is kept
+    #[serde(
+        default = "ToggleConfig::default_state_timeout",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub state_timeout: Duration,
+
     /// Icon ID when toggled on (default is "toggle_on")
     #[serde(default = "ToggleConfig::default_icon_on")]
     pub icon_on: String,
@@ -52,8 +91,38 @@ pub struct ToggleConfig {
     #[serde(default = "ToggleConfig::default_icon_off")]
     pub icon_off: String,
 
+    /// Icon ID while the state is unknown because `command_state` failed (default is the icon
+    /// of the last known state)
+    #[serde(default = "ToggleConfig::default_icon_unknown")]
+    pub icon_unknown: Option<String>,
+
     /// Text to display in i3bar for this block
     pub text: Option<String>,
+
+    /// Text to display while toggled on (default is `text`)
+    #[serde(default = "ToggleConfig::default_text_on")]
+    pub text_on: Option<String>,
+
+    /// Text to display while toggled off (default is `text`)
+    #[serde(default = "ToggleConfig::default_text_off")]
+    pub text_off: Option<String>,
+
+    /// Text to display while the state is unknown (default is the text of the last known state)
+    #[serde(default = "ToggleConfig::default_text_unknown")]
+    pub text_unknown: Option<String>,
+
+    /// Widget state while toggled on
+    #[serde(default = "ToggleConfig::default_state_on")]
+    pub state_on: State,
+
+    /// Widget state while toggled off
+    #[serde(default = "ToggleConfig::default_state_off")]
+    pub state_off: State,
+
+    /// Widget state while the state is unknown
+    #[serde(default = "ToggleConfig::default_state_unknown")]
+    pub state_unknown: State,
+
     #[serde(default = "ToggleConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
@@ -66,6 +135,42 @@ impl ToggleConfig {
     fn default_icon_off() -> String {
         "toggle_off".to_owned()
     }
+
+    fn default_icon_unknown() -> Option<String> {
+        None
+    }
+
+    fn default_text_on() -> Option<String> {
+        None
+    }
+
+    fn default_text_off() -> Option<String> {
+        None
+    }
+
+    fn default_text_unknown() -> Option<String> {
+        None
+    }
+
+    fn default_state_on() -> State {
+        State::Idle
+    }
+
+    fn default_state_off() -> State {
+        State::Idle
+    }
+
+    fn default_state_unknown() -> State {
+        State::Warning
+    }
+
+    fn default_state_from() -> StateFrom {
+        StateFrom::Output
+    }
+
+    fn default_state_timeout() -> Duration {
+        Duration::from_secs(2)
+    }
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
         None
     }
@@ -80,40 +185,99 @@ impl ConfigBlock for Toggle {
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
         let id = pseudo_uuid();
+        let text = block_config.text;
         Ok(Toggle {
-            text: ButtonWidget::new(config, &id).with_content(block_config.text),
+            text: ButtonWidget::new(config, &id).with_content(text.clone()),
             command_on: block_config.command_on,
             command_off: block_config.command_off,
             command_state: block_config.command_state,
+            state_from: block_config.state_from,
             icon_on: block_config.icon_on,
             icon_off: block_config.icon_off,
+            icon_unknown: block_config.icon_unknown,
+            text_on: block_config.text_on.or_else(|| text.clone()),
+            text_off: block_config.text_off.or(text),
+            text_unknown: block_config.text_unknown,
+            state_on: block_config.state_on,
+            state_off: block_config.state_off,
+            state_unknown: block_config.state_unknown,
             id,
             toggled: false,
             update_interval: block_config.interval,
+            state_timeout: block_config.state_timeout,
         })
     }
 }
 
-impl Block for Toggle {
-    fn update(&mut self) -> Result<Option<Update>> {
-        let output = Command::new(env::var("SHELL").unwrap_or_else(|_| "sh".to_owned()))
+impl Toggle {
+    /// Runs `command_state` to find out whether the toggle is currently on, or `None` if it
+    /// can't be run or doesn't finish within the `state_timeout`.
+    fn query_state(&self) -> Option<bool> {
+        let child = Command::new(env::var("SHELL").unwrap_or_else(|_| "sh".to_owned()))
             .args(&["-c", &self.command_state])
-            .output()
-            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_owned())
-            .unwrap_or_else(|e| e.to_string());
-
-        self.text.set_icon(match output.trim_start() {
-            "" => {
-                self.toggled = false;
-                self.icon_off.as_str()
-            }
-            _ => {
-                self.toggled = true;
-                self.icon_on.as_str()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = child.ok()?;
+
+        let (tx, rx) = unbounded();
+        if let Some(mut stdout) = child.stdout.take() {
+            thread::Builder::new()
+                .name("toggle".into())
+                .spawn(move || {
+                    let mut output = Vec::new();
+                    let _ = stdout.read_to_end(&mut output);
+                    let _ = tx.send(output);
+                })
+                .ok()?;
+        }
+        let output = match rx.recv_timeout(self.state_timeout) {
+            Ok(output) => output,
+            Err(_) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
             }
-        });
+        };
+        let success = child.wait().map(|status| status.success()).unwrap_or(false);
 
-        self.text.set_state(State::Idle);
+        Some(match self.state_from {
+            StateFrom::Output => !String::from_utf8_lossy(&output).trim().is_empty(),
+            StateFrom::ExitCode => success,
+        })
+    }
+
+    /// Re-reads the state. If that fails, the last known state is kept for clicks, but the
+    /// block is shown with the appearance of an unknown state.
+    fn refresh_state(&mut self) {
+        let known = self.query_state();
+        if let Some(toggled) = known {
+            self.toggled = toggled;
+        }
+
+        let (icon, text, state) = if self.toggled {
+            (&self.icon_on, &self.text_on, self.state_on)
+        } else {
+            (&self.icon_off, &self.text_off, self.state_off)
+        };
+        let (icon, text, state) = match known {
+            Some(_) => (icon.clone(), text.clone(), state),
+            None => (
+                self.icon_unknown.clone().unwrap_or_else(|| icon.clone()),
+                self.text_unknown.clone().or_else(|| text.clone()),
+                self.state_unknown,
+            ),
+        };
+        self.text.set_icon(&icon);
+        self.text.set_text(text.unwrap_or_default());
+        self.text.set_state(state);
+    }
+}
+
+impl Block for Toggle {
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.refresh_state();
 
         Ok(self.update_interval.map(|d| d.into()))
     }
@@ -137,13 +301,8 @@ impl Block for Toggle {
                     .block_error("toggle", "failed to run toggle command")?;
 
                 if output.status.success() {
-                    self.text.set_state(State::Idle);
-                    self.toggled = !self.toggled;
-                    self.text.set_icon(if self.toggled {
-                        self.icon_on.as_str()
-                    } else {
-                        self.icon_off.as_str()
-                    })
+                    // Re-read the state rather than assuming the command had the intended effect
+                    self.refresh_state();
                 } else {
                     self.text.set_state(State::Critical);
                 };
@@ -9,11 +9,13 @@ use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::Config;
 use crate::de::deserialize_duration;
 use crate::de::deserialize_local_timestamp;
+use crate::de::deserialize_opt_duration;
 use crate::errors::*;
-use crate::input::I3BarEvent;
+use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::{pseudo_uuid, xdg_config_home};
-use crate::widget::{I3BarWidget, State};
+use crate::subprocess::spawn_child_async;
+use crate::util::{escape_pango_value, pseudo_uuid, xdg_config_home, FormatTemplate, Formats};
+use crate::widget::{I3BarWidget, Markup, State};
 use crate::widgets::button::ButtonWidget;
 use chrono::offset::Local;
 use chrono::DateTime;
@@ -28,6 +30,9 @@ pub struct Watson {
     show_time: bool,
     prev_state: Option<WatsonState>,
     update_interval: Duration,
+    format: Option<FormatTemplate>,
+    max_session: Option<Duration>,
+    markup: Option<Markup>,
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -46,6 +51,18 @@ pub struct WatsonConfig {
     #[serde(default = "WatsonConfig::default_show_time")]
     pub show_time: bool,
 
+    /// Format override for an active frame
+    /// placeholders: {project}, {tags}, {elapsed}
+    #[serde(default = "WatsonConfig::default_format")]
+    pub format: Option<Formats>,
+
+    /// Seconds after which an active frame is shown as a warning
+    #[serde(
+        default = "WatsonConfig::default_max_session",
+        deserialize_with = "deserialize_opt_duration"
+    )]
+    pub max_session: Option<Duration>,
+
     #[serde(default = "WatsonConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
@@ -62,6 +79,12 @@ impl WatsonConfig {
     fn default_show_time() -> bool {
         false
     }
+    fn default_format() -> Option<Formats> {
+        None
+    }
+    fn default_max_session() -> Option<Duration> {
+        None
+    }
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
         None
     }
@@ -75,7 +98,16 @@ impl ConfigBlock for Watson {
         config: Config,
         tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let markup = config.markup;
         let id = pseudo_uuid();
+        let format = match block_config.format {
+            Some(ref format) => Some(
+                FormatTemplate::from_formats(format)
+                    .block_error("watson", "Invalid format specified")?
+                    .with_markup(markup),
+            ),
+            None => None,
+        };
 
         let watson = Watson {
             id: id.clone(),
@@ -84,6 +116,9 @@ impl ConfigBlock for Watson {
             show_time: block_config.show_time,
             update_interval: block_config.interval,
             prev_state: None,
+            format,
+            max_session: block_config.max_session,
+            markup,
         };
 
         // Spin up a thread to watch for changes to the brightness file for the
@@ -147,18 +182,45 @@ impl Block for Watson {
         };
 
         match state {
-            state @ WatsonState::Active { .. } => {
-                self.text.set_state(State::Good);
-                self.text
-                    .set_text(state.format(self.show_time, "started", format_delta_past));
+            WatsonState::Active {
+                ref project,
+                start,
+                ref tags,
+            } => {
+                // The elapsed time is computed from the start of the frame, so watson only
+                // has to be asked again when the state file changes
+                let elapsed = Local::now() - start;
+                let over_max_session = self.max_session.map_or(false, |max| {
+                    elapsed.to_std().map_or(false, |elapsed| elapsed >= max)
+                });
+                self.text.set_state(if over_max_session {
+                    State::Warning
+                } else {
+                    State::Good
+                });
+
+                let text = match self.format {
+                    Some(ref format) => {
+                        let values = map!(
+                            "{project}" => escape_pango_value(project.clone(), self.markup),
+                            "{tags}" => escape_pango_value(tags.join(" "), self.markup),
+                            "{elapsed}" => format_elapsed(&elapsed)
+                        );
+                        format.render_static_str(&values)?
+                    }
+                    None => state.format(self.show_time, "started", format_delta_past),
+                };
+                self.text.set_text(text);
 
                 self.prev_state = Some(state);
-                Ok(if self.show_time {
-                    // regular updates if time is enabled
-                    Some(self.update_interval.into())
-                } else {
-                    None
-                })
+                Ok(
+                    if self.show_time || self.format.is_some() || self.max_session.is_some() {
+                        // regular updates if time is enabled
+                        Some(self.update_interval.into())
+                    } else {
+                        None
+                    },
+                )
             }
             WatsonState::Idle {} => {
                 if let Some(prev_state @ WatsonState::Active { .. }) = &self.prev_state {
@@ -198,8 +260,21 @@ impl Block for Watson {
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
         if let Some(ref name) = e.name {
             if name.as_str() == self.id {
-                self.show_time = !self.show_time;
-                self.update()?;
+                match e.button {
+                    MouseButton::Right => {
+                        // The watcher thread notices the new state of watson
+                        let action = match self.prev_state {
+                            Some(WatsonState::Active { .. }) => "stop",
+                            _ => "restart",
+                        };
+                        spawn_child_async("watson", &[action])
+                            .block_error("watson", "could not spawn watson")?;
+                    }
+                    _ => {
+                        self.show_time = !self.show_time;
+                        self.update()?;
+                    }
+                }
             }
         }
         Ok(())
@@ -212,6 +287,12 @@ impl Block for Watson {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        if let Some(ref mut format) = self.format {
+            format.select(index);
+        }
+    }
 }
 
 fn format_delta_past(delta: &chrono::Duration) -> String {
@@ -230,6 +311,10 @@ fn format_delta_past(delta: &chrono::Duration) -> String {
         .unwrap_or_else(|| "now".into())
 }
 
+fn format_elapsed(delta: &chrono::Duration) -> String {
+    format!("{}:{:02}", delta.num_hours(), delta.num_minutes() % 60)
+}
+
 fn format_delta_after(delta: &chrono::Duration) -> String {
     let spans = &[
         ("week", delta.num_weeks()),
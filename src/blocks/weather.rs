@@ -1,18 +1,20 @@
 use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::process::Command;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Duration as ChronoDuration, Local, TimeZone, Utc};
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
+use crate::cache::{Cache, Cached};
 use crate::config::Config;
 use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::util::{expand_tilde, format_stale, pseudo_uuid, FormatTemplate, Formats};
 use crate::widget::I3BarWidget;
 use crate::widgets::button::ButtonWidget;
 
@@ -25,18 +27,28 @@ const OPENWEATHERMAP_PLACE_ENV: &str = "OPENWEATHERMAP_PLACE";
 pub enum WeatherService {
     OpenWeatherMap {
         #[serde(default = "WeatherService::getenv_openweathermap_api_key")]
-        api_key: Option<String>,
+        api_key: Option<ApiKey>,
         #[serde(default = "WeatherService::getenv_openweathermap_city_id")]
         city_id: Option<String>,
         #[serde(default = "WeatherService::getenv_openweathermap_place")]
         place: Option<String>,
         units: OpenWeatherMapUnits,
     },
+    MetNo {
+        lat: Option<f64>,
+        lon: Option<f64>,
+        /// Name used for the `{location}` placeholder
+        location: Option<String>,
+        #[serde(default = "WeatherService::default_metno_units")]
+        units: OpenWeatherMapUnits,
+    },
 }
 
 impl WeatherService {
-    fn getenv_openweathermap_api_key() -> Option<String> {
-        env::var(OPENWEATHERMAP_API_KEY_ENV).ok()
+    fn getenv_openweathermap_api_key() -> Option<ApiKey> {
+        env::var(OPENWEATHERMAP_API_KEY_ENV)
+            .ok()
+            .map(ApiKey::Literal)
     }
     fn getenv_openweathermap_city_id() -> Option<String> {
         env::var(OPENWEATHERMAP_CITY_ID_ENV).ok()
@@ -44,6 +56,49 @@ impl WeatherService {
     fn getenv_openweathermap_place() -> Option<String> {
         env::var(OPENWEATHERMAP_PLACE_ENV).ok()
     }
+    fn default_metno_units() -> OpenWeatherMapUnits {
+        OpenWeatherMapUnits::Metric
+    }
+}
+
+/// An API key, either given directly or read from an environment variable or a file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ApiKey {
+    Literal(String),
+    Env { env: String },
+    File { file: String },
+}
+
+impl ApiKey {
+    fn resolve(&self) -> Result<String> {
+        match self {
+            ApiKey::Literal(key) => Ok(key.clone()),
+            ApiKey::Env { env: name } => env::var(name).map_err(|_| {
+                ConfigurationError(
+                    "weather".to_string(),
+                    (
+                        format!(
+                            "environment variable '{}' of 'service.api_key' is not set",
+                            name
+                        ),
+                        "missing api_key".to_string(),
+                    ),
+                )
+            }),
+            ApiKey::File { file } => std::fs::read_to_string(expand_tilde(file))
+                .map(|key| key.trim_end_matches(&['\r', '\n'][..]).to_string())
+                .map_err(|e| {
+                    ConfigurationError(
+                        "weather".to_string(),
+                        (
+                            format!("failed to read 'service.api_key' from '{}': {}", file, e),
+                            "missing api_key".to_string(),
+                        ),
+                    )
+                }),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, Deserialize)]
@@ -53,63 +108,351 @@ pub enum OpenWeatherMapUnits {
     Imperial,
 }
 
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub enum WindUnit {
+    #[serde(rename = "m/s")]
+    MetersPerSecond,
+    #[serde(rename = "km/h")]
+    KilometersPerHour,
+    #[serde(rename = "mph")]
+    MilesPerHour,
+    #[serde(rename = "kt")]
+    Knots,
+}
+
+impl WindUnit {
+    fn convert_meters_per_second(self, speed: f64) -> f64 {
+        match self {
+            WindUnit::MetersPerSecond => speed,
+            WindUnit::KilometersPerHour => speed * 3.6,
+            WindUnit::MilesPerHour => speed / 0.447,
+            WindUnit::Knots => speed * 1.944,
+        }
+    }
+
+    fn default_for(units: OpenWeatherMapUnits) -> Self {
+        match units {
+            OpenWeatherMapUnits::Metric => WindUnit::MetersPerSecond,
+            OpenWeatherMapUnits::Imperial => WindUnit::MilesPerHour,
+        }
+    }
+}
+
+/// The wind speed placeholders, `{wind}` and `{wind_speed}` use `unit`. They're empty if the speed is unknown.
+fn wind_keys(speed_ms: Option<f64>, unit: WindUnit) -> HashMap<String, String> {
+    let format_speed = |unit: WindUnit| {
+        speed_ms
+            .map(|speed| format!("{:.1}", unit.convert_meters_per_second(speed)))
+            .unwrap_or_default()
+    };
+    map_to_owned!("{wind}" => format_speed(unit),
+                  "{wind_speed}" => format_speed(unit),
+                  "{wind_ms}" => format_speed(WindUnit::MetersPerSecond),
+                  "{wind_kmh}" => format_speed(WindUnit::KilometersPerHour),
+                  "{wind_mph}" => format_speed(WindUnit::MilesPerHour),
+                  "{wind_kt}" => format_speed(WindUnit::Knots))
+}
+
 pub struct Weather {
     id: String,
     weather: ButtonWidget,
-    format: String,
+    format: FormatTemplate,
     weather_keys: HashMap<String, String>,
+    /// Kept separately, so that a failed forecast doesn't hide the current weather
+    forecast_keys: HashMap<String, String>,
+    forecast_hours: i64,
+    icons: HashMap<String, String>,
     service: WeatherService,
+    wind_unit: Option<WindUnit>,
     update_interval: Duration,
     autolocate: bool,
+    autolocate_interval: Duration,
+    /// The last location found by `autolocate`, and when it was looked up
+    ip_location: Option<IpLocation>,
+    last_autolocate: Option<Instant>,
+    /// The last update failed, `weather_keys` are from an earlier one
+    stale: bool,
+    /// Template for the text while the data is outdated
+    stale_format: String,
+    /// The icon for the current weather
+    icon: String,
+    cache: Cache,
+    /// The weather cached before the last restart, until the first update
+    cached: Option<Cached<CachedWeather>>,
+}
+
+/// The weather keys, forecast keys and icon of the last update.
+type CachedWeather = (HashMap<String, String>, HashMap<String, String>, String);
+
+impl WeatherService {
+    /// Tells apart the caches of blocks for different locations.
+    fn cache_key(&self, autolocate: bool) -> String {
+        match *self {
+            _ if autolocate => "autolocate".to_string(),
+            WeatherService::OpenWeatherMap {
+                ref city_id,
+                ref place,
+                units,
+                ..
+            } => format!(
+                "openweathermap-{}-{:?}",
+                city_id
+                    .as_ref()
+                    .or_else(|| place.as_ref())
+                    .map_or("", |s| s),
+                units
+            ),
+            WeatherService::MetNo {
+                lat, lon, units, ..
+            } => format!(
+                "metno-{}-{}-{:?}",
+                lat.unwrap_or_default(),
+                lon.unwrap_or_default(),
+                units
+            ),
+        }
+    }
 }
 
 fn malformed_json_error() -> Error {
     BlockError("weather".to_string(), "Malformed JSON.".to_string())
 }
 
+const USER_AGENT: &str = concat!(
+    "i3status-rust/",
+    env!("CARGO_PKG_VERSION"),
+    " https://github.com/greshake/i3status-rust"
+);
+
+/// Fetches `url` with curl, returns None if the request fails or the status isn't 2xx.
+fn curl_get(url: &str) -> Option<String> {
+    let raw_output = Command::new("curl")
+        .args(&[
+            "-m",
+            "3",
+            "--silent",
+            "-A",
+            USER_AGENT,
+            url,
+            "--write-out",
+            "%{stderr} %{http_code}",
+        ])
+        .output()
+        .ok()?;
+    let status_code = String::from_utf8(raw_output.stderr).ok()?;
+    if status_code.trim().starts_with('2') {
+        String::from_utf8(raw_output.stdout).ok()
+    } else {
+        None
+    }
+}
+
+/// A step of a forecast, the temperature is in the configured units.
+struct ForecastEntry {
+    time: DateTime<Utc>,
+    temp: f64,
+    weather: String,
+    icon: &'static str,
+}
+
+/// Orders the icons by how bad the weather is, the worst weather of a period is displayed.
+fn icon_severity(icon: &str) -> u8 {
+    match icon {
+        "weather_thunder" => 4,
+        "weather_snow" => 3,
+        "weather_rain" => 2,
+        "weather_clouds" => 1,
+        _ => 0,
+    }
+}
+
+fn owm_icon(weather: &str) -> &'static str {
+    match weather {
+        "Clear" => "weather_sun",
+        "Rain" | "Drizzle" => "weather_rain",
+        "Clouds" | "Fog" | "Mist" => "weather_clouds",
+        "Thunderstorm" => "weather_thunder",
+        "Snow" => "weather_snow",
+        _ => "weather_default",
+    }
+}
+
+/// Computes the forecast placeholders for the next `hours` and for tomorrow. The placeholders
+/// are empty if there are no entries for the period.
+fn forecast_keys(
+    entries: &[ForecastEntry],
+    hours: i64,
+    icons: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    let now = Utc::now();
+    // Include the step that is in progress
+    let start = now - ChronoDuration::hours(1);
+    let end = now + ChronoDuration::hours(hours);
+    let tomorrow = Local::today().succ();
+
+    let periods = [
+        (
+            "forecast",
+            entries
+                .iter()
+                .filter(|entry| entry.time >= start && entry.time <= end)
+                .collect::<Vec<_>>(),
+        ),
+        (
+            "tomorrow",
+            entries
+                .iter()
+                .filter(|entry| entry.time.with_timezone(&Local).date() == tomorrow)
+                .collect::<Vec<_>>(),
+        ),
+    ];
+
+    let mut keys = HashMap::new();
+    for (prefix, period) in periods.iter() {
+        let max = period
+            .iter()
+            .map(|entry| entry.temp)
+            .fold(None, |max, temp| {
+                Some(max.map_or(temp, |max: f64| max.max(temp)))
+            });
+        let min = period
+            .iter()
+            .map(|entry| entry.temp)
+            .fold(None, |min, temp| {
+                Some(min.map_or(temp, |min: f64| min.min(temp)))
+            });
+        let worst = period
+            .iter()
+            .fold(None, |worst: Option<&&ForecastEntry>, entry| match worst {
+                Some(worst) if icon_severity(worst.icon) >= icon_severity(entry.icon) => {
+                    Some(worst)
+                }
+                _ => Some(entry),
+            });
+
+        keys.insert(
+            format!("{{{}_temp_max}}", prefix),
+            max.map(|t| format!("{:.0}", t)).unwrap_or_default(),
+        );
+        keys.insert(
+            format!("{{{}_temp_min}}", prefix),
+            min.map(|t| format!("{:.0}", t)).unwrap_or_default(),
+        );
+        keys.insert(
+            format!("{{{}_weather}}", prefix),
+            worst.map(|e| e.weather.clone()).unwrap_or_default(),
+        );
+        keys.insert(
+            format!("{{{}_icon}}", prefix),
+            worst
+                .and_then(|e| icons.get(e.icon))
+                .map(|icon| icon.trim().to_string())
+                .unwrap_or_default(),
+        );
+    }
+    keys
+}
+
+/// An approximate location determined from the IP address.
+#[derive(Clone, Debug)]
+struct IpLocation {
+    lat: f64,
+    lon: f64,
+    city: Option<String>,
+}
+
+/// Gets the location of the computer from its IP address using ipapi.co, returns None if the
+/// service can't be reached or doesn't know the location (e.g. when rate limited).
+fn find_ip_location() -> Option<IpLocation> {
+    // We don't want the bar to crash if we can't reach the geoip service
+    let geoip_output = curl_get("https://ipapi.co/json/")?;
+    let geoip_json: serde_json::value::Value = serde_json::from_str(&geoip_output).ok()?;
+    Some(IpLocation {
+        lat: geoip_json.pointer("/latitude")?.as_f64()?,
+        lon: geoip_json.pointer("/longitude")?.as_f64()?,
+        city: geoip_json
+            .pointer("/city")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+    })
+}
+
+// Convert wind direction in azimuth degrees to abbreviation names
+fn convert_wind_direction(direction: f64) -> String {
+    match direction.round() as i64 {
+        24..=68 => "NE".to_string(),
+        69..=113 => "E".to_string(),
+        114..=158 => "SE".to_string(),
+        159..=203 => "S".to_string(),
+        204..=248 => "SW".to_string(),
+        249..=293 => "W".to_string(),
+        294..=338 => "NW".to_string(),
+        _ => "N".to_string(),
+    }
+}
+
+/// Computes the Australian Apparent Temperature (AT) in Celsius, using the metric formula
+/// found on Wikipedia.
+fn apparent_temperature(temp_celsius: f64, humidity: f64, wind_speed_ms: f64) -> f64 {
+    let exponent = 17.27 * temp_celsius / (237.7 + temp_celsius);
+    let water_vapor_pressure = humidity * 0.06105 * exponent.exp();
+    temp_celsius + 0.33 * water_vapor_pressure - 0.7 * wind_speed_ms - 4.0
+}
+
+/// Maps a met.no symbol code, e.g. "lightrainshowers_day", onto the textual description used
+/// by OpenWeatherMap and the name of the icon.
+fn metno_symbol(symbol_code: &str) -> (&'static str, &'static str) {
+    let symbol = symbol_code.split('_').next().unwrap_or(symbol_code);
+    if symbol.contains("thunder") {
+        ("Thunderstorm", "weather_thunder")
+    } else if symbol.contains("snow") || symbol.contains("sleet") {
+        ("Snow", "weather_snow")
+    } else if symbol.contains("rain") {
+        ("Rain", "weather_rain")
+    } else if symbol == "clearsky" || symbol == "fair" {
+        ("Clear", "weather_sun")
+    } else if symbol == "fog" {
+        ("Fog", "weather_clouds")
+    } else if symbol.contains("cloudy") {
+        ("Clouds", "weather_clouds")
+    } else {
+        ("Unknown", "weather_default")
+    }
+}
+
 impl Weather {
+    /// Looks up the location if `autolocate` is enabled and the last lookup is older than
+    /// `autolocate_interval`. If the lookup fails the last known location is used.
+    fn autolocation(&mut self) -> Option<IpLocation> {
+        if !self.autolocate {
+            return None;
+        }
+        let due = self
+            .last_autolocate
+            .map_or(true, |last| last.elapsed() >= self.autolocate_interval);
+        if due {
+            if let Some(location) = find_ip_location() {
+                self.ip_location = Some(location);
+                self.last_autolocate = Some(Instant::now());
+            }
+        }
+        self.ip_location.clone()
+    }
+
     fn update_weather(&mut self) -> Result<()> {
+        // Fresh unless this fetch fails, whichever service is used
+        self.stale = false;
+        let ip_location = self.autolocation();
         match self.service {
             WeatherService::OpenWeatherMap {
-                api_key: Some(ref api_key),
+                api_key: Some(ApiKey::Literal(ref api_key)),
                 ref city_id,
                 ref place,
                 ref units,
             } => {
                 // TODO: might be good to allow for different geolocation services to be used, similar to how we have `service` for the weather API
-                let geoip_city = if self.autolocate {
-                    let geoip_output = match Command::new("sh")
-                        .args(&["-c", "curl --max-time 3 --silent 'https://ipapi.co/json/'"])
-                        .output()
-                    {
-                        Ok(raw_output) => String::from_utf8(raw_output.stdout)
-                            .block_error("weather", "Failed to decode")?,
-                        Err(_) => {
-                            // We don't want the bar to crash if we can't reach the geoip service
-                            String::from("")
-                        }
-                    };
-
-                    if geoip_output.is_empty() {
-                        None
-                    } else {
-                        let geoip_json: serde_json::value::Value =
-                            serde_json::from_str(&geoip_output).block_error(
-                                "weather",
-                                "Failed to parse JSON response from geoip service.",
-                            )?;
-
-                        geoip_json
-                            .pointer("/city")
-                            .and_then(|v| v.as_str())
-                            .map(|s| s.to_string())
-                    }
-                } else {
-                    None
-                };
-
-                let location_query = if let Some(city) = geoip_city {
-                    format!("q={}", city)
+                let location_query = if let Some(ref location) = ip_location {
+                    format!("lat={}&lon={}", location.lat, location.lon)
                 } else if city_id.is_some() {
                     format!("id={}", city_id.as_ref().unwrap())
                 } else if place.is_some() {
@@ -124,48 +467,25 @@ impl Weather {
                         ),
                     ));
                 };
-                let output = Command::new("sh")
-                    .args(&[
-                        "-c",
-                        // with these options curl will print http response body to stdout, http status code to stderr
-                        &format!(
-                            r#"curl -m 3 --silent \
-                                "https://api.openweathermap.org/data/2.5/weather?{location_query}&appid={api_key}&units={units}" \
-                                --write-out "%{{stderr}} %{{http_code}}""#,
-                            location_query = location_query,
-                            api_key = api_key,
-                            units = match *units {
-                                OpenWeatherMapUnits::Metric => "metric",
-                                OpenWeatherMapUnits::Imperial => "imperial",
-                            },
-                        ),
-                    ])
-                    .output()
-                    .block_error("weather", "Failed to execute curl.")
-                    .and_then(|raw_output| {
-                        let status_code = String::from_utf8(raw_output.stderr)
-                            .block_error("weather", "Invalid curl output")
-                            .and_then(|out|
-                                out.trim().parse::<i32>()
-                                    .block_error("weather", &format!("Unexpected curl output {}", out))
-                            )?;
-
-                        // All 300-399 and >500 http codes should be considered as temporary error,
-                        // and not result in block error, i.e. leave the output empty.
-                        match status_code {
-                            code if (code >= 300 && code < 400) || code >= 500 => Ok("".to_string()),
-                            _ => String::from_utf8(raw_output.stdout)
-                                .block_error("weather", "Received non-UTF8 characters in response."),
-                        }
-                    })?;
-
-                // Don't error out on empty responses e.g. for when not
-                // connected to the internet.
-                if output.is_empty() {
-                    self.weather.set_icon("weather_default");
-                    self.weather_keys = HashMap::new();
-                    return Ok(());
-                }
+                let output = curl_get(&format!(
+                    "https://api.openweathermap.org/data/2.5/weather?{}&appid={}&units={}",
+                    location_query,
+                    api_key,
+                    match *units {
+                        OpenWeatherMapUnits::Metric => "metric",
+                        OpenWeatherMapUnits::Imperial => "imperial",
+                    },
+                ));
+
+                // Keep the last data when the service can't be reached or rejects the request,
+                // e.g. because of rate limiting
+                let output = match output {
+                    Some(output) => output,
+                    None => {
+                        self.stale = true;
+                        return Ok(());
+                    }
+                };
 
                 let json: serde_json::value::Value = serde_json::from_str(&output)
                     .block_error("weather", "Failed to parse JSON response.")?;
@@ -188,14 +508,14 @@ impl Weather {
                     .and_then(|v| v.as_f64())
                     .ok_or_else(malformed_json_error)?;
 
-                let raw_humidity = json
+                let raw_humidity: Option<f64> = json
                     .pointer("/main/humidity")
-                    .map_or(Some(0.0), |v| v.as_f64()) // provide default value 0.0
+                    .map_or(Some(None), |v| v.as_f64().map(Some)) // provide default value None
                     .ok_or_else(malformed_json_error)?;
 
-                let raw_wind_speed: f64 = json
+                let raw_wind_speed: Option<f64> = json
                     .pointer("/wind/speed")
-                    .map_or(Some(0.0), |v| v.as_f64()) // provide default value 0.0
+                    .map_or(Some(None), |v| v.as_f64().map(Some)) // provide default value None
                     .ok_or_else(malformed_json_error)?; // error when conversion to f64 fails
 
                 let raw_wind_direction: Option<f64> = json
@@ -203,14 +523,18 @@ impl Weather {
                     .map_or(Some(None), |v| v.as_f64().map(Some)) // provide default value None
                     .ok_or_else(malformed_json_error)?; // error when conversion to f64 fails
 
-                let raw_location = json
-                    .pointer("/name")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .ok_or_else(malformed_json_error)?;
+                // Prefer the city found by autolocate, the name of coordinates can be e.g. a
+                // district of the city
+                let raw_location = match ip_location.and_then(|location| location.city) {
+                    Some(city) => city,
+                    None => json
+                        .pointer("/name")
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string())
+                        .ok_or_else(malformed_json_error)?,
+                };
 
-                // Compute the Australian Apparent Temperature (AT),
-                // using the metric formula found on Wikipedia.
+                // Compute the Australian Apparent Temperature (AT).
                 // If using imperial units, we must first convert to metric.
                 let metric = match *units {
                     OpenWeatherMapUnits::Metric => true,
@@ -224,57 +548,202 @@ impl Weather {
                     (raw_temp - 32.0) * 0.556
                 };
 
-                let exponent = 17.27 * temp_celsius / (237.7 + temp_celsius);
-                let water_vapor_pressure = raw_humidity * 0.06105 * exponent.exp();
-
                 let metric_wind_speed = if metric {
-                    raw_wind_speed
+                    raw_wind_speed.unwrap_or(0.0)
                 } else {
                     // convert mph to m/s
-                    raw_wind_speed * 0.447
+                    raw_wind_speed.unwrap_or(0.0) * 0.447
                 };
 
-                let metric_apparent_temp =
-                    temp_celsius + 0.33 * water_vapor_pressure - 0.7 * metric_wind_speed - 4.0;
+                let metric_apparent_temp = apparent_temperature(
+                    temp_celsius,
+                    raw_humidity.unwrap_or(0.0),
+                    metric_wind_speed,
+                );
                 let apparent_temp = if metric {
                     metric_apparent_temp
                 } else {
                     1.8 * metric_apparent_temp + 32.0
                 };
 
-                // Convert wind direction in azimuth degrees to abbreviation names
-                fn convert_wind_direction(direction_opt: Option<f64>) -> String {
-                    match direction_opt {
-                        Some(direction) => match direction.round() as i64 {
-                            24..=68 => "NE".to_string(),
-                            69..=113 => "E".to_string(),
-                            114..=158 => "SE".to_string(),
-                            159..=203 => "S".to_string(),
-                            204..=248 => "SW".to_string(),
-                            249..=293 => "W".to_string(),
-                            294..=338 => "NW".to_string(),
-                            _ => "N".to_string(),
-                        },
-                        None => "-".to_string(),
-                    }
-                }
+                // The apparent temperature can't be computed without humidity and wind speed
+                let apparent_temp_opt = raw_humidity
+                    .and(raw_wind_speed)
+                    .map(|_| format!("{:.0}", apparent_temp));
 
-                self.weather.set_icon(match raw_weather.as_str() {
-                    "Clear" => "weather_sun",
-                    "Rain" | "Drizzle" => "weather_rain",
-                    "Clouds" | "Fog" | "Mist" => "weather_clouds",
-                    "Thunderstorm" => "weather_thunder",
-                    "Snow" => "weather_snow",
-                    _ => "weather_default",
-                });
+                self.icon = owm_icon(raw_weather.as_str()).to_string();
 
                 self.weather_keys = map_to_owned!("{weather}" => raw_weather,
                                   "{temp}" => format!("{:.0}", raw_temp),
-                                  "{humidity}" => format!("{:.0}", raw_humidity),
-                                  "{apparent}" => format!("{:.0}",apparent_temp),
-                                  "{wind}" => format!("{:.1}", raw_wind_speed),
-                                  "{direction}" => convert_wind_direction(raw_wind_direction),
+                                  "{humidity}" => raw_humidity.map(|h| format!("{:.0}", h)).unwrap_or_default(),
+                                  "{apparent}" => apparent_temp_opt.clone().unwrap_or_default(),
+                                  "{apparent_temp}" => apparent_temp_opt.unwrap_or_default(),
+                                  "{direction}" => raw_wind_direction.map(convert_wind_direction).unwrap_or_default(),
+                                  "{wind_direction}" => raw_wind_direction.map(convert_wind_direction).unwrap_or_default(),
                                   "{location}" => raw_location);
+                self.weather_keys.extend(wind_keys(
+                    raw_wind_speed.map(|_| metric_wind_speed),
+                    self.wind_unit
+                        .unwrap_or_else(|| WindUnit::default_for(*units)),
+                ));
+
+                let forecast = curl_get(&format!(
+                    "https://api.openweathermap.org/data/2.5/forecast?{location_query}&appid={api_key}&units={units}",
+                    location_query = location_query,
+                    api_key = api_key,
+                    units = match *units {
+                        OpenWeatherMapUnits::Metric => "metric",
+                        OpenWeatherMapUnits::Imperial => "imperial",
+                    },
+                ))
+                .and_then(|output| serde_json::from_str::<serde_json::value::Value>(&output).ok())
+                .and_then(|json| {
+                    json.pointer("/list")?
+                        .as_array()?
+                        .iter()
+                        .map(|step| {
+                            let weather = step.pointer("/weather/0/main")?.as_str()?;
+                            Some(ForecastEntry {
+                                time: Utc.timestamp(step.pointer("/dt")?.as_i64()?, 0),
+                                temp: step.pointer("/main/temp")?.as_f64()?,
+                                weather: weather.to_string(),
+                                icon: owm_icon(weather),
+                            })
+                        })
+                        .collect::<Option<Vec<_>>>()
+                });
+                if let Some(forecast) = forecast {
+                    self.forecast_keys = forecast_keys(&forecast, self.forecast_hours, &self.icons);
+                }
+                Ok(())
+            }
+            WeatherService::MetNo {
+                lat,
+                lon,
+                ref location,
+                units,
+            } => {
+                let (lat, lon) = match (&ip_location, lat, lon) {
+                    (Some(location), _, _) => (location.lat, location.lon),
+                    (None, Some(lat), Some(lon)) => (lat, lon),
+                    _ => {
+                        return Err(BlockError(
+                            "weather".to_string(),
+                            "Both 'service.lat' and 'service.lon' must be provided.".to_string(),
+                        ))
+                    }
+                };
+                let location = ip_location
+                    .and_then(|location| location.city)
+                    .or_else(|| location.clone())
+                    .unwrap_or_else(|| format!("{:.2}, {:.2}", lat, lon));
+
+                // met.no requires an identifying User-Agent and allows at most 4 decimals
+                let output = curl_get(&format!(
+                    "https://api.met.no/weatherapi/locationforecast/2.0/compact?lat={:.4}&lon={:.4}",
+                    lat, lon
+                ));
+
+                // Keep the last data when the service can't be reached or is rate limiting
+                let output = match output {
+                    Some(output) => output,
+                    None => {
+                        self.stale = true;
+                        return Ok(());
+                    }
+                };
+
+                let json: serde_json::value::Value = serde_json::from_str(&output)
+                    .block_error("weather", "Failed to parse JSON response.")?;
+                let current = json
+                    .pointer("/properties/timeseries/0/data")
+                    .ok_or_else(malformed_json_error)?;
+                let details = current
+                    .pointer("/instant/details")
+                    .ok_or_else(malformed_json_error)?;
+
+                let temp_celsius = details
+                    .pointer("/air_temperature")
+                    .and_then(|v| v.as_f64())
+                    .ok_or_else(malformed_json_error)?;
+                let humidity = details
+                    .pointer("/relative_humidity")
+                    .and_then(|v| v.as_f64());
+                let wind_speed_ms = details.pointer("/wind_speed").and_then(|v| v.as_f64());
+                let wind_direction = details
+                    .pointer("/wind_from_direction")
+                    .and_then(|v| v.as_f64());
+                let symbol_code = current
+                    .pointer("/next_1_hours/summary/symbol_code")
+                    .or_else(|| current.pointer("/next_6_hours/summary/symbol_code"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+
+                let apparent_temp_celsius = apparent_temperature(
+                    temp_celsius,
+                    humidity.unwrap_or(0.0),
+                    wind_speed_ms.unwrap_or(0.0),
+                );
+                // convert to Fahrenheit for imperial units
+                let convert_temp = |celsius: f64| match units {
+                    OpenWeatherMapUnits::Metric => celsius,
+                    OpenWeatherMapUnits::Imperial => 1.8 * celsius + 32.0,
+                };
+                let temp = convert_temp(temp_celsius);
+                let apparent_temp = convert_temp(apparent_temp_celsius);
+                let apparent_temp_opt = humidity
+                    .and(wind_speed_ms)
+                    .map(|_| format!("{:.0}", apparent_temp));
+
+                let (weather, icon) = metno_symbol(symbol_code);
+                self.icon = icon.to_string();
+                self.weather_keys = map_to_owned!("{weather}" => weather.to_string(),
+                                  "{temp}" => format!("{:.0}", temp),
+                                  "{humidity}" => humidity.map(|h| format!("{:.0}", h)).unwrap_or_default(),
+                                  "{apparent}" => apparent_temp_opt.clone().unwrap_or_default(),
+                                  "{apparent_temp}" => apparent_temp_opt.unwrap_or_default(),
+                                  "{direction}" => wind_direction.map(convert_wind_direction).unwrap_or_default(),
+                                  "{wind_direction}" => wind_direction.map(convert_wind_direction).unwrap_or_default(),
+                                  "{location}" => location);
+                self.weather_keys.extend(wind_keys(
+                    wind_speed_ms,
+                    self.wind_unit
+                        .unwrap_or_else(|| WindUnit::default_for(units)),
+                ));
+
+                // The forecast is part of the same response
+                let forecast = json
+                    .pointer("/properties/timeseries")
+                    .and_then(|v| v.as_array())
+                    .map(|timeseries| {
+                        timeseries
+                            .iter()
+                            .filter_map(|step| {
+                                let symbol_code = step
+                                    .pointer("/data/next_1_hours/summary/symbol_code")
+                                    .or_else(|| {
+                                        step.pointer("/data/next_6_hours/summary/symbol_code")
+                                    })?
+                                    .as_str()?;
+                                let (weather, icon) = metno_symbol(symbol_code);
+                                Some(ForecastEntry {
+                                    time: DateTime::parse_from_rfc3339(
+                                        step.pointer("/time")?.as_str()?,
+                                    )
+                                    .ok()?
+                                    .with_timezone(&Utc),
+                                    temp: convert_temp(
+                                        step.pointer("/data/instant/details/air_temperature")?
+                                            .as_f64()?,
+                                    ),
+                                    weather: weather.to_string(),
+                                    icon,
+                                })
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                self.forecast_keys = forecast_keys(&forecast, self.forecast_hours, &self.icons);
                 Ok(())
             }
             WeatherService::OpenWeatherMap { ref api_key, .. } => {
@@ -303,10 +772,22 @@ pub struct WeatherConfig {
     )]
     pub interval: Duration,
     #[serde(default = "WeatherConfig::default_format")]
-    pub format: String,
+    pub format: Formats,
     pub service: WeatherService,
     #[serde(default = "WeatherConfig::default_autolocate")]
     pub autolocate: bool,
+    /// How often the location is looked up by `autolocate`, in seconds
+    #[serde(
+        default = "WeatherConfig::default_autolocate_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub autolocate_interval: Duration,
+    /// Unit of `{wind}`, defaults to m/s for metric and mph for imperial units
+    #[serde(default = "WeatherConfig::default_wind_unit")]
+    pub wind_unit: Option<WindUnit>,
+    /// Number of hours covered by the `{forecast_*}` placeholders
+    #[serde(default = "WeatherConfig::default_forecast_hours")]
+    pub forecast_hours: u32,
     #[serde(default = "WeatherConfig::default_color_overrides")]
     pub color_overrides: Option<BTreeMap<String, String>>,
 }
@@ -316,14 +797,26 @@ impl WeatherConfig {
         Duration::from_secs(600)
     }
 
-    fn default_format() -> String {
-        "{weather} {temp}\u{00b0}".to_string()
+    fn default_format() -> Formats {
+        "{weather} {temp}\u{00b0}".into()
     }
 
     fn default_autolocate() -> bool {
         false
     }
 
+    fn default_wind_unit() -> Option<WindUnit> {
+        None
+    }
+
+    fn default_autolocate_interval() -> Duration {
+        Duration::from_secs(1800)
+    }
+
+    fn default_forecast_hours() -> u32 {
+        12
+    }
+
     fn default_color_overrides() -> Option<BTreeMap<String, String>> {
         None
     }
@@ -337,30 +830,125 @@ impl ConfigBlock for Weather {
         config: Config,
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let markup = config.markup;
         let id = pseudo_uuid();
+        let forecast_hours = i64::from(block_config.forecast_hours);
+
+        // Resolve the API key once, so that a missing key is reported right away
+        let mut service = block_config.service;
+        if let WeatherService::OpenWeatherMap {
+            api_key: Some(ref mut api_key),
+            ..
+        } = service
+        {
+            *api_key = ApiKey::Literal(api_key.resolve()?);
+        }
+
+        if let WeatherService::MetNo { lat, lon, .. } = service {
+            let invalid = |message: &str| {
+                Err(ConfigurationError(
+                    "weather".to_string(),
+                    (message.to_string(), "invalid location".to_string()),
+                ))
+            };
+            match (lat, lon) {
+                (None, None) if block_config.autolocate => {}
+                (Some(lat), Some(lon)) => {
+                    if !(-90.0..=90.0).contains(&lat) {
+                        return invalid("service.lat must be between -90 and 90");
+                    }
+                    if !(-180.0..=180.0).contains(&lon) {
+                        return invalid("service.lon must be between -180 and 180");
+                    }
+                }
+                _ => return invalid("Both 'service.lat' and 'service.lon' must be provided."),
+            }
+        }
+
+        let cache = Cache::new(
+            "weather",
+            &service.cache_key(block_config.autolocate),
+            config.cache_ttl,
+        );
         Ok(Weather {
             id: id.clone(),
+            icon: "weather_default".to_string(),
+            cached: cache.load(),
+            cache,
+            forecast_keys: forecast_keys(&[], forecast_hours, &config.icons),
+            forecast_hours,
+            icons: config.icons.clone(),
+            stale_format: config.stale_format.clone(),
             weather: ButtonWidget::new(config, &id),
-            format: block_config.format,
+            format: FormatTemplate::from_formats(&block_config.format)
+                .block_error("weather", "Invalid format specified")?
+                .with_markup(markup),
             weather_keys: HashMap::new(),
-            service: block_config.service,
+            service,
+            wind_unit: block_config.wind_unit,
             update_interval: block_config.interval,
             autolocate: block_config.autolocate,
+            autolocate_interval: block_config.autolocate_interval,
+            ip_location: None,
+            last_autolocate: None,
+            stale: false,
         })
     }
 }
 
-impl Block for Weather {
-    fn update(&mut self) -> Result<Option<Update>> {
-        self.update_weather()?;
+impl Weather {
+    fn show(&mut self) -> Result<()> {
+        self.weather.set_icon(&self.icon);
         // Display an error/disabled-looking widget when we don't have any
         // weather information, which is likely due to internet connectivity.
         if self.weather_keys.keys().len() == 0 {
             self.weather.set_text("×".to_string());
         } else {
-            let fmt = FormatTemplate::from_string(&self.format)?;
-            self.weather.set_text(fmt.render(&self.weather_keys));
+            let mut keys = self.weather_keys.clone();
+            keys.extend(self.forecast_keys.clone());
+            let text = self.format.render(&keys);
+            if self.stale {
+                self.weather
+                    .set_text(format_stale(&self.stale_format, &text));
+            } else {
+                self.weather.set_text(text);
+            }
         }
+        Ok(())
+    }
+
+    fn restore(&mut self, cached: CachedWeather) {
+        let (weather_keys, forecast_keys, icon) = cached;
+        self.weather_keys = weather_keys;
+        self.forecast_keys = forecast_keys;
+        self.icon = icon;
+    }
+}
+
+impl Block for Weather {
+    fn update(&mut self) -> Result<Option<Update>> {
+        // After a restart, show the cached weather until it's due to be fetched again
+        if let Some(cached) = self.cached.take() {
+            if let Some(remaining) = cached.remaining(self.update_interval) {
+                self.restore(cached.value);
+                self.show()?;
+                return Ok(Some(remaining.into()));
+            }
+            let result = self.update_weather();
+            if result.is_err() || self.weather_keys.is_empty() {
+                // Older, but still better than nothing
+                self.restore(cached.value);
+                self.stale = true;
+            }
+        } else {
+            self.update_weather()?;
+        }
+
+        if !self.stale && !self.weather_keys.is_empty() {
+            self.cache
+                .store(&(&self.weather_keys, &self.forecast_keys, &self.icon));
+        }
+        self.show()?;
         Ok(Some(self.update_interval.into()))
     }
 
@@ -380,4 +968,8 @@ impl Block for Weather {
     fn id(&self) -> &str {
         &self.id
     }
+
+    fn set_format_index(&mut self, index: usize) {
+        self.format.select(index);
+    }
 }
@@ -0,0 +1,123 @@
+//! A small cache on disk, so that slow blocks can show their last result right after a
+//! restart instead of fetching it again at once.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::util::xdg_cache_home;
+
+/// The cached results of one block, stored in `$XDG_CACHE_HOME/i3status-rust`.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    path: PathBuf,
+    /// Older results are discarded, a zero TTL disables the cache
+    ttl: Duration,
+}
+
+/// A value read from the cache.
+#[derive(Debug)]
+pub struct Cached<T> {
+    pub value: T,
+    /// How long ago the value was stored
+    pub age: Duration,
+}
+
+impl<T> Cached<T> {
+    /// How long until the value is as old as `interval`, `None` if it already is.
+    pub fn remaining(&self, interval: Duration) -> Option<Duration> {
+        interval
+            .checked_sub(self.age)
+            .filter(|remaining| *remaining > Duration::default())
+    }
+}
+
+impl Cache {
+    /// The cache of a block, where the `key` tells apart blocks with different settings,
+    /// e.g. the location of a weather block.
+    pub fn new(block: &str, key: &str, ttl: Duration) -> Self {
+        let key: String = key
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let file_name = if key.is_empty() {
+            format!("{}.json", block)
+        } else {
+            format!("{}-{}.json", block, key)
+        };
+        Cache {
+            path: xdg_cache_home().join("i3status-rust").join(file_name),
+            ttl,
+        }
+    }
+
+    /// The stored value, unless it is older than the TTL or can't be read.
+    pub fn load<T: DeserializeOwned>(&self) -> Option<Cached<T>> {
+        if self.ttl == Duration::default() {
+            return None;
+        }
+        let contents = fs::read_to_string(&self.path).ok()?;
+        let mut entry: serde_json::Value = serde_json::from_str(&contents).ok()?;
+        let stored = UNIX_EPOCH + Duration::from_secs(entry.get("stored")?.as_u64()?);
+        // A time in the future means the clock was changed, so the age is unknown
+        let age = SystemTime::now().duration_since(stored).ok()?;
+        if age > self.ttl {
+            return None;
+        }
+        let value = serde_json::from_value(entry.get_mut("value")?.take()).ok()?;
+        Some(Cached { value, age })
+    }
+
+    /// Stores the value. Errors are ignored, as the cache is only a nicety.
+    pub fn store<T: Serialize>(&self, value: &T) {
+        if self.ttl == Duration::default() {
+            return;
+        }
+        let stored = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let entry = json!({ "stored": stored, "value": value });
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).ok();
+        }
+        // Written to a temporary file first, so that no half written file is ever read
+        let temporary = self.path.with_extension("json.tmp");
+        if fs::write(&temporary, entry.to_string()).is_ok() {
+            fs::rename(&temporary, &self.path).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache() {
+        let path = std::env::temp_dir().join(format!("i3rs-cache-test-{}", std::process::id()));
+        let cache = Cache {
+            path: path.clone(),
+            ttl: Duration::from_secs(60),
+        };
+        assert!(cache.load::<Vec<u32>>().is_none());
+        cache.store(&vec![1, 2, 3]);
+        let cached = cache.load::<Vec<u32>>().unwrap();
+        assert_eq!(cached.value, vec![1, 2, 3]);
+        assert!(cached.remaining(Duration::from_secs(30)).is_some());
+
+        // Results older than the TTL are discarded
+        fs::write(&path, r#"{"stored": 0, "value": [1]}"#).unwrap();
+        assert!(cache.load::<Vec<u32>>().is_none());
+        fs::remove_file(&path).ok();
+    }
+}
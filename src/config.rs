@@ -1,23 +1,33 @@
 use std::collections::HashMap as Map;
 use std::marker::PhantomData;
 use std::ops::Deref;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 
+use regex::Regex;
 use serde::de::{Deserialize, Deserializer, Error};
 use serde_derive::Deserialize;
 use toml::value;
 
 use crate::de::*;
+use crate::errors::{OptionExt, ResultExtInternal};
 use crate::input::MouseButton;
 use crate::themes::{Theme, ThemeConfig};
-use crate::util::deserialize_file;
+use crate::util::{deserialize_file, expand_tilde, levenshtein};
+use crate::widget::Markup;
 use crate::{errors, icons};
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
     #[serde(default = "icons::default", deserialize_with = "deserialize_icons")]
     pub icons: Map<String, String>,
+    /// Template for the icons of widgets, e.g. `" {icon} "` to add spacing
+    #[serde(default = "Config::default_icons_format")]
+    pub icons_format: String,
+    /// Template for the text of blocks whose data is outdated, e.g. because there's no network
+    #[serde(default = "Config::default_stale_format")]
+    pub stale_format: String,
     #[serde(deserialize_with = "deserialize_themes")]
     pub theme: Theme,
     /// Direction of scrolling, "natural" or "reverse".
@@ -27,31 +37,85 @@ pub struct Config {
     /// motion which is undesired for sliders. Use "natural" to invert this.
     #[serde(default = "Scrolling::default", rename = "scrolling")]
     pub scrolling: Scrolling,
+    /// Maximum random delay added to the first scheduled update of each block,
+    /// so that blocks with the same interval don't all update at the same time.
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub interval_jitter: Option<Duration>,
+    /// How long slow blocks keep their cached results across restarts, zero to not cache them
+    #[serde(
+        default = "Config::default_cache_ttl",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub cache_ttl: Duration,
     #[serde(rename = "block", deserialize_with = "deserialize_blocks")]
     pub blocks: Vec<(String, value::Value)>,
+    /// The file each block is configured in, and its index among the blocks of that file
+    #[serde(skip)]
+    pub block_sources: Vec<(PathBuf, usize)>,
+    /// The `markup` of the block that is being created, which its formats have to know
+    #[serde(skip)]
+    pub markup: Option<Markup>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             icons: icons::default(),
+            icons_format: Config::default_icons_format(),
+            stale_format: Config::default_stale_format(),
             theme: Theme::default(),
             scrolling: Scrolling::default(),
+            interval_jitter: None,
+            cache_ttl: Config::default_cache_ttl(),
             blocks: Vec::new(),
+            block_sources: Vec::new(),
+            markup: None,
         }
     }
 }
 
+impl Config {
+    fn default_icons_format() -> String {
+        "{icon}".to_owned()
+    }
+
+    fn default_stale_format() -> String {
+        "{text} (stale)".to_owned()
+    }
+
+    fn default_cache_ttl() -> Duration {
+        // Caching is opt-in, as the cached results are shown instead of fresh ones
+        Duration::default()
+    }
+
+    /// The icon with the given name formatted with `icons_format`, empty icons are left as is.
+    pub fn icon(&self, name: &str) -> Option<String> {
+        self.icons.get(name).map(|icon| {
+            if icon.is_empty() {
+                icon.clone()
+            } else {
+                self.icons_format.replace("{icon}", icon)
+            }
+        })
+    }
+}
+
 impl From<LegacyConfig> for Config {
     fn from(legacy_config: LegacyConfig) -> Self {
         Config {
             icons: legacy_config.icons,
+            icons_format: legacy_config.icons_format,
+            stale_format: Config::default_stale_format(),
             theme: legacy_config
                 .theme
                 .and_then(|s| Theme::from_name(s.as_str()))
                 .unwrap_or_default(),
             scrolling: legacy_config.scrolling,
+            interval_jitter: legacy_config.interval_jitter,
+            cache_ttl: Config::default_cache_ttl(),
             blocks: legacy_config.blocks,
+            block_sources: Vec::new(),
+            markup: None,
         }
     }
 }
@@ -60,6 +124,8 @@ impl From<LegacyConfig> for Config {
 pub struct LegacyConfig {
     #[serde(default = "icons::default", deserialize_with = "deserialize_icons")]
     pub icons: Map<String, String>,
+    #[serde(default = "Config::default_icons_format")]
+    pub icons_format: String,
     #[serde(default)]
     pub theme: Option<String>,
     /// Direction of scrolling, "natural" or "reverse".
@@ -69,6 +135,8 @@ pub struct LegacyConfig {
     /// motion which is undesired for sliders. Use "natural" to invert this.
     #[serde(default = "Scrolling::default", rename = "scrolling")]
     pub scrolling: Scrolling,
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub interval_jitter: Option<Duration>,
     #[serde(rename = "block", deserialize_with = "deserialize_blocks")]
     pub blocks: Vec<(String, value::Value)>,
 }
@@ -77,8 +145,10 @@ impl Default for LegacyConfig {
     fn default() -> Self {
         LegacyConfig {
             icons: icons::default(),
+            icons_format: Config::default_icons_format(),
             theme: None,
             scrolling: Scrolling::default(),
+            interval_jitter: None,
             blocks: Vec::new(),
         }
     }
@@ -152,18 +222,161 @@ where
         .ok_or_else(|| D::Error::custom("Unrecognized theme name."))
 }
 
+/// Reads a config file and the files it includes. The blocks of the included files come
+/// before the file's own blocks, in the order of the includes.
+fn read_config_file(
+    path: &Path,
+    including: &mut Vec<PathBuf>,
+    block_sources: &mut Vec<(PathBuf, usize)>,
+) -> errors::Result<value::Table> {
+    let path_name = path
+        .to_str()
+        .internal_error("config", "invalid path of config file")?;
+    let mut table: value::Table = deserialize_file(path_name)?;
+    let canonical_path = path
+        .canonicalize()
+        .configuration_error(&format!("failed to find config file {}", path_name))?;
+    if including.contains(&canonical_path) {
+        return Err(errors::ConfigurationError(
+            format!("{} includes itself", path_name),
+            (
+                format!(
+                    "include cycle: {} -> {}",
+                    including
+                        .iter()
+                        .map(|path| path.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(" -> "),
+                    canonical_path.display()
+                ),
+                "cyclic include".to_string(),
+            ),
+        ));
+    }
+
+    let invalid_include = || {
+        errors::ConfigurationError(
+            format!("invalid include in {}", path_name),
+            (
+                "include must be a list of paths".to_string(),
+                "invalid include".to_string(),
+            ),
+        )
+    };
+    let includes = match table.remove("include") {
+        Some(value::Value::Array(includes)) => includes,
+        Some(include @ value::Value::String(_)) => vec![include],
+        Some(_) => return Err(invalid_include()),
+        None => Vec::new(),
+    };
+
+    including.push(canonical_path);
+    let mut blocks = Vec::new();
+    for include in includes {
+        let include = include.as_str().ok_or_else(invalid_include)?;
+        // Relative paths are relative to the including file
+        let include_path = path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(expand_tilde(include));
+        let mut included = read_config_file(&include_path, including, block_sources)?;
+        if let Some(key) = included.keys().find(|key| *key != "block") {
+            return Err(errors::ConfigurationError(
+                format!("invalid option in {}", include_path.display()),
+                (
+                    format!("included files may only contain blocks, not {}", key),
+                    "invalid included file".to_string(),
+                ),
+            ));
+        }
+        if let Some(value::Value::Array(included_blocks)) = included.remove("block") {
+            blocks.extend(included_blocks);
+        }
+    }
+    including.pop();
+
+    if let Some(value::Value::Array(own_blocks)) = table.remove("block") {
+        // Blocks without a name are left out of the config
+        block_sources.extend(
+            own_blocks
+                .iter()
+                .enumerate()
+                .filter(|(_, block)| block.get("block").and_then(|name| name.as_str()).is_some())
+                .map(|(index, _)| (path.to_path_buf(), index)),
+        );
+        blocks.extend(own_blocks);
+    }
+    table.insert("block".to_string(), value::Value::Array(blocks));
+    Ok(table)
+}
+
 // this function may belong somewhere else...
 pub fn load_config(config_path: &Path) -> errors::Result<Config> {
-    let config: errors::Result<Config> = deserialize_file(config_path.to_str().unwrap());
-    config.or_else(|_| {
-        let legacy_config: errors::Result<LegacyConfig> =
-            deserialize_file(config_path.to_str().unwrap());
+    let mut block_sources = Vec::new();
+    let table = read_config_file(config_path, &mut Vec::new(), &mut block_sources)?;
+    let config: errors::Result<Config> = value::Value::Table(table.clone())
+        .try_into()
+        .configuration_error("failed to parse TOML from file contents");
+    let mut config = config.or_else(|_| {
+        let legacy_config: errors::Result<LegacyConfig> = value::Value::Table(table)
+            .try_into()
+            .configuration_error("failed to parse TOML from file contents");
         legacy_config.map(|legacy| legacy.into())
-    })
+    })?;
+    config.block_sources = block_sources;
+    Ok(config)
+}
+
+/// The unknown field and the expected fields of an error like
+/// "unknown field `intervall`, expected one of `interval`, `format`" reported by serde.
+pub fn unknown_field(message: &str) -> Option<(String, Vec<String>)> {
+    let re = Regex::new(r"unknown field `([^`]*)`, expected (.*)").unwrap();
+    let captures = re.captures(message)?;
+    let expected = Regex::new(r"`([^`]*)`")
+        .unwrap()
+        .captures_iter(&captures[2])
+        .map(|field| field[1].to_string())
+        .collect();
+    Some((captures[1].to_string(), expected))
 }
+
+/// The candidate closest to a mistyped name, if it is close enough to be a likely typo.
+pub fn closest_match<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let max_distance = std::cmp::max(2, name.chars().count() / 3);
+    candidates
+        .iter()
+        .map(|candidate| (levenshtein(name, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The line of the config file where the block with the given index, or a field of it, is
+/// configured. Only blocks written as `[[block]]` tables can be found.
+pub fn block_line(contents: &str, index: usize, field: Option<&str>) -> Option<usize> {
+    let lines: Vec<&str> = contents.lines().map(str::trim).collect();
+    let start = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.starts_with("[[block]]"))
+        .nth(index)?
+        .0;
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| line.starts_with("[[block]]"))
+        .map_or(lines.len(), |position| start + 1 + position);
+    let field_line = field.and_then(|field| {
+        lines[start..end].iter().position(|line| {
+            line.strip_prefix(field)
+                .map_or(false, |rest| rest.trim_start().starts_with('='))
+        })
+    });
+    Some(start + field_line.unwrap_or(0) + 1)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::config::load_config;
+    use crate::config::{block_line, closest_match, load_config};
     use assert_fs::prelude::{FileWriteStr, PathChild};
     use assert_fs::TempDir;
 
@@ -209,4 +422,55 @@ mod tests {
         let config = load_config(config_file_path.path());
         config.unwrap();
     }
+
+    #[test]
+    fn test_block_line() {
+        let contents = concat!(
+            "theme = \"plain\"\n",
+            "[[block]]\n",
+            "block = \"time\"\n",
+            "\n",
+            "[[block]]\n",
+            "block = \"load\"\n",
+            "intervall = 1\n",
+        );
+        assert_eq!(block_line(contents, 1, Some("intervall")), Some(7));
+        assert_eq!(block_line(contents, 0, Some("intervall")), Some(2));
+        assert_eq!(block_line(contents, 2, None), None);
+    }
+
+    #[test]
+    fn test_closest_match() {
+        let fields = &["interval", "format", "color_overrides"];
+        assert_eq!(closest_match("intervall", fields), Some("interval"));
+        assert_eq!(closest_match("fromat", fields), Some("format"));
+        assert_eq!(closest_match("zzzzz", fields), None);
+    }
+
+    #[test]
+    fn test_load_config_include() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_file_path = temp_dir.child("status.toml");
+        config_file_path
+            .write_str("include = [\"blocks/load.toml\"]\n[[block]]\nblock = \"time\"\n")
+            .unwrap();
+        temp_dir
+            .child("blocks/load.toml")
+            .write_str("[[block]]\nblock = \"load\"\n")
+            .unwrap();
+        let config = load_config(config_file_path.path()).unwrap();
+        let names: Vec<&str> = config
+            .blocks
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["load", "time"]);
+        assert_eq!(config.block_sources[0].1, 0);
+
+        temp_dir
+            .child("blocks/load.toml")
+            .write_str("include = [\"../status.toml\"]\n")
+            .unwrap();
+        assert!(load_config(config_file_path.path()).is_err());
+    }
 }
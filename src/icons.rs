@@ -59,9 +59,12 @@ lazy_static! {
         "toggle_on" => " ON ",
         "update" => " UPD ",
         "uptime" => " UP ",
+        "volume_bluetooth" => " BT ",
         "volume_empty" => " VOL ",
         "volume_full" => " VOL ",
         "volume_half" => " VOL ",
+        "volume_hdmi" => " HDMI ",
+        "volume_headphones" => " HEAD ",
         "volume_muted" => " VOL MUTED ",
         "microphone_empty" => " MIC ",
         "microphone_full" => " MIC ",
@@ -84,6 +87,7 @@ lazy_static! {
         "backlight_partial1" => " \u{1f314} ",
         "backlight_partial2" => " \u{1f313} ",
         "backlight_partial3" => " \u{1f312} ",
+        "bat" => " \u{f240} ",
         "bat_charging" => " \u{f1e6} ",
         "bat_discharging" => " \u{f242} ",
         "bat_empty" => " \u{f244} ",
@@ -135,9 +139,11 @@ lazy_static! {
         "unknown" => " \u{f128} ",
         "update" => " \u{f062} ", // Same as time symbol.
         "uptime" => " \u{f017} ",
+        "volume_bluetooth" => " \u{f294} ",
         "volume_empty" => " \u{f026} ",
         "volume_full" => " \u{f028} ",
         "volume_half" => " \u{f027} ",
+        "volume_headphones" => " \u{f025} ",
         "volume_muted" => " \u{f026} \u{f00d} ",
         "microphone_empty" => " \u{f130} ",
         "microphone_full" => " \u{f130} ",
@@ -160,6 +166,7 @@ lazy_static! {
         "backlight_partial1" => " \u{1f314} ",
         "backlight_partial2" => " \u{1f313} ",
         "backlight_partial3" => " \u{1f312} ",
+        "bat" => " \u{f240} ",
         "bat_charging" => " \u{f1e6} ",
         "bat_discharging" => " \u{f242} ",
         "bat_empty" => " \u{f244} ",
@@ -210,9 +217,12 @@ lazy_static! {
         "unknown" => " \u{f128} ",
         "update" => " \u{f062} ",
         "uptime" => " \u{f2f2} ",
+        "volume_bluetooth" => " \u{f294} ",
         "volume_empty" => " \u{f026} ",
         "volume_full" => " \u{f028} ",
         "volume_half" => " \u{f027} ",
+        "volume_hdmi" => " \u{f26c} ",
+        "volume_headphones" => " \u{f025} ",
         "volume_muted" => " \u{f6a9} ",
         "microphone_full" => " \u{f130} ",
         "microphone_half" => " \u{f130} ",
@@ -229,6 +239,7 @@ lazy_static! {
 
     pub static ref MATERIAL: Map<String, String> = map_to_owned! {
         "" => "",
+        "bat" => " \u{e1a5} ",
         "bat_charging" => " \u{e1a3} ",
         "bat_discharging" => " \u{e19c} ",
         "bat_empty" => " \u{e19c} ",
@@ -270,9 +281,12 @@ lazy_static! {
         "toggle_on" => " \u{e837} ",
         "update" => " \u{e8d7} ",
         "uptime" => " \u{e192} ", // Same as time symbol.
+        "volume_bluetooth" => " \u{e60f} ",
         "volume_empty" => " \u{e04e} ",
         "volume_full" => " \u{e050} ",
         "volume_half" => " \u{e04d} ",
+        "volume_hdmi" => " \u{e333} ",
+        "volume_headphones" => " \u{e310} ",
         "volume_muted" => " \u{e04e} \u{e04f} ",
         "xrandr" => " \u{e31e} "
     };
@@ -8,7 +8,8 @@ use crossbeam_channel::Sender;
 use serde::{de, Deserializer};
 use serde_derive::Deserialize;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
 pub enum MouseButton {
     Left,
     Middle,
@@ -20,6 +21,28 @@ pub enum MouseButton {
     Unknown,
 }
 
+impl MouseButton {
+    /// The number of the button in the i3bar protocol, 0 if it's unknown.
+    pub fn number(self) -> u64 {
+        match self {
+            MouseButton::Left => 1,
+            MouseButton::Middle => 2,
+            MouseButton::Right => 3,
+            MouseButton::WheelUp => 4,
+            MouseButton::WheelDown => 5,
+            MouseButton::Forward => 9,
+            MouseButton::Back => 8,
+            MouseButton::Unknown => 0,
+        }
+    }
+}
+
+impl Default for MouseButton {
+    fn default() -> Self {
+        MouseButton::Left
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct I3BarEvent {
     pub name: Option<String>,
@@ -8,6 +8,7 @@ mod de;
 #[macro_use]
 mod util;
 pub mod blocks;
+mod cache;
 mod config;
 mod errors;
 mod icons;
@@ -22,20 +23,21 @@ mod widgets;
 #[cfg(feature = "profiling")]
 use cpuprofiler::PROFILER;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::DerefMut;
+use std::path::PathBuf;
 use std::time::Duration;
 
 use clap::{crate_authors, crate_description, App, Arg, ArgMatches};
 use crossbeam_channel::{select, Receiver, Sender};
 
-use crate::blocks::create_block;
 use crate::blocks::Block;
-use crate::config::{load_config, Config};
+use crate::blocks::{create_block, validate_block};
+use crate::config::{block_line, load_config, unknown_field, Config};
 use crate::errors::*;
 use crate::input::{process_events, I3BarEvent};
 use crate::scheduler::{Task, UpdateScheduler};
-use crate::signals::process_signals;
+use crate::signals::{process_signals, SIGPAUSE, SIGRESUME};
 use crate::widget::{I3BarWidget, State};
 use crate::widgets::text::TextWidget;
 
@@ -73,6 +75,14 @@ fn main() {
                 .long("never-pause")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("validate")
+                .help(
+                    "Check that the config file is valid and all blocks can be created, then exit",
+                )
+                .long("validate")
+                .takes_value(false),
+        )
         .arg(
             Arg::with_name("one-shot")
                 .help("Print blocks once and exit")
@@ -101,6 +111,17 @@ fn main() {
     let matches = builder.get_matches();
     let exit_on_error = matches.is_present("exit-on-error");
 
+    if matches.is_present("validate") {
+        match validate(&matches) {
+            Ok(()) => println!("Configuration is valid"),
+            Err(error) => {
+                eprintln!("{:?}", error);
+                ::std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     // Run and match for potential error
     if let Err(error) = run(&matches) {
         if exit_on_error {
@@ -125,24 +146,88 @@ fn main() {
     }
 }
 
+fn config_path(matches: &ArgMatches) -> PathBuf {
+    match matches.value_of("config") {
+        Some(config_path) => PathBuf::from(config_path),
+        None => util::xdg_config_home().join("i3status-rust/config.toml"),
+    }
+}
+
+/// Checks the configurations of the blocks without creating them, so that no block starts
+/// threads or runs commands.
+fn validate(matches: &ArgMatches) -> Result<()> {
+    let config = load_config(&config_path(matches))?;
+    for (index, (block_name, block_config)) in config.blocks.iter().enumerate() {
+        validate_block(block_name, block_config.clone())
+            .map_err(|error| locate_config_error(error, config.block_sources.get(index)))?;
+    }
+    check_block_ids(
+        config
+            .blocks
+            .iter()
+            .filter_map(|(_, block_config)| block_config.get("block_id")?.as_str()),
+    )
+}
+
+fn create_blocks(
+    config: &Config,
+    tx_update_requests: &Sender<Task>,
+) -> Result<Vec<Box<dyn Block>>> {
+    let mut blocks: Vec<Box<dyn Block>> = Vec::new();
+    for (index, &(ref block_name, ref block_config)) in config.blocks.iter().enumerate() {
+        blocks.push(
+            create_block(
+                block_name,
+                block_config.clone(),
+                config.clone(),
+                tx_update_requests.clone(),
+            )
+            .map_err(|error| locate_config_error(error, config.block_sources.get(index)))?,
+        );
+    }
+
+    check_block_ids(
+        blocks
+            .iter()
+            .filter_map(|block| block.widget_options()?.block_id.as_deref()),
+    )?;
+
+    Ok(blocks)
+}
+
+/// Clicks are routed by the block_id, so it has to be unique.
+fn check_block_ids<'a>(block_ids: impl Iterator<Item = &'a str>) -> Result<()> {
+    let mut seen = HashSet::new();
+    for block_id in block_ids {
+        if !seen.insert(block_id) {
+            return Err(ConfigurationError(
+                "block_id".to_string(),
+                (
+                    format!("block_id '{}' is used by more than one block", block_id),
+                    "duplicate block_id".to_string(),
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn run(matches: &ArgMatches) -> Result<()> {
-    // Now we can start to run the i3bar protocol
-    let initialise = if matches.is_present("never-pause") {
-        format!(
-            "\"version\": 1, \"click_events\": true, \"stop_signal\": {}",
-            nix::sys::signal::Signal::SIGCONT as i8
-        )
-    } else {
-        "\"version\": 1, \"click_events\": true".to_string()
-    };
+    // Now we can start to run the i3bar protocol. Instead of the default SIGSTOP, which would
+    // freeze the background threads of the blocks, i3bar pauses the updates with SIGPAUSE.
+    let initialise = format!(
+        "\"version\": 1, \"click_events\": true, \"stop_signal\": {}, \"cont_signal\": {}",
+        if matches.is_present("never-pause") {
+            SIGRESUME
+        } else {
+            SIGPAUSE
+        },
+        SIGRESUME
+    );
     print!("{{{}}}\n[", initialise);
 
     // Read & parse the config file
-    let config_path = match matches.value_of("config") {
-        Some(config_path) => std::path::PathBuf::from(config_path),
-        None => util::xdg_config_home().join("i3status-rust/config.toml"),
-    };
-    let config = load_config(&config_path)?;
+    let config = load_config(&config_path(matches))?;
 
     // Update request channel
     let (tx_update_requests, rx_update_requests): (Sender<Task>, Receiver<Task>) =
@@ -160,15 +245,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
     }
 
     // Initialize the blocks
-    let mut blocks: Vec<Box<dyn Block>> = Vec::new();
-    for &(ref block_name, ref block_config) in &config.blocks {
-        blocks.push(create_block(
-            block_name,
-            block_config.clone(),
-            config.clone(),
-            tx_update_requests.clone(),
-        )?);
-    }
+    let mut blocks = create_blocks(&config, &tx_update_requests)?;
 
     // We save the order of the blocks here,
     // because they will be passed to an unordered HashMap
@@ -177,7 +254,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
         .map(|x| String::from(x.id()))
         .collect::<Vec<_>>();
 
-    let mut scheduler = UpdateScheduler::new(&blocks);
+    let mut scheduler = UpdateScheduler::new(&blocks, config.interval_jitter);
 
     let mut block_map: HashMap<String, &mut dyn Block> = HashMap::new();
 
@@ -198,6 +275,11 @@ fn run(matches: &ArgMatches) -> Result<()> {
     // Fires immediately for first updates
     let mut ttnu = crossbeam_channel::after(Duration::from_millis(0));
 
+    // While paused, blocks aren't updated and the bar isn't printed. Background threads keep
+    // running, and the blocks which requested an update meanwhile are updated on resume.
+    let mut paused = false;
+    let mut pending_updates = HashSet::new();
+
     let one_shot = matches.is_present("one-shot");
     loop {
         // We use the message passing concept of channel selection
@@ -208,15 +290,22 @@ fn run(matches: &ArgMatches) -> Result<()> {
                     for block in block_map.values_mut() {
                         block.click(&event)?;
                     }
-                    util::print_blocks(&order, &block_map, &config)?;
+                    if !paused {
+                        util::print_blocks(&order, &block_map, &config)?;
+                    }
             },
             // Receive async update requests
             recv(rx_update_requests) -> request => if let Ok(req) = request {
-                // Process immediately and forget
-                block_map
+                if paused {
+                    pending_updates.insert(req.id);
+                    continue;
+                }
+                // Process immediately, the block keeps its schedule if it has one
+                let update = block_map
                     .get_mut(&req.id)
                     .internal_error("scheduler", "could not get required block")?
                     .update()?;
+                scheduler.schedule_requested(&req.id, update);
                 util::print_blocks(&order, &block_map, &config)?;
             },
             // Receive update timer events
@@ -235,6 +324,19 @@ fn run(matches: &ArgMatches) -> Result<()> {
                         }
                         util::print_blocks(&order, &block_map, &config)?;
                     },
+                    SIGPAUSE => paused = true,
+                    SIGRESUME => if paused {
+                        paused = false;
+                        for id in pending_updates.drain() {
+                            let update = block_map
+                                .get_mut(&id)
+                                .internal_error("scheduler", "could not get required block")?
+                                .update()?;
+                            scheduler.schedule_requested(&id, update);
+                        }
+                        // The updates that became due meanwhile follow right away
+                        util::print_blocks(&order, &block_map, &config)?;
+                    },
                     signal_hook::SIGUSR2 => {
                         //USR2 signal that should reload the config
                         //TODO not implemented
@@ -252,7 +354,9 @@ fn run(matches: &ArgMatches) -> Result<()> {
         }
 
         // Set the time-to-next-update timer
-        if let Some(time) = scheduler.time_to_next_update() {
+        if paused {
+            ttnu = crossbeam_channel::never();
+        } else if let Some(time) = scheduler.time_to_next_update() {
             ttnu = crossbeam_channel::after(time)
         }
         if one_shot {
@@ -286,6 +390,24 @@ fn profile(iterations: i32, name: &str, block: &mut dyn Block) {
     PROFILER.lock().unwrap().stop().unwrap();
 }
 
+/// Adds the location of the block in the config files to a configuration error of the block.
+fn locate_config_error(error: Error, source: Option<&(PathBuf, usize)>) -> Error {
+    match (error, source) {
+        (ConfigurationError(message, (cause, debug)), Some((config_path, index))) => {
+            let field = unknown_field(&cause).map(|(field, _)| field);
+            let line = std::fs::read_to_string(config_path)
+                .ok()
+                .and_then(|contents| block_line(&contents, *index, field.as_deref()));
+            let message = match line {
+                Some(line) => format!("{}:{}: {}", config_path.display(), line, message),
+                None => message,
+            };
+            ConfigurationError(message, (cause, debug))
+        }
+        (error, _) => error,
+    }
+}
+
 #[cfg(feature = "profiling")]
 fn profile_config(name: &str, runs: &str, config: &Config, update: Sender<Task>) -> Result<()> {
     let profile_runs = runs
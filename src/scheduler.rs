@@ -42,11 +42,14 @@ impl cmp::Ord for Task {
 
 pub struct UpdateScheduler {
     schedule: BinaryHeap<Task>,
+    /// Offsets that are added once to the first interval of each block
+    jitter: HashMap<String, Duration>,
 }
 
 impl UpdateScheduler {
-    pub fn new(blocks: &[Box<dyn Block>]) -> UpdateScheduler {
+    pub fn new(blocks: &[Box<dyn Block>], max_jitter: Option<Duration>) -> UpdateScheduler {
         let mut schedule = BinaryHeap::new();
+        let mut jitter = HashMap::new();
 
         let now = Instant::now();
         for block in blocks.iter() {
@@ -54,9 +57,12 @@ impl UpdateScheduler {
                 id: String::from(block.id()),
                 update_time: now,
             });
+            if let Some(max_jitter) = max_jitter {
+                jitter.insert(String::from(block.id()), random_duration(max_jitter));
+            }
         }
 
-        UpdateScheduler { schedule }
+        UpdateScheduler { schedule, jitter }
     }
 
     pub fn time_to_next_update(&self) -> Option<Duration> {
@@ -74,6 +80,20 @@ impl UpdateScheduler {
         }
     }
 
+    /// Schedules the next update of a block that was updated on request, unless the block is
+    /// still scheduled. This way a block that stopped its scheduled updates, e.g. while it's
+    /// idle, can start them again.
+    pub fn schedule_requested(&mut self, id: &str, update: Option<Update>) {
+        if let Some(Update::Every(interval)) = update {
+            if !self.schedule.iter().any(|task| task.id == id) {
+                self.schedule.push(Task {
+                    id: id.to_string(),
+                    update_time: Instant::now() + interval,
+                });
+            }
+        }
+    }
+
     pub fn do_scheduled_updates(
         &mut self,
         block_map: &mut HashMap<String, &mut dyn Block>,
@@ -113,10 +133,13 @@ impl UpdateScheduler {
                 .update()?
             {
                 match dur {
-                    Update::Every(d) => self.schedule.push(Task {
-                        id: task.id,
-                        update_time: now + d,
-                    }),
+                    Update::Every(d) => {
+                        let offset = self.jitter.remove(&task.id).unwrap_or_default();
+                        self.schedule.push(Task {
+                            id: task.id,
+                            update_time: now + d + offset,
+                        })
+                    }
                     Update::Once => {} // do not schedule this task again
                 }
             }
@@ -125,3 +148,12 @@ impl UpdateScheduler {
         Ok(())
     }
 }
+
+/// Returns a random duration between zero and `max`.
+fn random_duration(max: Duration) -> Duration {
+    let mut bytes = [0u8; 4];
+    if getrandom::getrandom(&mut bytes).is_err() {
+        return Duration::default();
+    }
+    max.mul_f64(f64::from(u32::from_ne_bytes(bytes)) / f64::from(u32::MAX))
+}
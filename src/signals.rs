@@ -2,6 +2,11 @@ use crate::errors::*;
 use crossbeam_channel::Sender;
 use std::thread;
 
+/// Pauses the updates of the bar, i3bar sends it when the bar is hidden
+pub const SIGPAUSE: i32 = nix::sys::signal::Signal::SIGTSTP as i32;
+/// Resumes the updates of the bar
+pub const SIGRESUME: i32 = nix::sys::signal::Signal::SIGCONT as i32;
+
 /// Starts a thread that listens for provided signals and sends these on the provided channel
 pub fn process_signals(sender: Sender<i32>) {
     thread::Builder::new()
@@ -14,9 +19,11 @@ pub fn process_signals(sender: Sender<i32>) {
                 sigmax = __libc_current_sigrtmax();
             }
             loop {
-                let mut signals = (sigmin..sigmax).collect::<Vec<_>>();
+                let mut signals = (sigmin..=sigmax).collect::<Vec<_>>();
                 signals.push(signal_hook::SIGUSR1);
                 signals.push(signal_hook::SIGUSR2);
+                signals.push(SIGPAUSE);
+                signals.push(SIGRESUME);
                 let signals = signal_hook::iterator::Signals::new(&signals).unwrap();
                 for sig in signals.forever() {
                     sender.send(sig).unwrap();
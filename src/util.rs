@@ -1,5 +1,7 @@
 use num_traits::{clamp, ToPrimitive};
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::OsString;
 use std::fmt::Display;
 use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
@@ -7,13 +9,20 @@ use std::io::BufReader;
 use std::path::{Path, PathBuf};
 use std::prelude::v1::String;
 use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crossbeam_channel::Sender;
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
 use regex::Regex;
 use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
 
 use crate::blocks::Block;
 use crate::config::Config;
 use crate::errors::*;
+use crate::scheduler::Task;
+use crate::widget::Markup;
 
 pub const USR_SHARE_PATH: &str = "/usr/share/i3status-rust";
 
@@ -24,6 +33,23 @@ pub fn pseudo_uuid() -> String {
     uuid
 }
 
+/// The number of single character insertions, deletions and substitutions needed to turn one
+/// string into the other.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = distances[0];
+        distances[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + if ca == *cb { 0 } else { 1 };
+            previous = distances[j + 1];
+            distances[j + 1] = substitution.min(distances[j] + 1).min(previous + 1);
+        }
+    }
+    distances[b.len()]
+}
+
 pub fn escape_pango_text(text: String) -> String {
     text.chars()
         .map(|x| match x {
@@ -36,6 +62,84 @@ pub fn escape_pango_text(text: String) -> String {
         .collect()
 }
 
+/// The number of columns a character takes up in a monospace font, e.g. 2 for CJK characters.
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036F | 0x200B..=0x200F | 0x20D0..=0x20FF | 0xFE00..=0xFE0F => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// Shortens `text` to at most `max_width` columns, ending it with an ellipsis if anything
+/// had to be cut off.
+pub fn truncate_to_width(text: &str, max_width: usize) -> String {
+    if text.chars().map(char_width).sum::<usize>() <= max_width {
+        return text.to_string();
+    }
+    let mut truncated = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        // Leave a column for the ellipsis
+        if width + char_width(c) + 1 > max_width {
+            break;
+        }
+        width += char_width(c);
+        truncated.push(c);
+    }
+    truncated.push('…');
+    truncated
+}
+
+/// The text of a block whose data is outdated, formatted with the `stale_format` of the config.
+pub fn format_stale(stale_format: &str, text: &str) -> String {
+    stale_format.replace("{text}", text)
+}
+
+/// Quotes `value` as a single argument for `sh`.
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Replaces the placeholders in a shell command, like `{title}`, by their quoted values.
+/// Unknown placeholders are kept, and the values themselves are never interpolated again.
+pub fn interpolate_command(command: &str, values: &HashMap<String, String>) -> String {
+    let mut interpolated = String::new();
+    let mut rest = command;
+    while let Some(start) = rest.find('{') {
+        interpolated.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let placeholder = rest
+            .find('}')
+            .and_then(|end| Some((end, values.get(&rest[..=end])?)));
+        match placeholder {
+            Some((end, value)) => {
+                interpolated.push_str(&shell_quote(value));
+                rest = &rest[end + 1..];
+            }
+            None => {
+                interpolated.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    interpolated.push_str(rest);
+    interpolated
+}
+
 pub fn format_speed(
     bytes_speed: u64,
     total_digits: usize,
@@ -98,6 +202,37 @@ pub fn format_speed(
     format!("{:.*}{}", decimal_precision as usize, value, unit)
 }
 
+/// Remembers the recent values of a number to tell how fast it changes.
+#[derive(Clone, Debug)]
+pub struct DeltaTracker {
+    window: Duration,
+    samples: VecDeque<(Instant, f64)>,
+}
+
+impl DeltaTracker {
+    pub fn new(window: Duration) -> Self {
+        DeltaTracker {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Adds a value and returns its change since the previous value and since the oldest
+    /// value within the window. Both are zero for the first value.
+    pub fn push(&mut self, value: f64) -> (f64, f64) {
+        let now = Instant::now();
+        let previous = self.samples.back().map_or(value, |&(_, v)| v);
+        self.samples.push_back((now, value));
+        // Keep the previous value even if it is older than the window, so that a window
+        // shorter than the update interval compares with the previous update.
+        while self.samples.len() > 2 && now.duration_since(self.samples[0].0) > self.window {
+            self.samples.pop_front();
+        }
+        let oldest = self.samples.front().map_or(value, |&(_, v)| v);
+        (value - previous, value - oldest)
+    }
+}
+
 pub fn battery_level_to_icon(charge_level: Result<u64>) -> &'static str {
     match charge_level {
         Ok(0..=5) => "bat_empty",
@@ -108,6 +243,16 @@ pub fn battery_level_to_icon(charge_level: Result<u64>) -> &'static str {
     }
 }
 
+/// Converts a pattern with `*` wildcards to an anchored regex.
+pub fn wildcard_to_regex(pattern: &str) -> ::std::result::Result<Regex, regex::Error> {
+    let escaped = pattern
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<String>>()
+        .join(".*");
+    Regex::new(&format!("^{}$", escaped))
+}
+
 pub fn xdg_config_home() -> PathBuf {
     // In the unlikely event that $HOME is not set, it doesn't really matter
     // what we fall back on, so use /.config.
@@ -118,6 +263,167 @@ pub fn xdg_config_home() -> PathBuf {
     PathBuf::from(&config_path)
 }
 
+pub fn xdg_cache_home() -> PathBuf {
+    std::env::var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            PathBuf::from(format!(
+                "{}/.cache",
+                std::env::var("HOME").unwrap_or_else(|_| "".to_string())
+            ))
+        })
+}
+
+/// Replaces a leading `~` of a path by the home directory.
+pub fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(format!("{}{}", home, rest))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// How often the directory of a watched file is looked for again after it was removed.
+const WATCH_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A file watched by `watch_files`, with the watch of its directory while there is one.
+struct WatchedFile {
+    dir: PathBuf,
+    name: OsString,
+    watch: Option<WatchDescriptor>,
+}
+
+fn watch_dir(notify: &mut Inotify, dir: &Path) -> std::io::Result<WatchDescriptor> {
+    notify.add_watch(
+        dir,
+        WatchMask::MODIFY
+            | WatchMask::CLOSE_WRITE
+            | WatchMask::CREATE
+            | WatchMask::MOVED_TO
+            | WatchMask::DELETE
+            | WatchMask::DELETE_SELF,
+    )
+}
+
+/// Requests an update of a block whenever one of the files is written, created, replaced or
+/// removed. Files are often replaced by renaming a new file over them, or don't exist yet, so
+/// their directories are watched instead of the files themselves. The directories have to
+/// exist at first; one that is removed later is watched again once it's back.
+pub fn watch_files(
+    block: &str,
+    paths: &[PathBuf],
+    id: String,
+    tx_update_request: Sender<Task>,
+) -> Result<()> {
+    let mut notify = Inotify::init().block_error(block, "failed to start inotify")?;
+
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let name = path
+            .file_name()
+            .block_error(block, "watched path has no file name")?
+            .to_owned();
+        let dir = path
+            .parent()
+            .block_error(block, "watched path has no parent directory")?
+            .to_path_buf();
+        let watch = watch_dir(&mut notify, &dir).map_err(|e| {
+            BlockError(
+                block.to_string(),
+                format!("failed to watch the directory '{}': {}", dir.display(), e),
+            )
+        })?;
+        files.push(WatchedFile {
+            dir,
+            name,
+            watch: Some(watch),
+        });
+    }
+
+    thread::Builder::new()
+        .name(block.into())
+        .spawn(move || {
+            let mut buffer = [0; 1024];
+            loop {
+                // While a directory is missing, look for it again every now and then
+                let lost = files.iter().any(|file| file.watch.is_none());
+                let mut changed = false;
+                if lost {
+                    thread::sleep(WATCH_RETRY_INTERVAL);
+                    for file in files.iter_mut().filter(|file| file.watch.is_none()) {
+                        if let Ok(watch) = watch_dir(&mut notify, &file.dir) {
+                            file.watch = Some(watch);
+                            changed = true;
+                        }
+                    }
+                }
+
+                let events = match if lost {
+                    notify.read_events(&mut buffer)
+                } else {
+                    notify.read_events_blocking(&mut buffer)
+                } {
+                    Ok(events) => events,
+                    Err(e) => {
+                        // Start over with a new inotify instance, events may have been missed
+                        eprintln!("error while reading inotify events: {}", e);
+                        thread::sleep(WATCH_RETRY_INTERVAL);
+                        if let Ok(new_notify) = Inotify::init() {
+                            notify = new_notify;
+                            for file in files.iter_mut() {
+                                file.watch = watch_dir(&mut notify, &file.dir).ok();
+                            }
+                        }
+                        let task = Task {
+                            id: id.clone(),
+                            update_time: Instant::now(),
+                        };
+                        if tx_update_request.send(task).is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                };
+                for event in events {
+                    if event.mask.contains(EventMask::IGNORED) {
+                        // The directory was removed, the files along with it
+                        for file in files.iter_mut() {
+                            if file.watch.as_ref() == Some(&event.wd) {
+                                file.watch = None;
+                                changed = true;
+                            }
+                        }
+                    } else {
+                        let for_file = |file: &WatchedFile| {
+                            file.watch.as_ref() == Some(&event.wd)
+                                && event.name == Some(file.name.as_os_str())
+                        };
+                        changed |= files.iter().any(for_file);
+                    }
+                }
+
+                let task = Task {
+                    id: id.clone(),
+                    update_time: Instant::now(),
+                };
+                if changed && tx_update_request.send(task).is_err() {
+                    return;
+                }
+            }
+        })
+        .block_error(block, "failed to start watcher thread")?;
+
+    Ok(())
+}
+
+pub fn xdg_runtime_dir() -> PathBuf {
+    // Without a runtime directory, fall back on the temporary directory.
+    std::env::var("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| std::env::temp_dir())
+}
+
 pub fn deserialize_file<T>(file: &str) -> Result<T>
 where
     T: DeserializeOwned,
@@ -219,6 +525,9 @@ pub fn print_blocks(
 
     let mut alternator = visible_count % 2 == 0;
 
+    // The previous block asked for no separator after it
+    let mut merge_with_previous = false;
+
     for block_id in order {
         let block = &(*(block_map
             .get(block_id)
@@ -233,6 +542,9 @@ pub fn print_blocks(
             .iter()
             .map(|widget| {
                 let mut w_json: serde_json::Value = widget.get_rendered().to_owned();
+                if let Some(options) = block.widget_options() {
+                    options.apply(&mut w_json, widget.state(), &config.theme);
+                }
                 if alternator {
                     // Apply tint for all widgets of every second block
                     *w_json.get_mut("background").unwrap() = json!(add_colors(
@@ -266,6 +578,13 @@ pub fn print_blocks(
                 .unwrap() = json!(null);
         }
 
+        if let Some(options) = block.widget_options() {
+            options.apply_separator(rendered_widgets.last_mut().unwrap());
+        }
+        let merge_with_next = block
+            .widget_options()
+            .map_or(false, |options| options.separator == Some(false));
+
         // Serialize and concatenate widgets
         let block_str = rendered_widgets
             .iter()
@@ -273,11 +592,16 @@ pub fn print_blocks(
             .collect::<Vec<String>>()
             .join(",");
 
-        if config.theme.native_separators {
-            // Skip separator block for native theme
+        if config.theme.native_separators || merge_with_previous {
+            // Skip separator block for native theme or to merge the blocks
             rendered_blocks.push(block_str.to_string());
+            merge_with_previous = merge_with_next;
+            last_bg = rendered_widgets.last().unwrap()["background"]
+                .as_str()
+                .map(String::from);
             continue;
         }
+        merge_with_previous = merge_with_next;
 
         // The first widget's BG is used to get the FG color for the current separator
         let first_bg = rendered_widgets.first().unwrap()["background"]
@@ -423,13 +747,166 @@ where
     }
 }
 
+thread_local! {
+    /// The values of the placeholders rendered while recording, see `record_rendered_values`.
+    static RENDERED_VALUES: RefCell<Option<HashMap<String, String>>> = RefCell::new(None);
+}
+
+/// Escapes a value for pango that a block puts into its format. With a `markup` configured,
+/// the block's format escapes all values itself (`pango`) or nothing is escaped (`none`).
+pub fn escape_pango_value(text: String, markup: Option<Markup>) -> String {
+    match markup {
+        None => escape_pango_text(text),
+        Some(_) => text,
+    }
+}
+
+/// Runs `f` and returns the values of all placeholders it rendered, keyed like `{volume}`.
+/// This allows the shared options to look at the values of any block.
+pub fn record_rendered_values<T>(f: impl FnOnce() -> T) -> (T, HashMap<String, String>) {
+    let outer = RENDERED_VALUES.with(|values| values.replace(Some(HashMap::new())));
+    let result = f();
+    let values = RENDERED_VALUES
+        .with(|values| values.replace(outer))
+        .unwrap_or_default();
+    // A recording around this one sees the values as well
+    RENDERED_VALUES.with(|outer| {
+        if let Some(ref mut outer) = *outer.borrow_mut() {
+            outer.extend(values.clone());
+        }
+    });
+    (result, values)
+}
+
+fn record_rendered_value(key: &str, value: &str) {
+    RENDERED_VALUES.with(|values| {
+        if let Some(ref mut values) = *values.borrow_mut() {
+            values.insert(key.to_string(), value.to_string());
+        }
+    });
+}
+
+/// Appends the rendered value of a placeholder, escaped for pango if `escape` is set.
+fn push_value(rendered: &mut String, key: &str, value: &str, escape: bool) {
+    record_rendered_value(key, value);
+    if escape {
+        rendered.push_str(&escape_pango_text(value.to_string()));
+    } else {
+        rendered.push_str(value);
+    }
+}
+
+/// The `format` option of a block: either one format, or a list of formats of which the block
+/// shows one at a time.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Formats {
+    One(String),
+    List(Vec<String>),
+}
+
+impl Formats {
+    /// All formats, in the order they are cycled through.
+    pub fn all(&self) -> Vec<&str> {
+        match self {
+            Formats::One(format) => vec![format.as_str()],
+            Formats::List(formats) => formats.iter().map(String::as_str).collect(),
+        }
+    }
+
+    /// Whether any of the formats contains `pattern`, e.g. a placeholder whose value has to be
+    /// read only if it is shown.
+    pub fn contains(&self, pattern: &str) -> bool {
+        self.all().iter().any(|format| format.contains(pattern))
+    }
+
+    /// The format at `index`, as selected with `Block::set_format_index`.
+    pub fn get(&self, index: usize) -> &str {
+        let formats = self.all();
+        if formats.is_empty() {
+            ""
+        } else {
+            formats[index % formats.len()]
+        }
+    }
+}
+
+impl Default for Formats {
+    fn default() -> Self {
+        Formats::One(String::new())
+    }
+}
+
+impl From<&str> for Formats {
+    fn from(format: &str) -> Self {
+        Formats::One(format.to_string())
+    }
+}
+
+impl From<String> for Formats {
+    fn from(format: String) -> Self {
+        Formats::One(format)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum FormatTemplate {
     Str(String, Option<Box<FormatTemplate>>),
-    Var(String, Option<Box<FormatTemplate>>),
+    /// A placeholder, whose value is escaped for pango if the flag is set.
+    Var(String, bool, Option<Box<FormatTemplate>>),
+    /// A list of formats, of which the template at the selected index is rendered.
+    List(usize, Vec<FormatTemplate>),
 }
 
 impl FormatTemplate {
+    pub fn from_formats(formats: &Formats) -> Result<FormatTemplate> {
+        match formats {
+            Formats::One(format) => FormatTemplate::from_string(format),
+            Formats::List(formats) => Ok(FormatTemplate::List(
+                0,
+                formats
+                    .iter()
+                    .map(|format| FormatTemplate::from_string(format))
+                    .collect::<Result<Vec<_>>>()?,
+            )),
+        }
+    }
+
+    /// Escapes the values of the placeholders for pango with `markup = "pango"`, while the
+    /// literal text of the format, which may contain markup, is kept as is.
+    pub fn with_markup(mut self, markup: Option<Markup>) -> Self {
+        self.set_escape(markup == Some(Markup::Pango));
+        self
+    }
+
+    fn set_escape(&mut self, escape: bool) {
+        match *self {
+            FormatTemplate::Str(_, ref mut next) => {
+                if let Some(ref mut next) = *next {
+                    next.set_escape(escape);
+                }
+            }
+            FormatTemplate::Var(_, ref mut escaped, ref mut next) => {
+                *escaped = escape;
+                if let Some(ref mut next) = *next {
+                    next.set_escape(escape);
+                }
+            }
+            FormatTemplate::List(_, ref mut templates) => {
+                for template in templates {
+                    template.set_escape(escape);
+                }
+            }
+        }
+    }
+
+    /// Selects the template that is rendered of a list of formats.
+    pub fn select(&mut self, index: usize) {
+        if let FormatTemplate::List(ref mut selected, _) = *self {
+            *selected = index;
+        }
+    }
+
     pub fn from_string(s: &str) -> Result<FormatTemplate> {
         let s_as_bytes = s.as_bytes();
 
@@ -448,7 +925,11 @@ impl FormatTemplate {
                     None,
                 ));
             }
-            token_vec.push(FormatTemplate::Var(re_match.as_str().to_string(), None));
+            token_vec.push(FormatTemplate::Var(
+                re_match.as_str().to_string(),
+                false,
+                None,
+            ));
             start = re_match.end();
         }
         let str_vec: Vec<u8> = (&s_as_bytes)[start..].to_vec();
@@ -464,7 +945,10 @@ impl FormatTemplate {
         while let Some(token) = token_vec.pop() {
             template = match token {
                 FormatTemplate::Str(s, _) => FormatTemplate::Str(s, Some(Box::new(template))),
-                FormatTemplate::Var(s, _) => FormatTemplate::Var(s, Some(Box::new(template))),
+                FormatTemplate::Var(s, escape, _) => {
+                    FormatTemplate::Var(s, escape, Some(Box::new(template)))
+                }
+                list @ FormatTemplate::List(..) => list,
             }
         }
         Ok(template)
@@ -481,16 +965,23 @@ impl FormatTemplate {
                     rendered.push_str(&*next.render(vars));
                 };
             }
-            Var(ref key, ref next) => {
-                rendered.push_str(&format!(
+            Var(ref key, escape, ref next) => {
+                let value = format!(
                     "{}",
                     vars.get(key)
                         .unwrap_or_else(|| panic!("Unknown placeholder in format string: {}", key))
-                ));
+                );
+                push_value(&mut rendered, key, &value, escape);
                 if let Some(ref next) = *next {
                     rendered.push_str(&*next.render(vars));
                 };
             }
+            List(index, ref templates) => {
+                if !templates.is_empty() {
+                    let template = &templates[index % templates.len()];
+                    rendered.push_str(&template.render(vars));
+                }
+            }
         };
         rendered
     }
@@ -505,18 +996,25 @@ impl FormatTemplate {
                     rendered.push_str(&*next.render_static_str(vars)?);
                 };
             }
-            Var(ref key, ref next) => {
-                rendered.push_str(&format!(
+            Var(ref key, escape, ref next) => {
+                let value = format!(
                     "{}",
                     vars.get(&**key).internal_error(
                         "util",
                         &format!("Unknown placeholder in format string: {}", key)
                     )?
-                ));
+                );
+                push_value(&mut rendered, key, &value, escape);
                 if let Some(ref next) = *next {
                     rendered.push_str(&*next.render_static_str(vars)?);
                 };
             }
+            List(index, ref templates) => {
+                if !templates.is_empty() {
+                    let template = &templates[index % templates.len()];
+                    rendered.push_str(&template.render_static_str(vars)?);
+                }
+            }
         };
         Ok(rendered)
     }
@@ -528,7 +1026,11 @@ macro_rules! if_debug {
 
 #[cfg(test)]
 mod tests {
-    use crate::util::{color_from_rgba, has_command};
+    use crate::util::{
+        color_from_rgba, escape_pango_value, format_stale, has_command, interpolate_command,
+        levenshtein, truncate_to_width, FormatTemplate, Formats,
+    };
+    use crate::widget::Markup;
 
     #[test]
     // we assume sh is always available
@@ -547,6 +1049,86 @@ mod tests {
         let has_command = has_command.unwrap();
         assert!(!has_command)
     }
+    #[test]
+    fn test_truncate_to_width() {
+        assert_eq!(truncate_to_width("short", 10), "short");
+        assert_eq!(truncate_to_width("a long title", 7), "a long…");
+        // CJK characters take up two columns
+        assert_eq!(truncate_to_width("日本語のタイトル", 7), "日本語…");
+        assert_eq!(truncate_to_width("日本語", 6), "日本語");
+    }
+
+    #[test]
+    fn test_interpolate_command() {
+        let values = map!(
+            "{title}".to_string() => "it's $(rm -rf ~) {artist}".to_string(),
+            "{artist}".to_string() => "me".to_string()
+        );
+        assert_eq!(
+            interpolate_command("wl-copy {title} {unknown} {", &values),
+            "wl-copy 'it'\\''s $(rm -rf ~) {artist}' {unknown} {"
+        );
+    }
+
+    #[test]
+    fn test_format_list() {
+        let formats = Formats::List(vec!["{a}".to_string(), "b: {b}".to_string()]);
+        let mut template = FormatTemplate::from_formats(&formats).unwrap();
+        let vars = map!("{a}" => "1", "{b}" => "2");
+        assert_eq!(template.render_static_str(&vars).unwrap(), "1");
+        assert_eq!(formats.get(0), "{a}");
+        template.select(1);
+        assert_eq!(template.render_static_str(&vars).unwrap(), "b: 2");
+        assert_eq!(formats.get(1), "b: {b}");
+        assert!(formats.contains("{b}"));
+
+        let format: Formats = toml::from_str::<toml::Value>("format = \"{a}\"")
+            .unwrap()
+            .get("format")
+            .cloned()
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(format, Formats::from("{a}"));
+    }
+
+    #[test]
+    fn test_markup() {
+        let vars = map!("{title}" => "Tom & Jerry");
+        let pango = FormatTemplate::from_string("<b>{title}</b>")
+            .unwrap()
+            .with_markup(Some(Markup::Pango));
+        assert_eq!(
+            pango.render_static_str(&vars).unwrap(),
+            "<b>Tom &amp; Jerry</b>"
+        );
+        let none = FormatTemplate::from_string("<b>{title}</b>")
+            .unwrap()
+            .with_markup(Some(Markup::None));
+        assert_eq!(none.render_static_str(&vars).unwrap(), "<b>Tom & Jerry</b>");
+
+        // Values that blocks escape themselves are escaped once with pango, and not at all
+        // without markup
+        let title = || "Tom & Jerry".to_string();
+        assert_eq!(escape_pango_value(title(), None), "Tom &amp; Jerry");
+        assert_eq!(
+            escape_pango_value(title(), Some(Markup::Pango)),
+            "Tom & Jerry"
+        );
+        assert_eq!(
+            escape_pango_value(title(), Some(Markup::None)),
+            "Tom & Jerry"
+        );
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("interval", "interval"), 0);
+        assert_eq!(levenshtein("intervall", "interval"), 1);
+        assert_eq!(levenshtein("fromat", "format"), 2);
+        assert_eq!(levenshtein("", "icons"), 5);
+    }
+
     #[test]
     fn test_color_from_rgba() {
         let valid_rgb = "#AABBCC"; //rgb
@@ -571,4 +1153,16 @@ mod tests {
         let rgba = color_from_rgba(invalid);
         assert!(rgba.is_err());
     }
+
+    #[test]
+    fn test_format_stale() {
+        assert_eq!(
+            format_stale("{text} (stale)", "5 updates"),
+            "5 updates (stale)"
+        );
+        assert_eq!(
+            format_stale("<i>{text}</i>", "5 updates"),
+            "<i>5 updates</i>"
+        );
+    }
 }
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 
 use serde::de::value::{Error, StrDeserializer};
@@ -6,6 +7,7 @@ use serde_derive::Deserialize;
 use serde_json::value::Value;
 
 use crate::themes::Theme;
+use crate::util::record_rendered_values;
 
 #[derive(Debug, Copy, Clone, Deserialize)]
 pub enum Spacing {
@@ -17,7 +19,7 @@ pub enum Spacing {
     Hidden,
 }
 
-#[derive(Debug, Copy, Clone, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
 pub enum State {
     Idle,
     Info,
@@ -27,6 +29,25 @@ pub enum State {
 }
 
 impl State {
+    /// The states from the most to the least urgent one.
+    pub const BY_URGENCY: [State; 5] = [
+        State::Critical,
+        State::Warning,
+        State::Info,
+        State::Good,
+        State::Idle,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            State::Idle => "idle",
+            State::Info => "info",
+            State::Good => "good",
+            State::Warning => "warning",
+            State::Critical => "critical",
+        }
+    }
+
     pub fn theme_keys(self, theme: &Theme) -> (&Option<String>, &Option<String>) {
         use self::State::*;
         match self {
@@ -48,7 +69,292 @@ impl FromStr for State {
     }
 }
 
+/// Properties of the widgets of a block, set with the shared options.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WidgetOptions {
+    /// Used as the name of the widgets, their own name is moved to the instance
+    pub block_id: Option<String>,
+    pub min_width: Option<MinWidth>,
+    pub align: Option<Align>,
+    /// Whether i3bar draws a separator after the block
+    pub separator: Option<bool>,
+    /// The gap after the block in pixels
+    pub separator_block_width: Option<u64>,
+    pub thresholds: Option<Thresholds>,
+    pub markup: Option<Markup>,
+    /// The background color, instead of the one of the theme
+    pub background: Option<String>,
+    pub border: Option<Border>,
+    /// The widths of the border in pixels
+    pub border_top: Option<u64>,
+    pub border_right: Option<u64>,
+    pub border_bottom: Option<u64>,
+    pub border_left: Option<u64>,
+    /// The state picked by the `thresholds` at the last update
+    #[serde(skip)]
+    pub state: Option<State>,
+}
+
+/// Picks the state of the widgets by the numeric value of a placeholder.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Thresholds {
+    pub placeholder: String,
+    pub levels: Vec<Threshold>,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Threshold {
+    pub value: f64,
+    pub state: State,
+}
+
+impl Thresholds {
+    /// The state of the highest level reached by the placeholder, `None` if the placeholder
+    /// wasn't rendered or is below all levels.
+    pub fn state(&self, rendered_values: &HashMap<String, String>) -> Option<Option<State>> {
+        let key = format!(
+            "{{{}}}",
+            self.placeholder
+                .trim_start_matches('{')
+                .trim_end_matches('}')
+        );
+        let value = leading_number(rendered_values.get(&key)?);
+        Some(value.and_then(|value| {
+            self.levels
+                .iter()
+                .filter(|level| value >= level.value)
+                .fold(None, |highest: Option<Threshold>, level| match highest {
+                    Some(highest) if highest.value > level.value => Some(highest),
+                    _ => Some(*level),
+                })
+                .map(|level| level.state)
+        }))
+    }
+}
+
+/// The first number in a text like ` 85%` or `-3.5°C`. A `-` is only a sign when a digit
+/// follows it, so `up-to-date 5` gives 5.
+fn leading_number(text: &str) -> Option<f64> {
+    let bytes = text.as_bytes();
+    let start = (0..bytes.len()).find(|&i| {
+        bytes[i].is_ascii_digit()
+            || (bytes[i] == b'-' && matches!(bytes.get(i + 1), Some(c) if c.is_ascii_digit()))
+    })?;
+    let rest = &text[start..];
+    let end = rest
+        .char_indices()
+        .skip(1)
+        .find(|&(_, c)| !(c.is_ascii_digit() || c == '.'))
+        .map_or(rest.len(), |(i, _)| i);
+    rest[..end].parse().ok()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum MinWidth {
+    Pixels(u64),
+    /// The width of the text
+    Text(String),
+}
+
+#[derive(Deserialize, Debug, Copy, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// The color of the border around the widgets, either for all states or for some of them.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Border {
+    Color(String),
+    States(StateColors),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct StateColors {
+    pub idle: Option<String>,
+    pub info: Option<String>,
+    pub good: Option<String>,
+    pub warning: Option<String>,
+    pub critical: Option<String>,
+}
+
+impl Border {
+    fn color(&self, state: State) -> Option<&String> {
+        match self {
+            Border::Color(color) => Some(color),
+            Border::States(colors) => match state {
+                State::Idle => colors.idle.as_ref(),
+                State::Info => colors.info.as_ref(),
+                State::Good => colors.good.as_ref(),
+                State::Warning => colors.warning.as_ref(),
+                State::Critical => colors.critical.as_ref(),
+            },
+        }
+    }
+
+    fn colors(&self) -> Vec<&String> {
+        State::BY_URGENCY
+            .iter()
+            .filter_map(|state| self.color(*state))
+            .collect()
+    }
+}
+
+/// Whether `color` is like `#rrggbb` or `#rrggbbaa`.
+fn is_hex_color(color: &str) -> bool {
+    color.starts_with('#')
+        && (color.len() == 7 || color.len() == 9)
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// How i3bar interprets the text of the widgets.
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Markup {
+    /// The format may contain pango markup, the values of the placeholders are escaped
+    Pango,
+    /// The text is shown as is
+    None,
+}
+
+impl WidgetOptions {
+    /// Runs `f`, which may render the block, and picks the state by the `thresholds`.
+    pub fn record_state<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        let thresholds = match self.thresholds {
+            Some(ref thresholds) => thresholds,
+            None => return f(),
+        };
+        let (result, rendered_values) = record_rendered_values(f);
+        if let Some(state) = thresholds.state(&rendered_values) {
+            self.state = state;
+        }
+        result
+    }
+
+    /// The first color of the options that isn't a hex color.
+    pub fn invalid_color(&self) -> Option<&str> {
+        self.background
+            .iter()
+            .chain(self.border.iter().flat_map(Border::colors))
+            .find(|color| !is_hex_color(color))
+            .map(String::as_str)
+    }
+
+    /// Sets the options in the JSON of a widget, whose own state is `widget_state`.
+    pub fn apply(&self, widget: &mut Value, widget_state: State, theme: &Theme) {
+        if let Some(ref block_id) = self.block_id {
+            // The widget's own name is needed to route clicks back to it
+            if let Some(name) = widget.get("name").cloned() {
+                widget["instance"] = name;
+            }
+            widget["name"] = json!(block_id);
+        }
+        match self.min_width {
+            Some(MinWidth::Pixels(pixels)) => widget["min_width"] = json!(pixels),
+            Some(MinWidth::Text(ref text)) => widget["min_width"] = json!(text),
+            None => {}
+        }
+        if let Some(align) = self.align {
+            widget["align"] = json!(match align {
+                Align::Left => "left",
+                Align::Center => "center",
+                Align::Right => "right",
+            });
+        }
+        match self.markup {
+            Some(Markup::Pango) => widget["markup"] = json!("pango"),
+            Some(Markup::None) => widget["markup"] = json!("none"),
+            None => {}
+        }
+        if let Some(state) = self.state {
+            let (key_bg, key_fg) = state.theme_keys(theme);
+            widget["background"] = json!(key_bg);
+            widget["color"] = json!(key_fg);
+        }
+        if let Some(ref background) = self.background {
+            widget["background"] = json!(background);
+        }
+        let state = self.state.unwrap_or(widget_state);
+        if let Some(color) = self.border.as_ref().and_then(|border| border.color(state)) {
+            widget["border"] = json!(color);
+        }
+        let widths = [
+            ("border_top", self.border_top),
+            ("border_right", self.border_right),
+            ("border_bottom", self.border_bottom),
+            ("border_left", self.border_left),
+        ];
+        for (key, width) in widths.iter() {
+            if let Some(width) = width {
+                widget[*key] = json!(width);
+            }
+        }
+    }
+
+    /// Applied to the last widget of the block only, as it is the one followed by the
+    /// separator; between the widgets of a block there is none.
+    pub fn apply_separator(&self, widget: &mut Value) {
+        if let Some(separator) = self.separator {
+            widget["separator"] = json!(separator);
+        }
+        if let Some(width) = self.separator_block_width {
+            widget["separator_block_width"] = json!(width);
+        }
+    }
+}
+
 pub trait I3BarWidget {
     fn to_string(&self) -> String;
     fn get_rendered(&self) -> &Value;
+    fn state(&self) -> State;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thresholds_state() {
+        let thresholds = Thresholds {
+            placeholder: "volume".to_string(),
+            levels: vec![
+                Threshold {
+                    value: 90.,
+                    state: State::Critical,
+                },
+                Threshold {
+                    value: 50.,
+                    state: State::Warning,
+                },
+            ],
+        };
+        let state = |text: &str| {
+            let mut rendered_values = HashMap::new();
+            rendered_values.insert("{volume}".to_string(), text.to_string());
+            thresholds.state(&rendered_values)
+        };
+        assert_eq!(state(" 95%"), Some(Some(State::Critical)));
+        assert_eq!(state("50"), Some(Some(State::Warning)));
+        assert_eq!(state("49.9%"), Some(None));
+        assert_eq!(state("N/A"), Some(None));
+        assert_eq!(thresholds.state(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn test_leading_number() {
+        assert_eq!(leading_number(" 85%"), Some(85.));
+        assert_eq!(leading_number("-3.5°C"), Some(-3.5));
+        assert_eq!(leading_number("up-to-date 5"), Some(5.));
+        assert_eq!(leading_number("5-10"), Some(5.));
+        assert_eq!(leading_number("- 5"), Some(5.));
+        assert_eq!(leading_number("N/A"), None);
+    }
 }
@@ -8,6 +8,7 @@ use crate::widget::State;
 #[derive(Clone, Debug)]
 pub struct ButtonWidget {
     content: Option<String>,
+    short_text: Option<String>,
     icon: Option<String>,
     state: State,
     spacing: Spacing,
@@ -21,6 +22,7 @@ impl ButtonWidget {
     pub fn new(config: Config, id: &str) -> Self {
         ButtonWidget {
             content: None,
+            short_text: None,
             icon: None,
             state: State::Idle,
             spacing: Spacing::Normal,
@@ -39,7 +41,7 @@ impl ButtonWidget {
     }
 
     pub fn with_icon(mut self, name: &str) -> Self {
-        self.icon = self.config.icons.get(name).cloned();
+        self.icon = self.config.icon(name);
         self.update();
         self
     }
@@ -73,8 +75,14 @@ impl ButtonWidget {
         self.update();
     }
 
+    /// Text shown instead of the content when the bar is too narrow, e.g. with many blocks
+    pub fn set_short_text(&mut self, short_text: Option<String>) {
+        self.short_text = short_text;
+        self.update();
+    }
+
     pub fn set_icon(&mut self, name: &str) {
-        self.icon = self.config.icons.get(name).cloned();
+        self.icon = self.config.icon(name);
         self.update();
     }
 
@@ -92,19 +100,19 @@ impl ButtonWidget {
         let (key_bg, key_fg) = self.state.theme_keys(&self.config.theme);
 
         // When rendered inline, remove the leading space
+        let icon = self.icon.clone().unwrap_or_else(|| match self.spacing {
+            Spacing::Normal => String::from(" "),
+            _ => String::from(""),
+        });
+        let trailing = match self.spacing {
+            Spacing::Hidden => "",
+            _ => " ",
+        };
         self.rendered = json!({
             "full_text": format!("{}{}{}",
-                                self.icon.clone().unwrap_or_else(|| {
-                                    match self.spacing {
-                                        Spacing::Normal => String::from(" "),
-                                        _ => String::from("")
-                                    }
-                                }),
+                                icon,
                                 self.content.clone().unwrap_or_else(|| String::from("")),
-                                match self.spacing {
-                                    Spacing::Hidden => String::from(""),
-                                    _ => String::from(" ")
-                                }
+                                trailing
                             ),
             "separator": false,
             "name": self.id.clone(),
@@ -113,6 +121,10 @@ impl ButtonWidget {
             "color": key_fg,
             "markup": "pango"
         });
+        if let Some(ref short_text) = self.short_text {
+            self.rendered["short_text"] =
+                Value::String(format!("{}{}{}", icon, short_text, trailing));
+        }
 
         self.cached_output = Some(self.rendered.to_string());
     }
@@ -128,4 +140,8 @@ impl I3BarWidget for ButtonWidget {
     fn get_rendered(&self) -> &Value {
         &self.rendered
     }
+
+    fn state(&self) -> State {
+        self.state
+    }
 }
@@ -9,6 +9,10 @@ use crate::widget::State;
 #[derive(Clone, Debug)]
 pub struct GraphWidget {
     content: Option<String>,
+    history: Vec<u64>,
+    length: usize,
+    min: Option<u64>,
+    max: Option<u64>,
     icon: Option<String>,
     state: State,
     spacing: Spacing,
@@ -21,6 +25,10 @@ impl GraphWidget {
     pub fn new(config: Config) -> Self {
         GraphWidget {
             content: None,
+            history: Vec::new(),
+            length: 10,
+            min: None,
+            max: None,
             icon: None,
             state: State::Idle,
             spacing: Spacing::Normal,
@@ -37,7 +45,7 @@ impl GraphWidget {
     }
 
     pub fn with_icon(mut self, name: &str) -> Self {
-        self.icon = self.config.icons.get(name).cloned();
+        self.icon = self.config.icon(name);
         self.update();
         self
     }
@@ -54,6 +62,36 @@ impl GraphWidget {
         self
     }
 
+    /// Sets the number of values kept by `push_value`.
+    pub fn with_length(mut self, length: usize) -> Self {
+        self.length = length.max(1);
+        self
+    }
+
+    /// Sets the values corresponding to the lowest and highest bar. If unset, the graph is
+    /// scaled to the minimum and maximum of the values currently shown.
+    pub fn with_range(mut self, min: Option<u64>, max: Option<u64>) -> Self {
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    /// Appends a value to the graph, dropping the oldest one once `length` values are shown.
+    pub fn push_value(&mut self, value: u64) {
+        self.history.push(value);
+        if self.history.len() > self.length {
+            let excess = self.history.len() - self.length;
+            self.history.drain(..excess);
+        }
+        let history = self.history.clone();
+        self.set_values(&history, self.min, self.max);
+    }
+
+    /// The rendered graph, for use in a format placeholder.
+    pub fn graph(&self) -> String {
+        self.content.clone().unwrap_or_else(|| String::from(""))
+    }
+
     pub fn set_values<T>(&mut self, content: &[T], min: Option<T>, max: Option<T>)
     where
         T: Ord + ToPrimitive,
@@ -89,7 +127,7 @@ impl GraphWidget {
     }
 
     pub fn set_icon(&mut self, name: &str) {
-        self.icon = self.config.icons.get(name).cloned();
+        self.icon = self.config.icon(name);
         self.update();
     }
 
@@ -135,4 +173,8 @@ impl I3BarWidget for GraphWidget {
     fn get_rendered(&self) -> &Value {
         &self.rendered
     }
+
+    fn state(&self) -> State {
+        self.state
+    }
 }
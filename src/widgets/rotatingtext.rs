@@ -61,7 +61,7 @@ impl RotatingTextWidget {
     }
 
     pub fn with_icon(mut self, name: &str) -> Self {
-        self.icon = self.config.icons.get(name).cloned();
+        self.icon = self.config.icon(name);
         self.update();
         self
     }
@@ -96,7 +96,7 @@ impl RotatingTextWidget {
     }
 
     pub fn set_icon(&mut self, name: &str) {
-        self.icon = self.config.icons.get(name).cloned();
+        self.icon = self.config.icon(name);
         self.update();
     }
 
@@ -220,4 +220,8 @@ impl I3BarWidget for RotatingTextWidget {
     fn get_rendered(&self) -> &Value {
         &self.rendered
     }
+
+    fn state(&self) -> State {
+        self.state
+    }
 }
@@ -36,7 +36,7 @@ impl TextWidget {
     }
 
     pub fn with_icon(mut self, name: &str) -> Self {
-        self.icon = self.config.icons.get(name).cloned();
+        self.icon = self.config.icon(name);
         self.update();
         self
     }
@@ -65,7 +65,7 @@ impl TextWidget {
     }
 
     pub fn set_icon(&mut self, name: &str) {
-        self.icon = self.config.icons.get(name).cloned();
+        self.icon = self.config.icon(name);
         self.update();
     }
 
@@ -116,4 +116,8 @@ impl I3BarWidget for TextWidget {
     fn get_rendered(&self) -> &Value {
         &self.rendered
     }
+
+    fn state(&self) -> State {
+        self.state
+    }
 }
@@ -12,6 +12,17 @@ mod run_binary {
         assert_eq!(output.success(), true);
     }
 
+    #[test]
+    #[cfg(debug_assertions)]
+    fn validate() {
+        let output = Command::new("./target/debug/i3status-rs")
+            .args(&["--validate", "./tests/testconfig1.toml"])
+            .output()
+            .expect("failed to execute process");
+        assert_eq!(output.status.success(), true);
+        assert_eq!(output.stdout, b"Configuration is valid\n");
+    }
+
     #[test]
     #[cfg(not(debug_assertions))]
     fn release_build() {